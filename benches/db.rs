@@ -0,0 +1,27 @@
+//! Benchmarks of the main chat.db queries against a large synthetic database.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use im_tui::{bench, db};
+use std::env;
+
+/// Conversations and messages per conversation in the synthetic chat.db.
+const CONVERSATIONS: usize = 200;
+const MESSAGES: usize = 1000;
+
+fn queries(c: &mut Criterion) {
+    let path = env::temp_dir().join(format!("im-criterion-{}.db", std::process::id()));
+    let chat = bench::create_synthetic_db(&path, CONVERSATIONS, MESSAGES, true).unwrap();
+    let db = db::MessageDB::open_at(&path).unwrap();
+
+    let mut group = c.benchmark_group("chat.db");
+    for query in bench::QUERIES {
+        group.bench_function(query.name, |b| b.iter(|| (query.run)(&db, &chat).unwrap()));
+    }
+    group.finish();
+
+    drop(db);
+    let _ = std::fs::remove_file(path);
+}
+
+criterion_group!(benches, queries);
+criterion_main!(benches);
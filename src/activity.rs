@@ -0,0 +1,189 @@
+use crate::db::MessageDB;
+use crate::error::Result;
+use crate::search::local_midnight;
+use chrono::{Datelike, Duration, NaiveDate};
+use std::collections::HashMap;
+
+/// Weeks of activity shown in a heatmap: a year, plus the week in progress.
+pub const HEATMAP_WEEKS: usize = 53;
+
+/// Days in the heatmap's first column, labeled every other day like GitHub's.
+pub const WEEKDAY_LABELS: [&str; 7] = ["Mon", "", "Wed", "", "Fri", "", "Sun"];
+
+/// Heatmap cells from no messages to the busiest days.
+pub const HEATMAP_CELLS: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+/// Sparkline blocks from the quietest hour to the busiest.
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// How active a conversation has been over the last year: messages per day and per hour of the
+/// day.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Activity {
+    today: NaiveDate,
+    days: HashMap<NaiveDate, usize>,
+    hours: [usize; 24],
+}
+
+impl Activity {
+    /// Count the messages with any of a contact's handles in the heatmap's weeks up to `today`.
+    pub fn load(db: &MessageDB, handles: &[String], today: NaiveDate) -> Result<Self> {
+        let since = year_start(today)?;
+        Ok(Self {
+            today,
+            days: db.get_daily_counts(handles, since)?.into_iter().collect(),
+            hours: db.get_hourly_counts(handles, since)?,
+        })
+    }
+
+    /// Messages in the last year.
+    pub fn total(&self) -> usize {
+        self.days.values().sum()
+    }
+
+    /// The day with the most messages, and how many.
+    pub fn busiest_day(&self) -> Option<(NaiveDate, usize)> {
+        self.days
+            .iter()
+            .map(|(&day, &count)| (day, count))
+            .max_by_key(|&(day, count)| (count, day))
+    }
+
+    /// The hour of the day (0-23) with the most messages.
+    pub fn busiest_hour(&self) -> Option<usize> {
+        (0..24)
+            .filter(|&hour| self.hours[hour] > 0)
+            .max_by_key(|&hour| self.hours[hour])
+    }
+
+    /// Heatmap levels for the last `weeks` weeks, as indexes into `HEATMAP_CELLS`.
+    ///
+    /// There is one row per weekday from Monday and one column per week, oldest first. Days
+    /// after today are `None`.
+    pub fn heatmap(&self, weeks: usize) -> Vec<Vec<Option<usize>>> {
+        let start = heatmap_start(self.today, weeks);
+        let max = self.days.values().copied().max().unwrap_or(0);
+
+        (0..7)
+            .map(|weekday| {
+                (0..weeks)
+                    .map(|week| {
+                        let day = start + Duration::days((week * 7 + weekday) as i64);
+                        (day <= self.today).then(|| {
+                            let count = self.days.get(&day).copied().unwrap_or(0);
+                            level(count, max, HEATMAP_CELLS.len() - 1)
+                        })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// A label row for the heatmap's `weeks` columns, naming each month above the week it
+    /// starts in.
+    pub fn month_labels(&self, weeks: usize) -> String {
+        let start = heatmap_start(self.today, weeks);
+        let mut labels = String::new();
+        let mut month = None;
+
+        for week in 0..weeks {
+            let monday = start + Duration::days(week as i64 * 7);
+            let new_month = month.is_some_and(|month| month != monday.month());
+            month = Some(monday.month());
+
+            // Skip columns a previous label already covers
+            if labels.chars().count() > week {
+                continue;
+            }
+            let label = monday.format("%b").to_string();
+            if new_month && week + label.chars().count() <= weeks {
+                labels.push_str(&label);
+            } else {
+                labels.push(' ');
+            }
+        }
+
+        labels
+    }
+
+    /// Messages per hour of the day as a 24-character sparkline, from midnight.
+    pub fn sparkline(&self) -> String {
+        let max = self.hours.iter().copied().max().unwrap_or(0);
+        self.hours
+            .iter()
+            .map(|&count| match count {
+                0 => ' ',
+                count => SPARKS[level(count, max, SPARKS.len()) - 1],
+            })
+            .collect()
+    }
+}
+
+/// The Unix timestamp the heatmap ending `today` starts at, a little over a year ago.
+pub fn year_start(today: NaiveDate) -> Result<i64> {
+    local_midnight(heatmap_start(today, HEATMAP_WEEKS))
+}
+
+/// The Monday `weeks - 1` weeks before the week containing `today`.
+fn heatmap_start(today: NaiveDate, weeks: usize) -> NaiveDate {
+    let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    monday - Duration::weeks(weeks.saturating_sub(1) as i64)
+}
+
+/// Scale a count to a level from 1 to `levels` relative to the busiest `max`, or 0 for none.
+fn level(count: usize, max: usize, levels: usize) -> usize {
+    if count == 0 || max == 0 {
+        0
+    } else {
+        (count * levels).div_ceil(max).clamp(1, levels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_heatmap() {
+        // A Wednesday
+        let today = date(3, 6);
+        let mut hours = [0; 24];
+        hours[9] = 1;
+        hours[21] = 8;
+        let activity = Activity {
+            today,
+            days: HashMap::from([(date(3, 4), 8), (date(3, 5), 1), (date(2, 28), 4)]),
+            hours,
+        };
+
+        assert_eq!(heatmap_start(today, 2), date(2, 26));
+        let heatmap = activity.heatmap(2);
+        assert_eq!(heatmap.len(), 7);
+        assert_eq!(heatmap[0], vec![Some(0), Some(4)]); // Mondays
+        assert_eq!(heatmap[1], vec![Some(0), Some(1)]); // Tuesdays
+        assert_eq!(heatmap[2], vec![Some(2), Some(0)]); // Wednesdays, including today
+        assert_eq!(heatmap[3], vec![Some(0), None]); // Tomorrow is in the future
+
+        assert_eq!(activity.total(), 13);
+        assert_eq!(activity.busiest_day(), Some((date(3, 4), 8)));
+        assert_eq!(activity.busiest_hour(), Some(21));
+        let sparkline: Vec<char> = activity.sparkline().chars().collect();
+        assert_eq!((sparkline[0], sparkline[9], sparkline[21]), (' ', '▁', '█'));
+    }
+
+    #[test]
+    fn test_month_labels() {
+        let activity = Activity {
+            today: date(3, 6),
+            days: HashMap::new(),
+            hours: [0; 24],
+        };
+        // Weeks starting Jan 22, Jan 29, Feb 5, ..., Mar 4
+        assert_eq!(activity.month_labels(7), "  Feb  ");
+        assert_eq!(activity.month_labels(7).chars().count(), 7);
+    }
+}
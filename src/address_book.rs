@@ -0,0 +1,162 @@
+use crate::error::{Error, Result};
+use crate::formatter::normalize_identifier;
+use crate::i18n::t;
+use crate::sender::run_script;
+
+/// Separates fields in the people list printed by `LIST_PEOPLE_SCRIPT`.
+const FIELD_SEPARATOR: char = '\t';
+
+/// Separates phone numbers and emails within the last field.
+const IDENTIFIER_SEPARATOR: char = '|';
+
+/// Prints one line per person in Contacts.app: name, first name, last name, and their phone
+/// numbers and emails.
+const LIST_PEOPLE_SCRIPT: &str = r#"
+    set output to ""
+    tell application "Contacts"
+        repeat with p in people
+            set fullName to name of p
+            set firstName to first name of p
+            if firstName is missing value then set firstName to ""
+            set lastName to last name of p
+            if lastName is missing value then set lastName to ""
+            set handles to ""
+            repeat with ph in phones of p
+                set handles to handles & (value of ph) & "|"
+            end repeat
+            repeat with em in emails of p
+                set handles to handles & (value of em) & "|"
+            end repeat
+            set output to output & fullName & tab & firstName & tab & lastName & tab & handles & linefeed
+        end repeat
+    end tell
+    return output
+"#;
+
+/// Colors given to contacts' initials, picked by name so each contact keeps theirs. These are
+/// ratatui color names.
+const AVATAR_COLORS: &[&str] = &[
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "lightred",
+    "lightgreen",
+    "lightblue",
+    "lightmagenta",
+];
+
+/// A person in Contacts.app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Person {
+    /// The name Contacts.app shows, e.g. "Jane Appleseed".
+    pub name: String,
+    /// Initials for the person's avatar, like Messages.app shows for contacts without a photo.
+    pub initials: String,
+    /// Phone numbers and emails, normalized, in the order Contacts.app lists them.
+    pub identifiers: Vec<String>,
+    /// The name im saved the person under, when read back from a vCard it exported.
+    pub contact_name: Option<String>,
+}
+
+impl Person {
+    /// Color for the person's initials.
+    pub fn color(&self) -> &'static str {
+        avatar_color(&self.name)
+    }
+}
+
+/// List the people in Contacts.app who have a phone number or email.
+pub fn list_people() -> Result<Vec<Person>> {
+    let output = run_script(LIST_PEOPLE_SCRIPT, &[])?
+        .map_err(|error| Error::Generic(t!("address_book.read_failed", error = error.trim())))?;
+    Ok(parse_people(&output))
+}
+
+/// Parse the output of `LIST_PEOPLE_SCRIPT`.
+fn parse_people(output: &str) -> Vec<Person> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(FIELD_SEPARATOR);
+            let name = fields.next()?.trim().to_string();
+            let first_name = fields.next()?;
+            let last_name = fields.next()?;
+            let mut identifiers: Vec<String> = Vec::new();
+            let handles = fields.next()?.split(IDENTIFIER_SEPARATOR);
+            for identifier in handles.filter(|handle| !handle.trim().is_empty()) {
+                let identifier = normalize_identifier(identifier);
+                if !identifiers.contains(&identifier) {
+                    identifiers.push(identifier);
+                }
+            }
+            if name.is_empty() || identifiers.is_empty() {
+                return None;
+            }
+            Some(Person {
+                initials: initials(first_name, last_name, &name),
+                name,
+                identifiers,
+                contact_name: None,
+            })
+        })
+        .collect()
+}
+
+/// Initials from a first and last name, or the first two words of the full name when
+/// Contacts.app doesn't split it.
+pub fn initials(first_name: &str, last_name: &str, name: &str) -> String {
+    let first_letter = |word: &str| word.trim().chars().next();
+    let letters: String = match (first_letter(first_name), first_letter(last_name)) {
+        (None, None) => name
+            .split_whitespace()
+            .take(2)
+            .filter_map(first_letter)
+            .collect(),
+        (first, last) => first.into_iter().chain(last).collect(),
+    };
+    letters.to_uppercase()
+}
+
+/// Pick a color for a contact's initials from their name.
+pub fn avatar_color(name: &str) -> &'static str {
+    // FNV-1a, so the color doesn't change between runs or Rust versions
+    let hash = name
+        .to_lowercase()
+        .bytes()
+        .fold(0x811c9dc5u32, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x01000193)
+        });
+    AVATAR_COLORS[hash as usize % AVATAR_COLORS.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_people() {
+        let output = "Jane Appleseed\tJane\tAppleseed\t(301) 555-1234|jane@example.com|\n\
+                      Acme Corp\t\t\tinfo@acme.com|\n\
+                      No Handles\tNo\tHandles\t\n";
+        let people = parse_people(output);
+        assert_eq!(people.len(), 2);
+        assert_eq!(people[0].name, "Jane Appleseed");
+        assert_eq!(people[0].initials, "JA");
+        assert_eq!(
+            people[0].identifiers,
+            vec!["+13015551234", "jane@example.com"]
+        );
+        assert_eq!(people[1].initials, "AC");
+    }
+
+    #[test]
+    fn test_initials() {
+        assert_eq!(initials("émile", "zola", ""), "ÉZ");
+        assert_eq!(initials("Cher", "", "Cher"), "C");
+        assert_eq!(initials("", "", "mom"), "M");
+        assert_eq!(avatar_color("Jane"), avatar_color("jane"));
+    }
+}
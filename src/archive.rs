@@ -0,0 +1,281 @@
+use crate::db::{DateUnit, Message, SCHEMA};
+use crate::error::{Error, Result};
+use crate::i18n::t;
+use crate::paths;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+
+/// Version of the JSON archive format, written to every archive.
+const ARCHIVE_VERSION: u32 = 1;
+
+/// Bytes every SQLite database file starts with.
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// A conversation exported as JSON, which `im open-archive` can browse without chat.db.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    /// Phone number or email of the contact.
+    pub identifier: String,
+    /// The contact's name when the archive was written.
+    pub name: String,
+    /// Messages oldest first.
+    pub messages: Vec<ArchivedMessage>,
+}
+
+/// A message in a JSON archive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedMessage {
+    /// ROWID of the message in chat.db when it was exported.
+    pub rowid: i64,
+    pub guid: String,
+    pub text: Option<String>,
+    /// When the message was sent or received, as a Unix timestamp.
+    pub date: i64,
+    /// A label for non-text messages (e.g., "Image" or "Audio Message").
+    #[serde(default)]
+    pub message_type: Option<String>,
+    pub is_from_me: bool,
+    #[serde(default)]
+    pub service: Option<String>,
+}
+
+impl From<&Message> for ArchivedMessage {
+    fn from(message: &Message) -> Self {
+        Self {
+            rowid: message.rowid,
+            guid: message.guid.clone(),
+            text: message.text.clone(),
+            date: message.date.timestamp(),
+            message_type: message.message_type.clone(),
+            is_from_me: message.is_from_me,
+            service: message.service.clone(),
+        }
+    }
+}
+
+impl Archive {
+    /// Create an archive of a conversation with no messages yet.
+    pub fn new(identifier: &str, name: &str) -> Self {
+        Self {
+            version: ARCHIVE_VERSION,
+            identifier: identifier.to_string(),
+            name: name.to_string(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Read an archive from a JSON file.
+    pub fn read(path: &Path) -> Result<Self> {
+        let archive: Self =
+            serde_json::from_reader(BufReader::new(File::open(path)?)).map_err(|e| {
+                Error::Generic(t!("archive.not_archive", path = path.display(), error = e))
+            })?;
+        if archive.version > ARCHIVE_VERSION {
+            return Err(Error::Generic(t!(
+                "archive.newer_version",
+                path = path.display()
+            )));
+        }
+        Ok(archive)
+    }
+
+    /// Write the archive to a JSON file, replacing it.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        serde_json::to_writer_pretty(BufWriter::new(File::create(path)?), self)
+            .map_err(std::io::Error::from)?;
+        Ok(())
+    }
+}
+
+/// A conversation from an archive, ready to browse in the chat view.
+pub struct OpenedArchive {
+    /// The database in the shape of chat.db to query.
+    pub database: PathBuf,
+    /// The contact whose messages to show, and their name if the archive records it.
+    pub identifier: String,
+    pub name: Option<String>,
+    /// Whether `database` was built from a JSON archive and is removed when dropped.
+    temporary: bool,
+}
+
+impl Drop for OpenedArchive {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = fs::remove_file(&self.database);
+        }
+    }
+}
+
+/// Open an archive to browse: a JSON archive written by `im export --format json`, or a copy
+/// of chat.db with the conversation with `contact` in it.
+pub fn open(path: &Path, contact: Option<&str>) -> Result<OpenedArchive> {
+    if is_sqlite(path)? {
+        let identifier = contact.ok_or_else(|| {
+            Error::Generic(t!("archive.name_conversation", path = path.display()))
+        })?;
+        return Ok(OpenedArchive {
+            database: path.to_path_buf(),
+            identifier: identifier.to_string(),
+            name: None,
+            temporary: false,
+        });
+    }
+
+    let archive = Archive::read(path)?;
+    let database = paths::process_database("archive")?;
+    build_database(&archive, &database)?;
+    Ok(OpenedArchive {
+        database,
+        identifier: archive.identifier,
+        name: Some(archive.name),
+        temporary: true,
+    })
+}
+
+/// Whether the file at `path` is a SQLite database.
+fn is_sqlite(path: &Path) -> Result<bool> {
+    let mut header = [0; SQLITE_HEADER.len()];
+    let read = File::open(path)?.read(&mut header)?;
+    Ok(read == header.len() && header == SQLITE_HEADER)
+}
+
+/// Write a database in the shape of chat.db at `path` holding the archived conversation,
+/// replacing any file already there.
+fn build_database(archive: &Archive, path: &Path) -> Result<()> {
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+
+    let tx = conn.transaction()?;
+    let service = archive
+        .messages
+        .iter()
+        .rev()
+        .find_map(|message| message.service.as_deref())
+        .unwrap_or("iMessage");
+    tx.execute(
+        "INSERT INTO handle (ROWID, id, service) VALUES (1, ?1, ?2)",
+        params![archive.identifier, service],
+    )?;
+    tx.execute(
+        "INSERT INTO chat (ROWID, guid, style, chat_identifier, service_name, display_name)
+         VALUES (1, ?1, 45, ?2, ?3, '')",
+        params![
+            format!("{};-;{}", service, archive.identifier),
+            archive.identifier,
+            service
+        ],
+    )?;
+    tx.execute(
+        "INSERT INTO chat_handle_join (chat_id, handle_id) VALUES (1, 1)",
+        [],
+    )?;
+
+    {
+        let mut insert_message = tx.prepare(
+            "INSERT INTO message (ROWID, guid, text, handle_id, service, date, date_read,
+                                  date_delivered, is_from_me, is_read, is_audio_message,
+                                  cache_has_attachments, balloon_bundle_id, item_type)
+             VALUES (?1, ?2, ?3, 1, ?4, ?5, ?5, ?5, ?6, 1, ?7, ?8, ?9, ?10)",
+        )?;
+        let mut insert_join = tx.prepare(
+            "INSERT INTO chat_message_join (chat_id, message_id, message_date) VALUES (1, ?1, ?2)",
+        )?;
+        let mut insert_video =
+            tx.prepare("INSERT INTO attachment (guid, mime_type) VALUES (?1, 'video/mp4')")?;
+        let mut insert_attachment_join = tx.prepare(
+            "INSERT INTO message_attachment_join (message_id, attachment_id)
+             VALUES (?1, last_insert_rowid())",
+        )?;
+
+        for message in &archive.messages {
+            // Set the columns chat.db derives each message type from
+            let kind = message.message_type.as_deref();
+            let date = DateUnit::Nanoseconds.unix_to_date(message.date);
+            insert_message.execute(params![
+                message.rowid,
+                message.guid,
+                message.text,
+                message.service,
+                date,
+                message.is_from_me,
+                kind == Some("Audio Message"),
+                matches!(kind, Some("Image" | "Video")),
+                (kind == Some("iMessage Effect")).then_some("archived"),
+                i64::from(kind == Some("Special Message")),
+            ])?;
+            insert_join.execute(params![message.rowid, date])?;
+            if kind == Some("Video") {
+                insert_video.execute(params![format!("{}-video", message.guid)])?;
+                insert_attachment_join.execute(params![message.rowid])?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MessageDB;
+    use std::env;
+
+    fn message(rowid: i64, text: Option<&str>, message_type: Option<&str>) -> ArchivedMessage {
+        ArchivedMessage {
+            rowid,
+            guid: format!("GUID-{}", rowid),
+            text: text.map(String::from),
+            date: 1_700_000_000 + rowid * 60,
+            message_type: message_type.map(String::from),
+            is_from_me: rowid % 2 == 0,
+            service: Some("iMessage".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_open_json_archive() {
+        let dir = env::temp_dir().join(format!("im-archive-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("mom.json");
+
+        let mut archive = Archive::new("+15551234567", "Mom");
+        archive.messages = vec![
+            message(3, Some("running late"), None),
+            message(8, Some("on my way"), None),
+            message(9, None, Some("Video")),
+            message(12, None, Some("Audio Message")),
+        ];
+        archive.write(&path).unwrap();
+
+        let opened = open(&path, None).unwrap();
+        assert_eq!(opened.identifier, "+15551234567");
+        assert_eq!(opened.name.as_deref(), Some("Mom"));
+
+        let db = MessageDB::open_at(&opened.database).unwrap();
+        let messages = db.get_all_messages("+15551234567", 0).unwrap();
+        let restored: Vec<ArchivedMessage> = messages.iter().map(ArchivedMessage::from).collect();
+        assert_eq!(restored, archive.messages);
+        assert_eq!(db.get_conversations(10).unwrap().len(), 1);
+
+        // The built database belongs to the opened archive, but a chat.db copy doesn't
+        let database = opened.database.clone();
+        drop(db);
+        drop(opened);
+        assert!(!database.exists());
+
+        let copy = dir.join("chat.db");
+        build_database(&archive, &copy).unwrap();
+        assert!(open(&copy, None).is_err());
+        drop(open(&copy, Some("+15551234567")).unwrap());
+        assert!(copy.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
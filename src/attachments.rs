@@ -0,0 +1,415 @@
+use crate::config::Config;
+use crate::db::{Attachment, Message, MessageDB};
+use crate::error::{Error, Result};
+use crate::formatter::format_display_number;
+use crate::i18n::t;
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::Command;
+
+/// Name of the manifest written next to exported attachments.
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// File extensions used to classify attachments that have no MIME type.
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "heic", "heif", "webp", "tiff"];
+const VIDEO_EXTENSIONS: &[&str] = &["mov", "mp4", "m4v"];
+const AUDIO_EXTENSIONS: &[&str] = &["caf", "m4a", "mp3", "amr", "wav"];
+
+/// Kinds of attachment an export can be limited to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachmentKind {
+    /// Photos, screenshots, and stickers
+    Image,
+    /// Videos
+    Video,
+    /// Voice memos and other audio
+    Audio,
+    /// Everything else, such as PDFs and contact cards
+    Other,
+}
+
+impl AttachmentKind {
+    /// Classify an attachment by its MIME type, or its file extension when there is none.
+    pub fn of(attachment: &Attachment) -> Self {
+        if let Some(mime_type) = &attachment.mime_type {
+            return match mime_type.split('/').next() {
+                Some("image") => AttachmentKind::Image,
+                Some("video") => AttachmentKind::Video,
+                Some("audio") => AttachmentKind::Audio,
+                _ => AttachmentKind::Other,
+            };
+        }
+
+        let extension = attachment
+            .path
+            .as_deref()
+            .and_then(Path::extension)
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+            AttachmentKind::Image
+        } else if VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+            AttachmentKind::Video
+        } else if AUDIO_EXTENSIONS.contains(&extension.as_str()) {
+            AttachmentKind::Audio
+        } else {
+            AttachmentKind::Other
+        }
+    }
+}
+
+/// Open an attachment in its default app, or videos in QuickTime Player.
+pub fn open_attachment(attachment: &Attachment) -> Result<()> {
+    let Some(path) = attachment.path.as_deref().filter(|path| path.is_file()) else {
+        return Err(Error::Generic(t!("attachments.missing")));
+    };
+
+    let mut command = Command::new("open");
+    if AttachmentKind::of(attachment) == AttachmentKind::Video {
+        command.args(["-a", "QuickTime Player"]);
+    }
+    let status = command.arg(path).status()?;
+    if !status.success() {
+        return Err(Error::Generic(t!(
+            "attachments.open_failed",
+            path = path.display()
+        )));
+    }
+    Ok(())
+}
+
+/// One attachment as recorded in the export manifest.
+#[derive(Debug, Serialize)]
+struct ManifestEntry {
+    /// Name of the copied file in the output directory, or `None` if it is no longer on disk.
+    file: Option<String>,
+    /// The original file name.
+    name: String,
+    /// Where Messages.app stored the file.
+    source: Option<String>,
+    mime_type: Option<String>,
+    kind: AttachmentKind,
+    /// When the message carrying the attachment was sent, in RFC 3339.
+    date: String,
+    from_me: bool,
+    message_guid: String,
+    attachment_guid: String,
+}
+
+/// Counts from an attachment export.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ExportSummary {
+    /// Attachments copied to the output directory.
+    pub copied: usize,
+    /// Attachments skipped because the file is no longer on disk.
+    pub missing: usize,
+}
+
+/// Copy a conversation's attachments into a flat directory, with a manifest describing each one.
+///
+/// Only attachments of `kind` are exported when it is given, and only those sent at or after
+/// `since` (a Unix timestamp).
+pub fn export_attachments(
+    db: &MessageDB,
+    handles: &[String],
+    kind: Option<AttachmentKind>,
+    since: i64,
+    output: &Path,
+) -> Result<ExportSummary> {
+    fs::create_dir_all(output)?;
+
+    let mut summary = ExportSummary::default();
+    let mut manifest = Vec::new();
+    let mut taken = HashSet::new();
+    taken.insert(MANIFEST_NAME.to_string());
+
+    for (message, attachment) in db.get_conversation_attachments(handles, since)? {
+        let attachment_kind = AttachmentKind::of(&attachment);
+        if kind.is_some_and(|kind| kind != attachment_kind) {
+            continue;
+        }
+
+        let name = original_name(&attachment);
+        let file = match attachment.path.as_deref().filter(|path| path.is_file()) {
+            Some(path) => {
+                let file_name = export_file_name(&message.date, &name, &mut taken);
+                fs::copy(path, output.join(&file_name))?;
+                summary.copied += 1;
+                Some(file_name)
+            }
+            None => {
+                summary.missing += 1;
+                None
+            }
+        };
+
+        manifest.push(manifest_entry(
+            &message,
+            attachment,
+            attachment_kind,
+            name,
+            file,
+        ));
+    }
+
+    let mut writer = BufWriter::new(File::create(output.join(MANIFEST_NAME))?);
+    serde_json::to_writer_pretty(&mut writer, &manifest).map_err(io::Error::from)?;
+    writeln!(writer)?;
+    writer.flush()?;
+
+    Ok(summary)
+}
+
+/// How much disk space one conversation's attachments take.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttachmentUsage {
+    /// The chat identifier: a handle for one-on-one chats, or a group chat's id
+    pub identifier: String,
+    /// The name of a named group chat
+    pub chat_name: Option<String>,
+    /// Attachments exchanged in the conversation
+    pub count: usize,
+    /// Attachments no longer on disk, such as ones kept only in iCloud
+    pub missing: usize,
+    /// Total size of the attachments still on disk
+    pub bytes: u64,
+}
+
+impl AttachmentUsage {
+    /// The name to show for the conversation.
+    pub fn name(&self, config: &Config) -> String {
+        self.chat_name
+            .clone()
+            .or_else(|| config.display_name_for(&self.identifier))
+            .unwrap_or_else(|| format_display_number(&self.identifier))
+    }
+}
+
+/// Measure every conversation's attachments on disk, largest first.
+pub fn attachment_usage(db: &MessageDB) -> Result<Vec<AttachmentUsage>> {
+    let sizes =
+        db.get_chat_attachments()?
+            .into_iter()
+            .map(|(identifier, chat_name, attachment)| {
+                let size = attachment
+                    .path
+                    .and_then(|path| fs::metadata(path).ok())
+                    .filter(|metadata| metadata.is_file())
+                    .map(|metadata| metadata.len());
+                (identifier, chat_name, size)
+            });
+    Ok(summarize_usage(sizes))
+}
+
+/// Total attachment sizes by conversation, largest first. A size of `None` is an attachment
+/// that is no longer on disk.
+///
+/// Chats sharing an identifier, like the SMS and iMessage threads with one number, are
+/// counted together.
+fn summarize_usage(
+    sizes: impl IntoIterator<Item = (String, Option<String>, Option<u64>)>,
+) -> Vec<AttachmentUsage> {
+    let mut usage: HashMap<String, AttachmentUsage> = HashMap::new();
+    for (identifier, chat_name, size) in sizes {
+        let entry = usage
+            .entry(identifier.clone())
+            .or_insert_with(|| AttachmentUsage {
+                identifier,
+                ..Default::default()
+            });
+        entry.chat_name = entry.chat_name.take().or(chat_name);
+        entry.count += 1;
+        match size {
+            Some(size) => entry.bytes += size,
+            None => entry.missing += 1,
+        }
+    }
+
+    let mut usage: Vec<AttachmentUsage> = usage.into_values().collect();
+    usage.sort_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.identifier.cmp(&b.identifier))
+    });
+    usage
+}
+
+/// Format a number of bytes for people, like "1.5 MB", in the decimal units Finder uses.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while size >= 999.95 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Build the manifest record for an attachment.
+fn manifest_entry(
+    message: &Message,
+    attachment: Attachment,
+    kind: AttachmentKind,
+    name: String,
+    file: Option<String>,
+) -> ManifestEntry {
+    ManifestEntry {
+        file,
+        name,
+        source: attachment
+            .path
+            .map(|path| path.to_string_lossy().into_owned()),
+        mime_type: attachment.mime_type,
+        kind,
+        date: message.date.to_rfc3339(),
+        from_me: message.is_from_me,
+        message_guid: message.guid.clone(),
+        attachment_guid: attachment.guid,
+    }
+}
+
+/// The name an attachment was sent with, falling back to its file name on disk.
+fn original_name(attachment: &Attachment) -> String {
+    attachment
+        .name
+        .clone()
+        .or_else(|| {
+            attachment
+                .path
+                .as_deref()
+                .and_then(Path::file_name)
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "attachment".to_string())
+}
+
+/// Name an exported file after when it was sent and its original name, so files sort by date.
+///
+/// Names already in `taken` get a numeric suffix; the comparison ignores case because the macOS
+/// file system does.
+fn export_file_name(date: &DateTime<Local>, name: &str, taken: &mut HashSet<String>) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name.as_str(), String::new()),
+    };
+    let prefix = date.format("%Y-%m-%d_%H-%M-%S");
+
+    let mut candidate = format!("{}_{}{}", prefix, stem, extension);
+    let mut counter = 2;
+    while !taken.insert(candidate.to_lowercase()) {
+        candidate = format!("{}_{}-{}{}", prefix, stem, counter, extension);
+        counter += 1;
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::path::PathBuf;
+
+    fn attachment(path: &str, mime_type: Option<&str>) -> Attachment {
+        Attachment {
+            guid: "AT_0_ABC".to_string(),
+            path: Some(PathBuf::from(path)),
+            mime_type: mime_type.map(str::to_string),
+            name: None,
+        }
+    }
+
+    #[test]
+    fn test_attachment_kind() {
+        let kind = |path, mime_type| AttachmentKind::of(&attachment(path, mime_type));
+        assert_eq!(
+            kind("IMG_1.HEIC", Some("image/heic")),
+            AttachmentKind::Image
+        );
+        assert_eq!(
+            kind("a.mov", Some("video/quicktime")),
+            AttachmentKind::Video
+        );
+        assert_eq!(
+            kind("a.pdf", Some("application/pdf")),
+            AttachmentKind::Other
+        );
+        assert_eq!(kind("Audio Message.caf", None), AttachmentKind::Audio);
+        assert_eq!(kind("IMG_2.JPG", None), AttachmentKind::Image);
+        assert_eq!(kind("notes", None), AttachmentKind::Other);
+    }
+
+    #[test]
+    fn test_summarize_usage() {
+        let group = Some("Family".to_string());
+        let usage = summarize_usage([
+            ("+15551234567".to_string(), None, Some(2_000)),
+            ("chat123".to_string(), group.clone(), Some(500)),
+            ("+15551234567".to_string(), None, None),
+            ("chat123".to_string(), None, Some(4_000)),
+        ]);
+
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage[0].identifier, "chat123");
+        assert_eq!(usage[0].chat_name, group);
+        assert_eq!(
+            (usage[0].count, usage[0].missing, usage[0].bytes),
+            (2, 0, 4_500)
+        );
+        assert_eq!(
+            (usage[1].count, usage[1].missing, usage[1].bytes),
+            (2, 1, 2_000)
+        );
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(999), "999 B");
+        assert_eq!(format_size(1_500), "1.5 KB");
+        assert_eq!(format_size(999_990), "1.0 MB");
+        assert_eq!(format_size(23_400_000), "23.4 MB");
+        assert_eq!(format_size(5_000_000_000_000_000), "5000.0 TB");
+    }
+
+    #[test]
+    fn test_export_file_name() {
+        let date = Local.with_ymd_and_hms(2024, 3, 5, 14, 22, 10).unwrap();
+        let mut taken = HashSet::new();
+
+        assert_eq!(
+            export_file_name(&date, "IMG_1234.HEIC", &mut taken),
+            "2024-03-05_14-22-10_IMG_1234.HEIC"
+        );
+        assert_eq!(
+            export_file_name(&date, "img_1234.heic", &mut taken),
+            "2024-03-05_14-22-10_img_1234-2.heic"
+        );
+        assert_eq!(
+            export_file_name(&date, "a/b:c", &mut taken),
+            "2024-03-05_14-22-10_a_b_c"
+        );
+        assert_eq!(
+            export_file_name(&date, ".profile", &mut taken),
+            "2024-03-05_14-22-10_.profile"
+        );
+    }
+}
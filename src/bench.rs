@@ -0,0 +1,272 @@
+use crate::db::{MessageDB, SCHEMA};
+use crate::error::{Error, Result};
+use crate::i18n::t;
+use crate::search::SearchQuery;
+use crate::tui::PAGE_SIZE;
+use rusqlite::{params, Connection};
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Words synthetic messages are made of.
+const WORDS: &[&str] = &[
+    "hey", "are", "you", "free", "for", "dinner", "tonight", "running", "late", "see", "soon",
+    "lol", "sounds", "good", "what", "time", "works", "tomorrow", "call", "me", "when", "home",
+];
+
+/// Seconds between synthetic messages.
+const MESSAGE_INTERVAL_SECS: i64 = 60;
+
+/// Share of the newest incoming messages left unread, as one in this many.
+const UNREAD_EVERY: usize = 20;
+
+/// The conversation the benchmarks query in a synthetic chat.db.
+pub struct BenchChat {
+    pub handles: Vec<String>,
+    pub identifier: String,
+    /// A message halfway through the conversation, where pagination and unread counts start.
+    pub middle_rowid: i64,
+}
+
+/// A query measured by the benchmarks, returning how many rows it read.
+pub struct BenchQuery {
+    pub name: &'static str,
+    pub run: fn(&MessageDB, &BenchChat) -> Result<usize>,
+}
+
+/// The queries the chat view and inbox run most.
+pub const QUERIES: &[BenchQuery] = &[
+    BenchQuery {
+        name: "get_messages",
+        run: |db, chat| Ok(db.get_messages(&chat.handles, PAGE_SIZE)?.len()),
+    },
+    BenchQuery {
+        name: "get_messages_before",
+        run: |db, chat| {
+            Ok(db
+                .get_messages_before(&chat.handles, chat.middle_rowid, PAGE_SIZE)?
+                .len())
+        },
+    },
+    BenchQuery {
+        name: "search_messages",
+        run: |db, chat| {
+            let search = SearchQuery::parse("dinner tonight")?;
+            Ok(db.search_messages(&chat.handles, &search, PAGE_SIZE)?.len())
+        },
+    },
+    BenchQuery {
+        name: "search_messages_filtered",
+        run: |db, chat| {
+            let search = SearchQuery::parse("from:them has:link")?;
+            Ok(db.search_messages(&chat.handles, &search, PAGE_SIZE)?.len())
+        },
+    },
+    BenchQuery {
+        name: "get_conversations",
+        run: |db, _| Ok(db.get_conversations(PAGE_SIZE)?.len()),
+    },
+    BenchQuery {
+        name: "count_incoming_since",
+        run: |db, chat| db.count_incoming_since(&chat.identifier, chat.middle_rowid),
+    },
+    BenchQuery {
+        name: "get_unread_summary",
+        run: |db, _| Ok(db.get_unread_summary()?.total),
+    },
+];
+
+/// Timings of one query over several runs.
+pub struct BenchResult {
+    pub name: &'static str,
+    pub rows: usize,
+    pub mean: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+/// Write a chat.db at `path` with `conversations` one-on-one chats of `messages` messages each.
+/// A file already at `path` is only replaced if `overwrite` is set.
+///
+/// Messages from all chats are interleaved, as they are in a real database, and the newest
+/// incoming ones are sometimes unread. Returns the first conversation.
+pub fn create_synthetic_db(
+    path: &Path,
+    conversations: usize,
+    messages: usize,
+    overwrite: bool,
+) -> Result<BenchChat> {
+    if overwrite && path.exists() {
+        fs::remove_file(path)?;
+    }
+    // Claim the path first, so a file that appears in the meantime isn't written over either
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => {
+                Error::Generic(t!("bench.exists", path = path.display()))
+            }
+            _ => e.into(),
+        })?;
+    let mut conn = Connection::open(path)?;
+    conn.execute_batch(SCHEMA)?;
+
+    let tx = conn.transaction()?;
+    for i in 0..conversations {
+        let identifier = handle_id(i);
+        tx.execute(
+            "INSERT INTO handle (ROWID, id, service) VALUES (?1, ?2, 'iMessage')",
+            params![i as i64 + 1, identifier],
+        )?;
+        tx.execute(
+            "INSERT INTO chat (ROWID, guid, style, chat_identifier, service_name, display_name)
+             VALUES (?1, ?2, 45, ?3, 'iMessage', '')",
+            params![
+                i as i64 + 1,
+                format!("iMessage;-;{}", identifier),
+                identifier
+            ],
+        )?;
+        tx.execute(
+            "INSERT INTO chat_handle_join (chat_id, handle_id) VALUES (?1, ?1)",
+            params![i as i64 + 1],
+        )?;
+    }
+
+    let total = conversations * messages;
+    {
+        let mut insert_message = tx.prepare(
+            "INSERT INTO message (ROWID, guid, text, handle_id, service, date, date_read,
+                                  date_delivered, is_from_me, is_read, cache_has_attachments)
+             VALUES (?1, ?2, ?3, ?4, 'iMessage', ?5, ?6, ?5, ?7, ?8, 0)",
+        )?;
+        let mut insert_join = tx.prepare(
+            "INSERT INTO chat_message_join (chat_id, message_id, message_date) VALUES (?1, ?2, ?3)",
+        )?;
+        for n in 0..total {
+            let rowid = n as i64 + 1;
+            let chat_id = (n % conversations) as i64 + 1;
+            // Apple's epoch is 2001-01-01, in nanoseconds
+            let date = n as i64 * MESSAGE_INTERVAL_SECS * 1_000_000_000;
+            let is_from_me = n.is_multiple_of(3);
+            let is_read = is_from_me || n < total - total / UNREAD_EVERY || n.is_multiple_of(2);
+            insert_message.execute(params![
+                rowid,
+                format!("BENCH-{:08}", rowid),
+                message_text(n),
+                chat_id,
+                date,
+                if is_read { date } else { 0 },
+                is_from_me,
+                is_read,
+            ])?;
+            insert_join.execute(params![chat_id, rowid, date])?;
+        }
+    }
+    tx.commit()?;
+
+    let handles = vec![handle_id(0)];
+    let middle_rowid = (messages / 2 * conversations) as i64 + 1;
+    Ok(BenchChat {
+        identifier: handles[0].clone(),
+        handles,
+        middle_rowid,
+    })
+}
+
+/// Run each query once to warm the statement cache, then `iterations` more times.
+pub fn run(db: &MessageDB, chat: &BenchChat, iterations: usize) -> Result<Vec<BenchResult>> {
+    let iterations = iterations.max(1);
+    let mut results = Vec::new();
+
+    for query in QUERIES {
+        let rows = (query.run)(db, chat)?;
+        let mut total = Duration::ZERO;
+        let mut min = Duration::MAX;
+        let mut max = Duration::ZERO;
+        for _ in 0..iterations {
+            let start = Instant::now();
+            (query.run)(db, chat)?;
+            let elapsed = start.elapsed();
+            total += elapsed;
+            min = min.min(elapsed);
+            max = max.max(elapsed);
+        }
+        results.push(BenchResult {
+            name: query.name,
+            rows,
+            mean: total / iterations as u32,
+            min,
+            max,
+        });
+    }
+
+    Ok(results)
+}
+
+/// Phone number of the `i`th synthetic contact.
+fn handle_id(i: usize) -> String {
+    format!("+1555{:07}", i)
+}
+
+/// Text of the `n`th synthetic message; every tenth has a link.
+fn message_text(n: usize) -> String {
+    let length = 3 + n % 8;
+    let mut text: Vec<&str> = (0..length)
+        .map(|i| WORDS[(n * 7 + i * 13) % WORDS.len()])
+        .collect();
+    if n.is_multiple_of(10) {
+        text.push("https://example.com");
+    }
+    text.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_synthetic_db() {
+        let path = env::temp_dir().join(format!("im-bench-test-{}.db", std::process::id()));
+        let chat = create_synthetic_db(&path, 5, 200, true).unwrap();
+        // An existing file is left alone without `overwrite`
+        assert!(create_synthetic_db(&path, 5, 200, false).is_err());
+        let mut db = MessageDB::open_at(&path).unwrap();
+
+        let results = run(&db, &chat, 1).unwrap();
+        let rows = |name| results.iter().find(|r| r.name == name).unwrap().rows;
+        assert_eq!(rows("get_messages"), PAGE_SIZE);
+        assert_eq!(rows("get_messages_before"), PAGE_SIZE);
+        assert!(rows("search_messages") > 0);
+        assert!(rows("search_messages_filtered") > 0);
+        assert_eq!(rows("get_conversations"), 5);
+        assert!(rows("count_incoming_since") > 0);
+        assert!(rows("get_unread_summary") > 0);
+
+        let messages = db
+            .get_messages_before(&chat.handles, chat.middle_rowid, 500)
+            .unwrap();
+        assert_eq!(messages.len(), 100);
+        assert!(messages.iter().all(|m| m.rowid < chat.middle_rowid));
+
+        assert_eq!(db.missing_indexes().unwrap(), Vec::<String>::new());
+        assert_eq!(db.find_full_scans(&chat.handles).unwrap(), vec![]);
+
+        drop(db);
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch("DROP INDEX message_idx_handle;")
+            .unwrap();
+        let mut db = MessageDB::open_at(&path).unwrap();
+        assert_eq!(db.missing_indexes().unwrap(), vec!["message(handle_id)"]);
+        let scans = db.find_full_scans(&chat.handles).unwrap();
+        assert!(scans.iter().any(|scan| scan.query == "get_messages"));
+
+        drop(db);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::i18n::t;
+use crate::notifications::notify;
+use crate::state::StateStore;
+use chrono::{Datelike, Local, NaiveDate, Timelike};
+
+/// Settings key holding the last day birthday reminders were posted, so they're only posted
+/// once a day however many views are open.
+const REMINDED_SETTING: &str = "birthdays_reminded";
+
+/// Placeholder in `birthdays.template` replaced with the contact's first name.
+const NAME_PLACEHOLDER: &str = "{name}";
+
+/// Parse a birthday typed by the user, `MM-DD` or `YYYY-MM-DD`, into the form it's stored in.
+pub fn parse_birthday(value: &str) -> Result<String> {
+    let value = value.trim();
+    let invalid = || Error::Generic(t!("birthdays.invalid", value = value));
+    match value.len() {
+        // 2000 is a leap year, so February 29 parses
+        5 => NaiveDate::parse_from_str(&format!("2000-{}", value), "%Y-%m-%d")
+            .map(|date| date.format("%m-%d").to_string())
+            .map_err(|_| invalid()),
+        10 => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .map_err(|_| invalid()),
+        _ => Err(invalid()),
+    }
+}
+
+/// Month and day of a stored birthday.
+fn month_day(birthday: &str) -> Option<(u32, u32)> {
+    let (month, day) = birthday.rsplit_once('-')?;
+    let month = month.rsplit('-').next()?;
+    Some((month.parse().ok()?, day.parse().ok()?))
+}
+
+/// Whether `date` is the birthday. February 29 birthdays fall on February 28 in other years.
+pub fn is_birthday(birthday: &str, date: NaiveDate) -> bool {
+    match month_day(birthday) {
+        Some((2, 29)) if NaiveDate::from_ymd_opt(date.year(), 2, 29).is_none() => {
+            (date.month(), date.day()) == (2, 28)
+        }
+        Some(month_day) => month_day == (date.month(), date.day()),
+        None => false,
+    }
+}
+
+/// Days from `date` until the next birthday, 0 if it's today.
+pub fn days_until(birthday: &str, date: NaiveDate) -> Option<i64> {
+    (0..=366).find(|days| is_birthday(birthday, date + chrono::Duration::days(*days)))
+}
+
+/// The birthday message for a contact, from `birthdays.template`.
+pub fn birthday_message(template: &str, name: &str) -> String {
+    let first_name = name.split_whitespace().next().unwrap_or(name);
+    template.replace(NAME_PLACEHOLDER, first_name)
+}
+
+/// Names of contacts whose birthday is `date`.
+pub fn birthdays_on(config: &Config, date: NaiveDate) -> Vec<String> {
+    config
+        .list_contacts()
+        .into_iter()
+        .filter(|(_, entry)| {
+            entry
+                .birthday
+                .as_deref()
+                .is_some_and(|birthday| is_birthday(birthday, date))
+        })
+        .map(|(name, entry)| entry.display_name.clone().unwrap_or_else(|| name.clone()))
+        .collect()
+}
+
+/// Posts a notification for each birthday once a day, after `birthdays.notify_hour`.
+#[derive(Default)]
+pub struct BirthdayReminder {
+    /// Day reminders were last checked for, to skip the state database the rest of the day
+    checked: Option<NaiveDate>,
+}
+
+impl BirthdayReminder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Post today's reminders if it's time and no view has yet.
+    pub fn check(&mut self, config: &Config, state: &StateStore) -> Result<()> {
+        let now = Local::now();
+        let today = now.date_naive();
+        if self.checked == Some(today) || now.hour() < config.birthdays().notify_hour {
+            return Ok(());
+        }
+        self.checked = Some(today);
+        remind(config, state, today)
+    }
+}
+
+/// Post a notification for each of `date`'s birthdays, unless they were already posted.
+pub fn remind(config: &Config, state: &StateStore, date: NaiveDate) -> Result<()> {
+    let day = date.to_string();
+    if state.get_setting(REMINDED_SETTING)?.as_deref() == Some(day.as_str()) {
+        return Ok(());
+    }
+    for name in birthdays_on(config, date) {
+        notify("🎂 Birthday", &format!("It's {}'s birthday today", name))?;
+    }
+    state.set_setting(REMINDED_SETTING, &day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_parse_birthday() {
+        assert_eq!(parse_birthday("3-31").ok(), None);
+        assert_eq!(parse_birthday("03-31").unwrap(), "03-31");
+        assert_eq!(parse_birthday("02-29").unwrap(), "02-29");
+        assert_eq!(parse_birthday(" 1990-03-31 ").unwrap(), "1990-03-31");
+        assert!(parse_birthday("1991-02-29").is_err());
+        assert!(parse_birthday("13-01").is_err());
+    }
+
+    #[test]
+    fn test_is_birthday() {
+        assert!(is_birthday("03-31", date(2024, 3, 31)));
+        assert!(is_birthday("1990-03-31", date(2024, 3, 31)));
+        assert!(!is_birthday("03-31", date(2024, 3, 30)));
+        assert!(is_birthday("02-29", date(2024, 2, 29)));
+        assert!(!is_birthday("02-29", date(2024, 2, 28)));
+        assert!(is_birthday("02-29", date(2023, 2, 28)));
+        assert_eq!(days_until("01-02", date(2023, 12, 31)), Some(2));
+        assert_eq!(days_until("12-31", date(2023, 12, 31)), Some(0));
+    }
+
+    #[test]
+    fn test_birthday_message() {
+        assert_eq!(
+            birthday_message("Happy birthday, {name}! 🎂", "Jane Appleseed"),
+            "Happy birthday, Jane! 🎂"
+        );
+        assert_eq!(birthday_message("HBD", "mom"), "HBD");
+    }
+}
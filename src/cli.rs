@@ -1,8 +1,15 @@
+use crate::attachments::AttachmentKind;
+use crate::export::ExportFormat;
+use crate::status::DEFAULT_FORMAT;
+use chrono::NaiveDate;
 use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+use std::path::PathBuf;
 
 /// im - a tool for sending and receiving iMessages in the terminal
 #[derive(Parser)]
 #[command(
+    name = "im",
     version,
     author = "Justin Wang",
     about = "send and receive iMessages in the terminal"
@@ -24,6 +31,24 @@ pub struct Cli {
     #[arg(short, long)]
     pub verbose: bool,
 
+    /// Leave out confirmations and other informational output, for scripts. Errors are still
+    /// printed to stderr.
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Open the default contact instead of resuming the last conversation.
+    #[arg(long)]
+    pub fresh: bool,
+
+    /// Write messages to a sandbox log instead of sending them, to demo im or test hooks safely.
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Count what's sent as sent by a script, against the rate limit for automated messages.
+    /// Setting IM_AUTOMATED does the same.
+    #[arg(long, global = true)]
+    pub automated: bool,
+
     /// Optional contact name to fetch messages from. Uses contacts from the configuration.
     #[arg(value_name = "CONTACT_NAME")]
     pub contact_name: Option<String>,
@@ -49,6 +74,10 @@ pub enum Commands {
         /// Optional display name for the contact
         #[arg(short, long)]
         display_name: Option<String>,
+
+        /// Update an existing contact without asking
+        #[arg(short, long)]
+        force: bool,
     },
 
     /// Remove a contact from the configuration
@@ -58,9 +87,425 @@ pub enum Commands {
         name: String,
     },
 
-    /// List all configured contacts
-    Contacts,
+    /// List all configured contacts, or manage them with a subcommand
+    Contacts {
+        #[command(subcommand)]
+        action: Option<ContactsCommand>,
+    },
+
+    /// Reply to whoever most recently messaged you
+    Reply {
+        /// Message text to send
+        #[arg(value_name = "TEXT")]
+        text: String,
+    },
+
+    /// Reply to the conversation a specific message belongs to, e.g. from a notification action
+    ReplyTo {
+        /// Guid of the message being replied to (shown by `im last --verbose`)
+        #[arg(value_name = "GUID")]
+        guid: String,
+
+        /// Message text to send
+        #[arg(value_name = "TEXT")]
+        text: String,
+    },
+
+    /// Start a conversation with a phone number or email, even one you've never messaged
+    New {
+        /// Phone number or email to message
+        #[arg(value_name = "IDENTIFIER")]
+        identifier: String,
+
+        /// Send this as the first message instead of opening the chat
+        #[arg(value_name = "TEXT")]
+        text: Option<String>,
+    },
+
+    /// Send a note to yourself, to your own phone number or email
+    Me {
+        /// Text of the note
+        #[arg(value_name = "TEXT")]
+        text: String,
+    },
+
+    /// Print the most recent incoming message
+    Last,
+
+    /// Browse recent conversations
+    Inbox,
+
+    /// List starred messages across all conversations
+    Starred {
+        /// Print starred messages instead of opening the viewer
+        #[arg(short, long)]
+        print: bool,
+    },
+
+    /// Browse messages by tag, or list the tags in use
+    Tags {
+        /// Tag to show messages with, like `receipt`
+        #[arg(value_name = "TAG")]
+        tag: Option<String>,
+
+        /// Print the messages, or the tags, instead of opening the viewer
+        #[arg(short, long)]
+        print: bool,
+    },
+
+    /// List contacts' upcoming birthdays
+    Birthdays {
+        /// Post a notification for each of today's birthdays instead, once a day. Meant to be
+        /// run every morning from launchd or cron
+        #[arg(short, long)]
+        notify: bool,
+    },
+
+    /// Export a conversation to a mailbox that mail tools can index, or to a JSON archive
+    Export {
+        /// Contact name, alias, phone number, or email
+        #[arg(value_name = "CONTACT")]
+        contact: String,
+
+        /// Format to write
+        #[arg(short, long, value_enum, default_value = "mbox")]
+        format: ExportFormat,
+
+        /// File (mbox or JSON) or directory (Maildir) to write to
+        #[arg(short, long, value_name = "PATH")]
+        output: PathBuf,
+
+        /// Only add the messages sent since the last export to the same path
+        #[arg(short, long)]
+        incremental: bool,
+    },
+
+    /// Browse an exported JSON archive, or a copy of chat.db, in the chat view
+    #[command(name = "open-archive")]
+    OpenArchive {
+        /// JSON archive written by `im export --format json`, or a copy of chat.db
+        #[arg(value_name = "FILE")]
+        path: PathBuf,
+
+        /// Conversation to open in a copy of chat.db: contact name, alias, phone number, or email
+        #[arg(value_name = "CONTACT")]
+        contact: Option<String>,
+    },
+
+    /// Try the inbox and chat view on made-up conversations, without chat.db or permissions
+    Demo,
+
+    /// Summarize a conversation with the command set in the [summarize] section
+    Summarize {
+        /// Contact name, alias, phone number, or email
+        #[arg(value_name = "CONTACT")]
+        contact: String,
+
+        /// Only summarize messages sent on or after this date (YYYY-MM-DD)
+        #[arg(short, long, value_name = "DATE")]
+        since: Option<NaiveDate>,
+    },
+
+    /// Show how active a conversation has been over the last year
+    Stats {
+        /// Contact name, alias, phone number, or email
+        #[arg(value_name = "CONTACT")]
+        contact: String,
+
+        /// Also print messages per day as a heatmap, one column per week
+        #[arg(long)]
+        heatmap: bool,
+
+        /// Also print the words and emoji used most, leaving out common words like "the"
+        #[arg(long)]
+        words: bool,
+    },
+
+    /// Work with the files sent in a conversation
+    Attachments {
+        #[command(subcommand)]
+        action: AttachmentsCommand,
+    },
+
+    /// Work with the Messages accounts messages can be sent from
+    Accounts {
+        #[command(subcommand)]
+        action: AccountsCommand,
+    },
+
+    /// Run a read-only SQL statement against chat.db and print the rows
+    Query {
+        /// The statement; apple_date(), apple_to_unix(), and unix_to_apple() convert chat.db dates
+        #[arg(value_name = "SQL")]
+        sql: String,
+
+        /// Print the rows as a JSON array of objects instead of tab-separated lines
+        #[arg(short, long)]
+        json: bool,
+    },
+
+    /// Print unread counts for a tmux status line or shell prompt
+    Status {
+        /// Text to print, with #{unread_total}, #{unread_chats}, and #{latest_sender} filled in
+        #[arg(short, long, default_value = DEFAULT_FORMAT)]
+        format: String,
+    },
+
+    /// Show the path to the configuration file, or create or check it with a subcommand
+    Config {
+        #[command(subcommand)]
+        action: Option<ConfigCommand>,
+    },
+
+    /// Show where im keeps its configuration, data, caches, and logs
+    Paths {
+        /// Print only this path, e.g. `cache` or `state_db`
+        name: Option<String>,
+    },
+
+    /// Generate a shell completion script
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// List contact names for shell completion
+    #[command(name = "__complete-contacts", hide = true)]
+    CompleteContacts,
+
+    /// Time the main chat.db queries against a large synthetic database
+    #[command(name = "bench-db", hide = true)]
+    BenchDb {
+        /// Number of conversations to generate
+        #[arg(long, default_value_t = 200)]
+        conversations: usize,
+
+        /// Number of messages in each conversation
+        #[arg(long, default_value_t = 1000)]
+        messages: usize,
+
+        /// Number of timed runs of each query
+        #[arg(short, long, default_value_t = 20)]
+        iterations: usize,
+
+        /// Where to write the synthetic database (defaults to the temporary directory)
+        #[arg(long, value_name = "PATH")]
+        db: Option<PathBuf>,
+
+        /// Replace the file given with --db if it already exists
+        #[arg(long, requires = "db")]
+        overwrite: bool,
+    },
+}
+
+/// Subcommands for managing contacts
+#[derive(Subcommand)]
+pub enum ContactsCommand {
+    /// Add alternate names for a contact
+    Alias {
+        /// Name of the contact
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Alternate names that refer to the contact
+        #[arg(value_name = "ALIAS", required = true)]
+        aliases: Vec<String>,
+    },
 
-    /// Show the path to the configuration file
-    Config,
+    /// Add another phone number or email for a contact, merging its messages into the contact's chat
+    Link {
+        /// Name of the contact
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Other phone number or email the contact uses
+        #[arg(value_name = "IDENTIFIER")]
+        identifier: String,
+    },
+
+    /// Remove a phone number or email added with `link`
+    Unlink {
+        /// Identifier to remove
+        #[arg(value_name = "IDENTIFIER")]
+        identifier: String,
+    },
+
+    /// Find contacts with the same identifier and merge them
+    Dedupe {
+        /// Merge every group into its first contact without prompting
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Restore the most recently removed contact
+    Undo,
+
+    /// Check that each contact's identifier has message history in Messages.app
+    Verify,
+
+    /// Add people from Contacts.app or a vCard file, and give contacts their initials and
+    /// avatar colors
+    Import {
+        /// vCard (.vcf) file to read instead of Contacts.app
+        #[arg(value_name = "FILE")]
+        file: Option<PathBuf>,
+    },
+
+    /// Write contacts as CSV, or as vCards with --vcf
+    Export {
+        /// Write vCards that other address books can import
+        #[arg(long)]
+        vcf: bool,
+
+        /// File to write instead of stdout
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+
+    /// Add contacts from a CSV file of names and phone numbers or emails
+    ImportCsv {
+        /// CSV file to read
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Column with the names, counting from 1
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        name_col: usize,
+
+        /// Column with the phone numbers or emails, counting from 1
+        #[arg(long, value_name = "N", default_value_t = 2)]
+        phone_col: usize,
+
+        /// Add the contacts without asking after the preview
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Remove an alternate name
+    Unalias {
+        /// Alias to remove
+        #[arg(value_name = "ALIAS")]
+        alias: String,
+    },
+
+    /// Show, set, or clear a contact's birthday
+    Birthday {
+        /// Name of the contact
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Birthday as MM-DD, or YYYY-MM-DD if you know the year
+        #[arg(value_name = "DATE", conflicts_with = "clear")]
+        date: Option<String>,
+
+        /// Forget the contact's birthday
+        #[arg(long)]
+        clear: bool,
+    },
+
+    /// Manage canned replies shown above the composer in a contact's chat
+    Quickreply {
+        #[command(subcommand)]
+        action: QuickReplyCommand,
+    },
+}
+
+/// Subcommands for a contact's quick replies
+#[derive(Subcommand)]
+pub enum QuickReplyCommand {
+    /// List a contact's quick replies by number
+    List {
+        /// Name of the contact
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+
+    /// Add a quick reply, sent with the next free Alt+number in the chat
+    Add {
+        /// Name of the contact
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Text of the reply
+        #[arg(value_name = "TEXT")]
+        text: String,
+    },
+
+    /// Change the text of a quick reply
+    Edit {
+        /// Name of the contact
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Number of the reply, as shown by `list`
+        #[arg(value_name = "NUMBER")]
+        number: usize,
+
+        /// New text of the reply
+        #[arg(value_name = "TEXT")]
+        text: String,
+    },
+
+    /// Remove a quick reply; later ones move up a number
+    Remove {
+        /// Name of the contact
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Number of the reply, as shown by `list`
+        #[arg(value_name = "NUMBER")]
+        number: usize,
+    },
+}
+
+/// Subcommands for conversation attachments
+#[derive(Subcommand)]
+pub enum AttachmentsCommand {
+    /// Copy a conversation's attachments into a directory, with a manifest.json
+    Export {
+        /// Contact name, alias, phone number, or email
+        #[arg(value_name = "CONTACT")]
+        contact: String,
+
+        /// Only export attachments of this kind
+        #[arg(short = 't', long = "type", value_enum)]
+        kind: Option<AttachmentKind>,
+
+        /// Only export attachments sent on or after this date (YYYY-MM-DD)
+        #[arg(short, long, value_name = "DATE")]
+        since: Option<NaiveDate>,
+
+        /// Directory to copy the attachments into
+        #[arg(short, long, value_name = "DIR")]
+        out: PathBuf,
+    },
+
+    /// Show how much disk space each conversation's attachments take
+    Usage {
+        /// Print the usage, largest first, instead of opening the table
+        #[arg(short, long)]
+        print: bool,
+    },
+}
+
+/// Subcommands for the configuration file
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Write a configuration file listing every option with its default and what it does
+    Init {
+        /// Replace a configuration file that has settings or contacts, keeping it as a backup
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check the configuration file for unknown keys, values of the wrong type, and outdated
+    /// contacts
+    Validate,
+}
+
+/// Subcommands for Messages accounts
+#[derive(Subcommand)]
+pub enum AccountsCommand {
+    /// List the accounts signed in to Messages.app; the configured one is marked with *
+    List,
 }
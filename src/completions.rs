@@ -0,0 +1,64 @@
+use crate::cli::Cli;
+use crate::config::Config;
+use clap::CommandFactory;
+use clap_complete::{generate, Shell};
+use std::io;
+
+/// Bash wrapper adding contact names to the first positional argument.
+const BASH_CONTACTS: &str = r#"
+_im_with_contacts() {
+    _im "$@"
+    if [[ ${COMP_CWORD} -eq 1 ]]; then
+        local contacts
+        contacts="$(im __complete-contacts 2>/dev/null)"
+        COMPREPLY+=( $(compgen -W "${contacts}" -- "${COMP_WORDS[1]}") )
+    fi
+}
+complete -F _im_with_contacts -o bashdefault -o default im
+"#;
+
+/// Zsh wrapper adding contact names to the first positional argument.
+const ZSH_CONTACTS: &str = r#"
+_im_with_contacts() {
+    _im "$@"
+    if (( CURRENT == 2 )); then
+        local -a contacts
+        contacts=(${(f)"$(im __complete-contacts 2>/dev/null)"})
+        compadd -a contacts
+    fi
+}
+compdef _im_with_contacts im
+"#;
+
+/// Fish completion adding contact names to the first positional argument.
+const FISH_CONTACTS: &str = r#"
+complete -c im -n "__fish_use_subcommand" -f -a "(im __complete-contacts 2>/dev/null)"
+"#;
+
+/// Print the completion script for a shell, including dynamic contact names where supported.
+pub fn print_completions(shell: Shell) {
+    let mut cmd = Cli::command();
+    let name = cmd.get_name().to_string();
+    generate(shell, &mut cmd, name, &mut io::stdout());
+
+    match shell {
+        Shell::Bash => print!("{}", BASH_CONTACTS),
+        Shell::Zsh => print!("{}", ZSH_CONTACTS),
+        Shell::Fish => print!("{}", FISH_CONTACTS),
+        _ => {}
+    }
+}
+
+/// Print configured contact names and aliases, one per line, for the completion scripts.
+pub fn print_contact_names(config: &Config) {
+    let mut names: Vec<&String> = config
+        .list_contacts()
+        .into_iter()
+        .map(|(name, _)| name)
+        .chain(config.list_aliases())
+        .collect();
+    names.sort();
+    for name in names {
+        println!("{}", name);
+    }
+}
@@ -1,72 +1,713 @@
+use crate::address_book::{avatar_color, Person};
+use crate::config_env::{self, Override};
+use crate::db::{self, DbAccess};
 use crate::error::{Error, Result};
+use crate::formatter::normalize_identifier;
+use crate::i18n::t;
+use crate::paths;
+use crate::timestamps::TimestampStyle;
 use crate::APP_NAME;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::env;
 use std::path::PathBuf;
 
 /// Configuration for the application.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Config {
     /// The default contact identifier (e.g., phone number or email).
     default_contact: Option<String>,
     /// The display name for the default contact.
     default_display_name: Option<String>,
-    /// Map of named contacts to their identifiers.
-    #[serde(default)]
+    /// Map of normalized contact names (see `contact_key`) to their entries.
+    #[serde(default, deserialize_with = "deserialize_contacts")]
     contacts: HashMap<String, ContactEntry>,
+    /// Map of alternate names to the normalized names of the contacts they refer to.
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+    /// Inbox settings.
+    #[serde(default)]
+    inbox: InboxConfig,
+    /// Chat view settings.
+    #[serde(default)]
+    chat: ChatConfig,
+    /// Pane sizes for multi-pane views.
+    #[serde(default)]
+    layout: LayoutConfig,
+    /// How message times are shown.
+    #[serde(default)]
+    time: TimeConfig,
+    /// Sending settings.
+    #[serde(default)]
+    sender: SenderConfig,
+    /// How often views redraw and check for new messages.
+    #[serde(default)]
+    refresh: RefreshConfig,
+    /// Desktop notifications for new messages.
+    #[serde(default)]
+    notifications: NotificationConfig,
+    /// Reply suggestions from an external command.
+    #[serde(default)]
+    suggestions: SuggestionConfig,
+    /// Conversation summaries from an external command.
+    #[serde(default)]
+    summarize: SummarizeConfig,
+    /// Translations of incoming messages from an external command.
+    #[serde(default)]
+    translate: TranslateConfig,
+    /// Birthday reminders.
+    #[serde(default)]
+    birthdays: BirthdayConfig,
+    /// GIF search for the `/gif` composer command.
+    #[serde(default)]
+    gif: GifConfig,
+    /// Recording for the `/voice` composer command.
+    #[serde(default)]
+    voice: VoiceConfig,
+    /// How chat.db is read.
+    #[serde(default)]
+    db: DbConfig,
+    /// Plain-text logs of the messages shown in chats.
+    #[serde(default)]
+    transcript: TranscriptConfig,
+    /// Language for messages (e.g., "fr"). Defaults to the language in the environment.
+    #[serde(default)]
+    language: Option<String>,
+    /// Keys set by `IM_*` environment variables over the file, which saving leaves out.
+    #[serde(skip)]
+    env: Vec<Override>,
+}
+
+/// Convert the configuration to TOML, as it's written to the file.
+fn to_toml(config: &Config) -> Result<toml::Value> {
+    toml::Value::try_from(config)
+        .map_err(|e| Error::Generic(t!("config.convert_failed", error = e)))
+}
+
+/// Pane sizes for multi-pane views.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LayoutConfig {
+    /// Width of the inbox conversation list, as a percentage of the screen.
+    pub inbox_list_percent: u16,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            inbox_list_percent: 40,
+        }
+    }
+}
+
+/// Settings for the chat view.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ChatConfig {
+    /// Render Markdown-style formatting (code, emphasis, lists) in message text.
+    pub markdown: bool,
+    /// Show messages from all of a contact's identifiers (e.g., their iMessage
+    /// email and SMS number) in one timeline.
+    pub merge_identifiers: bool,
+    /// Show image attachments inline as small previews.
+    pub previews: bool,
+    /// Which key sends a message; the other Enter combinations insert a line break.
+    pub send_key: SendKey,
+    /// Most messages a chat keeps in memory. The oldest are dropped as new ones arrive and
+    /// loaded again when scrolling back to them.
+    pub max_messages: usize,
+    /// How messages to contacts marked `careful` are confirmed before they're sent.
+    pub careful_send: CarefulSend,
+    /// Where chats open, unless the contact sets `open_at`.
+    pub open_at: OpenAt,
+    /// Messages and pastes longer than this many bytes ask whether to send them as a text
+    /// file instead. 0 never asks.
+    pub large_message_bytes: usize,
+    /// Messages longer than this many characters are sent as several, split at paragraph
+    /// breaks where they can be. 0 sends them whole.
+    pub split_messages_at: usize,
+}
+
+/// Which key sends a message from the composer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SendKey {
+    /// Enter sends; Shift+Enter or Alt+Enter inserts a line break
+    Enter,
+    /// Ctrl+Enter sends; Enter inserts a line break. Needs a terminal with the enhanced
+    /// keyboard protocol, otherwise Enter sends.
+    CtrlEnter,
+}
+
+/// How a message to a careful contact is confirmed before it's sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CarefulSend {
+    /// Ask before sending each message, answered with `y`
+    #[default]
+    Ask,
+    /// Only Ctrl+Enter sends; Enter inserts a line break. Without the enhanced keyboard
+    /// protocol, asks instead.
+    CtrlEnter,
+}
+
+/// Where a chat is scrolled to when it opens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OpenAt {
+    /// The latest message
+    #[default]
+    Latest,
+    /// The first message received since the chat was last open, or the latest message if
+    /// there are none
+    FirstUnread,
+}
+
+impl OpenAt {
+    /// The name of the position, as written in the configuration file.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OpenAt::Latest => "latest",
+            OpenAt::FirstUnread => "first-unread",
+        }
+    }
+
+    /// Read a position from its name in the configuration file.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [OpenAt::Latest, OpenAt::FirstUnread]
+            .into_iter()
+            .find(|open_at| open_at.as_str() == name)
+    }
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            markdown: true,
+            merge_identifiers: true,
+            previews: true,
+            send_key: SendKey::Enter,
+            max_messages: 1000,
+            careful_send: CarefulSend::Ask,
+            open_at: OpenAt::Latest,
+            large_message_bytes: 10_000,
+            split_messages_at: 0,
+        }
+    }
+}
+
+/// Settings for sending messages.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SenderConfig {
+    /// Messages account to send from, by id or description (see `im accounts list`).
+    /// Defaults to the first iMessage account.
+    pub account: Option<String>,
+    /// Own phone number or email to send from, when the Apple ID can send as either.
+    pub send_as: Option<String>,
+    /// Own phone number or email that notes to self are sent to. Defaults to the address most
+    /// chats were sent to.
+    pub me: Option<String>,
+    /// Most times to try sending a message when Messages.app isn't available, including the
+    /// first.
+    pub max_attempts: u32,
+    /// Seconds to wait before retrying a message; each later retry waits twice as long.
+    pub retry_delay_secs: u64,
+    /// Seconds after sending a message during which sending the same text again asks first;
+    /// 0 never asks.
+    pub duplicate_window_secs: u64,
+    /// Most messages scripts may send in a minute; more wait in the spool. 0 is no limit.
+    pub automated_per_minute: u32,
+    /// Most messages scripts may send in an hour; more wait in the spool. 0 is no limit.
+    pub automated_per_hour: u32,
+}
+
+impl Default for SenderConfig {
+    fn default() -> Self {
+        Self {
+            account: None,
+            send_as: None,
+            me: None,
+            max_attempts: 3,
+            retry_delay_secs: 2,
+            duplicate_window_secs: 5,
+            automated_per_minute: 10,
+            automated_per_hour: 60,
+        }
+    }
+}
+
+/// How chat.db is read.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct DbConfig {
+    /// `live` queries Messages.app's database directly; `snapshot` queries a private copy,
+    /// taken again whenever Messages.app writes to it.
+    pub access: DbAccess,
+    /// Messages database to read instead of `~/Library/Messages/chat.db`.
+    pub path: Option<PathBuf>,
+}
+
+/// Plain-text logs of the messages shown in chats.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TranscriptConfig {
+    /// Append each message shown or sent in a chat to a log for the conversation in the app
+    /// data directory.
+    pub enabled: bool,
+}
+
+/// How often views redraw and check for new messages.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RefreshConfig {
+    /// Milliseconds between redraws while waiting for keys.
+    pub tick_rate_ms: u64,
+    /// Milliseconds between checks of chat.db for new messages.
+    pub poll_interval_ms: u64,
+    /// Redraw and check less often while the terminal is in the background or nothing has
+    /// happened for `idle_after_secs`.
+    pub power_saving: bool,
+    /// Seconds without keys or new messages before slowing down in power-saving mode.
+    pub idle_after_secs: u64,
+    /// Milliseconds between redraws and checks while slowed down.
+    pub idle_interval_ms: u64,
+}
+
+impl Default for RefreshConfig {
+    fn default() -> Self {
+        Self {
+            tick_rate_ms: 100,
+            poll_interval_ms: 500,
+            power_saving: false,
+            idle_after_secs: 60,
+            idle_interval_ms: 5000,
+        }
+    }
+}
+
+/// Desktop notifications for new messages.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct NotificationConfig {
+    /// Notify about new messages while a chat is open, except ones in the conversation on
+    /// screen while the terminal has focus.
+    pub enabled: bool,
+}
+
+/// Reply suggestions from an external command.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SuggestionConfig {
+    /// Shell command given the latest messages as JSON on stdin, printing one suggested reply
+    /// per line. Suggestions are off when unset.
+    pub command: Option<String>,
+    /// How many of the latest messages to give the command.
+    pub context_messages: usize,
+    /// Seconds to wait for the command before giving up on it.
+    pub timeout_secs: u64,
+}
+
+impl Default for SuggestionConfig {
+    fn default() -> Self {
+        Self {
+            command: None,
+            context_messages: 10,
+            timeout_secs: 10,
+        }
+    }
+}
+
+/// Conversation summaries from an external command.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SummarizeConfig {
+    /// Shell command given a conversation transcript on stdin, printing a summary. Summaries
+    /// are off when unset.
+    pub command: Option<String>,
+    /// Longest transcript given to the command at once, in characters. Longer conversations
+    /// are summarized in parts, then the parts' summaries are summarized.
+    pub chunk_chars: usize,
+    /// Seconds to wait for each run of the command before giving up on it.
+    pub timeout_secs: u64,
+}
+
+impl Default for SummarizeConfig {
+    fn default() -> Self {
+        Self {
+            command: None,
+            chunk_chars: 12_000,
+            timeout_secs: 120,
+        }
+    }
+}
+
+/// Translations of incoming messages from an external command.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TranslateConfig {
+    /// Shell command given a message's text on stdin, printing its translation. Used for
+    /// contacts with `translate = true`.
+    pub command: Option<String>,
+    /// Seconds to wait for the command before giving up on a message.
+    pub timeout_secs: u64,
+}
+
+impl Default for TranslateConfig {
+    fn default() -> Self {
+        Self {
+            command: None,
+            timeout_secs: 30,
+        }
+    }
+}
+
+/// GIF search for the `/gif` composer command.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct GifConfig {
+    /// Shell command given the search on stdin, printing the URL or path of a GIF.
+    pub command: Option<String>,
+    /// Giphy API key, used to search Giphy when no command is set.
+    pub api_key: Option<String>,
+    /// Seconds to wait for the search and download before giving up.
+    pub timeout_secs: u64,
+}
+
+impl Default for GifConfig {
+    fn default() -> Self {
+        Self {
+            command: None,
+            api_key: None,
+            timeout_secs: 15,
+        }
+    }
+}
+
+/// Recording for the `/voice` composer command.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct VoiceConfig {
+    /// Shell command that records the default microphone to the WAV file named by
+    /// `$IM_VOICE_FILE` until it's interrupted.
+    pub command: String,
+    /// Longest voice memo, in seconds; recording stops on its own after this.
+    pub max_secs: u64,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        Self {
+            command: r#"sox -q -d -c 1 "$IM_VOICE_FILE""#.to_string(),
+            max_secs: 300,
+        }
+    }
+}
+
+/// Birthday reminders for contacts with a `birthday`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BirthdayConfig {
+    /// Hour of the day (0-23) from which open views post the day's birthday reminders, when
+    /// `notifications.enabled` is set.
+    pub notify_hour: u32,
+    /// Message sent with `b` in the inbox; `{name}` is replaced with the contact's first name.
+    pub template: String,
+}
+
+impl Default for BirthdayConfig {
+    fn default() -> Self {
+        Self {
+            notify_hour: 9,
+            template: "Happy birthday, {name}! 🎂".to_string(),
+        }
+    }
+}
+
+/// How message times are shown.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TimeConfig {
+    /// Show times relative to now ("2m ago") or as clock times.
+    pub style: TimestampStyle,
+    /// `strftime` format for clock times.
+    pub time_format: String,
+    /// `strftime` format for dates older than a week in relative times.
+    pub date_format: String,
+    /// Locale for month and weekday names (e.g., "fr_FR"). Defaults to the
+    /// `LC_ALL`, `LC_TIME`, or `LANG` environment variable.
+    pub locale: Option<String>,
+}
+
+impl Default for TimeConfig {
+    fn default() -> Self {
+        Self {
+            style: TimestampStyle::Absolute,
+            time_format: "%H:%M".to_string(),
+            date_format: "%b %d".to_string(),
+            locale: None,
+        }
+    }
+}
+
+/// Settings for the inbox view.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct InboxConfig {
+    /// Move short codes and automated senders into a separate "Other" section.
+    pub filter_automated: bool,
+    /// Extra identifiers (or parts of them) to treat as automated senders.
+    pub automated_senders: Vec<String>,
+}
+
+impl Default for InboxConfig {
+    fn default() -> Self {
+        Self {
+            filter_automated: true,
+            automated_senders: Vec::new(),
+        }
+    }
+}
+
+/// Most quick replies a contact can have, one for each of Alt+1 to Alt+9.
+pub const MAX_QUICK_REPLIES: usize = 9;
+
+/// Check a quick reply's text, trimming surrounding whitespace.
+fn quick_reply_text(text: &str) -> Result<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(Error::Generic(t!("contacts.quick_reply_empty")));
+    }
+    if text.contains('\n') {
+        return Err(Error::Generic(t!("contacts.quick_reply_one_line")));
+    }
+    Ok(text.to_string())
 }
 
 /// A contact entry in the contacts map.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ContactEntry {
+    /// The contact's name as the user typed it.
+    ///
+    /// Older configuration files don't have this; it's filled in from the map key on load.
+    #[serde(default)]
+    pub name: String,
     /// The contact identifier (e.g., phone number or email).
     pub identifier: String,
     /// Optional display name for the contact.
     pub display_name: Option<String>,
+    /// Other phone numbers or emails for the same person, whose messages are
+    /// shown in the same chat.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub other_identifiers: Vec<String>,
+    /// Messages account to send to this contact from, overriding `sender.account`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// Own phone number or email to send to this contact from, overriding `sender.send_as`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_as: Option<String>,
+    /// Canned replies offered above the composer in this contact's chat.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quick_replies: Vec<String>,
+    /// Show translations of this contact's messages, from `translate.command`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub translate: bool,
+    /// Initials shown as the contact's avatar, imported from Contacts.app.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initials: Option<String>,
+    /// Background color of the contact's avatar, as a color name like "cyan" or a hex code.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// The contact's birthday, `MM-DD` or `YYYY-MM-DD`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub birthday: Option<String>,
+    /// Confirm each message to this contact before it's sent, as `chat.careful_send` says.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub careful: bool,
+    /// Where this contact's chat opens, overriding `chat.open_at`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_at: Option<OpenAt>,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            default_contact: None,
-            default_display_name: None,
-            contacts: HashMap::new(),
+impl ContactEntry {
+    /// All of the contact's identifiers, the main one first.
+    pub fn identifiers(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.identifier).chain(&self.other_identifiers)
+    }
+
+    /// Fold another entry for the same person into this one: its identifiers become other
+    /// identifiers, its quick replies are added, and its settings fill in ones this lacks.
+    fn absorb(&mut self, other: ContactEntry) {
+        for identifier in std::iter::once(other.identifier).chain(other.other_identifiers) {
+            if !self.identifiers().any(|existing| *existing == identifier) {
+                self.other_identifiers.push(identifier);
+            }
+        }
+        for reply in other.quick_replies {
+            if !self.quick_replies.contains(&reply) {
+                self.quick_replies.push(reply);
+            }
+        }
+        self.display_name = self.display_name.take().or(other.display_name);
+        self.account = self.account.take().or(other.account);
+        self.send_as = self.send_as.take().or(other.send_as);
+        self.initials = self.initials.take().or(other.initials);
+        self.color = self.color.take().or(other.color);
+        self.birthday = self.birthday.take().or(other.birthday);
+        self.open_at = self.open_at.or(other.open_at);
+        self.translate |= other.translate;
+        self.careful |= other.careful;
+    }
+}
+
+/// What importing a person from Contacts.app did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportedPerson {
+    /// They were added as a new contact.
+    Added,
+    /// An existing contact with one of their phone numbers or emails got their avatar.
+    Updated,
+    /// An existing contact already had their avatar.
+    Unchanged,
+    /// A different contact already has their name.
+    Skipped,
+}
+
+/// A field of a saved contact that adding a contact under the same name would change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContactChange {
+    pub field: ContactField,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Fields of a contact set by `add_contact`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactField {
+    Name,
+    Identifier,
+    DisplayName,
+}
+
+/// A contact removed from the configuration, kept so the removal can be undone.
+#[derive(Debug, Clone)]
+pub struct RemovedContact {
+    /// The removed contact.
+    pub entry: ContactEntry,
+    /// Alternate names that referred to the contact.
+    pub aliases: Vec<String>,
+}
+
+/// Normalize a contact name or alias for lookups, so "Mom" and " mom" are the same contact.
+fn contact_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Read the contacts map, re-keying entries by their normalized name.
+///
+/// Older configuration files keyed contacts by the name as typed, so entries
+/// may lack a saved name or have names that differ only in case. Those are
+/// merged into the first in sorted order, so none of their identifiers or
+/// settings are lost.
+fn deserialize_contacts<'de, D>(
+    deserializer: D,
+) -> std::result::Result<HashMap<String, ContactEntry>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut entries: Vec<(String, ContactEntry)> =
+        HashMap::<String, ContactEntry>::deserialize(deserializer)?
+            .into_iter()
+            .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut contacts: HashMap<String, ContactEntry> = HashMap::new();
+    for (key, mut entry) in entries {
+        if entry.name.is_empty() {
+            entry.name = key.trim().to_string();
+        }
+        match contacts.entry(contact_key(&entry.name)) {
+            Entry::Occupied(mut kept) => kept.get_mut().absorb(entry),
+            Entry::Vacant(slot) => {
+                slot.insert(entry);
+            }
         }
     }
+    Ok(contacts)
 }
 
 impl Config {
     /// Load configuration from disk.
+    ///
+    /// `IM_*` environment variables override keys from the file (see `config_env::apply`).
     pub fn load() -> Result<Self> {
-        match confy::load(APP_NAME, None) {
-            Ok(config) => Ok(config),
+        match confy::load::<Self>(APP_NAME, None) {
+            Ok(config) => config.with_env(env::vars()),
             Err(e) => {
                 // Get the config path for error reporting
-                let path = confy::get_configuration_file_path(APP_NAME, None)
-                    .unwrap_or_else(|_| PathBuf::from("unknown path"));
+                let path = paths::config_file().unwrap_or_else(|| PathBuf::from("unknown path"));
 
                 // Try to read the raw file contents
-                let contents = std::fs::read_to_string(&path)
-                    .unwrap_or_else(|_| "Could not read file".to_string());
-
-                Err(Error::Generic(format!(
-                    "Failed to load config from {}: {}\nFile contents:\n{}",
-                    path.display(),
-                    e,
-                    contents
+                let contents =
+                    std::fs::read_to_string(&path).unwrap_or_else(|_| t!("config.unreadable"));
+
+                Err(Error::Generic(t!(
+                    "config.load_failed",
+                    path = path.display(),
+                    error = e,
+                    contents = contents
                 )))
             }
         }
     }
 
-    /// Save configuration to disk.
+    /// Layer environment variables over the configuration.
+    fn with_env(self, vars: impl IntoIterator<Item = (String, String)>) -> Result<Self> {
+        let mut value = to_toml(&self)?;
+        let overrides = config_env::apply(&mut value, vars);
+        if overrides.is_empty() {
+            return Ok(self);
+        }
+        let mut config: Self = value
+            .try_into()
+            .map_err(|e| Error::Generic(t!("config.invalid_environment", error = e)))?;
+        config.env = overrides;
+        Ok(config)
+    }
+
+    /// Save configuration to disk, without the keys environment variables set.
+    ///
+    /// Nothing is written while an archive or the demo is open, since the configuration then
+    /// holds contacts that aren't the user's; changes last until `im` exits.
     pub fn save(&self) -> Result<()> {
-        Ok(confy::store(APP_NAME, None, self)?)
+        if db::is_archive() {
+            return Ok(());
+        }
+        if self.env.is_empty() {
+            return Ok(confy::store(APP_NAME, None, self)?);
+        }
+        let mut value = to_toml(self)?;
+        config_env::restore(&mut value, &self.env);
+        let file: Self = value
+            .try_into()
+            .map_err(|e| Error::Generic(t!("config.restore_failed", error = e)))?;
+        Ok(confy::store(APP_NAME, None, file)?)
+    }
+
+    /// Whether `other` has the same settings and contacts.
+    pub fn same_as(&self, other: &Config) -> bool {
+        matches!((to_toml(self), to_toml(other)), (Ok(a), Ok(b)) if a == b)
+    }
+
+    /// Names of the environment variables that override keys from the file.
+    pub fn env_vars(&self) -> impl Iterator<Item = &str> {
+        self.env.iter().map(|applied| applied.var.as_str())
     }
 
     /// Get the path to the configuration file.
     pub fn config_path() -> Option<PathBuf> {
-        confy::get_configuration_file_path(APP_NAME, None).ok()
+        paths::config_file()
     }
 
     /// Get the default contact identifier.
@@ -89,38 +730,676 @@ impl Config {
         self.default_display_name = Some(name);
     }
 
+    /// What `add_contact` would change on the contact already saved under `name`, if any.
+    pub fn contact_changes(
+        &self,
+        name: &str,
+        identifier: &str,
+        display_name: Option<&str>,
+    ) -> Vec<ContactChange> {
+        let Some(existing) = self.get_contact(name) else {
+            return Vec::new();
+        };
+        let change = |field, before: Option<&str>, after: Option<&str>| {
+            (before != after).then(|| ContactChange {
+                field,
+                before: before.map(String::from),
+                after: after.map(String::from),
+            })
+        };
+        [
+            change(ContactField::Name, Some(&existing.name), Some(name.trim())),
+            change(
+                ContactField::Identifier,
+                Some(&existing.identifier),
+                Some(identifier),
+            ),
+            change(
+                ContactField::DisplayName,
+                existing.display_name.as_deref(),
+                display_name,
+            ),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Name of a contact other than `name` with an identifier that normalizes to the same
+    /// handle as `identifier`.
+    pub fn identifier_taken_by(&self, identifier: &str, name: &str) -> Option<&String> {
+        let handle = normalize_identifier(identifier);
+        self.contacts
+            .values()
+            .filter(|entry| contact_key(&entry.name) != contact_key(name))
+            .find(|entry| {
+                entry
+                    .identifiers()
+                    .any(|id| normalize_identifier(id) == handle)
+            })
+            .map(|entry| &entry.name)
+    }
+
     /// Add or update a named contact.
+    ///
+    /// A contact whose name differs only in case or surrounding whitespace is
+    /// replaced, taking the new casing and keeping its other identifiers.
     pub fn add_contact(&mut self, name: String, identifier: String, display_name: Option<String>) {
+        let name = name.trim().to_string();
+        let key = contact_key(&name);
+        let existing = self.contacts.get(&key);
+        let account = existing.and_then(|entry| entry.account.clone());
+        let send_as = existing.and_then(|entry| entry.send_as.clone());
+        let quick_replies = existing
+            .map(|entry| entry.quick_replies.clone())
+            .unwrap_or_default();
+        let translate = existing.is_some_and(|entry| entry.translate);
+        let initials = existing.and_then(|entry| entry.initials.clone());
+        let color = existing.and_then(|entry| entry.color.clone());
+        let birthday = existing.and_then(|entry| entry.birthday.clone());
+        let careful = existing.is_some_and(|entry| entry.careful);
+        let open_at = existing.and_then(|entry| entry.open_at);
+        let other_identifiers = existing
+            .map(|entry| entry.other_identifiers.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|other| *other != identifier)
+            .collect();
         self.contacts.insert(
-            name,
+            key,
             ContactEntry {
+                name,
                 identifier,
                 display_name,
+                other_identifiers,
+                account,
+                send_as,
+                quick_replies,
+                translate,
+                initials,
+                color,
+                birthday,
+                careful,
+                open_at,
             },
         );
     }
 
-    /// Remove a named contact.
-    pub fn remove_contact(&mut self, name: &str) -> bool {
-        self.contacts.remove(name).is_some()
+    /// Add or update a contact from Contacts.app.
+    ///
+    /// A person whose phone number or email already belongs to a contact only updates that
+    /// contact's avatar; a color set by hand is kept. Otherwise they're added under their full
+    /// name, or the name im exported them with, unless a contact already has that name. A
+    /// full name that differs from the exported name becomes the display name.
+    pub fn import_person(&mut self, person: &Person) -> ImportedPerson {
+        let existing = self.contacts.values_mut().find(|entry| {
+            entry
+                .identifiers()
+                .any(|id| person.identifiers.contains(&normalize_identifier(id)))
+        });
+        if let Some(entry) = existing {
+            let color = entry
+                .color
+                .clone()
+                .unwrap_or_else(|| person.color().to_string());
+            if entry.initials.as_deref() == Some(&person.initials)
+                && entry.color.as_deref() == Some(&color)
+            {
+                return ImportedPerson::Unchanged;
+            }
+            entry.initials = Some(person.initials.clone());
+            entry.color = Some(color);
+            return ImportedPerson::Updated;
+        }
+
+        let name = person.contact_name.as_ref().unwrap_or(&person.name);
+        if self.resolve_contact(name).is_some() {
+            return ImportedPerson::Skipped;
+        }
+        let Some((identifier, others)) = person.identifiers.split_first() else {
+            return ImportedPerson::Skipped;
+        };
+        let display_name = (*name != person.name).then(|| person.name.clone());
+        self.add_contact(name.clone(), identifier.clone(), display_name);
+        let entry = self
+            .contacts
+            .get_mut(&contact_key(name))
+            .expect("contact was just added");
+        entry.other_identifiers = others.to_vec();
+        entry.initials = Some(person.initials.clone());
+        entry.color = Some(person.color().to_string());
+        ImportedPerson::Added
+    }
+
+    /// Add another phone number or email for a contact, returning the contact's name.
+    ///
+    /// Fails if the identifier already belongs to a different contact.
+    pub fn link_identifier(&mut self, name: &str, identifier: &str) -> Result<String> {
+        if let Some((owner, _)) = self.find_contact_by_identifier(identifier) {
+            if contact_key(owner) != contact_key(name) {
+                return Err(Error::Generic(t!(
+                    "contacts.identifier_belongs",
+                    identifier = identifier,
+                    name = owner
+                )));
+            }
+        }
+
+        let Some(entry) = self.contacts.get_mut(&contact_key(name)) else {
+            return Err(Error::ContactNotFound(name.to_string()));
+        };
+        if !entry.identifiers().any(|existing| existing == identifier) {
+            entry.other_identifiers.push(identifier.to_string());
+        }
+        Ok(entry.name.clone())
+    }
+
+    /// Remove one of a contact's other identifiers, returning the contact's name.
+    ///
+    /// A contact's main identifier can't be unlinked.
+    pub fn unlink_identifier(&mut self, identifier: &str) -> Option<String> {
+        self.contacts.values_mut().find_map(|entry| {
+            let before = entry.other_identifiers.len();
+            entry.other_identifiers.retain(|other| other != identifier);
+            (entry.other_identifiers.len() != before).then(|| entry.name.clone())
+        })
+    }
+
+    /// Every identifier of the contact that `identifier` belongs to, the main one
+    /// first, or just `identifier` if it isn't a named contact's.
+    pub fn identifiers_for(&self, identifier: &str) -> Vec<String> {
+        match self.find_contact_by_identifier(identifier) {
+            Some((_, entry)) => entry.identifiers().cloned().collect(),
+            None => vec![identifier.to_string()],
+        }
+    }
+
+    /// Remove a named contact and its aliases, returning them so they can be restored.
+    pub fn take_contact(&mut self, name: &str) -> Option<RemovedContact> {
+        let key = contact_key(name);
+        let entry = self.contacts.remove(&key)?;
+        let mut aliases: Vec<String> = self
+            .aliases
+            .iter()
+            .filter(|(_, target)| contact_key(target) == key)
+            .map(|(alias, _)| alias.clone())
+            .collect();
+        aliases.sort();
+        self.aliases.retain(|_, target| contact_key(target) != key);
+
+        Some(RemovedContact { entry, aliases })
     }
 
-    /// Get a contact by name (case-sensitive).
+    /// Restore a removed contact along with whichever of its aliases are still free.
+    ///
+    /// Fails without changing anything if the name has since been used for a
+    /// contact or alias.
+    pub fn restore_contact(&mut self, removed: RemovedContact) -> Result<()> {
+        let name = &removed.entry.name;
+        if let Some((existing, _)) = self.resolve_contact(name) {
+            return Err(Error::Generic(t!(
+                "contacts.restore_taken",
+                name = name,
+                existing = existing
+            )));
+        }
+
+        let key = contact_key(name);
+        for alias in removed.aliases {
+            if self.resolve_contact(&alias).is_none() {
+                self.aliases.insert(alias, key.clone());
+            }
+        }
+        self.contacts.insert(key, removed.entry);
+        Ok(())
+    }
+
+    /// Get a contact by name, ignoring case and surrounding whitespace.
     pub fn get_contact(&self, name: &str) -> Option<&ContactEntry> {
-        self.contacts.get(name)
+        self.contacts.get(&contact_key(name))
     }
 
-    /// Get a contact by name (case-insensitive).
-    pub fn get_contact_case_insensitive(&self, name: &str) -> Option<(&String, &ContactEntry)> {
-        let lowercase_name = name.to_lowercase();
-        self.contacts
+    /// Resolve a contact by name or alias, returning its name as saved.
+    pub fn resolve_contact(&self, name: &str) -> Option<(&String, &ContactEntry)> {
+        let entry = match self.get_contact(name) {
+            Some(entry) => entry,
+            None => self.get_contact(self.alias_target(name)?)?,
+        };
+        Some((&entry.name, entry))
+    }
+
+    /// Get the name of the contact an alias refers to (case-insensitive).
+    pub fn alias_target(&self, alias: &str) -> Option<&String> {
+        let key = contact_key(alias);
+        let target = self
+            .aliases
+            .iter()
+            .find(|(k, _)| contact_key(k) == key)
+            .map(|(_, target)| target)?;
+        Some(
+            self.get_contact(target)
+                .map(|entry| &entry.name)
+                .unwrap_or(target),
+        )
+    }
+
+    /// Add alternate names for a contact.
+    ///
+    /// Fails without changing anything if an alias is already a contact name or
+    /// an alias for a different contact.
+    pub fn add_aliases(&mut self, name: &str, aliases: &[String]) -> Result<String> {
+        let target = match self.get_contact(name) {
+            Some(entry) => entry.name.clone(),
+            None => return Err(Error::ContactNotFound(name.to_string())),
+        };
+
+        for alias in aliases {
+            if let Some(existing) = self.get_contact(alias) {
+                return Err(Error::Generic(t!(
+                    "contacts.alias_is_contact",
+                    alias = alias,
+                    name = existing.name
+                )));
+            }
+            if let Some(existing) = self.alias_target(alias) {
+                if *existing != target {
+                    return Err(Error::Generic(t!(
+                        "contacts.alias_taken",
+                        alias = alias,
+                        name = existing
+                    )));
+                }
+            }
+        }
+
+        for alias in aliases {
+            self.remove_alias(alias);
+            self.aliases.insert(alias.clone(), contact_key(&target));
+        }
+        Ok(target)
+    }
+
+    /// Remove an alias (case-insensitive).
+    pub fn remove_alias(&mut self, alias: &str) -> bool {
+        let key = contact_key(alias);
+        let before = self.aliases.len();
+        self.aliases.retain(|k, _| contact_key(k) != key);
+        self.aliases.len() != before
+    }
+
+    /// Get the aliases for a contact, sorted.
+    pub fn aliases_for(&self, name: &str) -> Vec<&String> {
+        let key = contact_key(name);
+        let mut aliases: Vec<&String> = self
+            .aliases
             .iter()
-            .find(|(k, _)| k.to_lowercase() == lowercase_name)
+            .filter(|(_, target)| contact_key(target) == key)
+            .map(|(alias, _)| alias)
+            .collect();
+        aliases.sort();
+        aliases
+    }
+
+    /// List all aliases.
+    pub fn list_aliases(&self) -> Vec<&String> {
+        self.aliases.keys().collect()
+    }
+
+    /// Find groups of contacts whose identifiers, main or other, normalize to the same handle.
+    /// Contacts that each share a handle with a third are in the same group.
+    ///
+    /// Each group is sorted by name, and groups are sorted by their first name.
+    pub fn find_duplicates(&self) -> Vec<Vec<String>> {
+        let entries: Vec<&ContactEntry> = self.contacts.values().collect();
+        // Each contact points at another in its group, and the group's first at itself
+        let mut parent: Vec<usize> = (0..entries.len()).collect();
+        fn root(parent: &mut [usize], mut index: usize) -> usize {
+            while parent[index] != index {
+                parent[index] = parent[parent[index]];
+                index = parent[index];
+            }
+            index
+        }
+
+        let mut owners: HashMap<String, usize> = HashMap::new();
+        for (index, entry) in entries.iter().enumerate() {
+            for handle in entry.identifiers().map(|id| normalize_identifier(id)) {
+                match owners.entry(handle) {
+                    Entry::Occupied(owner) => {
+                        let group = root(&mut parent, *owner.get());
+                        let other = root(&mut parent, index);
+                        parent[other] = group;
+                    }
+                    Entry::Vacant(slot) => {
+                        slot.insert(index);
+                    }
+                }
+            }
+        }
+
+        let mut by_group: HashMap<usize, Vec<String>> = HashMap::new();
+        for (index, entry) in entries.iter().enumerate() {
+            by_group
+                .entry(root(&mut parent, index))
+                .or_default()
+                .push(entry.name.clone());
+        }
+        let mut groups: Vec<Vec<String>> = by_group
+            .into_values()
+            .filter(|names| names.len() > 1)
+            .map(|mut names| {
+                names.sort();
+                names
+            })
+            .collect();
+        groups.sort();
+        groups
+    }
+
+    /// Merge contacts into `keep`, turning their names and aliases into aliases of `keep`.
+    ///
+    /// `keep` takes a merged contact's identifiers and quick replies, and its settings where
+    /// `keep` has none.
+    pub fn merge_contacts(&mut self, keep: &str, others: &[String]) -> Result<()> {
+        if self.get_contact(keep).is_none() {
+            return Err(Error::ContactNotFound(keep.to_string()));
+        }
+        let keep = contact_key(keep);
+
+        for other in others.iter().map(|other| contact_key(other)) {
+            if other == keep {
+                continue;
+            }
+            let Some(entry) = self.contacts.remove(&other) else {
+                continue;
+            };
+
+            for target in self.aliases.values_mut() {
+                if contact_key(target) == other {
+                    *target = keep.clone();
+                }
+            }
+            self.aliases.insert(entry.name.clone(), keep.clone());
+
+            if let Some(kept) = self.contacts.get_mut(&keep) {
+                kept.absorb(entry);
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the named contact with a given identifier, main or other.
+    pub fn find_contact_by_identifier(&self, identifier: &str) -> Option<(&String, &ContactEntry)> {
+        self.contacts
+            .values()
+            .find(|entry| entry.identifiers().any(|id| id == identifier))
+            .map(|entry| (&entry.name, entry))
+    }
+
+    /// Point every contact with identifier `old`, including the default contact, at `new`.
+    ///
+    /// Returns whether any contact changed.
+    pub fn replace_identifier(&mut self, old: &str, new: &str) -> bool {
+        let mut changed = false;
+        for entry in self.contacts.values_mut() {
+            if entry.identifier == old {
+                entry.identifier = new.to_string();
+                changed = true;
+            }
+            for other in entry.other_identifiers.iter_mut().filter(|id| *id == old) {
+                *other = new.to_string();
+                changed = true;
+            }
+        }
+        if self.default_contact.as_deref() == Some(old) {
+            self.default_contact = Some(new.to_string());
+            changed = true;
+        }
+        changed
+    }
+
+    /// Check whether an identifier belongs to a named contact or the default contact.
+    pub fn is_saved_identifier(&self, identifier: &str) -> bool {
+        self.find_contact_by_identifier(identifier).is_some()
+            || self.default_contact.as_deref() == Some(identifier)
+    }
+
+    /// Get the name to show for an identifier, preferring a configured contact.
+    pub fn display_name_for(&self, identifier: &str) -> Option<String> {
+        if let Some((name, entry)) = self.find_contact_by_identifier(identifier) {
+            return Some(entry.display_name.clone().unwrap_or_else(|| name.clone()));
+        }
+
+        if self.default_contact.as_deref() == Some(identifier) {
+            return self.default_display_name.clone();
+        }
+
+        None
+    }
+
+    /// Get the Messages account to send to an identifier from: the matching contact's
+    /// account if it has one, otherwise `sender.account`.
+    pub fn account_for(&self, identifier: &str) -> Option<String> {
+        self.find_contact_by_identifier(identifier)
+            .and_then(|(_, entry)| entry.account.clone())
+            .or_else(|| self.sender.account.clone())
+    }
+
+    /// Get the phone number or email to send to an identifier from: the matching contact's
+    /// `send_as` if it has one, otherwise `sender.send_as`.
+    pub fn send_as_for(&self, identifier: &str) -> Option<String> {
+        self.find_contact_by_identifier(identifier)
+            .and_then(|(_, entry)| entry.send_as.clone())
+            .or_else(|| self.sender.send_as.clone())
+    }
+
+    /// Initials and avatar color for the contact `identifier` belongs to, if it has initials.
+    pub fn avatar_for(&self, identifier: &str) -> Option<(&str, &str)> {
+        let (name, entry) = self.find_contact_by_identifier(identifier)?;
+        let initials = entry.initials.as_deref()?;
+        let color = entry.color.as_deref().unwrap_or_else(|| avatar_color(name));
+        Some((initials, color))
+    }
+
+    /// Set or clear a contact's birthday, returning the contact's name.
+    pub fn set_birthday(&mut self, name: &str, birthday: Option<String>) -> Result<String> {
+        let entry = self.resolve_contact_mut(name)?;
+        entry.birthday = birthday;
+        Ok(entry.name.clone())
+    }
+
+    /// Birthday of the contact `identifier` belongs to, if set.
+    pub fn birthday_for(&self, identifier: &str) -> Option<&str> {
+        self.find_contact_by_identifier(identifier)
+            .and_then(|(_, entry)| entry.birthday.as_deref())
+    }
+
+    /// Quick replies for the contact `identifier` belongs to, if any.
+    pub fn quick_replies_for(&self, identifier: &str) -> Vec<String> {
+        self.find_contact_by_identifier(identifier)
+            .map(|(_, entry)| entry.quick_replies.clone())
+            .unwrap_or_default()
+    }
+
+    /// Get a contact by name or alias to change it.
+    fn resolve_contact_mut(&mut self, name: &str) -> Result<&mut ContactEntry> {
+        let key = match self.resolve_contact(name) {
+            Some((name, _)) => contact_key(name),
+            None => return Err(Error::ContactNotFound(name.to_string())),
+        };
+        Ok(self
+            .contacts
+            .get_mut(&key)
+            .expect("resolved contacts exist"))
+    }
+
+    /// Add a quick reply for a contact, returning the contact's name.
+    ///
+    /// Each contact has at most `MAX_QUICK_REPLIES`, one for each of Alt+1 to Alt+9.
+    pub fn add_quick_reply(&mut self, name: &str, text: &str) -> Result<String> {
+        let text = quick_reply_text(text)?;
+        let entry = self.resolve_contact_mut(name)?;
+        if !entry.quick_replies.contains(&text) {
+            if entry.quick_replies.len() >= MAX_QUICK_REPLIES {
+                return Err(Error::Generic(t!(
+                    "contacts.too_many_quick_replies",
+                    name = entry.name,
+                    max = MAX_QUICK_REPLIES
+                )));
+            }
+            entry.quick_replies.push(text);
+        }
+        Ok(entry.name.clone())
+    }
+
+    /// Replace a contact's quick reply by its number, counting from 1. Returns the contact's
+    /// name.
+    pub fn edit_quick_reply(&mut self, name: &str, number: usize, text: &str) -> Result<String> {
+        let text = quick_reply_text(text)?;
+        let entry = self.resolve_contact_mut(name)?;
+        let Some(reply) = number
+            .checked_sub(1)
+            .and_then(|index| entry.quick_replies.get_mut(index))
+        else {
+            return Err(Error::Generic(t!(
+                "contacts.no_quick_reply",
+                name = entry.name,
+                number = number
+            )));
+        };
+        *reply = text;
+        Ok(entry.name.clone())
+    }
+
+    /// Remove a contact's quick reply by its number, counting from 1. Returns the contact's
+    /// name and the removed reply.
+    pub fn remove_quick_reply(&mut self, name: &str, number: usize) -> Result<(String, String)> {
+        let entry = self.resolve_contact_mut(name)?;
+        if number == 0 || number > entry.quick_replies.len() {
+            return Err(Error::Generic(t!(
+                "contacts.no_quick_reply",
+                name = entry.name,
+                number = number
+            )));
+        }
+        let reply = entry.quick_replies.remove(number - 1);
+        Ok((entry.name.clone(), reply))
+    }
+
+    /// Get the sending settings.
+    pub fn sender(&self) -> &SenderConfig {
+        &self.sender
+    }
+
+    /// Get the refresh rate settings.
+    pub fn refresh(&self) -> &RefreshConfig {
+        &self.refresh
+    }
+
+    /// Get the notification settings.
+    pub fn notifications(&self) -> &NotificationConfig {
+        &self.notifications
+    }
+
+    /// Get the reply suggestion settings.
+    pub fn suggestions(&self) -> &SuggestionConfig {
+        &self.suggestions
+    }
+
+    /// Get the conversation summary settings.
+    pub fn summarize(&self) -> &SummarizeConfig {
+        &self.summarize
+    }
+
+    /// Get the translation settings.
+    pub fn translate(&self) -> &TranslateConfig {
+        &self.translate
+    }
+
+    /// Whether to translate messages from the contact `identifier` belongs to.
+    pub fn translate_for(&self, identifier: &str) -> bool {
+        self.find_contact_by_identifier(identifier)
+            .is_some_and(|(_, entry)| entry.translate)
+    }
+
+    /// Whether messages to the contact with this identifier are confirmed before they're sent.
+    pub fn careful_for(&self, identifier: &str) -> bool {
+        self.find_contact_by_identifier(identifier)
+            .is_some_and(|(_, entry)| entry.careful)
+    }
+
+    /// Where the chat with the contact with this identifier opens.
+    pub fn open_at_for(&self, identifier: &str) -> OpenAt {
+        self.find_contact_by_identifier(identifier)
+            .and_then(|(_, entry)| entry.open_at)
+            .unwrap_or(self.chat.open_at)
+    }
+
+    /// Get the database settings.
+    pub fn db(&self) -> &DbConfig {
+        &self.db
+    }
+
+    /// Get the transcript log settings.
+    pub fn transcript(&self) -> &TranscriptConfig {
+        &self.transcript
+    }
+
+    /// Get the voice memo settings.
+    pub fn voice(&self) -> &VoiceConfig {
+        &self.voice
+    }
+
+    /// Get the GIF search settings.
+    pub fn gif(&self) -> &GifConfig {
+        &self.gif
+    }
+
+    /// Get the birthday reminder settings.
+    pub fn birthdays(&self) -> &BirthdayConfig {
+        &self.birthdays
+    }
+
+    /// Get the inbox settings.
+    pub fn inbox(&self) -> &InboxConfig {
+        &self.inbox
     }
 
-    /// List all contacts in the configuration.
+    /// Get the chat view settings.
+    pub fn chat(&self) -> &ChatConfig {
+        &self.chat
+    }
+
+    /// Get the pane layout settings.
+    pub fn layout(&self) -> &LayoutConfig {
+        &self.layout
+    }
+
+    /// Get the configured language, if any.
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// Get the timestamp settings.
+    pub fn time(&self) -> &TimeConfig {
+        &self.time
+    }
+
+    /// Set the width of the inbox conversation list, as a percentage of the screen.
+    pub fn set_inbox_list_percent(&mut self, percent: u16) -> Result<()> {
+        self.layout.inbox_list_percent = percent;
+        self.save()
+    }
+
+    /// List all contacts in the configuration, sorted by name.
     pub fn list_contacts(&self) -> Vec<(&String, &ContactEntry)> {
-        self.contacts.iter().collect()
+        let mut contacts: Vec<(&String, &ContactEntry)> = self
+            .contacts
+            .values()
+            .map(|entry| (&entry.name, entry))
+            .collect();
+        contacts.sort_by(|a, b| a.0.cmp(b.0));
+        contacts
     }
 
     /// Get the number of configured contacts.
@@ -128,3 +1407,398 @@ impl Config {
         self.contacts.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliases() {
+        let mut config = Config::default();
+        config.add_contact("mom".to_string(), "+15551234567".to_string(), None);
+        config.add_contact("dad".to_string(), "+15557654321".to_string(), None);
+
+        config
+            .add_aliases("Mom", &["mother".to_string(), "mamá".to_string()])
+            .unwrap();
+        assert_eq!(config.resolve_contact("MOTHER").unwrap().0, "mom");
+        assert_eq!(config.aliases_for("mom"), vec!["mamá", "mother"]);
+
+        // Aliases can't shadow contact names or other contacts' aliases
+        assert!(config.add_aliases("mom", &["dad".to_string()]).is_err());
+        assert!(config.add_aliases("dad", &["Mother".to_string()]).is_err());
+
+        // Merging duplicates keeps the merged names as aliases
+        config.add_contact(
+            "Mama".to_string(),
+            "(555) 123-4567".to_string(),
+            Some("Mom".to_string()),
+        );
+        assert_eq!(config.find_duplicates(), vec![vec!["Mama", "mom"]]);
+        config.merge_contacts("mom", &["Mama".to_string()]).unwrap();
+        assert!(config.find_duplicates().is_empty());
+        assert_eq!(config.aliases_for("mom"), vec!["Mama", "mamá", "mother"]);
+        assert_eq!(
+            config.get_contact("mom").unwrap().display_name.as_deref(),
+            Some("Mom")
+        );
+
+        // Removing a contact removes its aliases
+        let removed = config.take_contact("mom").unwrap();
+        assert!(config.resolve_contact("mother").is_none());
+
+        // Restoring brings back the aliases that are still free
+        config.add_aliases("dad", &["mother".to_string()]).unwrap();
+        config.restore_contact(removed.clone()).unwrap();
+        assert_eq!(config.aliases_for("mom"), vec!["Mama", "mamá"]);
+        assert_eq!(config.resolve_contact("mother").unwrap().0, "dad");
+
+        // The name can't be restored once it's taken
+        config.take_contact("mom");
+        config.add_contact("Mom".to_string(), "+15550000000".to_string(), None);
+        assert!(config.restore_contact(removed).is_err());
+    }
+
+    #[test]
+    fn test_contact_names_ignore_case() {
+        let mut config = Config::default();
+        config.add_contact("Mom".to_string(), "+15551234567".to_string(), None);
+        config.add_contact(" mom ".to_string(), "+15557654321".to_string(), None);
+
+        // The second add updates the first contact, taking its casing
+        assert_eq!(config.contact_count(), 1);
+        let entry = config.get_contact("MOM").unwrap();
+        assert_eq!(entry.name, "mom");
+        assert_eq!(entry.identifier, "+15557654321");
+
+        config.add_aliases("Mom", &["mother".to_string()]).unwrap();
+        assert_eq!(config.alias_target("Mother").unwrap(), "mom");
+        assert_eq!(config.aliases_for("MOM"), vec!["mother"]);
+
+        // Other identifiers find the same contact
+        config.link_identifier("MOM", "mom@example.com").unwrap();
+        assert_eq!(
+            config.identifiers_for("mom@example.com"),
+            vec!["+15557654321", "mom@example.com"]
+        );
+        assert_eq!(config.display_name_for("mom@example.com").unwrap(), "mom");
+        config.add_contact("dad".to_string(), "+15550000000".to_string(), None);
+        assert!(config.link_identifier("dad", "mom@example.com").is_err());
+        assert_eq!(
+            config.unlink_identifier("mom@example.com").as_deref(),
+            Some("mom")
+        );
+        assert_eq!(config.identifiers_for("+15557654321").len(), 1);
+
+        // Fixing a mistyped identifier keeps the name
+        assert!(config.replace_identifier("+15557654321", "+15557654322"));
+        assert_eq!(
+            config.get_contact("mom").unwrap().identifier,
+            "+15557654322"
+        );
+        assert!(!config.replace_identifier("+15557654321", "+15557654322"));
+    }
+
+    #[test]
+    fn test_merge_contacts() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [contacts.mom]
+            identifier = "+15551234567"
+
+            [contacts.Mama]
+            identifier = "mom@example.com"
+            other_identifiers = ["(555) 123-4567"]
+            display_name = "Mom"
+            quick_replies = ["On my way"]
+            careful = true
+            birthday = "03-14"
+            color = "magenta"
+
+            [contacts.dad]
+            identifier = "+15557654321"
+            "#,
+        )
+        .unwrap();
+
+        // Sharing an other identifier makes a duplicate too
+        assert_eq!(config.find_duplicates(), vec![vec!["Mama", "mom"]]);
+        config.merge_contacts("mom", &["Mama".to_string()]).unwrap();
+        assert!(config.find_duplicates().is_empty());
+
+        // Nothing the merged contact had is lost
+        let entry = config.get_contact("mom").unwrap();
+        assert_eq!(
+            entry.identifiers().collect::<Vec<_>>(),
+            vec!["+15551234567", "mom@example.com", "(555) 123-4567"]
+        );
+        assert_eq!(entry.display_name.as_deref(), Some("Mom"));
+        assert_eq!(entry.quick_replies, vec!["On my way"]);
+        assert!(entry.careful);
+        assert_eq!(entry.birthday.as_deref(), Some("03-14"));
+        assert_eq!(entry.color.as_deref(), Some("magenta"));
+    }
+
+    #[test]
+    fn test_merge_contacts_differing_in_case() {
+        let config: Config = toml::from_str(
+            r#"
+            [contacts.Mom]
+            identifier = "+15551234567"
+            display_name = "Mom"
+
+            [contacts.mom]
+            identifier = "mom@example.com"
+            quick_replies = ["On my way"]
+            careful = true
+            "#,
+        )
+        .unwrap();
+
+        // Neither entry's identifiers or settings are dropped
+        assert_eq!(config.contact_count(), 1);
+        let entry = config.get_contact("mom").unwrap();
+        assert_eq!(entry.name, "Mom");
+        assert_eq!(
+            entry.identifiers().collect::<Vec<_>>(),
+            vec!["+15551234567", "mom@example.com"]
+        );
+        assert_eq!(entry.display_name.as_deref(), Some("Mom"));
+        assert_eq!(entry.quick_replies, vec!["On my way"]);
+        assert!(entry.careful);
+    }
+
+    #[test]
+    fn test_contact_changes() {
+        let mut config = Config::default();
+        config.add_contact(
+            "Mom".to_string(),
+            "+15551234567".to_string(),
+            Some("Mother".to_string()),
+        );
+        assert!(config
+            .contact_changes("dad", "+15557654321", None)
+            .is_empty());
+        assert!(config
+            .contact_changes("Mom", "+15551234567", Some("Mother"))
+            .is_empty());
+
+        let changes = config.contact_changes("mom", "+15557654321", None);
+        assert_eq!(
+            changes.iter().map(|c| c.field).collect::<Vec<_>>(),
+            vec![
+                ContactField::Name,
+                ContactField::Identifier,
+                ContactField::DisplayName
+            ]
+        );
+        assert_eq!(changes[1].before.as_deref(), Some("+15551234567"));
+        assert_eq!(changes[2].after, None);
+
+        // Identifiers are compared as handles, and a contact doesn't collide with itself
+        assert_eq!(
+            config.identifier_taken_by("(555) 123-4567", "dad").unwrap(),
+            "Mom"
+        );
+        assert!(config.identifier_taken_by("+15551234567", "mom").is_none());
+        assert!(config.identifier_taken_by("+15550000000", "dad").is_none());
+    }
+
+    #[test]
+    fn test_quick_replies() {
+        let mut config = Config::default();
+        config.add_contact("mom".to_string(), "+15551234567".to_string(), None);
+        config.add_aliases("mom", &["mother".to_string()]).unwrap();
+
+        assert_eq!(
+            config.add_quick_reply("mother", " On my way ").unwrap(),
+            "mom"
+        );
+        config.add_quick_reply("mom", "Call you later").unwrap();
+        config.add_quick_reply("mom", "On my way").unwrap();
+        assert_eq!(
+            config.quick_replies_for("+15551234567"),
+            vec!["On my way", "Call you later"]
+        );
+
+        config
+            .edit_quick_reply("mom", 2, "Call you tonight")
+            .unwrap();
+        assert!(config.edit_quick_reply("mom", 3, "Hi").is_err());
+        assert_eq!(
+            config.remove_quick_reply("mom", 1).unwrap(),
+            ("mom".to_string(), "On my way".to_string())
+        );
+        assert!(config.remove_quick_reply("mom", 0).is_err());
+        assert!(config.add_quick_reply("mom", "  ").is_err());
+        assert!(config.add_quick_reply("dad", "Hi").is_err());
+
+        // Quick replies survive updating the contact
+        config.add_contact("mom".to_string(), "+15551234567".to_string(), None);
+        assert_eq!(
+            config.quick_replies_for("+15551234567"),
+            vec!["Call you tonight"]
+        );
+
+        for i in 1..MAX_QUICK_REPLIES {
+            config.add_quick_reply("mom", &i.to_string()).unwrap();
+        }
+        assert!(config.add_quick_reply("mom", "One too many").is_err());
+    }
+
+    #[test]
+    fn test_import_person() {
+        let mut config = Config::default();
+        config.add_contact("mom".to_string(), "+15551234567".to_string(), None);
+        config.add_contact("Jane".to_string(), "+15550000000".to_string(), None);
+        let person = |name: &str, initials: &str, identifiers: &[&str]| Person {
+            name: name.to_string(),
+            initials: initials.to_string(),
+            identifiers: identifiers.iter().map(|id| id.to_string()).collect(),
+            contact_name: None,
+        };
+
+        let mom = person("Ann Smith", "AS", &["ann@example.com", "+15551234567"]);
+        assert_eq!(config.import_person(&mom), ImportedPerson::Updated);
+        assert_eq!(config.import_person(&mom), ImportedPerson::Unchanged);
+        assert_eq!(
+            config.avatar_for("+15551234567"),
+            Some(("AS", avatar_color("Ann Smith")))
+        );
+        // The existing contact keeps its name and identifiers
+        assert!(config.get_contact("Ann Smith").is_none());
+        assert_eq!(config.identifiers_for("+15551234567"), vec!["+15551234567"]);
+
+        let bob = person("Bob Jones", "BJ", &["+15552223333", "bob@example.com"]);
+        assert_eq!(config.import_person(&bob), ImportedPerson::Added);
+        assert_eq!(
+            config.identifiers_for("bob@example.com"),
+            vec!["+15552223333", "bob@example.com"]
+        );
+
+        let jane = person("jane", "J", &["+15554445555"]);
+        assert_eq!(config.import_person(&jane), ImportedPerson::Skipped);
+        assert_eq!(config.avatar_for("+15550000000"), None);
+
+        // Colors set by hand survive another import
+        config.contacts.get_mut("mom").unwrap().color = Some("#ff8800".to_string());
+        config.add_contact("mom".to_string(), "+15551234567".to_string(), None);
+        assert_eq!(config.import_person(&mom), ImportedPerson::Unchanged);
+        assert_eq!(config.avatar_for("+15551234567"), Some(("AS", "#ff8800")));
+    }
+
+    #[test]
+    fn test_birthday() {
+        let mut config = Config::default();
+        config.add_contact("mom".to_string(), "+15551234567".to_string(), None);
+        config.add_aliases("mom", &["mother".to_string()]).unwrap();
+
+        assert_eq!(
+            config
+                .set_birthday("mother", Some("03-31".to_string()))
+                .unwrap(),
+            "mom"
+        );
+        assert!(config.set_birthday("dad", None).is_err());
+
+        // The birthday survives updating the contact
+        config.add_contact("mom".to_string(), "+15551234567".to_string(), None);
+        assert_eq!(config.birthday_for("+15551234567"), Some("03-31"));
+        config.set_birthday("mom", None).unwrap();
+        assert_eq!(config.birthday_for("+15551234567"), None);
+    }
+
+    #[test]
+    fn test_account_for() {
+        let mut config = Config::default();
+        config.add_contact("boss".to_string(), "+15551234567".to_string(), None);
+        config.link_identifier("boss", "boss@work.com").unwrap();
+        assert_eq!(config.account_for("boss@work.com"), None);
+
+        config.sender.account = Some("me@icloud.com".to_string());
+        assert_eq!(
+            config.account_for("+15550000000").as_deref(),
+            Some("me@icloud.com")
+        );
+
+        // A contact's own account wins, and survives updating the contact
+        config.contacts.get_mut("boss").unwrap().account = Some("me@work.com".to_string());
+        config.add_contact("Boss".to_string(), "+15551234567".to_string(), None);
+        assert_eq!(
+            config.account_for("boss@work.com").as_deref(),
+            Some("me@work.com")
+        );
+
+        // So does its send_as address
+        config.sender.send_as = Some("me@icloud.com".to_string());
+        config.contacts.get_mut("boss").unwrap().send_as = Some("+15559876543".to_string());
+        config.add_contact("boss".to_string(), "+15551234567".to_string(), None);
+        assert_eq!(
+            config.send_as_for("+15551234567").as_deref(),
+            Some("+15559876543")
+        );
+        assert_eq!(
+            config.send_as_for("+15550000000").as_deref(),
+            Some("me@icloud.com")
+        );
+    }
+
+    #[test]
+    fn test_open_at_for() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [chat]
+            open_at = "first-unread"
+
+            [contacts.mom]
+            name = "mom"
+            identifier = "+15551234567"
+            open_at = "latest"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.open_at_for("+15550000000"), OpenAt::FirstUnread);
+        assert_eq!(config.open_at_for("+15551234567"), OpenAt::Latest);
+
+        // Updating the contact keeps its position
+        config.chat.open_at = OpenAt::Latest;
+        config.contacts.get_mut("mom").unwrap().open_at = Some(OpenAt::FirstUnread);
+        config.add_contact("mom".to_string(), "+15551234567".to_string(), None);
+        assert_eq!(config.open_at_for("+15551234567"), OpenAt::FirstUnread);
+        assert_eq!(OpenAt::from_name("first-unread"), Some(OpenAt::FirstUnread));
+    }
+
+    #[test]
+    fn test_with_env() {
+        let mut file = Config::default();
+        file.add_contact("mom".to_string(), "+15551234567".to_string(), None);
+        let vars = [
+            ("IM_DEFAULT_CONTACT", "+15557654321"),
+            ("IM_DB_ACCESS", "snapshot"),
+            ("IM_DB_PATH", "/tmp/chat.db"),
+            ("IM_SENDER_MAX_ATTEMPTS", "5"),
+        ];
+        let config = file
+            .clone()
+            .with_env(vars.map(|(name, value)| (name.to_string(), value.to_string())))
+            .unwrap();
+        assert_eq!(config.default_contact().as_deref(), Some("+15557654321"));
+        assert_eq!(config.db.access, DbAccess::Snapshot);
+        assert_eq!(config.db.path, Some(PathBuf::from("/tmp/chat.db")));
+        assert_eq!(config.sender.max_attempts, 5);
+        assert_eq!(config.env_vars().count(), 4);
+        assert!(config.get_contact("mom").is_some());
+        assert!(config.same_as(&config.clone()));
+        assert!(!config.same_as(&file));
+
+        // Saving writes the file's values back
+        let mut value = to_toml(&config).unwrap();
+        config_env::restore(&mut value, &config.env);
+        assert_eq!(value, to_toml(&file).unwrap());
+
+        // Values of the wrong type are reported
+        assert!(file
+            .with_env([("IM_SENDER_MAX_ATTEMPTS".to_string(), "lots".to_string())])
+            .is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use toml::value::Table;
+use toml::Value;
+
+/// Prefix of the environment variables that override configuration keys.
+const PREFIX: &str = "IM_";
+
+/// `IM_*` variables that aren't settings: switches like `--dry-run` and `--automated` passed on
+/// to the `im`s that hooks run, and what im sets for the commands it runs itself.
+const NOT_SETTINGS: &[&str] = &["IM_DRY_RUN", "IM_AUTOMATED", "IM_VOICE_FILE"];
+
+/// Top-level tables keyed by the user's own names rather than by settings, which the
+/// environment can't override.
+const USER_TABLES: &[&str] = &["contacts", "aliases"];
+
+/// A configuration key set from an environment variable, and what the file had for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Override {
+    /// The environment variable, e.g. `IM_SENDER_MAX_ATTEMPTS`.
+    pub var: String,
+    /// Section the key is in, or `None` for a top-level key like `default_contact`.
+    section: Option<String>,
+    key: String,
+    value: Value,
+    /// The value from the configuration file, or `None` if it wasn't set.
+    original: Option<Value>,
+}
+
+/// Layer `IM_*` environment variables over `config`, the configuration file as TOML,
+/// returning what was overridden.
+///
+/// `IM_<KEY>` sets a top-level key and `IM_<SECTION>_<KEY>` a key in a section, so
+/// `IM_DEFAULT_CONTACT` sets `default_contact` and `IM_SENDER_MAX_ATTEMPTS` sets `max_attempts`
+/// under `[sender]`. Values are read as TOML for keys that aren't strings; lists can also be
+/// given separated by commas.
+pub fn apply(
+    config: &mut Value,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Vec<Override> {
+    let Value::Table(table) = config else {
+        return Vec::new();
+    };
+    let mut overrides = Vec::new();
+    for (var, raw) in vars {
+        let Some((section, key)) = locate(table, &var) else {
+            continue;
+        };
+        let target = match &section {
+            Some(section) => match table.get_mut(section) {
+                Some(Value::Table(section)) => section,
+                _ => continue,
+            },
+            None => &mut *table,
+        };
+        let original = target.get(&key).cloned();
+        let value = parse(&raw, original.as_ref());
+        target.insert(key.clone(), value.clone());
+        overrides.push(Override {
+            var,
+            section,
+            key,
+            value,
+            original,
+        });
+    }
+    overrides
+}
+
+/// Put the file's values back for keys that still hold their overrides, so saving the
+/// configuration doesn't write the environment into the file. Keys changed since keep the
+/// change.
+pub fn restore(config: &mut Value, overrides: &[Override]) {
+    let Value::Table(table) = config else {
+        return;
+    };
+    for applied in overrides {
+        let target = match &applied.section {
+            Some(section) => match table.get_mut(section) {
+                Some(Value::Table(section)) => section,
+                _ => continue,
+            },
+            None => &mut *table,
+        };
+        if target.get(&applied.key) != Some(&applied.value) {
+            continue;
+        }
+        match &applied.original {
+            Some(original) => target.insert(applied.key.clone(), original.clone()),
+            None => target.remove(&applied.key),
+        };
+    }
+}
+
+/// Find the section and key an environment variable sets, if it's one of ours.
+fn locate(table: &Table, var: &str) -> Option<(Option<String>, String)> {
+    if NOT_SETTINGS.contains(&var) {
+        return None;
+    }
+    let name = var.strip_prefix(PREFIX)?.to_lowercase();
+    if USER_TABLES.iter().any(|user| {
+        name.strip_prefix(user)
+            .is_some_and(|rest| rest.starts_with('_'))
+    }) {
+        return None;
+    }
+    let section = table
+        .iter()
+        .filter(|(section, value)| value.is_table() && !USER_TABLES.contains(&section.as_str()))
+        .find_map(|(section, _)| {
+            let key = name.strip_prefix(section.as_str())?.strip_prefix('_')?;
+            (!key.is_empty()).then(|| (Some(section.clone()), key.to_string()))
+        });
+    match section {
+        Some(found) => Some(found),
+        // Whole sections can't be replaced by one variable
+        None if table.get(&name).is_some_and(Value::is_table) => None,
+        None => Some((None, name)),
+    }
+}
+
+/// Read a variable's value as the type of the key's current value, defaulting to a string.
+fn parse(raw: &str, current: Option<&Value>) -> Value {
+    match current {
+        None | Some(Value::String(_)) => Value::String(raw.to_string()),
+        Some(current) => match format!("value = {}", raw).parse::<Value>() {
+            Ok(Value::Table(mut parsed)) => parsed.remove("value").expect("the value was parsed"),
+            _ if current.is_array() => Value::Array(
+                raw.split(',')
+                    .map(|item| Value::String(item.trim().to_string()))
+                    .filter(|item| item.as_str() != Some(""))
+                    .collect(),
+            ),
+            _ => Value::String(raw.to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_and_restore() {
+        let file: Value = r#"
+            language = "fr"
+            [sender]
+            max_attempts = 3
+            [inbox]
+            automated_senders = ["noreply"]
+            [contacts.mom]
+            identifier = "+15551234567"
+        "#
+        .parse()
+        .unwrap();
+        let mut config = file.clone();
+        let overrides = apply(
+            &mut config,
+            vars(&[
+                ("IM_DEFAULT_CONTACT", "+15557654321"),
+                ("IM_SENDER_MAX_ATTEMPTS", "5"),
+                ("IM_INBOX_AUTOMATED_SENDERS", "alerts, noreply"),
+                ("IM_CONTACTS_MOM", "nope"),
+                ("IM_SENDER", "nope"),
+                ("IM_DRY_RUN", "1"),
+                ("HOME", "/Users/me"),
+            ]),
+        );
+        assert_eq!(overrides.len(), 3);
+        assert_eq!(config["default_contact"].as_str(), Some("+15557654321"));
+        assert_eq!(config["sender"]["max_attempts"].as_integer(), Some(5));
+        assert_eq!(
+            config["inbox"]["automated_senders"],
+            Value::Array(vec!["alerts".into(), "noreply".into()])
+        );
+        assert!(config["contacts"]["mom"].is_table());
+
+        // Keys changed since they were overridden are saved as changed
+        config["default_contact"] = "+15550000000".into();
+        restore(&mut config, &overrides);
+        assert_eq!(config["default_contact"].as_str(), Some("+15550000000"));
+        config.as_table_mut().unwrap().remove("default_contact");
+        assert_eq!(config, file);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::config::Config;
+use std::collections::{HashMap, HashSet};
+use toml::Value;
+
+/// The commented configuration `im config init` writes, with every option and its default.
+pub const TEMPLATE: &str = include_str!("../config.example.toml");
+
+/// Section whose tables are contacts, keyed by the user's names for them.
+const CONTACTS: &str = "contacts";
+
+/// Section whose keys are the user's own names for contacts.
+const ALIASES: &str = "aliases";
+
+/// Something wrong with a configuration file, found by `validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// Line of the file it's on, counting from 1, when it's known.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+/// Check a configuration file for syntax errors, values of the wrong type, unknown keys, and
+/// contacts saved in the format of older versions.
+pub fn validate(source: &str) -> Vec<Problem> {
+    let value: Value = match source.parse() {
+        Ok(value) => value,
+        Err(e) => {
+            return vec![Problem {
+                line: e.line_col().map(|(line, _)| line + 1),
+                message: e.to_string(),
+            }]
+        }
+    };
+    let mut problems = Vec::new();
+    if let Err(e) = toml::from_str::<Config>(source) {
+        problems.push(Problem {
+            line: e.line_col().map(|(line, _)| line + 1),
+            message: e.to_string(),
+        });
+    }
+
+    let known = known_keys();
+    let lines: HashMap<String, usize> = keys(source, false).into_iter().rev().collect();
+    let problem = |path: &str, message: String| Problem {
+        line: lines.get(path).copied(),
+        message,
+    };
+    let Value::Table(table) = value else {
+        return problems;
+    };
+    for (key, value) in &table {
+        match (key.as_str(), value) {
+            (ALIASES, Value::Table(_)) => {}
+            (CONTACTS, Value::Table(contacts)) => {
+                for (name, entry) in contacts {
+                    let path = format!("{}.{}", CONTACTS, name);
+                    let Value::Table(entry) = entry else { continue };
+                    if !entry.contains_key("name") {
+                        problems.push(problem(
+                            &path,
+                            format!(
+                                "`{}` has no `name`, as saved by older versions of im; \
+                                 saving any change to the contacts adds it",
+                                path
+                            ),
+                        ));
+                    }
+                    for field in entry.keys() {
+                        if !known.contains(&format!("{}.*.{}", CONTACTS, field)) {
+                            let path = format!("{}.{}", path, field);
+                            problems.push(problem(&path, format!("Unknown key `{}`", path)));
+                        }
+                    }
+                }
+            }
+            (section, Value::Table(settings)) if known.contains(section) => {
+                for field in settings.keys() {
+                    let path = format!("{}.{}", section, field);
+                    if !known.contains(&path) {
+                        problems.push(problem(&path, format!("Unknown key `{}`", path)));
+                    }
+                }
+            }
+            (key, _) if known.contains(key) => {}
+            (key, Value::Table(_)) => {
+                problems.push(problem(key, format!("Unknown section `[{}]`", key)))
+            }
+            (key, _) => problems.push(problem(key, format!("Unknown key `{}`", key))),
+        }
+    }
+    problems.sort_by_key(|problem| problem.line);
+    problems
+}
+
+/// Every key and section the template lists, as dotted paths with contacts' names as `*`.
+fn known_keys() -> HashSet<String> {
+    let mut known = HashSet::new();
+    for (path, _) in keys(TEMPLATE, true) {
+        let mut parts: Vec<&str> = path.split('.').collect();
+        if parts[0] == CONTACTS && parts.len() == 3 {
+            parts[1] = "*";
+        }
+        for end in 1..=parts.len() {
+            known.insert(parts[..end].join("."));
+        }
+    }
+    known
+}
+
+/// The keys set in a file, as dotted paths and the line each is on, in order. With
+/// `commented`, keys and section headers commented out with `# ` count too.
+fn keys(source: &str, commented: bool) -> Vec<(String, usize)> {
+    let mut section = String::new();
+    let mut keys = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let mut line = line.trim();
+        if commented {
+            line = line.strip_prefix("# ").unwrap_or(line);
+        }
+        if let Some(header) = line
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            section = header.trim().to_string();
+            keys.push((section.clone(), number + 1));
+            continue;
+        }
+        let Some((key, _)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().trim_matches('"');
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_alphanumeric() || "_-.".contains(c))
+        {
+            continue;
+        }
+        let path = if section.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", section, key)
+        };
+        keys.push((path, number + 1));
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template() {
+        let config: Config = toml::from_str(TEMPLATE).unwrap();
+        assert!(config.same_as(&Config::default()));
+        assert_eq!(validate(TEMPLATE), Vec::new());
+
+        // Every option im has is in the template
+        let known = known_keys();
+        for (path, _) in keys(&toml::to_string(&Config::default()).unwrap(), false) {
+            assert!(
+                known.contains(&path),
+                "{} is missing from the template",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let problems = validate(
+            r#"default_contat = "+15551234567"
+
+[sender]
+max_attempts = 3
+retry_delay = 2
+
+[contacts.mom]
+identifier = "+15551234567"
+nickname = "ma"
+
+[aliases]
+mother = "mom"
+
+[themes]
+dark = true
+"#,
+        );
+        let found: Vec<(Option<usize>, &str)> = problems
+            .iter()
+            .map(|problem| (problem.line, problem.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Some(1), "Unknown key `default_contat`"),
+                (Some(5), "Unknown key `sender.retry_delay`"),
+                (
+                    Some(7),
+                    "`contacts.mom` has no `name`, as saved by older versions of im; saving any \
+                     change to the contacts adds it"
+                ),
+                (Some(9), "Unknown key `contacts.mom.nickname`"),
+                (Some(14), "Unknown section `[themes]`"),
+            ]
+        );
+
+        // Values of the wrong type are reported where toml finds them
+        let problems = validate("[sender]\nmax_attempts = \"lots\"\n");
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.contains("max_attempts"));
+        assert_eq!(validate("[sender\n")[0].line, Some(1));
+    }
+}
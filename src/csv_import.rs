@@ -0,0 +1,207 @@
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::formatter::{looks_like_identifier, normalize_identifier};
+use crate::i18n::t;
+use std::collections::HashMap;
+
+/// What importing a row of a CSV file would do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CsvRow {
+    /// Add a contact with this name and identifier.
+    Add { name: String, identifier: String },
+    /// Skip the row because the name or handle is already a contact, or came earlier in the
+    /// file.
+    Duplicate { name: String, existing: String },
+    /// Skip the row because it has no name or no phone number or email.
+    Invalid { row: usize, value: String },
+}
+
+/// Read the rows of a CSV file: fields separated by commas, optionally in double quotes,
+/// with `""` for a quote inside a quoted field.
+pub fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.trim_start_matches('\u{FEFF}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Write fields as a line of CSV, quoting the ones that need it.
+pub fn format_csv_row(fields: &[&str]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    fields.join(",")
+}
+
+/// Work out what importing `text` would do, reading names from the 1-based column `name_col`
+/// and phone numbers or emails from `phone_col`.
+///
+/// A first row without a phone number or email is taken as a header and left out.
+pub fn plan_import(
+    config: &Config,
+    text: &str,
+    name_col: usize,
+    phone_col: usize,
+) -> Result<Vec<CsvRow>> {
+    if name_col == 0 || phone_col == 0 {
+        return Err(Error::Generic(t!("csv_import.columns_from_one")));
+    }
+
+    // Names and handles earlier in the file, with the name each came with
+    let mut names = HashMap::new();
+    let mut handles = HashMap::new();
+    let mut plan = Vec::new();
+    for (i, row) in parse_csv(text).into_iter().enumerate() {
+        let cell = |col: usize| row.get(col - 1).map_or("", |cell| cell.trim());
+        let (name, value) = (cell(name_col), cell(phone_col));
+        if row.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        if name.is_empty() || !looks_like_identifier(value) {
+            if i > 0 {
+                plan.push(CsvRow::Invalid {
+                    row: i + 1,
+                    value: row.join(","),
+                });
+            }
+            continue;
+        }
+
+        let identifier = normalize_identifier(value);
+        let existing = config
+            .get_contact(name)
+            .map(|entry| entry.name.clone())
+            .or_else(|| config.identifier_taken_by(&identifier, name).cloned())
+            .or_else(|| names.get(&name.to_lowercase()).cloned())
+            .or_else(|| handles.get(&identifier).cloned());
+        names
+            .entry(name.to_lowercase())
+            .or_insert_with(|| name.to_string());
+        handles
+            .entry(identifier.clone())
+            .or_insert_with(|| name.to_string());
+
+        plan.push(match existing {
+            Some(existing) => CsvRow::Duplicate {
+                name: name.to_string(),
+                existing,
+            },
+            None => CsvRow::Add {
+                name: name.to_string(),
+                identifier,
+            },
+        });
+    }
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        assert_eq!(
+            parse_csv("name,phone\r\n\"Smith, Jo\",555-1234\n\"say \"\"hi\"\"\",\n"),
+            vec![
+                vec!["name", "phone"],
+                vec!["Smith, Jo", "555-1234"],
+                vec!["say \"hi\"", ""],
+            ]
+        );
+        assert_eq!(parse_csv("a,b"), vec![vec!["a", "b"]]);
+        assert!(parse_csv("").is_empty());
+
+        let row = format_csv_row(&["Smith, Jo", "say \"hi\"", "+15551234567"]);
+        assert_eq!(row, "\"Smith, Jo\",\"say \"\"hi\"\"\",+15551234567");
+        assert_eq!(
+            parse_csv(&row),
+            vec![vec!["Smith, Jo", "say \"hi\"", "+15551234567"]]
+        );
+    }
+
+    #[test]
+    fn test_plan_import() {
+        let mut config = Config::default();
+        config.add_contact("mom".to_string(), "+15551234567".to_string(), None);
+
+        let csv = "Name,Phone\n\
+                   Jo,(555) 000-1111\n\
+                   Mom,5559999999\n\
+                   Aunt May,555.123.4567\n\
+                   jo,5552223333\n\
+                   Sam,5550001111\n\
+                   No Number,n/a\n";
+        let plan = plan_import(&config, csv, 1, 2).unwrap();
+        assert_eq!(
+            plan,
+            vec![
+                CsvRow::Add {
+                    name: "Jo".to_string(),
+                    identifier: "+15550001111".to_string()
+                },
+                CsvRow::Duplicate {
+                    name: "Mom".to_string(),
+                    existing: "mom".to_string()
+                },
+                CsvRow::Duplicate {
+                    name: "Aunt May".to_string(),
+                    existing: "mom".to_string()
+                },
+                CsvRow::Duplicate {
+                    name: "jo".to_string(),
+                    existing: "Jo".to_string()
+                },
+                CsvRow::Duplicate {
+                    name: "Sam".to_string(),
+                    existing: "Jo".to_string()
+                },
+                CsvRow::Invalid {
+                    row: 7,
+                    value: "No Number,n/a".to_string()
+                },
+            ]
+        );
+
+        // Columns can be in any order
+        let plan = plan_import(&config, "dad@example.com,Dad\n", 2, 1).unwrap();
+        assert_eq!(
+            plan,
+            vec![CsvRow::Add {
+                name: "Dad".to_string(),
+                identifier: "dad@example.com".to_string()
+            }]
+        );
+        assert!(plan_import(&config, "", 0, 1).is_err());
+    }
+}
@@ -1,75 +1,2185 @@
 use crate::error::{Error, Result};
-use chrono::{DateTime, Local, TimeZone};
-use rusqlite::{params, Connection};
+use crate::formatter::{is_automated_sender, similar_identifiers};
+use crate::i18n::t;
+use crate::paths;
+use crate::reactions::{self, Reaction, TapbackChange, TapbackRow};
+use crate::search::{local_midnight, Author, SearchQuery};
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use rusqlite::backup::Backup;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::Value;
+use rusqlite::{
+    params, params_from_iter, CachedStatement, Connection, OpenFlags, OptionalExtension,
+};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::env;
-use std::path::PathBuf;
+use std::fs::{self, OpenOptions};
+use std::ops::Deref;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, SystemTime};
 
 /// Database path relative to the home directory.
 const DB_PATH: &str = "Library/Messages/chat.db";
 
+/// Maximum number of idle connections kept by `MessageDBPool`.
+const POOL_SIZE: usize = 4;
+
+/// Start of the file name of the copy of chat.db queried in snapshot mode. Each process takes
+/// its own copy, named with its process id.
+const SNAPSHOT_PREFIX: &str = "chat-snapshot";
+
+/// Pages copied at a time while taking a snapshot, and the pause when the copy is busy.
+const SNAPSHOT_STEP_PAGES: i32 = 1024;
+const SNAPSHOT_BUSY_PAUSE: Duration = Duration::from_millis(10);
+
+/// Tables and indexes of chat.db that the queries read, as Messages.app creates them, for
+/// building databases in the same shape.
+pub const SCHEMA: &str = r#"
+    CREATE TABLE handle (
+        ROWID INTEGER PRIMARY KEY AUTOINCREMENT UNIQUE,
+        id TEXT NOT NULL,
+        country TEXT,
+        service TEXT NOT NULL,
+        uncanonicalized_id TEXT,
+        person_centric_id TEXT,
+        UNIQUE (id, service)
+    );
+    CREATE TABLE chat (
+        ROWID INTEGER PRIMARY KEY AUTOINCREMENT,
+        guid TEXT UNIQUE NOT NULL,
+        style INTEGER,
+        chat_identifier TEXT,
+        service_name TEXT,
+        display_name TEXT
+    );
+    CREATE TABLE message (
+        ROWID INTEGER PRIMARY KEY AUTOINCREMENT,
+        guid TEXT UNIQUE NOT NULL,
+        text TEXT,
+        handle_id INTEGER DEFAULT 0,
+        service TEXT,
+        date INTEGER,
+        date_read INTEGER,
+        date_delivered INTEGER,
+        is_from_me INTEGER DEFAULT 0,
+        is_read INTEGER DEFAULT 0,
+        is_audio_message INTEGER DEFAULT 0,
+        cache_has_attachments INTEGER DEFAULT 0,
+        balloon_bundle_id TEXT,
+        item_type INTEGER DEFAULT 0,
+        associated_message_guid TEXT,
+        associated_message_type INTEGER DEFAULT 0,
+        group_action_type INTEGER DEFAULT 0,
+        group_title TEXT,
+        other_handle INTEGER DEFAULT 0
+    );
+    CREATE TABLE attachment (
+        ROWID INTEGER PRIMARY KEY AUTOINCREMENT,
+        guid TEXT UNIQUE NOT NULL,
+        filename TEXT,
+        mime_type TEXT,
+        transfer_name TEXT
+    );
+    CREATE TABLE chat_handle_join (
+        chat_id INTEGER REFERENCES chat (ROWID) ON DELETE CASCADE,
+        handle_id INTEGER REFERENCES handle (ROWID) ON DELETE CASCADE,
+        UNIQUE (chat_id, handle_id)
+    );
+    CREATE TABLE chat_message_join (
+        chat_id INTEGER REFERENCES chat (ROWID) ON DELETE CASCADE,
+        message_id INTEGER REFERENCES message (ROWID) ON DELETE CASCADE,
+        message_date INTEGER DEFAULT 0,
+        PRIMARY KEY (chat_id, message_id)
+    );
+    CREATE TABLE message_attachment_join (
+        message_id INTEGER REFERENCES message (ROWID) ON DELETE CASCADE,
+        attachment_id INTEGER REFERENCES attachment (ROWID) ON DELETE CASCADE,
+        UNIQUE (message_id, attachment_id)
+    );
+    CREATE INDEX message_idx_handle ON message (handle_id, date);
+    CREATE INDEX message_idx_associated_message ON message (associated_message_guid);
+    CREATE INDEX message_idx_is_read ON message (is_read, is_from_me, item_type);
+    CREATE INDEX chat_message_join_idx_message_id_only ON chat_message_join (message_id);
+    CREATE INDEX chat_idx_chat_identifier ON chat (chat_identifier);
+    CREATE INDEX message_attachment_join_idx_message_id ON message_attachment_join (message_id);
+"#;
+
+/// How queries read chat.db, chosen by `init`.
+static ACCESS: OnceLock<DbAccess> = OnceLock::new();
+
+/// chat.db's location when the configuration moves it, chosen by `init`.
+static PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// An archive queried in place of chat.db, chosen by `use_archive`.
+static ARCHIVE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Seconds from the Unix epoch to 2001-01-01, the epoch of chat.db's dates.
+const APPLE_EPOCH: i64 = 978_307_200;
+
+/// Dates below this are taken to be in seconds rather than nanoseconds (see `DateUnit`).
+const SECONDS_DATE_LIMIT: i64 = 100_000_000_000;
+
+/// When a message was sent, as a Unix timestamp.
+const MESSAGE_TIMESTAMP: &str = "apple_to_unix(message.date)";
+
+/// Columns selected for each `Message`, in the order `query_messages` reads them.
+const MESSAGE_COLUMNS: &str = r#"
+    message.ROWID as rowid,
+    message.guid,
+    message.text,
+    apple_to_unix(message.date) as unix_timestamp,
+    CASE
+        WHEN message.is_audio_message = 1 THEN 'Audio Message'
+        WHEN message.cache_has_attachments = 1 AND (message.text IS NULL OR message.text = '￼') THEN
+            CASE
+                WHEN EXISTS (
+                    SELECT 1
+                    FROM message_attachment_join
+                    JOIN attachment ON message_attachment_join.attachment_id = attachment.ROWID
+                    WHERE message_attachment_join.message_id = message.ROWID
+                      AND attachment.mime_type LIKE 'video/%'
+                ) THEN 'Video'
+                ELSE 'Image'
+            END
+        WHEN message.balloon_bundle_id IS NOT NULL THEN 'iMessage Effect'
+        WHEN message.item_type != 0 THEN 'Special Message'
+        ELSE NULL
+    END as message_type,
+    message.is_from_me,
+    message.service,
+    message.item_type,
+    message.group_action_type,
+    message.group_title,
+    (SELECT id FROM handle WHERE handle.ROWID = message.handle_id) as sender,
+    (SELECT id FROM handle WHERE handle.ROWID = message.other_handle) as other_handle
+"#;
+
+/// Leaves out the rows that record classic tapbacks and their removal, which are shown on the
+/// messages they react to instead. Emoji and sticker tapbacks, which `TapbackChange` doesn't
+/// decode, stay in as messages of their own, so they aren't lost.
+const NOT_TAPBACK: &str = "message.associated_message_type NOT BETWEEN 2000 AND 2005
+    AND message.associated_message_type NOT BETWEEN 3000 AND 3005";
+
+/// Guid of the message a tapback row reacts to. chat.db prefixes it with the part of the
+/// message reacted to, as "p:0/GUID", or with "bp:" for rich links.
+const TAPBACK_TARGET: &str = r#"
+    CASE
+        WHEN message.associated_message_guid LIKE 'bp:%' THEN SUBSTR(message.associated_message_guid, 4)
+        ELSE SUBSTR(message.associated_message_guid, INSTR(message.associated_message_guid, '/') + 1)
+    END
+"#;
+
+/// Prefixes of the parts of a message tapbacks are looked up by. Messages with an attachment
+/// and text keep the text in the second part.
+const TAPBACK_PREFIXES: &[&str] = &["p:0/", "p:1/", "bp:"];
+
+/// Most messages whose tapbacks are looked up with one query, to stay under SQLite's limit
+/// on parameters.
+const TAPBACK_BATCH: usize = 250;
+
+/// Columns the queries read that chat.db only has on newer versions of macOS, and what to
+/// read in their place where they're missing.
+const OPTIONAL_COLUMNS: &[(&str, &str)] = &[
+    ("message.service", "NULL"),
+    ("message.date_read", "0"),
+    ("message.date_delivered", "0"),
+    ("message.is_audio_message", "0"),
+    ("message.cache_has_attachments", "0"),
+    ("message.balloon_bundle_id", "NULL"),
+    ("message.item_type", "0"),
+    ("message.associated_message_guid", "NULL"),
+    ("message.associated_message_type", "0"),
+    ("message.group_action_type", "0"),
+    ("message.group_title", "NULL"),
+    ("message.other_handle", "0"),
+    ("chat.display_name", "NULL"),
+    ("chat.last_addressed_handle", "NULL"),
+    ("chat.account_login", "NULL"),
+    ("attachment.mime_type", "NULL"),
+    ("attachment.transfer_name", "NULL"),
+];
+
+/// Tables that grow with every message, which queries shouldn't read in full.
+const LARGE_TABLES: &[&str] = &["message", "chat_message_join", "message_attachment_join"];
+
+/// Columns the message queries look rows up by, each of which should lead an index.
+const EXPECTED_INDEXES: &[(&str, &str)] = &[
+    ("handle", "id"),
+    ("message", "handle_id"),
+    ("chat", "chat_identifier"),
+    ("chat_message_join", "chat_id"),
+    ("message_attachment_join", "message_id"),
+];
+
+/// Number of columns in `MESSAGE_COLUMNS`.
+const MESSAGE_COLUMN_COUNT: usize = 12;
+
+/// Columns selected for each `Attachment`, in the order `attachment_from_row` reads them.
+const ATTACHMENT_COLUMNS: &str = r#"
+    attachment.guid,
+    attachment.filename,
+    attachment.mime_type,
+    attachment.transfer_name
+"#;
+
+/// How queries read the Messages database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DbAccess {
+    /// Query chat.db directly
+    #[default]
+    Live,
+    /// Query a private copy of chat.db, taken again whenever Messages.app writes to it
+    Snapshot,
+}
+
+/// Choose how queries read chat.db, and where it is if not in its usual place. Has no effect
+/// after the first call.
+pub fn init(access: DbAccess, path: Option<PathBuf>) {
+    let _ = ACCESS.set(access);
+    if let Some(path) = path {
+        let _ = PATH.set(path);
+    }
+}
+
+/// How queries read chat.db.
+fn access() -> DbAccess {
+    ACCESS.get().copied().unwrap_or_default()
+}
+
+/// Query a database in the shape of chat.db at `path`, such as an opened archive, instead of
+/// chat.db for the rest of the process. Has no effect after the first call.
+pub fn use_archive(path: PathBuf) {
+    let _ = ARCHIVE.set(path);
+}
+
+/// Whether queries read an archive rather than chat.db.
+pub fn is_archive() -> bool {
+    ARCHIVE.get().is_some()
+}
+
+/// How a chat.db counts the time since 2001-01-01 in its dates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateUnit {
+    /// Since macOS High Sierra
+    Nanoseconds,
+    /// Before macOS High Sierra, so still in old backups and copies of chat.db
+    Seconds,
+}
+
+impl DateUnit {
+    /// Tell the unit of a database's dates from the latest message's.
+    ///
+    /// In seconds, dates stay below `SECONDS_DATE_LIMIT` for thousands of years, while in
+    /// nanoseconds they pass it a couple of minutes into 2001. A database without dated
+    /// messages is taken to be current.
+    fn detect(conn: &Connection) -> Self {
+        let latest: Option<i64> = conn
+            .query_row(
+                "SELECT date FROM message WHERE date > 0 ORDER BY ROWID DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .ok();
+        match latest {
+            Some(date) if date < SECONDS_DATE_LIMIT => DateUnit::Seconds,
+            _ => DateUnit::Nanoseconds,
+        }
+    }
+
+    /// How many of the unit make a second.
+    fn per_second(self) -> i64 {
+        match self {
+            DateUnit::Nanoseconds => 1_000_000_000,
+            DateUnit::Seconds => 1,
+        }
+    }
+
+    /// Convert a date in this unit to a Unix timestamp.
+    pub fn date_to_unix(self, date: i64) -> i64 {
+        date / self.per_second() + APPLE_EPOCH
+    }
+
+    /// Convert a Unix timestamp to a date in this unit.
+    pub fn unix_to_date(self, timestamp: i64) -> i64 {
+        (timestamp - APPLE_EPOCH) * self.per_second()
+    }
+}
+
+/// A single message in a conversation.
+#[derive(Debug, Clone)]
+pub struct Message {
+    /// ROWID of the message in chat.db.
+    pub rowid: i64,
+    /// Globally unique message identifier.
+    pub guid: String,
+    /// The message text, if any.
+    pub text: Option<String>,
+    /// When the message was sent or received.
+    pub date: DateTime<Local>,
+    /// A label for non-text messages (e.g., "Image", "Video", or "Audio Message").
+    pub message_type: Option<String>,
+    /// Whether the message was sent by the local user.
+    pub is_from_me: bool,
+    /// The service the message went over (e.g., "iMessage" or "SMS"), if known.
+    pub service: Option<String>,
+    /// Tapbacks the message has now, oldest first.
+    pub reactions: Vec<Reaction>,
+    /// The change to a group chat this row records, if it's one rather than a message.
+    pub event: Option<GroupEvent>,
+}
+
+/// A change to a group chat, which chat.db stores as a row in the message table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupEvent {
+    /// Handle of whoever made the change, or `None` for the user.
+    pub actor: Option<String>,
+    pub change: GroupChange,
+}
+
+/// What changed in a group chat.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GroupChange {
+    /// Named the conversation, or removed its name with `None`
+    Renamed(Option<String>),
+    /// Added someone, by handle
+    Added(String),
+    /// Removed someone, by handle
+    Removed(String),
+    Left,
+    PhotoChanged,
+    PhotoRemoved,
+}
+
+impl GroupEvent {
+    /// Decode a row's `item_type` and `group_action_type`, with the handles of whoever made
+    /// the change and whoever it was made to.
+    fn decode(
+        item_type: i64,
+        action: i64,
+        actor: Option<String>,
+        other: Option<String>,
+        title: Option<String>,
+    ) -> Option<Self> {
+        let change = match (item_type, action) {
+            (1, 0) => GroupChange::Added(other?),
+            (1, 1) => GroupChange::Removed(other?),
+            (2, _) => GroupChange::Renamed(title.filter(|title| !title.is_empty())),
+            (3, 0) => GroupChange::Left,
+            (3, 1) => GroupChange::PhotoChanged,
+            (3, 2) => GroupChange::PhotoRemoved,
+            _ => return None,
+        };
+        Some(Self { actor, change })
+    }
+
+    /// Describe the change in a sentence, naming people with `name`, e.g. "Alice named the
+    /// conversation “Ski Trip”".
+    pub fn describe(&self, name: impl Fn(&str) -> String) -> String {
+        let actor = self.actor.as_deref().map_or(t!("group.you"), &name);
+        match &self.change {
+            GroupChange::Renamed(Some(title)) => {
+                t!("group.renamed", actor = actor, title = title)
+            }
+            GroupChange::Renamed(None) => t!("group.unnamed", actor = actor),
+            GroupChange::Added(other) => t!("group.added", actor = actor, other = name(other)),
+            GroupChange::Removed(other) => {
+                t!("group.removed", actor = actor, other = name(other))
+            }
+            GroupChange::Left => t!("group.left", actor = actor),
+            GroupChange::PhotoChanged => t!("group.photo_changed", actor = actor),
+            GroupChange::PhotoRemoved => t!("group.photo_removed", actor = actor),
+        }
+    }
+}
+
+/// A file attached to a message.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    /// Globally unique attachment identifier.
+    pub guid: String,
+    /// Where Messages.app stored the file, with `~` expanded.
+    pub path: Option<PathBuf>,
+    /// The file's MIME type, if Messages.app recorded one.
+    pub mime_type: Option<String>,
+    /// The original file name.
+    pub name: Option<String>,
+}
+
+/// A summary of a conversation for the inbox.
+#[derive(Debug, Clone)]
+pub struct Conversation {
+    /// The chat identifier (a handle for one-on-one chats).
+    pub identifier: String,
+    /// The group name set in Messages.app, if any.
+    pub display_name: Option<String>,
+    /// ROWID of the most recent message.
+    pub last_rowid: i64,
+    /// ROWID of the most recent incoming message.
+    pub last_incoming_rowid: Option<i64>,
+    /// Number of incoming messages Messages.app considers unread.
+    pub unread_count: usize,
+    /// Text of the most recent message, if any.
+    pub last_text: Option<String>,
+    /// When the most recent message was sent or received.
+    pub last_date: DateTime<Local>,
+}
+
+/// How much and how long a contact has been messaged, for the contacts view.
+#[derive(Debug, Clone)]
+pub struct ContactSummary {
+    /// Number of messages with any of the contact's handles.
+    pub message_count: usize,
+    /// When the first message was sent or received, if there are any.
+    pub first_date: Option<DateTime<Local>>,
+    /// The most recent message.
+    pub last_message: Option<Message>,
+}
+
+/// Unread incoming messages across every conversation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnreadSummary {
+    /// Number of incoming messages Messages.app considers unread.
+    pub total: usize,
+    /// Number of conversations with unread messages.
+    pub chats: usize,
+    /// Handle that sent the most recent unread message, if any.
+    pub latest_sender: Option<String>,
+}
+
+impl Conversation {
+    /// Whether the conversation is with a short code or automated sender.
+    ///
+    /// Named group chats always count as conversations with people.
+    pub fn is_automated(&self, extra_patterns: &[String]) -> bool {
+        self.display_name.is_none() && is_automated_sender(&self.identifier, extra_patterns)
+    }
+}
+
 /// Struct representing the Messages database.
 pub struct MessageDB {
     conn: Connection,
+    /// Which of the `OPTIONAL_COLUMNS` this database has
+    schema: Schema,
+    /// Query plan steps recorded while `find_full_scans` runs the queries
+    plans: Option<RefCell<Vec<String>>>,
 }
 
 impl MessageDB {
     /// Open the Messages database.
+    ///
+    /// The connection is read-only: chat.db belongs to Messages.app.
+    /// In snapshot mode, the copy is opened instead, after bringing it up to date, and an
+    /// archive chosen with `use_archive` takes the place of both.
     pub fn open() -> Result<Self> {
-        // Build the path to the Messages database
+        if let Some(archive) = ARCHIVE.get() {
+            return Self::open_at(archive);
+        }
+        match access() {
+            DbAccess::Live => Self::open_at(&Self::path()?),
+            DbAccess::Snapshot => Self::open_at(&refresh_snapshot()?),
+        }
+    }
+
+    /// Open a copy of the Messages database at `path`, read-only like `open`.
+    ///
+    /// Dates are converted in whichever unit the database stores them in, and columns older
+    /// versions of macOS don't have are left out of queries, so old backups open as well as
+    /// the current chat.db.
+    pub fn open_at(path: &Path) -> Result<Self> {
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        // Older databases count dates in seconds, so the date functions follow the database
+        register_functions(&conn, DateUnit::detect(&conn))?;
+        let schema = Schema::detect(&conn)?;
+
+        Ok(Self {
+            conn,
+            schema,
+            plans: None,
+        })
+    }
+
+    /// Path to the Messages database.
+    pub fn path() -> Result<PathBuf> {
+        if let Some(path) = PATH.get() {
+            return Ok(path.clone());
+        }
         let home_dir = env::var("HOME")?;
         let mut db_path = PathBuf::from(home_dir);
         db_path.push(DB_PATH);
+        Ok(db_path)
+    }
+
+    /// Check that the database can actually be read.
+    ///
+    /// Opening chat.db succeeds without Full Disk Access; reading from it doesn't.
+    pub fn check_access(&self) -> Result<()> {
+        self.conn
+            .prepare_cached("SELECT ROWID FROM message LIMIT 1")?
+            .exists([])?;
+        Ok(())
+    }
 
-        // Open the database
-        let conn = Connection::open(db_path)?;
+    /// Run a statement written by the user, such as `im query`'s, and collect every row.
+    ///
+    /// Only statements that leave the database unchanged are run, and the SQL functions in
+    /// `register_functions` are available to them.
+    pub fn run_query(&self, sql: &str) -> Result<QueryRows> {
+        let mut stmt = self.conn.prepare(sql)?;
+        if !stmt.readonly() {
+            return Err(Error::Generic(t!("db.read_only_query")));
+        }
+
+        let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+        let mut rows = stmt.query([])?;
+        let mut values = Vec::new();
+        while let Some(row) = rows.next()? {
+            values.push(
+                (0..columns.len())
+                    .map(|i| row.get(i))
+                    .collect::<rusqlite::Result<_>>()?,
+            );
+        }
 
-        Ok(Self { conn })
+        Ok(QueryRows {
+            columns,
+            rows: values,
+        })
     }
 
-    /// Get messages for a contact.
-    pub fn get_messages(
+    /// Get the latest `limit` messages with any of a contact's handles, merged into one
+    /// timeline, newest first.
+    pub fn get_messages(&self, handles: &[String], limit: usize) -> Result<Vec<Message>> {
+        let rowids = self.handle_rowids(handles)?;
+        let query = format!(
+            r#"
+            SELECT {}
+            FROM message
+            WHERE message.handle_id IN ({}) AND {}
+            ORDER BY date DESC
+            LIMIT ?1;
+            "#,
+            MESSAGE_COLUMNS,
+            placeholders(2, rowids.len()),
+            NOT_TAPBACK
+        );
+
+        self.query_messages(&query, &handle_params(&[&(limit as i64)], &rowids))
+    }
+
+    /// Get up to `limit` messages with any of a contact's handles sent before the message
+    /// `rowid`, newest first.
+    pub fn get_messages_before(
         &self,
-        contact: &str,
-    ) -> Result<Vec<(Option<String>, DateTime<Local>, Option<String>, bool)>> {
-        // SQL query to select messages FROM the specified contact (not TO them)
+        handles: &[String],
+        rowid: i64,
+        limit: usize,
+    ) -> Result<Vec<Message>> {
+        let rowids = self.handle_rowids(handles)?;
+        let query = format!(
+            r#"
+            SELECT {}
+            FROM message
+            WHERE message.handle_id IN ({})
+              AND (message.date, message.ROWID) < (SELECT date, ROWID FROM message WHERE ROWID = ?1)
+              AND {}
+            ORDER BY date DESC, message.ROWID DESC
+            LIMIT ?2;
+            "#,
+            MESSAGE_COLUMNS,
+            placeholders(3, rowids.len()),
+            NOT_TAPBACK
+        );
+
+        self.query_messages(&query, &handle_params(&[&rowid, &(limit as i64)], &rowids))
+    }
+
+    /// Get the latest messages in a chat by chat identifier, newest first.
+    ///
+    /// Unlike `get_messages`, this works for group chats too.
+    pub fn get_chat_messages(&self, chat_identifier: &str, limit: usize) -> Result<Vec<Message>> {
+        let query = format!(
+            r#"
+            SELECT {}
+            FROM chat
+            JOIN chat_message_join ON chat_message_join.chat_id = chat.ROWID
+            JOIN message ON message.ROWID = chat_message_join.message_id
+            WHERE chat.chat_identifier = ? AND {}
+            ORDER BY message.date DESC
+            LIMIT ?;
+            "#,
+            MESSAGE_COLUMNS, NOT_TAPBACK
+        );
+
+        self.query_messages(&query, params![chat_identifier, limit as i64])
+    }
+
+    /// Get every message for a contact after a ROWID (0 for all of them), oldest first.
+    pub fn get_all_messages(&self, contact: &str, after_rowid: i64) -> Result<Vec<Message>> {
+        let query = format!(
+            r#"
+            SELECT {}
+            FROM message
+            WHERE message.handle_id IN (SELECT ROWID FROM handle WHERE id = ?1)
+              AND message.ROWID > ?2
+              AND {}
+            ORDER BY date ASC, message.ROWID ASC;
+            "#,
+            MESSAGE_COLUMNS, NOT_TAPBACK
+        );
+
+        self.query_messages(&query, params![contact, after_rowid])
+    }
+
+    /// Get the attachments of a message.
+    pub fn get_attachments(&self, message_rowid: i64) -> Result<Vec<Attachment>> {
+        let query = format!(
+            r#"
+            SELECT {}
+            FROM attachment
+            JOIN message_attachment_join ON message_attachment_join.attachment_id = attachment.ROWID
+            WHERE message_attachment_join.message_id = ?
+            ORDER BY attachment.ROWID;
+            "#,
+            ATTACHMENT_COLUMNS
+        );
+
+        let home_dir = env::var("HOME")?;
+        let mut stmt = self.prepare(&query)?;
+        let mut rows = stmt.query(params![message_rowid])?;
+        let mut attachments = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            attachments.push(attachment_from_row(row, 0, &home_dir)?);
+        }
+
+        Ok(attachments)
+    }
+
+    /// Get every attachment exchanged with any of a contact's handles since a Unix timestamp,
+    /// oldest first, along with the message it came with.
+    pub fn get_conversation_attachments(
+        &self,
+        handles: &[String],
+        since: i64,
+    ) -> Result<Vec<(Message, Attachment)>> {
+        let rowids = self.handle_rowids(handles)?;
+        let query = format!(
+            r#"
+            SELECT {}, {}
+            FROM message
+            JOIN message_attachment_join ON message_attachment_join.message_id = message.ROWID
+            JOIN attachment ON message_attachment_join.attachment_id = attachment.ROWID
+            WHERE message.handle_id IN ({})
+              AND message.date >= unix_to_apple(?1)
+            ORDER BY message.date ASC, message.ROWID ASC, attachment.ROWID ASC;
+            "#,
+            MESSAGE_COLUMNS,
+            ATTACHMENT_COLUMNS,
+            placeholders(2, rowids.len())
+        );
+
+        let home_dir = env::var("HOME")?;
+        let mut stmt = self.prepare(&query)?;
+        let mut rows = stmt.query(handle_params(&[&since], &rowids).as_slice())?;
+        let mut attachments = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            attachments.push((
+                message_from_row(row, 0)?,
+                attachment_from_row(row, MESSAGE_COLUMN_COUNT, &home_dir)?,
+            ));
+        }
+
+        Ok(attachments)
+    }
+
+    /// Get every attachment along with the identifier of the chat it was sent in, and the
+    /// chat's name if it has one.
+    pub fn get_chat_attachments(&self) -> Result<Vec<(String, Option<String>, Attachment)>> {
+        let query = format!(
+            r#"
+            SELECT chat.chat_identifier, chat.display_name, {}
+            FROM attachment
+            JOIN message_attachment_join ON message_attachment_join.attachment_id = attachment.ROWID
+            JOIN chat_message_join ON chat_message_join.message_id = message_attachment_join.message_id
+            JOIN chat ON chat.ROWID = chat_message_join.chat_id
+            ORDER BY chat.chat_identifier;
+            "#,
+            ATTACHMENT_COLUMNS
+        );
+
+        let home_dir = env::var("HOME")?;
+        let mut stmt = self.prepare(&query)?;
+        let mut rows = stmt.query([])?;
+        let mut attachments = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            let display_name: Option<String> = row.get(1)?;
+            attachments.push((
+                row.get(0)?,
+                // Messages.app stores an empty string for unnamed chats
+                display_name.filter(|name| !name.is_empty()),
+                attachment_from_row(row, 2, &home_dir)?,
+            ));
+        }
+
+        Ok(attachments)
+    }
+
+    /// Get every message with any of a contact's handles since a Unix timestamp, oldest first.
+    pub fn get_conversation_since(&self, handles: &[String], since: i64) -> Result<Vec<Message>> {
+        let rowids = self.handle_rowids(handles)?;
+        let query = format!(
+            r#"
+            SELECT {}
+            FROM message
+            WHERE message.handle_id IN ({})
+              AND message.date >= unix_to_apple(?1)
+              AND {}
+            ORDER BY message.date ASC, message.ROWID ASC;
+            "#,
+            MESSAGE_COLUMNS,
+            placeholders(2, rowids.len()),
+            NOT_TAPBACK
+        );
+
+        self.query_messages(&query, &handle_params(&[&since], &rowids))
+    }
+
+    /// Count the messages with any of a contact's handles on each local day since a Unix
+    /// timestamp. Days without messages are left out.
+    pub fn get_daily_counts(
+        &self,
+        handles: &[String],
+        since: i64,
+    ) -> Result<Vec<(NaiveDate, usize)>> {
+        let rowids = self.handle_rowids(handles)?;
+        let query = format!(
+            r#"
+            SELECT date({timestamp}, 'unixepoch', 'localtime') AS day, COUNT(*)
+            FROM message
+            WHERE message.handle_id IN ({handles})
+              AND message.date >= unix_to_apple(?1)
+            GROUP BY day;
+            "#,
+            timestamp = MESSAGE_TIMESTAMP,
+            handles = placeholders(2, rowids.len())
+        );
+
+        let mut stmt = self.prepare(&query)?;
+        let mut rows = stmt.query(handle_params(&[&since], &rowids).as_slice())?;
+        let mut counts = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            let day: String = row.get(0)?;
+            let count: i64 = row.get(1)?;
+            let day = NaiveDate::parse_from_str(&day, "%Y-%m-%d")
+                .map_err(|_| Error::Generic(t!("db.invalid_message_date", day = day)))?;
+            counts.push((day, count as usize));
+        }
+
+        Ok(counts)
+    }
+
+    /// Count the messages with any of a contact's handles in each local hour of the day
+    /// (0-23) since a Unix timestamp.
+    pub fn get_hourly_counts(&self, handles: &[String], since: i64) -> Result<[usize; 24]> {
+        let rowids = self.handle_rowids(handles)?;
+        let query = format!(
+            r#"
+            SELECT CAST(strftime('%H', {timestamp}, 'unixepoch', 'localtime') AS INTEGER) AS hour,
+                   COUNT(*)
+            FROM message
+            WHERE message.handle_id IN ({handles})
+              AND message.date >= unix_to_apple(?1)
+            GROUP BY hour;
+            "#,
+            timestamp = MESSAGE_TIMESTAMP,
+            handles = placeholders(2, rowids.len())
+        );
+
+        let mut stmt = self.prepare(&query)?;
+        let mut rows = stmt.query(handle_params(&[&since], &rowids).as_slice())?;
+        let mut counts = [0; 24];
+
+        while let Some(row) = rows.next()? {
+            let hour: i64 = row.get(0)?;
+            let count: i64 = row.get(1)?;
+            if let Some(slot) = counts.get_mut(hour as usize) {
+                *slot = count as usize;
+            }
+        }
+
+        Ok(counts)
+    }
+
+    /// Count the messages with any of a contact's handles, and get the first one's date and
+    /// the latest message.
+    pub fn get_contact_summary(&self, handles: &[String]) -> Result<ContactSummary> {
+        let rowids = self.handle_rowids(handles)?;
+        let query = format!(
+            r#"
+            SELECT COUNT(*), MIN({timestamp})
+            FROM message
+            WHERE message.handle_id IN ({handles}) AND {not_tapback};
+            "#,
+            timestamp = MESSAGE_TIMESTAMP,
+            handles = placeholders(1, rowids.len()),
+            not_tapback = NOT_TAPBACK
+        );
+
+        let (count, first): (i64, Option<i64>) = self
+            .prepare(&query)?
+            .query_row(params_from_iter(&rowids), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+        Ok(ContactSummary {
+            message_count: count as usize,
+            first_date: first.map(local_datetime).transpose()?,
+            last_message: self.get_messages(handles, 1)?.into_iter().next(),
+        })
+    }
+
+    /// Get messages with any of a contact's handles surrounding a given message, newest first.
+    pub fn get_messages_around(&self, handles: &[String], rowid: i64) -> Result<Vec<Message>> {
+        let rowids = self.handle_rowids(handles)?;
+        let query = format!(
+            r#"
+            SELECT * FROM (
+                SELECT {columns}
+                FROM message
+                WHERE message.handle_id IN ({handles}) AND message.ROWID <= ?1 AND {not_tapback}
+                ORDER BY date DESC
+                LIMIT 25
+            )
+            UNION ALL
+            SELECT * FROM (
+                SELECT {columns}
+                FROM message
+                WHERE message.handle_id IN ({handles}) AND message.ROWID > ?1 AND {not_tapback}
+                ORDER BY date ASC
+                LIMIT 25
+            )
+            ORDER BY unix_timestamp DESC, rowid DESC;
+            "#,
+            columns = MESSAGE_COLUMNS,
+            handles = placeholders(2, rowids.len()),
+            not_tapback = NOT_TAPBACK
+        );
+
+        self.query_messages(&query, &handle_params(&[&rowid], &rowids))
+    }
+
+    /// Get messages with any of a contact's handles newer than `after_rowid`, plus
+    /// messages from `oldest_rowid` onwards that were delivered or read at or after
+    /// `changed_since`.
+    ///
+    /// Returned oldest first, so new messages can be appended and changed ones
+    /// replaced in place.
+    pub fn get_messages_since(
+        &self,
+        handles: &[String],
+        after_rowid: i64,
+        oldest_rowid: i64,
+        changed_since: i64,
+    ) -> Result<Vec<Message>> {
+        let rowids = self.handle_rowids(handles)?;
+        let query = format!(
+            r#"
+            SELECT {columns}
+            FROM message
+            WHERE message.handle_id IN ({handles})
+              AND {not_tapback}
+              AND (
+                message.ROWID > ?1
+                OR (
+                    message.ROWID >= ?2
+                    AND MAX(message.date_delivered, message.date_read) >= unix_to_apple(?3)
+                )
+                OR (
+                    message.ROWID >= ?2
+                    AND message.guid IN (
+                        SELECT {target}
+                        FROM message
+                        WHERE message.handle_id IN ({handles})
+                          AND message.ROWID > ?1
+                          AND NOT ({not_tapback})
+                    )
+                )
+              )
+            ORDER BY date ASC, message.ROWID ASC;
+            "#,
+            columns = MESSAGE_COLUMNS,
+            handles = placeholders(4, rowids.len()),
+            not_tapback = NOT_TAPBACK,
+            target = TAPBACK_TARGET
+        );
+
+        self.query_messages(
+            &query,
+            &handle_params(&[&after_rowid, &oldest_rowid, &changed_since], &rowids),
+        )
+    }
+
+    /// Search a contact's messages, returning up to `limit` of the latest matches oldest first.
+    pub fn search_messages(
+        &self,
+        handles: &[String],
+        search: &SearchQuery,
+        limit: usize,
+    ) -> Result<Vec<Message>> {
+        let rowids = self.handle_rowids(handles)?;
+        let mut conditions = vec![
+            format!("message.handle_id IN ({})", placeholders(1, rowids.len())),
+            NOT_TAPBACK.to_string(),
+        ];
+        let mut values: Vec<Value> = rowids.iter().copied().map(Value::Integer).collect();
+        let mut bind = |conditions: &mut Vec<String>, condition: &str, value: Value| {
+            values.push(value);
+            conditions.push(condition.replace('?', &format!("?{}", values.len())));
+        };
+
+        for term in &search.terms {
+            bind(
+                &mut conditions,
+                r"message.text LIKE ? ESCAPE '\'",
+                Value::Text(format!("%{}%", escape_like(term))),
+            );
+        }
+        match search.from {
+            Some(Author::Me) => conditions.push("message.is_from_me = 1".to_string()),
+            Some(Author::Them) => conditions.push("message.is_from_me = 0".to_string()),
+            None => {}
+        }
+        if search.has_attachment {
+            conditions.push("message.cache_has_attachments = 1".to_string());
+        }
+        if search.has_link {
+            conditions.push(
+                r#"(
+                    message.text LIKE '%http://%'
+                    OR message.text LIKE '%https://%'
+                    OR message.balloon_bundle_id = 'com.apple.messages.URLBalloonProvider'
+                )"#
+                .to_string(),
+            );
+        }
+        for (date, comparison) in [(search.before, "<"), (search.after, ">=")] {
+            if let Some(date) = date {
+                bind(
+                    &mut conditions,
+                    &format!("message.date {} unix_to_apple(?)", comparison),
+                    Value::Integer(local_midnight(date)?),
+                );
+            }
+        }
+
+        let query = format!(
+            r#"
+            SELECT * FROM (
+                SELECT {}
+                FROM message
+                WHERE {}
+                ORDER BY message.date DESC
+                LIMIT {}
+            )
+            ORDER BY unix_timestamp ASC, rowid ASC;
+            "#,
+            MESSAGE_COLUMNS,
+            conditions.join(" AND "),
+            limit
+        );
+
+        let params: Vec<&dyn rusqlite::ToSql> =
+            values.iter().map(|v| v as &dyn rusqlite::ToSql).collect();
+        self.query_messages(&query, &params)
+    }
+
+    /// Get the handles with the most recent messages and when each last messaged, newest first.
+    pub fn get_recent_handles(&self, limit: usize) -> Result<Vec<(String, DateTime<Local>)>> {
         let query = r#"
-            SELECT text,
-                   date / 1000000000 + strftime('%s','2001-01-01') as unix_timestamp,
-                   CASE
-                       WHEN is_audio_message = 1 THEN 'Audio Message'
-                       WHEN cache_has_attachments = 1 AND (text IS NULL OR text = '￼') THEN 'Image'
-                       WHEN balloon_bundle_id IS NOT NULL THEN 'iMessage Effect'
-                       WHEN item_type != 0 THEN 'Special Message'
-                       ELSE NULL
-                   END as message_type,
-                   is_from_me
+            SELECT handle.id,
+                   apple_to_unix(MAX(message.date)) as unix_timestamp
             FROM message
             JOIN handle ON message.handle_id = handle.ROWID
-            WHERE handle.id = ?
-            ORDER BY date DESC
-            LIMIT 50;
+            GROUP BY handle.id
+            ORDER BY unix_timestamp DESC
+            LIMIT ?;
         "#;
 
-        let mut stmt = self.conn.prepare(query)?;
-        let mut rows = stmt.query(params![contact])?;
-        let mut messages = Vec::new();
+        let mut stmt = self.prepare(query)?;
+        let mut rows = stmt.query(params![limit as i64])?;
+        let mut handles = Vec::new();
 
         while let Some(row) = rows.next()? {
-            // Retrieve the text and timestamp for the message
-            let text: Option<String> = row.get(0)?;
             let timestamp: i64 = row.get(1)?;
-            let message_type: Option<String> = row.get(2)?;
-            let is_from_me: bool = row.get(3)?;
+            handles.push((row.get(0)?, local_datetime(timestamp)?));
+        }
+
+        Ok(handles)
+    }
 
-            // Convert Unix timestamp to DateTime<Local>
-            let dt = match Local.timestamp_opt(timestamp, 0) {
-                chrono::LocalResult::Single(dt) => dt,
-                _ => return Err(Error::Generic("Invalid timestamp".to_string())),
-            };
+    /// Get every handle in chat.db and how many messages it has.
+    pub fn get_handle_message_counts(&self) -> Result<Vec<(String, usize)>> {
+        let query = r#"
+            SELECT handle.id, COUNT(message.ROWID)
+            FROM handle
+            LEFT JOIN message ON message.handle_id = handle.ROWID
+            GROUP BY handle.id;
+        "#;
 
-            messages.push((text, dt, message_type, is_from_me));
+        let mut stmt = self.prepare(query)?;
+        let mut rows = stmt.query([])?;
+        let mut handles = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            let count: i64 = row.get(1)?;
+            handles.push((row.get(0)?, count as usize));
+        }
+
+        Ok(handles)
+    }
+
+    /// Find handles with messages that are probably `identifier` formatted
+    /// differently or mistyped, closest first, with their message counts.
+    ///
+    /// Differences in country code and phone number formatting are ignored.
+    pub fn find_similar_handles(&self, identifier: &str) -> Result<Vec<(String, usize)>> {
+        let counts: Vec<(String, usize)> = self
+            .get_handle_message_counts()?
+            .into_iter()
+            .filter(|(handle, count)| *count > 0 && handle != identifier)
+            .collect();
+        let handles: Vec<String> = counts.iter().map(|(handle, _)| handle.clone()).collect();
+
+        Ok(similar_identifiers(identifier, &handles)
+            .into_iter()
+            .filter_map(|handle| counts.iter().find(|(h, _)| h == handle).cloned())
+            .collect())
+    }
+
+    /// Get the service (e.g., "iMessage" or "SMS") of the most recent message with a handle.
+    pub fn get_handle_service(&self, handle: &str) -> Result<Option<String>> {
+        let query = r#"
+            SELECT message.service
+            FROM message
+            WHERE message.handle_id IN (SELECT ROWID FROM handle WHERE id = ?)
+            ORDER BY message.date DESC
+            LIMIT 1;
+        "#;
+
+        Ok(self
+            .prepare(query)?
+            .query_row(params![handle], |row| row.get(0))
+            .optional()?
+            .flatten())
+    }
+
+    /// Get the guid of the most recent one-on-one chat with a handle that was addressed from
+    /// `alias`, one of the user's own phone numbers or emails.
+    pub fn get_chat_from_alias(&self, handle: &str, alias: &str) -> Result<Option<String>> {
+        let query = r#"
+            SELECT guid
+            FROM chat
+            WHERE chat.chat_identifier = ?1
+              AND (chat.last_addressed_handle = ?2
+                   OR chat.account_login IN ('E:' || ?2, 'P:' || ?2))
+            ORDER BY ROWID DESC
+            LIMIT 1;
+        "#;
+
+        Ok(self
+            .prepare(query)?
+            .query_row(params![handle, alias], |row| row.get(0))
+            .optional()?)
+    }
+
+    /// Guess the user's own phone number or email from the addresses their chats were sent to,
+    /// picking the one most chats use, or of those, the one the newest chat uses.
+    pub fn get_own_handle(&self) -> Result<Option<String>> {
+        let query = r#"
+            SELECT address
+            FROM (
+                SELECT COALESCE(
+                    NULLIF(chat.last_addressed_handle, ''),
+                    CASE WHEN chat.account_login LIKE 'E:%' OR chat.account_login LIKE 'P:%'
+                         THEN SUBSTR(chat.account_login, 3) END
+                ) AS address,
+                chat.ROWID AS chat_id
+                FROM chat
+            )
+            WHERE address IS NOT NULL AND address != ''
+            GROUP BY address
+            ORDER BY COUNT(*) DESC, MAX(chat_id) DESC
+            LIMIT 1;
+        "#;
+
+        Ok(self
+            .prepare(query)?
+            .query_row([], |row| row.get(0))
+            .optional()?)
+    }
+
+    /// Get the handle of the conversation a message belongs to, by message guid.
+    pub fn get_message_handle(&self, guid: &str) -> Result<Option<String>> {
+        let query = r#"
+            SELECT handle.id
+            FROM message
+            JOIN handle ON message.handle_id = handle.ROWID
+            WHERE message.guid = ?;
+        "#;
+
+        Ok(self
+            .prepare(query)?
+            .query_row(params![guid], |row| row.get(0))
+            .optional()?)
+    }
+
+    /// Get the guid of the group chat a message was sent in, by message guid, or `None` if it
+    /// was a one-on-one conversation.
+    pub fn get_group_chat(&self, guid: &str) -> Result<Option<String>> {
+        let query = r#"
+            SELECT chat.guid
+            FROM message
+            JOIN chat_message_join ON chat_message_join.message_id = message.ROWID
+            JOIN chat ON chat.ROWID = chat_message_join.chat_id
+            WHERE message.guid = ?1
+              AND (SELECT COUNT(*) FROM chat_handle_join
+                   WHERE chat_handle_join.chat_id = chat.ROWID) > 1
+            LIMIT 1;
+        "#;
+
+        Ok(self
+            .prepare(query)?
+            .query_row(params![guid], |row| row.get(0))
+            .optional()?)
+    }
+
+    /// Get the most recent incoming message and the handle that sent it.
+    pub fn get_latest_incoming(&self) -> Result<Option<(String, Message)>> {
+        let query = format!(
+            r#"
+            SELECT handle.id, {}
+            FROM message
+            JOIN handle ON message.handle_id = handle.ROWID
+            WHERE message.is_from_me = 0
+            ORDER BY message.date DESC
+            LIMIT 1;
+            "#,
+            MESSAGE_COLUMNS
+        );
+
+        let mut stmt = self.prepare(&query)?;
+        let mut rows = stmt.query([])?;
+        match rows.next()? {
+            Some(row) => {
+                let handle: String = row.get(0)?;
+                Ok(Some((handle, message_from_row(row, 1)?)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Get the ROWID of the newest message, or 0 if there are none.
+    ///
+    /// ROWIDs only grow, so unlike message dates they can't make a message that's already here
+    /// look new.
+    pub fn get_latest_rowid(&self) -> Result<i64> {
+        let mut stmt = self.prepare("SELECT COALESCE(MAX(ROWID), 0) FROM message")?;
+        Ok(stmt.query_row([], |row| row.get(0))?)
+    }
+
+    /// Get incoming messages newer than a message ROWID and the handles that sent them, oldest
+    /// first.
+    pub fn get_incoming_since(&self, rowid: i64) -> Result<Vec<(String, Message)>> {
+        let query = format!(
+            r#"
+            SELECT handle.id, {}
+            FROM message
+            JOIN handle ON message.handle_id = handle.ROWID
+            WHERE message.is_from_me = 0
+              AND message.ROWID > ?1
+            ORDER BY message.ROWID ASC;
+            "#,
+            MESSAGE_COLUMNS
+        );
+
+        let mut stmt = self.prepare(&query)?;
+        let mut rows = stmt.query(params![rowid])?;
+        let mut messages = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            let handle: String = row.get(0)?;
+            messages.push((handle, message_from_row(row, 1)?));
         }
 
         Ok(messages)
     }
+
+    /// Find the ROWIDs of a contact's handles, so message queries can filter on
+    /// `message.handle_id` directly instead of joining `handle` for every message.
+    fn handle_rowids(&self, handles: &[String]) -> Result<Vec<i64>> {
+        let query = format!(
+            "SELECT ROWID FROM handle WHERE id IN ({});",
+            placeholders(1, handles.len())
+        );
+
+        let mut stmt = self.prepare(&query)?;
+        let rowids = stmt
+            .query_map(params_from_iter(handles), |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(rowids)
+    }
+
+    /// Prepare a cached statement, recording its query plan while `find_full_scans` runs.
+    ///
+    /// Optional columns the database doesn't have are read as their fallback values.
+    fn prepare(&self, query: &str) -> Result<CachedStatement<'_>> {
+        let query = &*self.schema.adapt(query);
+        if let Some(plans) = &self.plans {
+            let mut stmt = self
+                .conn
+                .prepare(&format!("EXPLAIN QUERY PLAN {}", query))?;
+            // The plan doesn't depend on the parameters, so they're left unbound
+            let mut rows = stmt.raw_query();
+            while let Some(row) = rows.next()? {
+                plans.borrow_mut().push(row.get(3)?);
+            }
+        }
+        Ok(self.conn.prepare_cached(query)?)
+    }
+
+    /// Run a query selecting `MESSAGE_COLUMNS` and collect the messages.
+    fn query_messages(&self, query: &str, params: &[&dyn rusqlite::ToSql]) -> Result<Vec<Message>> {
+        let mut stmt = self.prepare(query)?;
+        let mut rows = stmt.query(params)?;
+        let mut messages = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            messages.push(message_from_row(row, 0)?);
+        }
+
+        self.add_reactions(&mut messages)?;
+        Ok(messages)
+    }
+
+    /// Fill in the tapbacks messages have now.
+    fn add_reactions(&self, messages: &mut [Message]) -> Result<()> {
+        let mut rows = Vec::new();
+        for batch in messages.chunks(TAPBACK_BATCH) {
+            // Looked up by the full associated guid, which chat.db indexes
+            let targets: Vec<String> = batch
+                .iter()
+                .flat_map(|message| {
+                    TAPBACK_PREFIXES
+                        .iter()
+                        .map(move |prefix| format!("{}{}", prefix, message.guid))
+                })
+                .collect();
+            let query = format!(
+                r#"
+                SELECT {}, message.associated_message_type, message.is_from_me, handle.id
+                FROM message
+                LEFT JOIN handle ON message.handle_id = handle.ROWID
+                WHERE message.associated_message_guid IN ({})
+                ORDER BY message.date ASC, message.ROWID ASC;
+                "#,
+                TAPBACK_TARGET,
+                placeholders(1, targets.len())
+            );
+
+            let mut stmt = self.prepare(&query)?;
+            let mut results = stmt.query(params_from_iter(&targets))?;
+            while let Some(row) = results.next()? {
+                let Some(change) = TapbackChange::from_type(row.get(1)?) else {
+                    continue;
+                };
+                let is_from_me: bool = row.get(2)?;
+                rows.push(TapbackRow {
+                    target: row.get(0)?,
+                    change,
+                    sender: if is_from_me { None } else { row.get(3)? },
+                });
+            }
+        }
+
+        let mut reactions = reactions::associate(rows);
+        for message in messages {
+            message.reactions = reactions.remove(&message.guid).unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    /// Get the most recently active conversations, newest first.
+    pub fn get_conversations(&self, limit: usize) -> Result<Vec<Conversation>> {
+        // Chats sharing an identifier (e.g., SMS and iMessage threads with the
+        // same number) are folded into a single conversation.
+        let query = r#"
+            WITH convo AS (
+                SELECT chat.chat_identifier as identifier,
+                       MAX(chat.display_name) as display_name,
+                       MAX(message.ROWID) as last_rowid,
+                       MAX(CASE WHEN message.is_from_me = 0 THEN message.ROWID END) as last_incoming_rowid,
+                       SUM(CASE WHEN message.is_from_me = 0 AND message.is_read = 0 THEN 1 ELSE 0 END) as unread_count,
+                       MAX(message.date) as last_date
+                FROM chat
+                JOIN chat_message_join ON chat_message_join.chat_id = chat.ROWID
+                JOIN message ON message.ROWID = chat_message_join.message_id
+                GROUP BY chat.chat_identifier
+            )
+            SELECT convo.identifier,
+                   convo.display_name,
+                   convo.last_rowid,
+                   convo.last_incoming_rowid,
+                   convo.unread_count,
+                   message.text,
+                   apple_to_unix(convo.last_date) as unix_timestamp
+            FROM convo
+            JOIN message ON message.ROWID = convo.last_rowid
+            ORDER BY convo.last_date DESC
+            LIMIT ?;
+        "#;
+
+        let mut stmt = self.prepare(query)?;
+        let mut rows = stmt.query(params![limit as i64])?;
+        let mut conversations = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            let display_name: Option<String> = row.get(1)?;
+            let unread_count: i64 = row.get(4)?;
+            let timestamp: i64 = row.get(6)?;
+
+            conversations.push(Conversation {
+                identifier: row.get(0)?,
+                // Messages.app stores an empty string for unnamed chats
+                display_name: display_name.filter(|name| !name.is_empty()),
+                last_rowid: row.get(2)?,
+                last_incoming_rowid: row.get(3)?,
+                unread_count: unread_count as usize,
+                last_text: row.get(5)?,
+                last_date: local_datetime(timestamp)?,
+            });
+        }
+
+        Ok(conversations)
+    }
+
+    /// Count incoming messages in a conversation newer than a message ROWID.
+    pub fn count_incoming_since(&self, identifier: &str, rowid: i64) -> Result<usize> {
+        let query = r#"
+            SELECT COUNT(*)
+            FROM chat
+            JOIN chat_message_join ON chat_message_join.chat_id = chat.ROWID
+            JOIN message ON message.ROWID = chat_message_join.message_id
+            WHERE chat.chat_identifier = ?1
+              AND message.is_from_me = 0
+              AND message.ROWID > ?2;
+        "#;
+
+        let count: i64 = self
+            .prepare(query)?
+            .query_row(params![identifier, rowid], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// Count the unread incoming messages and the conversations they're in, and find who sent
+    /// the latest one.
+    pub fn get_unread_summary(&self) -> Result<UnreadSummary> {
+        let counts = r#"
+            SELECT COUNT(DISTINCT message.ROWID), COUNT(DISTINCT chat.chat_identifier)
+            FROM message
+            JOIN chat_message_join ON chat_message_join.message_id = message.ROWID
+            JOIN chat ON chat.ROWID = chat_message_join.chat_id
+            WHERE message.is_read = 0 AND message.is_from_me = 0;
+        "#;
+        let latest = r#"
+            SELECT handle.id
+            FROM message
+            JOIN handle ON message.handle_id = handle.ROWID
+            WHERE message.is_read = 0 AND message.is_from_me = 0
+            ORDER BY message.date DESC
+            LIMIT 1;
+        "#;
+
+        let (total, chats): (i64, i64) = self
+            .prepare(counts)?
+            .query_row([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        let latest_sender = self
+            .prepare(latest)?
+            .query_row([], |row| row.get(0))
+            .optional()?;
+        Ok(UnreadSummary {
+            total: total as usize,
+            chats: chats as usize,
+            latest_sender,
+        })
+    }
+
+    /// Run the queries a chat makes for a contact and report those whose query plan reads the
+    /// whole of a large table, with the plan step that does.
+    pub fn find_full_scans(&mut self, handles: &[String]) -> Result<Vec<FullScan>> {
+        let identifier = handles.first().map(String::as_str).unwrap_or_default();
+        let search = SearchQuery::parse("a from:them")?;
+        type Check<'a> = &'a dyn Fn(&MessageDB) -> Result<()>;
+        let checks: [(&'static str, Check); 6] = [
+            ("get_messages", &|db| db.get_messages(handles, 1).map(drop)),
+            ("get_messages_before", &|db| {
+                db.get_messages_before(handles, 0, 1).map(drop)
+            }),
+            ("get_messages_since", &|db| {
+                db.get_messages_since(handles, i64::MAX, i64::MAX, 0)
+                    .map(drop)
+            }),
+            ("get_messages_around", &|db| {
+                db.get_messages_around(handles, 0).map(drop)
+            }),
+            ("search_messages", &|db| {
+                db.search_messages(handles, &search, 1).map(drop)
+            }),
+            ("count_incoming_since", &|db| {
+                db.count_incoming_since(identifier, i64::MAX).map(drop)
+            }),
+        ];
+
+        let mut scans = Vec::new();
+        for (query, check) in checks {
+            self.plans = Some(RefCell::default());
+            let result = check(self);
+            let steps = self.plans.take().unwrap_or_default().into_inner();
+            result?;
+            scans.extend(
+                steps
+                    .into_iter()
+                    .filter(|step| is_full_scan(step))
+                    .map(|detail| FullScan { query, detail }),
+            );
+        }
+        Ok(scans)
+    }
+
+    /// Columns the queries read that this chat.db is too old to have, as `table.column`.
+    pub fn missing_columns(&self) -> Vec<&'static str> {
+        self.schema.missing.clone()
+    }
+
+    /// Indexes the message queries rely on that this chat.db doesn't have, as `table(column)`.
+    pub fn missing_indexes(&self) -> Result<Vec<String>> {
+        let query = r#"
+            SELECT 1
+            FROM pragma_index_list(?1) AS list
+            JOIN pragma_index_info(list.name) AS info
+            WHERE info.seqno = 0 AND info.name = ?2;
+        "#;
+
+        let mut missing = Vec::new();
+        for (table, column) in EXPECTED_INDEXES {
+            if !self.prepare(query)?.exists(params![table, column])? {
+                missing.push(format!("{}({})", table, column));
+            }
+        }
+        Ok(missing)
+    }
+}
+
+/// The rows a statement run with `run_query` returned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryRows {
+    /// Column names, in the order the statement selects them
+    pub columns: Vec<String>,
+    /// Each row's values, one per column
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// The optional columns a database in the shape of chat.db has.
+#[derive(Debug, Clone, Default)]
+struct Schema {
+    /// The `OPTIONAL_COLUMNS` it lacks, as `table.column`
+    missing: Vec<&'static str>,
+}
+
+impl Schema {
+    /// Read which optional columns a database has from its tables.
+    fn detect(conn: &Connection) -> Result<Self> {
+        let mut stmt = conn.prepare("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2")?;
+        let mut missing = Vec::new();
+        for (column, _) in OPTIONAL_COLUMNS {
+            let (table, name) = column.split_once('.').unwrap_or_default();
+            if !stmt.exists(params![table, name])? {
+                missing.push(*column);
+            }
+        }
+        Ok(Self { missing })
+    }
+
+    /// Rewrite a query to read each missing column as its fallback value.
+    fn adapt<'a>(&self, query: &'a str) -> Cow<'a, str> {
+        let mut query = Cow::Borrowed(query);
+        for (column, fallback) in OPTIONAL_COLUMNS {
+            if self.missing.contains(column) && query.contains(column) {
+                query = Cow::Owned(query.replace(column, fallback));
+            }
+        }
+        query
+    }
+}
+
+/// A query whose plan reads the whole of a large table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FullScan {
+    /// The `MessageDB` method making the query
+    pub query: &'static str,
+    /// The step of the query plan, as `EXPLAIN QUERY PLAN` describes it
+    pub detail: String,
+}
+
+/// A small pool of connections to the Messages database.
+///
+/// Connections cache their prepared statements, so reusing them across views
+/// avoids reopening chat.db and re-preparing queries.
+#[derive(Clone, Default)]
+pub struct MessageDBPool {
+    idle: Arc<Mutex<Vec<MessageDB>>>,
+}
+
+impl MessageDBPool {
+    /// Get the pool shared by the whole process.
+    pub fn shared() -> &'static MessageDBPool {
+        static POOL: OnceLock<MessageDBPool> = OnceLock::new();
+        POOL.get_or_init(MessageDBPool::default)
+    }
+
+    /// Take an idle connection, or open a new one if none are idle.
+    pub fn get(&self) -> Result<PooledMessageDB> {
+        let idle = self.idle.lock().ok().and_then(|mut idle| idle.pop());
+        let db = match idle {
+            Some(db) => {
+                // Idle connections read the snapshot file, so updating it updates them
+                if access() == DbAccess::Snapshot && !is_archive() {
+                    refresh_snapshot()?;
+                }
+                db
+            }
+            None => MessageDB::open()?,
+        };
+
+        Ok(PooledMessageDB {
+            db: Some(db),
+            pool: Arc::clone(&self.idle),
+        })
+    }
+}
+
+/// A connection borrowed from a `MessageDBPool`, returned to it when dropped.
+pub struct PooledMessageDB {
+    db: Option<MessageDB>,
+    pool: Arc<Mutex<Vec<MessageDB>>>,
+}
+
+impl Deref for PooledMessageDB {
+    type Target = MessageDB;
+
+    fn deref(&self) -> &MessageDB {
+        self.db.as_ref().expect("connection is only taken on drop")
+    }
+}
+
+impl Drop for PooledMessageDB {
+    fn drop(&mut self) {
+        if let (Some(db), Ok(mut idle)) = (self.db.take(), self.pool.lock()) {
+            if idle.len() < POOL_SIZE {
+                idle.push(db);
+            }
+        }
+    }
+}
+
+/// Delete this process's snapshot of chat.db, if it took one, before exiting.
+pub fn remove_snapshot() {
+    if let Ok(snapshot) = paths::process_database(SNAPSHOT_PREFIX) {
+        let _ = fs::remove_file(snapshot);
+    }
+}
+
+/// Copy chat.db to the snapshot if Messages.app has written to it since the last copy, and
+/// return the snapshot's path.
+fn refresh_snapshot() -> Result<PathBuf> {
+    static COPIED: Mutex<Option<FileStamp>> = Mutex::new(None);
+
+    let live = MessageDB::path()?;
+    // Its own, so two `im`s never copy over each other's
+    let snapshot = paths::process_database(SNAPSHOT_PREFIX)?;
+    let stamp = file_stamp(&live);
+
+    let mut copied = COPIED.lock().unwrap_or_else(PoisonError::into_inner);
+    if copied.as_ref() != Some(&stamp) || !snapshot.is_file() {
+        copy_database(&live, &snapshot)?;
+        *copied = Some(stamp);
+    }
+    Ok(snapshot)
+}
+
+/// Modification times and sizes of a database and its write-ahead log.
+type FileStamp = [Option<(SystemTime, u64)>; 2];
+
+/// Stamp a database, so a change to it or its write-ahead log can be noticed.
+fn file_stamp(path: &Path) -> FileStamp {
+    let stamp = |path: PathBuf| {
+        let metadata = fs::metadata(path).ok()?;
+        Some((metadata.modified().ok()?, metadata.len()))
+    };
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+    [stamp(path.to_path_buf()), stamp(PathBuf::from(wal))]
+}
+
+/// Copy a live database, including changes still in its write-ahead log, over `destination`
+/// with SQLite's backup API.
+///
+/// The copy is only readable by the user, and connections already open on it see the new
+/// contents.
+fn copy_database(source: &Path, destination: &Path) -> Result<()> {
+    let source = Connection::open_with_flags(
+        source,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    // Create the file before SQLite does, so it isn't readable by other users
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(destination)?;
+
+    let mut copy = Connection::open(destination)?;
+    copy.busy_timeout(Duration::from_secs(5))?;
+    Backup::new(&source, &mut copy)?.run_to_completion(
+        SNAPSHOT_STEP_PAGES,
+        SNAPSHOT_BUSY_PAUSE,
+        None,
+    )?;
+    // The copy has no log of its own, so read-only connections can open it
+    copy.pragma_update(None, "journal_mode", "DELETE")?;
+    Ok(())
+}
+
+/// Register the SQL functions statements can use on a connection, converting dates stored in
+/// `unit`:
+///
+/// - `apple_to_unix(date)`: a chat.db date as a Unix timestamp
+/// - `unix_to_apple(timestamp)`: a Unix timestamp as a chat.db date, for comparing with dates
+///   without converting every row
+/// - `apple_date(date)`: a chat.db date as local time, like `2024-06-01 18:02:11`
+///
+/// Each returns NULL for NULL.
+fn register_functions(conn: &Connection, unit: DateUnit) -> Result<()> {
+    let flags = || FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC;
+    conn.create_scalar_function("apple_to_unix", 1, flags(), move |ctx| {
+        Ok(ctx
+            .get::<Option<i64>>(0)?
+            .map(|date| unit.date_to_unix(date)))
+    })?;
+    conn.create_scalar_function("unix_to_apple", 1, flags(), move |ctx| {
+        Ok(ctx
+            .get::<Option<i64>>(0)?
+            .map(|timestamp| unit.unix_to_date(timestamp)))
+    })?;
+    conn.create_scalar_function("apple_date", 1, flags(), move |ctx| {
+        Ok(ctx
+            .get::<Option<i64>>(0)?
+            .and_then(|date| local_datetime(unit.date_to_unix(date)).ok())
+            .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string()))
+    })?;
+    Ok(())
+}
+
+/// Numbered placeholders for an `IN` list of `count` parameters, starting at `?first`.
+fn placeholders(first: usize, count: usize) -> String {
+    (first..first + count)
+        .map(|i| format!("?{}", i))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Escape `%`, `_`, and `\` for a `LIKE ... ESCAPE '\'` pattern.
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Query parameters: `fixed` ones first, then one per handle ROWID for `placeholders`.
+fn handle_params<'a>(
+    fixed: &[&'a dyn rusqlite::ToSql],
+    rowids: &'a [i64],
+) -> Vec<&'a dyn rusqlite::ToSql> {
+    fixed
+        .iter()
+        .copied()
+        .chain(rowids.iter().map(|h| h as &dyn rusqlite::ToSql))
+        .collect()
+}
+
+/// Whether a query plan step reads the whole of one of the large message tables.
+fn is_full_scan(detail: &str) -> bool {
+    // Older SQLite versions say "SCAN TABLE message"
+    let Some(scan) = detail.strip_prefix("SCAN ") else {
+        return false;
+    };
+    let table = scan.strip_prefix("TABLE ").unwrap_or(scan);
+    let table = table.split_whitespace().next().unwrap_or_default();
+    LARGE_TABLES.contains(&table)
+}
+
+/// Read a `Message` from `MESSAGE_COLUMNS` starting at column `offset`.
+pub(crate) fn message_from_row(row: &rusqlite::Row, offset: usize) -> Result<Message> {
+    // Retrieve the text and timestamp for the message
+    let timestamp: i64 = row.get(offset + 3)?;
+    let is_from_me: bool = row.get(offset + 5)?;
+    let actor: Option<String> = if is_from_me {
+        None
+    } else {
+        row.get(offset + 10)?
+    };
+
+    Ok(Message {
+        rowid: row.get(offset)?,
+        guid: row.get(offset + 1)?,
+        text: row.get(offset + 2)?,
+        date: local_datetime(timestamp)?,
+        message_type: row.get(offset + 4)?,
+        is_from_me,
+        service: row.get(offset + 6)?,
+        reactions: Vec::new(),
+        event: GroupEvent::decode(
+            row.get::<_, Option<i64>>(offset + 7)?.unwrap_or(0),
+            row.get::<_, Option<i64>>(offset + 8)?.unwrap_or(0),
+            actor,
+            row.get(offset + 11)?,
+            row.get(offset + 9)?,
+        ),
+    })
+}
+
+/// Read an `Attachment` from `ATTACHMENT_COLUMNS` starting at column `offset`, expanding `~`
+/// in its path to `home_dir`.
+fn attachment_from_row(row: &rusqlite::Row, offset: usize, home_dir: &str) -> Result<Attachment> {
+    let filename: Option<String> = row.get(offset + 1)?;
+
+    Ok(Attachment {
+        guid: row.get(offset)?,
+        path: filename.map(|f| match f.strip_prefix("~/") {
+            Some(rest) => PathBuf::from(home_dir).join(rest),
+            None => PathBuf::from(f),
+        }),
+        mime_type: row.get(offset + 2)?,
+        name: row.get(offset + 3)?,
+    })
+}
+
+/// Convert a Unix timestamp to a local `DateTime`.
+fn local_datetime(timestamp: i64) -> Result<DateTime<Local>> {
+    match Local.timestamp_opt(timestamp, 0) {
+        chrono::LocalResult::Single(dt) => Ok(dt),
+        _ => Err(Error::Generic(t!("db.invalid_timestamp"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reactions::Tapback;
+    use std::ops::Deref;
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A database file of its own in the temporary directory, deleted along with its journals
+    /// when dropped, even if the test failed
+    struct TempDb(PathBuf);
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let path = env::temp_dir().join(format!(
+                "im-{}-test-{}-{}.db",
+                name,
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            let db = Self(path);
+            db.remove();
+            db
+        }
+
+        fn remove(&self) {
+            for suffix in ["", "-wal", "-shm", "-journal"] {
+                let mut path = self.0.clone().into_os_string();
+                path.push(suffix);
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    impl Deref for TempDb {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TempDb {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            self.remove();
+        }
+    }
+
+    #[test]
+    fn test_copy_database() {
+        let (live_path, copy_path) = (TempDb::new("snapshot-live"), TempDb::new("snapshot-copy"));
+
+        let live = Connection::open(&live_path).unwrap();
+        live.pragma_update(None, "journal_mode", "WAL").unwrap();
+        live.execute_batch("CREATE TABLE message (text TEXT); INSERT INTO message VALUES ('hi');")
+            .unwrap();
+
+        copy_database(&live_path, &copy_path).unwrap();
+        let reader =
+            Connection::open_with_flags(&copy_path, OpenFlags::SQLITE_OPEN_READ_ONLY).unwrap();
+        let count = || -> i64 {
+            reader
+                .query_row("SELECT COUNT(*) FROM message", [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(count(), 1);
+
+        // Rows still in the live database's log are copied, and the open reader sees them
+        live.execute("INSERT INTO message VALUES ('there')", [])
+            .unwrap();
+        assert_ne!(file_stamp(&live_path)[1], None);
+        copy_database(&live_path, &copy_path).unwrap();
+        assert_eq!(count(), 2);
+        assert_eq!(
+            fs::metadata(&copy_path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+    }
+
+    #[test]
+    fn test_old_schema() {
+        let path = TempDb::new("old-schema");
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                r#"
+                CREATE TABLE handle (ROWID INTEGER PRIMARY KEY, id TEXT);
+                CREATE TABLE chat (ROWID INTEGER PRIMARY KEY, guid TEXT, chat_identifier TEXT);
+                CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT, text TEXT,
+                                      handle_id INTEGER, date INTEGER, is_from_me INTEGER,
+                                      is_read INTEGER);
+                CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);
+                CREATE TABLE attachment (ROWID INTEGER PRIMARY KEY, guid TEXT, filename TEXT);
+                CREATE TABLE message_attachment_join (message_id INTEGER, attachment_id INTEGER);
+                INSERT INTO handle VALUES (1, 'mom@example.com');
+                INSERT INTO chat VALUES (1, 'iMessage;-;mom@example.com', 'mom@example.com');
+                INSERT INTO message VALUES (1, 'A', 'hi', 1, 500000000, 0, 0);
+                INSERT INTO chat_message_join VALUES (1, 1);
+                "#,
+            )
+            .unwrap();
+        let db = MessageDB::open_at(&path).unwrap();
+
+        assert!(db.missing_columns().contains(&"message.item_type"));
+        let handles = vec!["mom@example.com".to_string()];
+        let messages = db.get_messages(&handles, 10).unwrap();
+        assert_eq!(messages[0].text.as_deref(), Some("hi"));
+        assert_eq!(messages[0].message_type, None);
+        assert_eq!(messages[0].service, None);
+        assert!(db.get_messages_since(&handles, 0, 0, 0).is_ok());
+        assert_eq!(db.get_conversations(10).unwrap()[0].unread_count, 1);
+        assert_eq!(
+            db.get_chat_from_alias("mom@example.com", "me@example.com")
+                .unwrap(),
+            None
+        );
+        assert_eq!(db.get_own_handle().unwrap(), None);
+    }
+
+    #[test]
+    fn test_own_handle() {
+        let path = TempDb::new("own-handle");
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                r#"
+                CREATE TABLE chat (ROWID INTEGER PRIMARY KEY, chat_identifier TEXT,
+                                   last_addressed_handle TEXT, account_login TEXT);
+                CREATE TABLE message (date INTEGER);
+                INSERT INTO chat VALUES (1, 'mom@example.com', '+15551234567', 'E:me@example.com');
+                INSERT INTO chat VALUES (2, 'sam@example.com', '', 'E:me@example.com');
+                INSERT INTO chat VALUES (3, '+15557654321', NULL, 'E:me@example.com');
+                INSERT INTO chat VALUES (4, '+15550000000', '+15551234567', NULL);
+                INSERT INTO chat VALUES (5, 'alex@example.com', NULL, 'E:me@example.com');
+                "#,
+            )
+            .unwrap();
+        let db = MessageDB::open_at(&path).unwrap();
+
+        // The account login stands in where the addressed handle wasn't recorded
+        assert_eq!(
+            db.get_own_handle().unwrap().as_deref(),
+            Some("me@example.com")
+        );
+
+        // A tie goes to the address of the newest chat
+        Connection::open(&path)
+            .unwrap()
+            .execute(
+                "INSERT INTO chat VALUES (6, '+15559999999', NULL, 'P:+15551234567')",
+                [],
+            )
+            .unwrap();
+        assert_eq!(
+            db.get_own_handle().unwrap().as_deref(),
+            Some("+15551234567")
+        );
+    }
+
+    #[test]
+    fn test_group_chat() {
+        let path = TempDb::new("group-chat");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn.execute_batch(
+            r#"
+            INSERT INTO handle (ROWID, id, service)
+            VALUES (1, 'mom@example.com', 'iMessage'), (2, 'sam@example.com', 'iMessage');
+            INSERT INTO chat (ROWID, guid, style, chat_identifier)
+            VALUES (1, 'iMessage;-;mom@example.com', 45, 'mom@example.com'),
+                   (2, 'iMessage;+;chat123', 43, 'chat123');
+            INSERT INTO chat_handle_join (chat_id, handle_id) VALUES (1, 1), (2, 1), (2, 2);
+            INSERT INTO message (ROWID, guid, text, handle_id, date)
+            VALUES (1, 'A', 'hi', 1, 1), (2, 'B', 'hi all', 1, 2);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (2, 2);
+            "#,
+        )
+        .unwrap();
+        let db = MessageDB::open_at(&path).unwrap();
+
+        assert_eq!(db.get_group_chat("A").unwrap(), None);
+        assert_eq!(
+            db.get_group_chat("B").unwrap().as_deref(),
+            Some("iMessage;+;chat123")
+        );
+        assert_eq!(db.get_group_chat("Z").unwrap(), None);
+        assert_eq!(db.get_latest_rowid().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_reactions() {
+        let path = TempDb::new("reactions");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn.execute_batch(
+            r#"
+            INSERT INTO handle (ROWID, id, service) VALUES (1, 'mom@example.com', 'iMessage');
+            INSERT INTO message (ROWID, guid, text, handle_id, date, is_from_me,
+                                 associated_message_guid, associated_message_type)
+            VALUES (1, 'A', 'dinner at 7?', 1, 1, 0, NULL, 0),
+                   (2, 'B', 'Loved “dinner at 7?”', 1, 2, 0, 'p:0/A', 2000),
+                   (3, 'C', 'Liked “dinner at 7?”', 1, 3, 0, 'p:0/A', 2001),
+                   (4, 'D', 'Removed a heart from “dinner at 7?”', 1, 4, 0, 'p:0/A', 3000),
+                   (5, 'E', 'Laughed at “dinner at 7?”', 1, 5, 1, 'p:0/A', 2003),
+                   (6, 'F', 'Removed a laugh from “dinner at 7?”', 1, 6, 1, 'p:0/A', 3003),
+                   (7, 'G', 'Reacted 🎉 to “dinner at 7?”', 1, 7, 0, 'p:0/A', 2006);
+            "#,
+        )
+        .unwrap();
+        let db = MessageDB::open_at(&path).unwrap();
+        let handles = vec!["mom@example.com".to_string()];
+
+        // Tapback rows are shown on the message they react to, as it is now, except emoji
+        // tapbacks, which are shown as they are
+        let messages = db.get_messages(&handles, 10).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].guid, "G");
+        assert_eq!(
+            messages[1].reactions,
+            vec![Reaction {
+                tapback: Tapback::Like,
+                sender: Some("mom@example.com".to_string()),
+            }]
+        );
+
+        // A new tapback brings back the message it reacts to, so it's updated in place
+        conn.execute(
+            "INSERT INTO message (ROWID, guid, handle_id, date, is_from_me,
+                                  associated_message_guid, associated_message_type)
+             VALUES (8, 'H', 1, 8, 1, 'p:0/A', 2000)",
+            [],
+        )
+        .unwrap();
+        let changed = db.get_messages_since(&handles, 7, 1, i64::MAX).unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].reactions.len(), 2);
+    }
+
+    #[test]
+    fn test_contact_summary() {
+        let path = TempDb::new("summary");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn.execute_batch(
+            r#"
+            INSERT INTO handle (ROWID, id, service)
+            VALUES (1, 'mom@example.com', 'iMessage'), (2, '+15551234567', 'SMS');
+            INSERT INTO message (ROWID, guid, text, handle_id, date, is_from_me, service,
+                                 associated_message_guid, associated_message_type)
+            VALUES (1, 'A', 'hi', 2, 100000000000000000, 0, 'SMS', NULL, 0),
+                   (2, 'B', 'dinner at 7?', 1, 200000000000000000, 1, 'iMessage', NULL, 0),
+                   (3, 'C', 'Liked “dinner at 7?”', 1, 300000000000000000, 0, 'iMessage',
+                    'p:0/B', 2001);
+            "#,
+        )
+        .unwrap();
+        let db = MessageDB::open_at(&path).unwrap();
+
+        // Tapbacks aren't counted as messages
+        let summary = db
+            .get_contact_summary(&["mom@example.com".to_string(), "+15551234567".to_string()])
+            .unwrap();
+        assert_eq!(summary.message_count, 2);
+        assert_eq!(
+            summary.first_date.map(|date| date.timestamp()),
+            Some(APPLE_EPOCH + 100_000_000)
+        );
+        let last = summary.last_message.unwrap();
+        assert_eq!(last.text.as_deref(), Some("dinner at 7?"));
+        assert_eq!(last.service.as_deref(), Some("iMessage"));
+
+        let summary = db
+            .get_contact_summary(&["sam@example.com".to_string()])
+            .unwrap();
+        assert_eq!(summary.message_count, 0);
+        assert!(summary.first_date.is_none() && summary.last_message.is_none());
+    }
+
+    #[test]
+    fn test_group_events() {
+        let path = TempDb::new("group-events");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn.execute_batch(
+            r#"
+            INSERT INTO handle (ROWID, id, service)
+            VALUES (1, 'alice@example.com', 'iMessage'), (2, 'bob@example.com', 'iMessage'),
+                   (3, '+15551234567', 'iMessage');
+            INSERT INTO chat (ROWID, guid, chat_identifier) VALUES (1, 'iMessage;+;chat1', 'chat1');
+            INSERT INTO message (ROWID, guid, handle_id, date, is_from_me, item_type,
+                                 group_action_type, group_title, other_handle)
+            VALUES (1, 'A', 1, 1, 0, 2, 0, 'Ski Trip', 0),
+                   (2, 'B', 2, 2, 0, 3, 0, NULL, 0),
+                   (3, 'C', 0, 3, 1, 1, 0, NULL, 3),
+                   (4, 'D', 0, 4, 1, 6, 0, NULL, 0);
+            INSERT INTO chat_message_join (chat_id, message_id) VALUES (1, 1), (1, 2), (1, 3), (1, 4);
+            "#,
+        )
+        .unwrap();
+        let db = MessageDB::open_at(&path).unwrap();
+
+        let name = |handle: &str| match handle {
+            "alice@example.com" => "Alice".to_string(),
+            "+15551234567" => "Carol".to_string(),
+            _ => handle.to_string(),
+        };
+        let events: Vec<Option<String>> = db
+            .get_chat_messages("chat1", 10)
+            .unwrap()
+            .iter()
+            .rev()
+            .map(|message| message.event.as_ref().map(|event| event.describe(name)))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                Some("Alice named the conversation “Ski Trip”".to_string()),
+                Some("bob@example.com left the conversation".to_string()),
+                Some("You added Carol".to_string()),
+                // Other special rows, like FaceTime calls, aren't group events
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_query() {
+        let path = TempDb::new("query");
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch("CREATE TABLE message (date INTEGER); INSERT INTO message VALUES (0);")
+            .unwrap();
+        let db = MessageDB::open_at(&path).unwrap();
+
+        let rows = db
+            .run_query("SELECT date, apple_date(date) AS sent, apple_date(NULL) FROM message")
+            .unwrap();
+        assert_eq!(rows.columns[..2], ["date", "sent"]);
+        assert_eq!(
+            db.run_query(
+                "SELECT unix_to_apple(1717264931), apple_to_unix(unix_to_apple(1717264931))"
+            )
+            .unwrap()
+            .rows,
+            vec![vec![
+                Value::Integer(738_957_731_000_000_000),
+                Value::Integer(1_717_264_931)
+            ]]
+        );
+        let expected = local_datetime(APPLE_EPOCH)
+            .unwrap()
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string();
+        assert_eq!(
+            rows.rows,
+            vec![vec![Value::Integer(0), Value::Text(expected), Value::Null]]
+        );
+
+        assert!(db.run_query("DELETE FROM message").is_err());
+
+        // Databases from before High Sierra count in seconds
+        drop(db);
+        Connection::open(&path)
+            .unwrap()
+            .execute("INSERT INTO message VALUES (?1)", [738_957_731])
+            .unwrap();
+        let db = MessageDB::open_at(&path).unwrap();
+        assert_eq!(
+            db.run_query("SELECT apple_to_unix(MAX(date)), unix_to_apple(1717264931) FROM message")
+                .unwrap()
+                .rows,
+            vec![vec![
+                Value::Integer(1_717_264_931),
+                Value::Integer(738_957_731)
+            ]]
+        );
+        assert!(db.run_query("PRAGMA journal_mode = WAL").is_err());
+    }
 }
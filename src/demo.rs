@@ -0,0 +1,200 @@
+use crate::config::Config;
+use crate::db::{DateUnit, SCHEMA};
+use crate::error::Result;
+use crate::paths;
+use rusqlite::{params, Connection};
+use std::fs;
+use std::path::PathBuf;
+
+/// When the last demo message was sent, as a Unix timestamp (2024-06-01 18:00 UTC), so the
+/// demo looks the same every time it runs.
+const DEMO_END: i64 = 1_717_264_800;
+
+/// A person the demo conversations are with.
+struct DemoContact {
+    name: &'static str,
+    identifier: &'static str,
+    display_name: Option<&'static str>,
+}
+
+/// One demo message: minutes before `DEMO_END`, whether it was sent by the user, the text, and
+/// whether it's been read.
+type DemoMessage = (i64, bool, &'static str, bool);
+
+/// The demo conversations, each with who it's with and its messages oldest first.
+const CONVERSATIONS: &[(DemoContact, &[DemoMessage])] = &[
+    (
+        DemoContact {
+            name: "mom",
+            identifier: "+15555550101",
+            display_name: Some("Mom"),
+        },
+        &[
+            (190, false, "Still coming for dinner Sunday?", true),
+            (185, true, "Yes! Can I bring anything?", true),
+            (184, false, "Just yourself. And maybe dessert 🍰", true),
+            (12, false, "Dad found the old photo albums", false),
+            (11, false, "You have to see your haircut from 1998", false),
+        ],
+    ),
+    (
+        DemoContact {
+            name: "sam",
+            identifier: "sam@example.com",
+            display_name: Some("Sam Rivera"),
+        },
+        &[
+            (95, false, "running late, grab us a table?", true),
+            (94, true, "On it. Patio or inside?", true),
+            (93, false, "patio!! it's finally warm", true),
+            (40, true, "That place was great", true),
+            (38, false, "we're going back next week", true),
+            (37, false, "https://example.com/menus/lunch", true),
+            (3, false, "also, **book club** is at mine this month", false),
+        ],
+    ),
+    (
+        DemoContact {
+            name: "alex",
+            identifier: "+15555550123",
+            display_name: Some("Alex Chen"),
+        },
+        &[
+            (1440, true, "Did the package arrive?", true),
+            (1430, false, "Yep, just now. Thank you!", true),
+            (60, false, "Are we still on for climbing tomorrow?", true),
+            (58, true, "Wouldn't miss it. 7am?", true),
+            (57, false, "`7:00` sharp 🧗", true),
+        ],
+    ),
+    (
+        DemoContact {
+            name: "",
+            identifier: "262966",
+            display_name: None,
+        },
+        &[(25, false, "Your code is 481516. Don't share it.", false)],
+    ),
+];
+
+/// A database in the shape of chat.db holding the demo conversations, removed when dropped.
+pub struct Demo {
+    /// The database to query in place of chat.db.
+    pub database: PathBuf,
+}
+
+impl Demo {
+    /// Write the demo database to the cache directory.
+    pub fn create() -> Result<Self> {
+        let database = paths::process_database("demo")?;
+        if database.exists() {
+            fs::remove_file(&database)?;
+        }
+        build_database(&Connection::open(&database)?)?;
+        Ok(Self { database })
+    }
+
+    /// Add the people in the demo conversations to a configuration, so they're shown by name.
+    pub fn add_contacts(&self, config: &mut Config) {
+        for (contact, _) in CONVERSATIONS {
+            if !contact.name.is_empty() {
+                config.add_contact(
+                    contact.name.to_string(),
+                    contact.identifier.to_string(),
+                    contact.display_name.map(str::to_string),
+                );
+            }
+        }
+    }
+}
+
+impl Drop for Demo {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.database);
+    }
+}
+
+/// Create the chat.db tables and fill them with the demo conversations.
+fn build_database(conn: &Connection) -> Result<()> {
+    conn.execute_batch(SCHEMA)?;
+
+    let mut rowid = 0;
+    for (i, (contact, messages)) in CONVERSATIONS.iter().enumerate() {
+        let id = i as i64 + 1;
+        conn.execute(
+            "INSERT INTO handle (ROWID, id, service) VALUES (?1, ?2, 'iMessage')",
+            params![id, contact.identifier],
+        )?;
+        conn.execute(
+            "INSERT INTO chat (ROWID, guid, style, chat_identifier, service_name, display_name)
+             VALUES (?1, ?2, 45, ?3, 'iMessage', '')",
+            params![
+                id,
+                format!("iMessage;-;{}", contact.identifier),
+                contact.identifier
+            ],
+        )?;
+        conn.execute(
+            "INSERT INTO chat_handle_join (chat_id, handle_id) VALUES (?1, ?1)",
+            params![id],
+        )?;
+
+        for &(minutes_ago, is_from_me, text, is_read) in messages.iter() {
+            rowid += 1;
+            let date = DateUnit::Nanoseconds.unix_to_date(DEMO_END - minutes_ago * 60);
+            conn.execute(
+                "INSERT INTO message (ROWID, guid, text, handle_id, service, date, date_read,
+                                      date_delivered, is_from_me, is_read)
+                 VALUES (?1, ?2, ?3, ?4, 'iMessage', ?5, ?6, ?5, ?7, ?8)",
+                params![
+                    rowid,
+                    format!("DEMO-{:04}", rowid),
+                    text,
+                    id,
+                    date,
+                    if is_read { date } else { 0 },
+                    is_from_me,
+                    is_read || is_from_me,
+                ],
+            )?;
+            conn.execute(
+                "INSERT INTO chat_message_join (chat_id, message_id, message_date)
+                 VALUES (?1, ?2, ?3)",
+                params![id, rowid, date],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MessageDB;
+
+    #[test]
+    fn test_demo_database() {
+        let demo = Demo::create().unwrap();
+        let db = MessageDB::open_at(&demo.database).unwrap();
+
+        let conversations = db.get_conversations(10).unwrap();
+        assert_eq!(conversations.len(), CONVERSATIONS.len());
+        assert_eq!(conversations[0].identifier, "sam@example.com");
+        assert_eq!(conversations[0].last_date.timestamp(), DEMO_END - 3 * 60);
+        assert_eq!(db.get_unread_summary().unwrap().total, 4);
+
+        let messages = db.get_messages(&["+15555550101".to_string()], 10).unwrap();
+        assert_eq!(messages.len(), 5);
+
+        let mut config = Config::default();
+        demo.add_contacts(&mut config);
+        assert_eq!(
+            config.display_name_for("+15555550101").as_deref(),
+            Some("Mom")
+        );
+
+        let path = demo.database.clone();
+        drop((db, demo));
+        assert!(!path.exists());
+    }
+}
@@ -1,29 +1,191 @@
+use crate::i18n::t;
 use confy::ConfyError;
-use rusqlite;
+use rusqlite::ErrorCode;
 use std::env::VarError;
+use std::fmt;
+use std::io;
+
+/// Exit code for failures without a more specific code. Clap exits with 2 for usage errors.
+pub const EXIT_FAILURE: i32 = 1;
+
+/// Exit code when no contact was given, or the one given isn't in the configuration.
+pub const EXIT_NO_CONTACT: i32 = 3;
+
+/// Exit code when macOS denied access to chat.db or to controlling Messages.app.
+pub const EXIT_PERMISSION_DENIED: i32 = 4;
+
+/// Exit code when Messages.app couldn't send a message.
+pub const EXIT_SEND_FAILED: i32 = 5;
+
+/// Exit code when chat.db or the state database stayed locked by another process.
+pub const EXIT_DB_LOCKED: i32 = 6;
+
+/// Exit code when a message wasn't sent yet but was queued, to go out on a later run.
+pub const EXIT_QUEUED: i32 = 7;
 
 /// Custom error type for the application.
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     /// Error from SQLite database operations.
-    #[error("Database error: {0}")]
+    #[error("{}", t!("error.database", error = .0))]
     Database(#[from] rusqlite::Error),
     /// Error from configuration operations.
-    #[error("Configuration error: {0}")]
+    #[error("{}", t!("error.config", error = .0))]
     Config(#[from] ConfyError),
     /// Error from environment variables.
-    #[error("Environment variable error: {0}")]
+    #[error("{}", t!("error.env", error = .0))]
     Env(#[from] VarError),
     /// Error for missing contact.
-    #[error("No contact specified")]
+    #[error("{}", t!("error.no_contact"))]
     NoContact,
+    /// A contact name that isn't in the configuration.
+    #[error("{}", t!("error.contact_not_found", name = .0))]
+    ContactNotFound(String),
     /// Generic error with message.
     #[error("{0}")]
     Generic(String),
     /// IO error.
-    #[error("IO error: {0}")]
+    #[error("{}", t!("error.io", error = .0))]
     Io(#[from] std::io::Error),
+    /// Messages.app couldn't send a message.
+    #[error("{0}")]
+    Send(#[from] SendError),
+    /// A message was queued to be sent later instead of now, saying when.
+    #[error("{0}")]
+    Queued(String),
+}
+
+impl Error {
+    /// Whether the error may go away on its own, so the operation is worth retrying.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Error::Send(error) if error.is_transient())
+    }
+
+    /// The code to exit with when the error ends the process, so scripts can tell failures
+    /// apart.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::NoContact | Error::ContactNotFound(_) => EXIT_NO_CONTACT,
+            Error::Send(SendError::AutomationDenied) => EXIT_PERMISSION_DENIED,
+            Error::Send(_) => EXIT_SEND_FAILED,
+            Error::Queued(_) => EXIT_QUEUED,
+            Error::Database(error) => match error.sqlite_error_code() {
+                Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) => EXIT_DB_LOCKED,
+                // Without Full Disk Access, chat.db opens but can't be read
+                Some(ErrorCode::PermissionDenied | ErrorCode::AuthorizationForStatementDenied) => {
+                    EXIT_PERMISSION_DENIED
+                }
+                _ => EXIT_FAILURE,
+            },
+            Error::Io(error) if error.kind() == io::ErrorKind::PermissionDenied => {
+                EXIT_PERMISSION_DENIED
+            }
+            _ => EXIT_FAILURE,
+        }
+    }
 }
 
+/// Why Messages.app couldn't send a message, translated from AppleScript errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendError {
+    /// The recipient can't be found on the service the message was sent over.
+    NotReachable(String),
+    /// The terminal isn't allowed to control Messages.app.
+    AutomationDenied,
+    /// Messages.app isn't running or didn't respond.
+    MessagesUnavailable,
+    /// The configured account isn't signed in to Messages.app.
+    AccountNotFound(String),
+    /// `send_as` asks for an address no conversation with the recipient was started from,
+    /// as (recipient, address).
+    NoChatFrom(String, String),
+    /// Any other AppleScript error, as osascript printed it.
+    Other(String),
+}
+
+impl SendError {
+    /// Whether sending again later may work without the user doing anything.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, SendError::MessagesUnavailable)
+    }
+
+    /// What the user can do about the error.
+    pub fn suggestion(&self) -> Option<String> {
+        match self {
+            SendError::NotReachable(_) => Some(t!("send_error.not_reachable_hint")),
+            SendError::AutomationDenied => Some(t!("send_error.automation_denied_hint")),
+            SendError::MessagesUnavailable => Some(t!("send_error.messages_unavailable_hint")),
+            SendError::AccountNotFound(_) => Some(t!("send_error.account_not_found_hint")),
+            SendError::NoChatFrom(_, address) => {
+                Some(t!("send_error.no_chat_from_hint", address = address))
+            }
+            SendError::Other(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            SendError::NotReachable(recipient) => {
+                t!("send_error.not_reachable", recipient = recipient)
+            }
+            SendError::AutomationDenied => t!("send_error.automation_denied"),
+            SendError::MessagesUnavailable => t!("send_error.messages_unavailable"),
+            SendError::AccountNotFound(account) => {
+                t!("send_error.account_not_found", account = account)
+            }
+            SendError::NoChatFrom(recipient, address) => t!(
+                "send_error.no_chat_from",
+                recipient = recipient,
+                address = address
+            ),
+            SendError::Other(error) => t!("send_error.other", error = error),
+        };
+        f.write_str(&message)
+    }
+}
+
+impl std::error::Error for SendError {}
+
 /// Result type for the application.
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sqlite_error(code: i32) -> Error {
+        Error::Database(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(code),
+            None,
+        ))
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(Error::NoContact.exit_code(), EXIT_NO_CONTACT);
+        assert_eq!(
+            Error::ContactNotFound("mom".to_string()).exit_code(),
+            EXIT_NO_CONTACT
+        );
+        assert_eq!(
+            Error::Send(SendError::AutomationDenied).exit_code(),
+            EXIT_PERMISSION_DENIED
+        );
+        assert_eq!(
+            Error::Send(SendError::MessagesUnavailable).exit_code(),
+            EXIT_SEND_FAILED
+        );
+        assert_eq!(
+            sqlite_error(rusqlite::ffi::SQLITE_BUSY).exit_code(),
+            EXIT_DB_LOCKED
+        );
+        assert_eq!(
+            sqlite_error(rusqlite::ffi::SQLITE_AUTH).exit_code(),
+            EXIT_PERMISSION_DENIED
+        );
+        assert_eq!(Error::Queued("later".to_string()).exit_code(), EXIT_QUEUED);
+        assert_eq!(Error::Generic("oops".to_string()).exit_code(), EXIT_FAILURE);
+    }
+}
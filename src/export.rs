@@ -0,0 +1,330 @@
+use crate::archive::{Archive, ArchivedMessage};
+use crate::db::{Attachment, Message, MessageDB};
+use crate::error::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use clap::ValueEnum;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Domain used to turn phone numbers and the local user into mail addresses.
+const ADDRESS_DOMAIN: &str = "imessage.invalid";
+
+/// Longest subject synthesized from message text, in characters.
+const SUBJECT_LENGTH: usize = 60;
+
+/// Character Messages.app stores in message text in place of an attachment.
+const OBJECT_REPLACEMENT: char = '\u{FFFC}';
+
+/// Line length for base64-encoded attachment bodies.
+const BASE64_LINE_LENGTH: usize = 76;
+
+/// Formats a conversation can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// A single mbox file (mboxrd quoting)
+    Mbox,
+    /// A Maildir directory with one file per message
+    Maildir,
+    /// A JSON archive that `im open-archive` can browse
+    Json,
+}
+
+/// The other side of an exported conversation.
+pub struct Correspondent<'a> {
+    /// Phone number or email of the contact.
+    pub identifier: &'a str,
+    /// Name to show in From/To headers.
+    pub name: &'a str,
+}
+
+/// An attachment read from disk, ready to be written as a MIME part.
+struct AttachmentPart {
+    name: String,
+    mime_type: String,
+    data: Vec<u8>,
+}
+
+/// What an export wrote.
+pub struct ExportSummary {
+    /// Number of messages written.
+    pub count: usize,
+    /// ROWID of the newest message written, if any.
+    pub last_rowid: Option<i64>,
+}
+
+/// Export a conversation as RFC822 messages, or as a JSON archive.
+///
+/// With `after_rowid`, only messages after that ROWID are written and they're added to the
+/// existing archive; otherwise the whole conversation replaces whatever is at `output`.
+pub fn export_conversation(
+    db: &MessageDB,
+    correspondent: &Correspondent,
+    format: ExportFormat,
+    output: &Path,
+    after_rowid: Option<i64>,
+) -> Result<ExportSummary> {
+    let messages = db.get_all_messages(correspondent.identifier, after_rowid.unwrap_or(0))?;
+
+    match format {
+        ExportFormat::Mbox => {
+            let file = if after_rowid.is_some() {
+                OpenOptions::new().create(true).append(true).open(output)?
+            } else {
+                File::create(output)?
+            };
+            let mut file = BufWriter::new(file);
+            for message in &messages {
+                let email =
+                    message_to_email(message, &read_attachments(db, message)?, correspondent);
+                write_mbox_entry(&mut file, message, correspondent, &email)?;
+            }
+            file.flush()?;
+        }
+        ExportFormat::Maildir => {
+            for dir in ["tmp", "new", "cur"] {
+                fs::create_dir_all(output.join(dir))?;
+            }
+            for message in &messages {
+                let email =
+                    message_to_email(message, &read_attachments(db, message)?, correspondent);
+                // Write to tmp/ first and rename, as the Maildir format requires
+                let name = format!("{}.M{}.im:2,S", message.date.timestamp(), message.rowid);
+                let tmp_path = output.join("tmp").join(&name);
+                fs::write(&tmp_path, email)?;
+                fs::rename(&tmp_path, output.join("cur").join(&name))?;
+            }
+        }
+        ExportFormat::Json => {
+            let mut archive = match after_rowid {
+                Some(_) => Archive::read(output)?,
+                None => Archive::new(correspondent.identifier, correspondent.name),
+            };
+            archive
+                .messages
+                .extend(messages.iter().map(ArchivedMessage::from));
+            archive.write(output)?;
+        }
+    }
+
+    Ok(ExportSummary {
+        count: messages.len(),
+        last_rowid: messages.iter().map(|message| message.rowid).max(),
+    })
+}
+
+/// Read a message's attachments from disk, skipping files that no longer exist.
+fn read_attachments(db: &MessageDB, message: &Message) -> Result<Vec<AttachmentPart>> {
+    let mut parts = Vec::new();
+    for attachment in db.get_attachments(message.rowid)? {
+        let Attachment {
+            path,
+            mime_type,
+            name,
+            ..
+        } = attachment;
+        let Some(path) = path else {
+            continue;
+        };
+        let Ok(data) = fs::read(&path) else {
+            continue;
+        };
+
+        parts.push(AttachmentPart {
+            name: name
+                .or_else(|| path.file_name().map(|n| n.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| "attachment".to_string()),
+            mime_type: mime_type.unwrap_or_else(|| "application/octet-stream".to_string()),
+            data,
+        });
+    }
+    Ok(parts)
+}
+
+/// Build an RFC822 message with synthesized headers for a chat message.
+fn message_to_email(
+    message: &Message,
+    attachments: &[AttachmentPart],
+    correspondent: &Correspondent,
+) -> String {
+    let them = format!(
+        "{} <{}>",
+        encode_header(correspondent.name),
+        mail_address(correspondent.identifier)
+    );
+    let me = format!("Me <me@{}>", ADDRESS_DOMAIN);
+    let (from, to) = if message.is_from_me {
+        (me, them)
+    } else {
+        (them, me)
+    };
+
+    // Drop the placeholder Messages.app puts in the text where attachments go
+    let text = message
+        .text
+        .as_deref()
+        .unwrap_or_default()
+        .replace(OBJECT_REPLACEMENT, "");
+    let subject = match (text.lines().next(), &message.message_type) {
+        (Some(line), _) if !line.trim().is_empty() => line.chars().take(SUBJECT_LENGTH).collect(),
+        (_, Some(kind)) => format!("[{}]", kind),
+        _ => "(no text)".to_string(),
+    };
+
+    let mut email = format!(
+        "From: {}\nTo: {}\nDate: {}\nSubject: {}\nMessage-ID: <{}@{}>\nMIME-Version: 1.0\n",
+        from,
+        to,
+        message.date.to_rfc2822(),
+        encode_header(&subject),
+        message.guid,
+        ADDRESS_DOMAIN
+    );
+
+    if attachments.is_empty() {
+        email.push_str("Content-Type: text/plain; charset=utf-8\n");
+        email.push_str("Content-Transfer-Encoding: 8bit\n\n");
+        email.push_str(&text);
+        email.push('\n');
+        return email;
+    }
+
+    let boundary = format!("im-{}", message.guid);
+    email.push_str(&format!(
+        "Content-Type: multipart/mixed; boundary=\"{}\"\n\n",
+        boundary
+    ));
+    email.push_str(&format!("--{}\n", boundary));
+    email.push_str("Content-Type: text/plain; charset=utf-8\n");
+    email.push_str("Content-Transfer-Encoding: 8bit\n\n");
+    email.push_str(&text);
+    email.push('\n');
+
+    for attachment in attachments {
+        email.push_str(&format!("--{}\n", boundary));
+        email.push_str(&format!(
+            "Content-Type: {}; name=\"{}\"\n",
+            attachment.mime_type, attachment.name
+        ));
+        email.push_str("Content-Transfer-Encoding: base64\n");
+        email.push_str(&format!(
+            "Content-Disposition: attachment; filename=\"{}\"\n\n",
+            attachment.name
+        ));
+        let encoded = STANDARD.encode(&attachment.data);
+        for line in encoded.as_bytes().chunks(BASE64_LINE_LENGTH) {
+            email.push_str(&String::from_utf8_lossy(line));
+            email.push('\n');
+        }
+    }
+    email.push_str(&format!("--{}--\n", boundary));
+
+    email
+}
+
+/// Append a message to an mbox file, with a From_ line and mboxrd quoting.
+fn write_mbox_entry(
+    out: &mut impl Write,
+    message: &Message,
+    correspondent: &Correspondent,
+    email: &str,
+) -> Result<()> {
+    let sender = if message.is_from_me {
+        format!("me@{}", ADDRESS_DOMAIN)
+    } else {
+        mail_address(correspondent.identifier)
+    };
+    writeln!(
+        out,
+        "From {} {}",
+        sender,
+        message.date.format("%a %b %e %H:%M:%S %Y")
+    )?;
+    for line in email.lines() {
+        writeln!(out, "{}", quote_from_line(line))?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Quote lines that would be mistaken for a message separator (mboxrd).
+fn quote_from_line(line: &str) -> String {
+    if line.trim_start_matches('>').starts_with("From ") {
+        format!(">{}", line)
+    } else {
+        line.to_string()
+    }
+}
+
+/// Turn a phone number or email into a mail address.
+fn mail_address(identifier: &str) -> String {
+    if identifier.contains('@') {
+        identifier.to_string()
+    } else {
+        format!("{}@{}", identifier, ADDRESS_DOMAIN)
+    }
+}
+
+/// Encode a header value as an RFC 2047 encoded word if it isn't plain ASCII.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", STANDARD.encode(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn message(text: &str, is_from_me: bool) -> Message {
+        Message {
+            rowid: 1,
+            guid: "ABC".to_string(),
+            text: Some(text.to_string()),
+            date: Local.timestamp_opt(1_700_000_000, 0).unwrap(),
+            message_type: None,
+            is_from_me,
+            service: None,
+            reactions: Vec::new(),
+            event: None,
+        }
+    }
+
+    #[test]
+    fn test_quote_from_line() {
+        assert_eq!(quote_from_line("From here"), ">From here");
+        assert_eq!(quote_from_line(">From here"), ">>From here");
+        assert_eq!(quote_from_line("Fromage"), "Fromage");
+    }
+
+    #[test]
+    fn test_message_to_email() {
+        let correspondent = Correspondent {
+            identifier: "+15551234567",
+            name: "Zoë",
+        };
+
+        let email = message_to_email(
+            &message("hi there\nsecond line", false),
+            &[],
+            &correspondent,
+        );
+        assert!(email.starts_with("From: =?UTF-8?B?Wm/Dqw==?= <+15551234567@imessage.invalid>\n"));
+        assert!(email.contains("To: Me <me@imessage.invalid>\n"));
+        assert!(email.contains("Subject: hi there\n"));
+        assert!(email.ends_with("\n\nhi there\nsecond line\n"));
+
+        let attachment = AttachmentPart {
+            name: "a.txt".to_string(),
+            mime_type: "text/plain".to_string(),
+            data: b"hello".to_vec(),
+        };
+        let email = message_to_email(&message("", true), &[attachment], &correspondent);
+        assert!(email.starts_with("From: Me <me@imessage.invalid>\n"));
+        assert!(email.contains("boundary=\"im-ABC\""));
+        assert!(email.contains("filename=\"a.txt\"\n\naGVsbG8=\n--im-ABC--\n"));
+    }
+}
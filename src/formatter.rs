@@ -1,12 +1,16 @@
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
 /// Format a phone number to a standardized format with country code.
 pub fn format_phone_number(number: &str) -> String {
     // If it's a digit-only string without country code, add +1
-    if number.chars().all(|c| c.is_digit(10)) {
+    if number.chars().all(|c| c.is_ascii_digit()) {
         format!("+1{}", number)
     } else if !number.contains('+') && number.starts_with('1') && number.len() > 1 {
         // Handle numbers with country code digit but missing "+" (e.g., "13015057171" → "+13015057171")
         let rest = &number[1..];
-        if rest.chars().all(|c| c.is_digit(10)) {
+        if rest.chars().all(|c| c.is_ascii_digit()) {
             format!("+1{}", rest)
         } else {
             format!("+{}", number)
@@ -21,17 +25,363 @@ pub fn format_phone_number(number: &str) -> String {
 pub fn format_display_number(number: &str) -> String {
     if number.starts_with("+1") && number.len() > 2 {
         number[2..].to_string()
-    } else if number.starts_with("1") && number.chars().skip(1).all(|c| c.is_digit(10)) {
+    } else if number.starts_with("1") && number.chars().skip(1).all(|c| c.is_ascii_digit()) {
         number[1..].to_string()
     } else {
         number.to_string()
     }
 }
 
+/// Format a length in seconds as `m:ss`, or `h:mm:ss` for an hour or more.
+pub fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Normalize an identifier so different spellings of the same handle compare equal.
+///
+/// Emails are trimmed and lowercased; phone numbers have formatting characters
+/// removed and a country code added.
+pub fn normalize_identifier(identifier: &str) -> String {
+    let trimmed = identifier.trim();
+    if trimmed.contains('@') {
+        return trimmed.to_lowercase();
+    }
+
+    let stripped: String = trimmed
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '(' | ')' | '.'))
+        .collect();
+    format_phone_number(&stripped)
+}
+
+/// Check whether a string looks like a phone number or email rather than a contact name.
+pub fn looks_like_identifier(value: &str) -> bool {
+    let value = value.trim();
+    if value.contains('@') {
+        return !value.starts_with('@') && !value.ends_with('@');
+    }
+
+    let digit_count = value.chars().filter(|c| c.is_ascii_digit()).count();
+    digit_count >= 3
+        && value
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '+' | ' ' | '-' | '(' | ')' | '.'))
+}
+
+/// Words that commonly start a line of code.
+const CODE_KEYWORDS: &[&str] = &[
+    "fn ",
+    "def ",
+    "let ",
+    "const ",
+    "var ",
+    "import ",
+    "from ",
+    "class ",
+    "return ",
+    "#include",
+    "pub ",
+    "use ",
+    "function ",
+    "if (",
+    "for (",
+    "while (",
+];
+
+/// Check whether multi-line text looks like a code snippet rather than prose.
+///
+/// At least half of the non-empty lines must be indented, end in code punctuation,
+/// or start with a common keyword.
+pub fn looks_like_code(text: &str) -> bool {
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.len() < 2 {
+        return false;
+    }
+
+    let code_lines = lines
+        .iter()
+        .filter(|line| {
+            let trimmed = line.trim();
+            line.starts_with("  ")
+                || line.starts_with('\t')
+                || trimmed.ends_with(['{', '}', ';', '(', ')', '[', ']'])
+                || CODE_KEYWORDS.iter().any(|k| trimmed.starts_with(k))
+        })
+        .count();
+    code_lines * 2 >= lines.len()
+}
+
+/// Local parts of email senders that are almost always automated.
+const AUTOMATED_LOCAL_PARTS: &[&str] = &[
+    "noreply",
+    "no-reply",
+    "no_reply",
+    "donotreply",
+    "do-not-reply",
+    "notifications",
+    "notification",
+    "alerts",
+    "alert",
+    "verify",
+    "verification",
+    "security",
+    "info",
+];
+
+/// Check whether a sender looks like a short code or automated service rather than a person.
+///
+/// Short codes are 5–6 digit numbers. `extra_patterns` are matched case-insensitively
+/// against the identifier and come from configuration.
+pub fn is_automated_sender(identifier: &str, extra_patterns: &[String]) -> bool {
+    let lowercase = identifier.to_lowercase();
+    if extra_patterns
+        .iter()
+        .any(|pattern| !pattern.is_empty() && lowercase.contains(&pattern.to_lowercase()))
+    {
+        return true;
+    }
+
+    let digits = identifier.strip_prefix('+').unwrap_or(identifier);
+    if digits.chars().all(|c| c.is_ascii_digit()) && (5..=6).contains(&digits.len()) {
+        return true;
+    }
+
+    match lowercase.split_once('@') {
+        Some((local, _)) => AUTOMATED_LOCAL_PARTS.contains(&local),
+        None => false,
+    }
+}
+
+/// Characters in the GSM 03.38 basic alphabet, which SMS can send 7 bits at a time.
+const GSM_BASIC: &str = "@£$¥èéùìòÇ\nØø\rÅåΔ_ΦΓΛΩΠΨΣΘΞÆæßÉ !\"#¤%&'()*+,-./0123456789:;<=>?\
+    ¡ABCDEFGHIJKLMNOPQRSTUVWXYZÄÖÑÜ§¿abcdefghijklmnopqrstuvwxyzäöñüà";
+
+/// Characters in the GSM 03.38 extension table, which take two 7-bit units each.
+const GSM_EXTENDED: &str = "^{}\\[~]|€\x0c";
+
+/// How many SMS segments a message takes and how many characters it counts as.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SmsLength {
+    /// Characters counted against the segment limit.
+    pub units: usize,
+    /// Number of SMS segments the message is split into.
+    pub segments: usize,
+    /// Whether the message needs Unicode (UCS-2) encoding, with shorter segments.
+    pub unicode: bool,
+}
+
+/// Count how many SMS segments `text` would be split into.
+///
+/// GSM-7 messages fit 160 characters in one segment or 153 per segment when
+/// split; anything else is sent as UCS-2, with 70 or 67.
+pub fn sms_length(text: &str) -> SmsLength {
+    let gsm_units: Option<usize> = text
+        .chars()
+        .map(|c| {
+            if GSM_BASIC.contains(c) {
+                Some(1)
+            } else if GSM_EXTENDED.contains(c) {
+                Some(2)
+            } else {
+                None
+            }
+        })
+        .sum();
+
+    let (units, unicode, single, split) = match gsm_units {
+        Some(units) => (units, false, 160, 153),
+        // UCS-2 counts characters outside the Basic Multilingual Plane (like emoji) twice
+        None => (text.encode_utf16().count(), true, 70, 67),
+    };
+    let segments = match units {
+        0 => 0,
+        units if units <= single => 1,
+        units => units.div_ceil(split),
+    };
+    SmsLength {
+        units,
+        segments,
+        unicode,
+    }
+}
+
+/// Columns `text` takes in a terminal: two for emoji and wide CJK characters, and none for
+/// combining marks or the joiners and variation selectors inside emoji sequences.
+pub fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+/// Pad `text` with spaces to `width` columns, since `format!` pads by characters.
+pub fn pad_to_width(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(text));
+    format!("{}{}", text, " ".repeat(padding))
+}
+
+/// Cut `text` to at most `max` graphemes, ending with `…` if anything was cut, so that flags,
+/// skin tones, and joined emoji are never split.
+pub fn truncate_graphemes(text: &str, max: usize) -> String {
+    let graphemes: Vec<&str> = text.graphemes(true).collect();
+    if graphemes.len() <= max {
+        return text.to_string();
+    }
+    let kept = max.saturating_sub(1);
+    format!("{}…", graphemes[..kept].concat())
+}
+
+/// Character indices where graphemes start in `text`, and its length in characters.
+pub fn grapheme_boundaries(text: &str) -> Vec<usize> {
+    let mut boundaries: Vec<usize> = text
+        .graphemes(true)
+        .scan(0, |start, grapheme| {
+            let boundary = *start;
+            *start += grapheme.chars().count();
+            Some(boundary)
+        })
+        .collect();
+    boundaries.push(text.chars().count());
+    boundaries
+}
+
+/// Split text longer than `max_chars` into parts that each fit, as character ranges.
+///
+/// Parts break at the last paragraph break that fits, or failing that the last line break,
+/// then the last space; a part without any is cut at the last grapheme boundary that fits, so
+/// emoji aren't split. The whitespace a part breaks at is left out of both parts.
+pub fn message_parts(text: &str, max_chars: usize) -> Vec<Range<usize>> {
+    let chars: Vec<char> = text.chars().collect();
+    let boundaries = grapheme_boundaries(text);
+    let max_chars = if max_chars == 0 {
+        usize::MAX
+    } else {
+        max_chars
+    };
+
+    let mut parts = Vec::new();
+    let mut start = 0;
+    while chars.len() - start > max_chars {
+        // Where the part can end, latest first
+        let ends = (start + 1..=start + max_chars).rev();
+        let paragraph = |&end: &usize| chars[end] == '\n' && chars.get(end + 1) == Some(&'\n');
+        let end = ends
+            .clone()
+            .find(paragraph)
+            .or_else(|| ends.clone().find(|&end| chars[end] == '\n'))
+            .or_else(|| ends.clone().find(|&end| chars[end].is_whitespace()))
+            .or_else(|| {
+                ends.clone()
+                    .find(|end| boundaries.binary_search(end).is_ok())
+            })
+            .unwrap_or(start + max_chars);
+
+        let mut next = end;
+        while next < chars.len() && chars[next].is_whitespace() {
+            next += 1;
+        }
+        let mut trimmed = end;
+        while trimmed > start && chars[trimmed - 1].is_whitespace() {
+            trimmed -= 1;
+        }
+        parts.push(start..trimmed);
+        start = next;
+    }
+    if start < chars.len() {
+        parts.push(start..chars.len());
+    }
+    parts
+}
+
+/// Split text longer than `max_chars` into messages, as `message_parts` does.
+pub fn split_message(text: &str, max_chars: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    message_parts(text, max_chars)
+        .into_iter()
+        .map(|part| chars[part].iter().collect())
+        .collect()
+}
+
+/// Most edits between two identifiers for them to count as similar.
+const MAX_IDENTIFIER_EDITS: usize = 2;
+
+/// Find identifiers in `candidates` that are probably `identifier` formatted
+/// differently or mistyped, closest first.
+///
+/// Phone numbers whose last ten digits match count as exact, since they often
+/// differ only in country code.
+pub fn similar_identifiers<'a>(identifier: &str, candidates: &'a [String]) -> Vec<&'a String> {
+    let normalized = normalize_identifier(identifier);
+    let national = national_digits(&normalized);
+
+    let mut similar: Vec<(usize, &String)> = candidates
+        .iter()
+        .filter_map(|candidate| {
+            let other = normalize_identifier(candidate);
+            let distance = match (&national, national_digits(&other)) {
+                (Some(a), Some(b)) if *a == b => 0,
+                _ => edit_distance(&normalized, &other),
+            };
+            (distance <= MAX_IDENTIFIER_EDITS).then_some((distance, candidate))
+        })
+        .collect();
+    similar.sort();
+    similar
+        .into_iter()
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// The last ten digits of a normalized phone number, or `None` for emails and short numbers.
+fn national_digits(normalized: &str) -> Option<String> {
+    if normalized.contains('@') {
+        return None;
+    }
+    let digits: Vec<char> = normalized.chars().filter(|c| c.is_ascii_digit()).collect();
+    (digits.len() >= 10).then(|| digits[digits.len() - 10..].iter().collect())
+}
+
+/// Number of single-character insertions, deletions, or substitutions to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_looks_like_code() {
+        assert!(looks_like_code("fn main() {\n    println!(\"hi\");\n}"));
+        assert!(looks_like_code("def f(x):\n    return x"));
+        assert!(!looks_like_code("let's get dinner"));
+        assert!(!looks_like_code("see you soon\nbring snacks"));
+        assert!(!looks_like_code("groceries:\n- eggs\n- milk\n- bread"));
+    }
+
+    #[test]
+    fn test_looks_like_identifier() {
+        assert!(looks_like_identifier("+15551234567"));
+        assert!(looks_like_identifier("(555) 123-4567"));
+        assert!(looks_like_identifier("someone@example.com"));
+        assert!(!looks_like_identifier("mom"));
+        assert!(!looks_like_identifier("bob2"));
+        assert!(!looks_like_identifier("@handle"));
+    }
+
     #[test]
     fn test_format_phone_number() {
         // US number with no country code
@@ -67,4 +417,134 @@ mod tests {
             "email@example.com"
         );
     }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0), "0:00");
+        assert_eq!(format_duration(42), "0:42");
+        assert_eq!(format_duration(605), "10:05");
+        assert_eq!(format_duration(3723), "1:02:03");
+    }
+
+    #[test]
+    fn test_normalize_identifier() {
+        assert_eq!(normalize_identifier("(555) 123-4567"), "+15551234567");
+        assert_eq!(normalize_identifier("+1 555.123.4567"), "+15551234567");
+        assert_eq!(
+            normalize_identifier(" Friend@Example.com "),
+            "friend@example.com"
+        );
+    }
+
+    #[test]
+    fn test_is_automated_sender() {
+        // Short codes
+        assert!(is_automated_sender("32665", &[]));
+        assert!(is_automated_sender("262966", &[]));
+
+        // Automated email senders
+        assert!(is_automated_sender("no-reply@example.com", &[]));
+
+        // People
+        assert!(!is_automated_sender("+15551234567", &[]));
+        assert!(!is_automated_sender("friend@example.com", &[]));
+
+        // Configured patterns
+        assert!(is_automated_sender(
+            "+18005550100",
+            &["8005550100".to_string()]
+        ));
+    }
+
+    #[test]
+    fn test_similar_identifiers() {
+        let handles: Vec<String> = [
+            "+15551234567",
+            "+15551234568",
+            "+447700900123",
+            "friend@example.com",
+        ]
+        .iter()
+        .map(|h| h.to_string())
+        .collect();
+
+        // Formatting differences and typos, closest first
+        assert_eq!(
+            similar_identifiers("(555) 123-4567", &handles),
+            vec!["+15551234567", "+15551234568"]
+        );
+        // A wrong country code still matches
+        assert_eq!(
+            similar_identifiers("7700900123", &handles),
+            vec!["+447700900123"]
+        );
+        assert_eq!(
+            similar_identifiers("freind@example.com", &handles),
+            vec!["friend@example.com"]
+        );
+        assert!(similar_identifiers("stranger@example.com", &handles).is_empty());
+    }
+
+    #[test]
+    fn test_split_message() {
+        assert_eq!(split_message("short", 10), vec!["short"]);
+        assert_eq!(split_message("no limit at all", 0), vec!["no limit at all"]);
+
+        // Paragraphs come first, even with line breaks and spaces later on
+        let text = "First paragraph.\n\nSecond one,\nwith two lines.";
+        assert_eq!(
+            split_message(text, 40),
+            vec!["First paragraph.", "Second one,\nwith two lines."]
+        );
+        assert_eq!(
+            split_message(text, 20),
+            vec!["First paragraph.", "Second one,", "with two lines."]
+        );
+        assert_eq!(split_message("one two three", 8), vec!["one two", "three"]);
+        assert_eq!(split_message("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(message_parts("héllo wörld", 6), vec![0..5, 6..11]);
+        // Flags are two characters each and aren't cut in half
+        assert_eq!(split_message("🇺🇸🇨🇦🇲🇽", 3), vec!["🇺🇸", "🇨🇦", "🇲🇽"]);
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("hello"), 5);
+        assert_eq!(display_width("日本"), 4);
+        // A family joined with zero-width joiners, a heart with a variation selector, a flag,
+        // and a thumbs up with a skin tone each take two columns
+        assert_eq!(display_width("👨\u{200d}👩\u{200d}👧"), 2);
+        assert_eq!(display_width("❤\u{fe0f}"), 2);
+        assert_eq!(display_width("🇺🇸👍🏽"), 4);
+        assert_eq!(display_width("e\u{301}"), 1);
+
+        assert_eq!(pad_to_width("日本", 6), "日本  ");
+        assert_eq!(pad_to_width("toolong", 3), "toolong");
+        assert_eq!(truncate_graphemes("🇺🇸🇨🇦🇲🇽", 2), "🇺🇸…");
+        assert_eq!(truncate_graphemes("🇺🇸🇨🇦", 2), "🇺🇸🇨🇦");
+        assert_eq!(grapheme_boundaries("a👍🏽b"), vec![0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn test_sms_length() {
+        assert_eq!(sms_length("").segments, 0);
+        assert_eq!(
+            sms_length(&"a".repeat(160)),
+            SmsLength {
+                units: 160,
+                segments: 1,
+                unicode: false
+            }
+        );
+        assert_eq!(sms_length(&"a".repeat(161)).segments, 2);
+        // Extension characters count twice
+        assert_eq!(sms_length("{}").units, 4);
+        // Emoji switch to UCS-2 and count as two units each
+        let unicode = sms_length(&format!("{}👋", "a".repeat(68)));
+        assert!(unicode.unicode);
+        assert_eq!(unicode.units, 70);
+        assert_eq!(unicode.segments, 1);
+        assert_eq!(sms_length(&"é€ü".repeat(23)).segments, 1);
+        assert_eq!(sms_length(&"ç".repeat(71)).segments, 2);
+    }
 }
@@ -0,0 +1,187 @@
+use crate::config::GifConfig;
+use crate::error::{Error, Result};
+use crate::hooks::run_command;
+use crate::i18n::t;
+use crate::paths;
+use crate::thumbnails::preview_file;
+use chrono::Local;
+use image::RgbImage;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Composer command that searches for a GIF, e.g. `/gif happy dance`.
+const GIF_COMMAND: &str = "/gif";
+
+/// Giphy's search endpoint, used when `gif.api_key` is set.
+const GIPHY_SEARCH_URL: &str = "https://api.giphy.com/v1/gifs/search";
+
+/// A GIF found by a search, downloaded and waiting to be sent.
+pub struct Gif {
+    /// What was searched for.
+    pub query: String,
+    /// The GIF on disk.
+    pub path: PathBuf,
+    /// Its first frame, if it could be decoded.
+    pub preview: Option<RgbImage>,
+}
+
+/// The search in a `/gif <query>` composer command, if the text is one.
+pub fn gif_query(text: &str) -> Option<&str> {
+    let query = text.trim().strip_prefix(GIF_COMMAND)?;
+    if !query.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(query.trim()).filter(|query| !query.is_empty())
+}
+
+/// Find a GIF for `query` with `gif.command`, or Giphy if only `gif.api_key` is set, and
+/// download it.
+pub fn search(config: &GifConfig, query: &str) -> Result<Gif> {
+    let timeout = Duration::from_secs(config.timeout_secs);
+    let found = match (&config.command, &config.api_key) {
+        (Some(command), _) => {
+            let output = run_command(command, query.as_bytes(), timeout, "GIF search")?;
+            first_line(&output)
+                .ok_or_else(|| Error::Generic(t!("gifs.not_found", query = query)))?
+                .to_string()
+        }
+        (None, Some(api_key)) => giphy_search(api_key, query, timeout)?,
+        (None, None) => return Err(Error::Generic(t!("gifs.not_configured"))),
+    };
+
+    let path = if is_url(&found) {
+        download(&found, timeout)?
+    } else {
+        let path = PathBuf::from(&found);
+        if !path.is_file() {
+            return Err(Error::Generic(t!("gifs.not_url", output = found)));
+        }
+        path
+    };
+    Ok(Gif {
+        query: query.to_string(),
+        preview: preview_file(&path).ok(),
+        path,
+    })
+}
+
+/// The first non-blank line of a command's output.
+fn first_line(output: &str) -> Option<&str> {
+    output.lines().map(str::trim).find(|line| !line.is_empty())
+}
+
+fn is_url(value: &str) -> bool {
+    value.starts_with("https://") || value.starts_with("http://")
+}
+
+/// Search Giphy, returning the URL of the top result.
+///
+/// The API key goes to curl on stdin, so it doesn't show up in the process list.
+fn giphy_search(api_key: &str, query: &str, timeout: Duration) -> Result<String> {
+    let mut child = curl(timeout)
+        .arg("--get")
+        .args(["--data-urlencode", "api_key@-"])
+        .args(["--data-urlencode", &format!("q={}", query)])
+        .args(["--data", "limit=1"])
+        .arg(GIPHY_SEARCH_URL)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(api_key.trim().as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::Generic(t!(
+            "gifs.giphy_failed",
+            error = String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let response: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|_| Error::Generic(t!("gifs.giphy_not_json")))?;
+    giphy_url(&response).ok_or_else(|| Error::Generic(t!("gifs.not_found", query = query)))
+}
+
+/// URL of the first result in a Giphy search response.
+fn giphy_url(response: &serde_json::Value) -> Option<String> {
+    response
+        .pointer("/data/0/images/original/url")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Download a GIF into the cache directory.
+fn download(url: &str, timeout: Duration) -> Result<PathBuf> {
+    let dir = paths::gifs().ok_or_else(|| Error::Generic(t!("paths.no_cache_dir")))?;
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(download_name(url));
+
+    let output = curl(timeout).arg("--output").arg(&path).arg(url).output()?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&path);
+        return Err(Error::Generic(t!(
+            "gifs.download_failed",
+            error = String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(path)
+}
+
+/// File name for a downloaded GIF, keeping the URL's extension so Messages.app sends it as the
+/// right kind of file.
+fn download_name(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| ext.len() <= 4 && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or("gif");
+    format!("{}.{}", Local::now().format("%Y%m%d-%H%M%S%3f"), extension)
+}
+
+/// A quiet `curl` that fails on HTTP errors and gives up after `timeout`.
+fn curl(timeout: Duration) -> Command {
+    let mut command = Command::new("curl");
+    command
+        .args(["--silent", "--show-error", "--fail", "--location"])
+        .args(["--max-time", &timeout.as_secs().max(1).to_string()]);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gif_query() {
+        assert_eq!(gif_query("/gif happy dance"), Some("happy dance"));
+        assert_eq!(gif_query("  /gif   cats  "), Some("cats"));
+        assert_eq!(gif_query("/gif"), None);
+        assert_eq!(gif_query("/gif   "), None);
+        assert_eq!(gif_query("/gifs cats"), None);
+        assert_eq!(gif_query("send a /gif cats"), None);
+    }
+
+    #[test]
+    fn test_giphy_url() {
+        let response = serde_json::json!({
+            "data": [{"images": {"original": {"url": "https://media.giphy.com/a.gif"}}}]
+        });
+        assert_eq!(
+            giphy_url(&response).as_deref(),
+            Some("https://media.giphy.com/a.gif")
+        );
+        assert_eq!(giphy_url(&serde_json::json!({"data": []})), None);
+    }
+
+    #[test]
+    fn test_download_name() {
+        assert!(download_name("https://media.giphy.com/a.webp?cid=1").ends_with(".webp"));
+        assert!(download_name("https://example.com/gif?id=1").ends_with(".gif"));
+        assert_eq!(first_line("\n  /tmp/a.gif \nmore"), Some("/tmp/a.gif"));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::error::{Error, Result};
+use crate::i18n::t;
+use std::io::{Read, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often to check whether a command has finished.
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Run a user-configured `command` in the shell with `input` on stdin, returning what it
+/// printed to stdout.
+///
+/// The command, and anything it started, is killed if it takes longer than `timeout` to exit
+/// or to close its stdout. `what` names the hook in errors, e.g. "suggestion".
+pub fn run_command(command: &str, input: &[u8], timeout: Duration, what: &str) -> Result<String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        // Its own process group, so a timeout also kills what the shell started
+        .process_group(0)
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_vec();
+    // Write from another thread so a command that doesn't read stdin can't block us
+    thread::spawn(move || stdin.write_all(&input));
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = String::new();
+        let _ = tx.send(stdout.read_to_string(&mut output).map(|_| output));
+    });
+
+    let timed_out = |child: &mut Child| {
+        kill_group(child);
+        Error::Generic(t!(
+            "hooks.timed_out",
+            what = what,
+            seconds = timeout.as_secs()
+        ))
+    };
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            return Err(timed_out(&mut child));
+        }
+        thread::sleep(WAIT_INTERVAL);
+    };
+
+    if !status.success() {
+        return Err(Error::Generic(t!(
+            "hooks.failed",
+            what = what,
+            status = status
+        )));
+    }
+    // Something the command started in the background may still hold its stdout open
+    match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(output) => output.map_err(Error::from),
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(timed_out(&mut child)),
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            Err(Error::Generic(t!("hooks.unreadable", what = what)))
+        }
+    }
+}
+
+/// Kill `child` and every process in its process group.
+fn kill_group(child: &mut Child) {
+    let _ = Command::new("kill")
+        .arg("-KILL")
+        .arg(format!("-{}", child.id()))
+        .stderr(Stdio::null())
+        .status();
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_command() {
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            run_command("tr a-z A-Z", b"hello", timeout, "test").unwrap(),
+            "HELLO"
+        );
+        assert!(run_command("exit 1", b"", timeout, "test").is_err());
+        assert!(run_command("sleep 5", b"", Duration::from_millis(100), "test").is_err());
+    }
+
+    #[test]
+    fn test_run_command_background_output() {
+        // The shell exits at once, but the background sleep keeps its stdout open
+        let started = Instant::now();
+        assert!(run_command("sleep 5 & echo hi", b"", Duration::from_millis(200), "test").is_err());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// Language used for anything a translation is missing.
+const FALLBACK_LANGUAGE: &str = "en";
+
+/// Bundled message catalogs, by language code.
+const LOCALES: &[(&str, &str)] = &[("en", include_str!("../locales/en.toml"))];
+
+/// Environment variables that name the user's language, most specific first.
+const LANGUAGE_VARIABLES: &[&str] = &["LC_ALL", "LC_MESSAGES", "LANG"];
+
+/// Messages for the chosen language, with the fallback language filling gaps.
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Look up a user-facing message, replacing `{name}` placeholders.
+///
+/// ```ignore
+/// println!("{}", t!("cli.sent", name = display_name));
+/// ```
+#[macro_export]
+macro_rules! t {
+    ($key:expr) => {
+        $crate::i18n::message($key).to_string()
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::format(
+            $crate::i18n::message($key),
+            &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+],
+        )
+    };
+}
+pub use crate::t;
+
+/// Choose the language for messages: `language` from the configuration if given, otherwise
+/// the one in the environment. Has no effect once a message has been looked up.
+pub fn init(language: Option<&str>) {
+    let language = language
+        .map(str::to_string)
+        .or_else(environment_language)
+        .unwrap_or_else(|| FALLBACK_LANGUAGE.to_string());
+    let _ = CATALOG.set(load_catalog(&language));
+}
+
+/// Get the message for `key`, or the key itself if no catalog has it.
+pub fn message(key: &str) -> &str {
+    CATALOG
+        .get_or_init(|| {
+            load_catalog(&environment_language().unwrap_or_else(|| FALLBACK_LANGUAGE.into()))
+        })
+        .get(key)
+        .map(String::as_str)
+        .unwrap_or(key)
+}
+
+/// Replace `{name}` placeholders in a message.
+///
+/// The message is read once, so braces in the values themselves are left as they are.
+pub fn format(message: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut formatted = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(start) = rest.find('{') {
+        formatted.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let value = after.find('}').and_then(|end| {
+            let name = &after[..end];
+            args.iter()
+                .find(|(arg, _)| *arg == name)
+                .map(|(_, value)| (end, value))
+        });
+        match value {
+            Some((end, value)) => {
+                formatted.push_str(&value.to_string());
+                rest = &after[end + 1..];
+            }
+            // Not a placeholder, so keep the brace
+            None => {
+                formatted.push('{');
+                rest = after;
+            }
+        }
+    }
+    formatted.push_str(rest);
+    formatted
+}
+
+/// The language code from the environment, like "fr" for `LANG=fr_FR.UTF-8`.
+fn environment_language() -> Option<String> {
+    LANGUAGE_VARIABLES
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| language_code(&value))
+}
+
+/// The language part of a locale name.
+fn language_code(locale: &str) -> Option<String> {
+    let code = locale.split(['_', '-', '.', '@']).next()?.to_lowercase();
+    (!code.is_empty() && code != "c" && code != "posix").then_some(code)
+}
+
+/// Load a language's catalog on top of the fallback language.
+fn load_catalog(language: &str) -> HashMap<String, String> {
+    let mut catalog = HashMap::new();
+    let code = language_code(language).unwrap_or_else(|| FALLBACK_LANGUAGE.to_string());
+    for wanted in [FALLBACK_LANGUAGE, code.as_str()] {
+        if let Some((_, source)) = LOCALES.iter().find(|(code, _)| *code == wanted) {
+            catalog.extend(parse_catalog(source));
+        }
+    }
+    catalog
+}
+
+/// Flatten a TOML catalog into `section.key` messages.
+fn parse_catalog(source: &str) -> HashMap<String, String> {
+    let mut messages = HashMap::new();
+    let Ok(toml::Value::Table(sections)) = source.parse::<toml::Value>() else {
+        return messages;
+    };
+
+    for (section, entries) in sections {
+        let toml::Value::Table(entries) = entries else {
+            continue;
+        };
+        for (key, value) in entries {
+            if let toml::Value::String(value) = value {
+                messages.insert(format!("{}.{}", section, key), value);
+            }
+        }
+    }
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalogs() {
+        let english = parse_catalog(LOCALES[0].1);
+        assert_eq!(english["cli.sent"], "Sent to {name}");
+
+        // Every bundled language has the same messages as English
+        for (code, source) in LOCALES {
+            let catalog = parse_catalog(source);
+            let mut missing: Vec<&String> = english
+                .keys()
+                .filter(|k| !catalog.contains_key(*k))
+                .collect();
+            missing.sort();
+            assert!(missing.is_empty(), "{} is missing {:?}", code, missing);
+        }
+
+        // Unknown languages fall back to English
+        assert_eq!(load_catalog("xx_XX")["cli.sent"], "Sent to {name}");
+    }
+
+    #[test]
+    fn test_format() {
+        let count = 3;
+        assert_eq!(
+            format(
+                "{count} of {count} from {name}",
+                &[("count", &count), ("name", &"Mom")]
+            ),
+            "3 of 3 from Mom"
+        );
+        // Placeholders in values aren't replaced again
+        assert_eq!(
+            format(
+                "{name} said {text} {unknown}",
+                &[("name", &"{text}"), ("text", &"{name}")]
+            ),
+            "{text} said {name} {unknown}"
+        );
+        assert_eq!(t!("cli.no_such_message"), "cli.no_such_message");
+        assert_eq!(t!("cli.sent", name = "Mom"), "Sent to Mom");
+    }
+
+    #[test]
+    fn test_language_code() {
+        assert_eq!(language_code("fr_FR.UTF-8").as_deref(), Some("fr"));
+        assert_eq!(language_code("pt-BR").as_deref(), Some("pt"));
+        assert_eq!(language_code("C"), None);
+    }
+}
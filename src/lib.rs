@@ -0,0 +1,50 @@
+//! The modules behind the `im` binary, kept in a library so the benchmarks can use them.
+
+pub mod activity;
+pub mod address_book;
+pub mod archive;
+pub mod attachments;
+pub mod bench;
+pub mod birthdays;
+pub mod cli;
+pub mod completions;
+pub mod config;
+pub mod config_env;
+pub mod config_file;
+pub mod csv_import;
+pub mod db;
+pub mod demo;
+pub mod error;
+pub mod export;
+pub mod formatter;
+pub mod gifs;
+pub mod hooks;
+pub mod i18n;
+pub mod notifications;
+pub mod paths;
+pub mod permissions;
+pub mod query;
+pub mod reactions;
+pub mod search;
+pub mod send_queue;
+pub mod sender;
+pub mod state;
+pub mod status;
+pub mod suggestions;
+pub mod summarize;
+pub mod tags;
+pub mod thumbnails;
+pub mod timestamps;
+pub mod transcript;
+pub mod translate;
+pub mod tui;
+pub mod vcard;
+pub mod voice;
+pub mod watcher;
+pub mod words;
+
+/// Application name used for configuration files.
+pub const APP_NAME: &str = "im";
+
+/// Application version.
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
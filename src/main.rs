@@ -1,47 +1,156 @@
-mod cli;
-mod config;
-mod db;
-mod error;
-mod formatter;
-mod sender;
-mod tui;
-
-use crate::cli::{Cli, Commands};
-use crate::config::Config;
-use crate::error::{Error, Result};
-use crate::formatter::{format_display_number, format_phone_number};
+use chrono::{Local, NaiveDate, TimeZone};
 use clap::Parser;
+use im_tui::activity::{Activity, HEATMAP_CELLS, HEATMAP_WEEKS, WEEKDAY_LABELS};
+use im_tui::attachments::{format_size, AttachmentUsage};
+use im_tui::cli::{
+    AccountsCommand, AttachmentsCommand, Cli, Commands, ConfigCommand, ContactsCommand,
+    QuickReplyCommand,
+};
+use im_tui::config::{Config, ContactField, ImportedPerson};
+use im_tui::csv_import::CsvRow;
+use im_tui::db::{Message, MessageDB};
+use im_tui::error::{Error, Result};
+use im_tui::formatter::{
+    display_width, format_display_number, format_phone_number, looks_like_identifier,
+    normalize_identifier, pad_to_width,
+};
+use im_tui::i18n::t;
+use im_tui::send_queue::{send_with_retry, RateLimit, RetryPolicy};
+use im_tui::sender::{own_handle, Sender};
+use im_tui::state::{RateSlot, StarredMessage, StateStore};
+use im_tui::words::{WordCounts, TOP_EMOJI, TOP_WORDS};
+use im_tui::{
+    activity, address_book, archive, attachments, bench, birthdays, completions, config_file,
+    csv_import, db, demo, export, i18n, paths, permissions, query, search, send_queue, sender,
+    status, summarize, tags, tui, vcard, APP_VERSION,
+};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-/// Application name used for configuration files.
-pub const APP_NAME: &str = "im";
+/// Most similar handles suggested for a contact with no message history.
+const MAX_SUGGESTIONS: usize = 3;
 
-/// Application version.
-pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// State store setting recording that the permission onboarding was completed.
+const ONBOARDED_SETTING: &str = "onboarded";
 
-fn main() {
-    if let Err(err) = run() {
-        eprintln!("Error: {}", err);
+/// Environment variable that puts im in the sandbox like `--dry-run`.
+const DRY_RUN_VAR: &str = "IM_DRY_RUN";
 
-        // Try to print the config path even if there's an error
-        if let Some(path) = Config::config_path() {
-            eprintln!("Configuration file is located at: {}", path.display());
-            eprintln!("You may need to delete this file to fix the 'Bad TOML data' error.");
+/// Environment variable that marks what im sends as automated like `--automated`.
+const AUTOMATED_VAR: &str = "IM_AUTOMATED";
+
+/// How far ahead `im birthdays` looks (days).
+const UPCOMING_BIRTHDAY_DAYS: i64 = 30;
+
+/// Set by `--quiet` to leave out informational output.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Print a confirmation or other informational line to stdout, unless `--quiet` was given.
+///
+/// What a command was asked to print, like `im last`'s message, uses `println!`, and warnings
+/// and errors go to stderr.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
+
+fn main() {
+    paths::remove_stale_databases();
+    let result = run();
+    db::remove_snapshot();
+    if let Err(err) = result {
+        match &err {
+            // Not a failure as such, so it isn't called an error
+            Error::Queued(notice) => eprintln!("{}", notice),
+            _ => eprintln!("{}", t!("error.prefix", error = err)),
+        }
+        match &err {
+            Error::Send(error) => {
+                if let Some(suggestion) = error.suggestion() {
+                    eprintln!("{}", suggestion);
+                }
+            }
+            // Point at the file in case it can't be parsed
+            Error::Config(_) => {
+                if let Some(path) = Config::config_path() {
+                    eprintln!("{}", t!("error.config_location", path = path.display()));
+                    eprintln!("{}", t!("error.config_hint"));
+                }
+            }
+            _ => {}
         }
 
-        process::exit(1);
+        process::exit(err.exit_code());
     }
 }
 
 fn run() -> Result<()> {
     let args = Cli::parse();
     let verbose = args.verbose;
+    QUIET.store(args.quiet, Ordering::Relaxed);
+    if args.dry_run || env::var_os(DRY_RUN_VAR).is_some() {
+        sender::use_sandbox();
+        // Hooks and rules reply by running im, so their sends stay in the sandbox too
+        env::set_var(DRY_RUN_VAR, "1");
+    }
+    if args.automated {
+        // Scripts the automation runs count as automated too
+        env::set_var(AUTOMATED_VAR, "1");
+    }
 
-    if verbose {
-        println!("im v{}", APP_VERSION);
+    // Loading the configuration writes a bare one, and fails on one that doesn't parse, so
+    // the commands that write and check it run first
+    if let Some(Commands::Config { action }) = args.command {
+        let existing = Config::config_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|source| toml::from_str::<Config>(&source).ok());
+        i18n::init(existing.as_ref().and_then(Config::language));
+        return config_command(action);
     }
 
     let mut config = Config::load()?;
+    i18n::init(config.language());
+    db::init(config.db().access, config.db().path.clone());
+
+    // Only the views and commands that send deliver the spool, so reading never sends
+    // anything. The sandbox keeps it for a real run.
+    if !sender::is_sandbox()
+        && matches!(
+            args.command,
+            None | Some(
+                Commands::Inbox
+                    | Commands::Reply { .. }
+                    | Commands::ReplyTo { .. }
+                    | Commands::New { .. }
+                    | Commands::Me { .. }
+            )
+        )
+    {
+        // What's spooled stays spooled for next time, so it doesn't stop the command
+        match StateStore::open().and_then(|state| send_queue::deliver_spooled(&config, &state)) {
+            // On stderr, so it stays out of the output of the command it runs before
+            Ok(delivered) if delivered > 0 && !QUIET.load(Ordering::Relaxed) => {
+                eprintln!("{}", t!("cli.spooled_delivered", count = delivered))
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("{}", t!("cli.spool_failed", error = e)),
+        }
+    }
+
+    if verbose {
+        eprintln!("{}", t!("cli.version", version = APP_VERSION));
+        for var in config.env_vars() {
+            eprintln!("{}", t!("cli.env_override", var = var));
+        }
+    }
 
     // Handle subcommands for contact management
     if let Some(cmd) = args.command {
@@ -51,19 +160,22 @@ fn run() -> Result<()> {
     if let Some(set_contact) = &args.set {
         let formatted_contact = format_phone_number(set_contact);
         config.set_default_contact(formatted_contact.clone());
-        println!("Saved default contact: {}", formatted_contact);
+        info!(
+            "{}",
+            t!("cli.saved_default_contact", contact = formatted_contact)
+        );
 
         if verbose {
-            println!("Contact identifier normalized and saved to configuration.");
+            eprintln!("{}", t!("cli.saved_default_contact_verbose"));
         }
     }
 
     if let Some(name) = &args.name {
         config.set_default_display_name(name.clone());
-        println!("Saved default display name: {}", name);
+        info!("{}", t!("cli.saved_display_name", name = name));
 
         if verbose {
-            println!("Display name saved to configuration.");
+            eprintln!("{}", t!("cli.saved_display_name_verbose"));
         }
     }
 
@@ -73,15 +185,31 @@ fn run() -> Result<()> {
         return Ok(());
     }
 
+    ensure_permissions(verbose)?;
+
+    // With no contact given, pick up where the last session left off
+    if !args.fresh && args.contact.is_none() && args.contact_name.is_none() {
+        if let Some(session) = StateStore::open()?.session()? {
+            if verbose {
+                eprintln!("{}", t!("cli.resuming", name = session.display_name));
+            }
+            return tui::resume_chat_tui(session);
+        }
+    }
+
     // Try to get contact info, if it fails with NoContact, run the setup TUI
     match get_contact_info(&args, &config, verbose) {
         Ok((contact, display_name)) => {
+            if verbose {
+                print_query_plans(&config.identifiers_for(&contact))?;
+            }
+
             // Run the TUI with the contact
             tui::run_chat_tui(contact, display_name)
         }
         Err(Error::NoContact) => {
             if verbose {
-                println!("No contact configured. Launching setup TUI.");
+                eprintln!("{}", t!("cli.launching_setup"));
             }
 
             let new_config = tui::run_setup_tui()?;
@@ -113,68 +241,1393 @@ fn handle_command(cmd: Commands, config: &mut Config, verbose: bool) -> Result<(
             name,
             identifier,
             display_name,
+            force,
         } => {
+            if let Some(target) = config.alias_target(&name) {
+                return Err(Error::Generic(t!(
+                    "cli.already_alias",
+                    name = name,
+                    target = target
+                )));
+            }
+
             let formatted_id = format_phone_number(&identifier);
+            if !force && !confirm_contact_update(config, &name, &formatted_id, &display_name)? {
+                info!("{}", t!("cli.kept_contact", name = name));
+                return Ok(());
+            }
+
+            // Names differing only in case are the same contact
+            let message = if config.get_contact(&name).is_some() {
+                "cli.updated_contact"
+            } else {
+                "cli.added_contact"
+            };
             config.add_contact(name.clone(), formatted_id.clone(), display_name.clone());
             config.save()?;
 
-            println!(
-                "Added contact '{}' with identifier '{}'",
-                name, formatted_id
-            );
+            info!("{}", t!(message, name = name, identifier = formatted_id));
             if let Some(display) = display_name {
-                println!("Display name: {}", display);
+                info!("{}", t!("cli.display_name", name = display));
             }
 
             if verbose {
-                println!("Configuration updated successfully.");
+                eprintln!("{}", t!("cli.config_updated"));
             }
         }
 
         Commands::Remove { name } => {
-            // Try to find a case-insensitive match first
-            if let Some((actual_name, _)) = config.get_contact_case_insensitive(&name) {
-                let actual_name = actual_name.clone(); // Clone to avoid borrow issues
+            if let Some(removed) = config.take_contact(&name) {
+                config.save()?;
+                StateStore::open()?.push_removed_contact(&removed)?;
 
-                // Case-insensitive match found, now remove it
-                if config.remove_contact(&actual_name) {
-                    config.save()?;
+                let actual_name = &removed.entry.name;
+                if *actual_name != name {
+                    info!(
+                        "{}",
+                        t!(
+                            "cli.removed_contact_matched",
+                            name = actual_name,
+                            input = name
+                        )
+                    );
+                } else {
+                    info!("{}", t!("cli.removed_contact", name = name));
+                }
+                info!("{}", t!("cli.undo_hint"));
+            } else {
+                return Err(Error::ContactNotFound(name));
+            }
+        }
+
+        Commands::Reply { text } => {
+            let db = MessageDB::open()?;
+            let (handle, message) = latest_incoming(&db)?;
+            let chat = db.get_group_chat(&message.guid)?;
+            send_reply(config, &handle, chat, &text)?;
+        }
+
+        Commands::ReplyTo { guid, text } => {
+            let db = MessageDB::open()?;
+            let handle = db
+                .get_message_handle(&guid)?
+                .ok_or_else(|| Error::Generic(t!("cli.no_message_with_guid", guid = guid)))?;
+            let chat = db.get_group_chat(&guid)?;
+            send_reply(config, &handle, chat, &text)?;
+        }
 
-                    if actual_name != name {
-                        println!(
-                            "Removed contact '{}' (matched '{}' case-insensitively)",
-                            actual_name, name
+        Commands::New { identifier, text } => {
+            if !looks_like_identifier(&identifier) {
+                return Err(Error::Generic(t!(
+                    "cli.not_an_identifier",
+                    identifier = identifier
+                )));
+            }
+            let handle = normalize_identifier(&identifier);
+            match text {
+                Some(text) => send_reply(config, &handle, None, &text)?,
+                None => {
+                    ensure_permissions(verbose)?;
+                    let display_name = config
+                        .display_name_for(&handle)
+                        .unwrap_or_else(|| format_display_number(&handle));
+                    tui::run_chat_tui(handle, display_name)?;
+                }
+            }
+        }
+
+        Commands::Me { text } => {
+            let handle = own_handle(config, &MessageDB::open()?)?
+                .ok_or_else(|| Error::Generic(t!("cli.no_own_handle")))?;
+            send_reply(config, &handle, None, &text)?;
+        }
+
+        Commands::Last => {
+            let (handle, message) = latest_incoming(&MessageDB::open()?)?;
+            let name = config
+                .display_name_for(&handle)
+                .unwrap_or_else(|| format_display_number(&handle));
+            let text = message
+                .text
+                .or(message.message_type.map(|kind| format!("[{}]", kind)))
+                .unwrap_or_default();
+
+            println!(
+                "{}",
+                t!(
+                    "cli.last_message",
+                    name = name,
+                    handle = handle,
+                    date = message.date.format("%Y-%m-%d %H:%M"),
+                    text = text
+                )
+            );
+            if verbose {
+                println!("{}", t!("cli.guid", guid = message.guid));
+            }
+        }
+
+        Commands::Inbox => {
+            ensure_permissions(verbose)?;
+            tui::run_inbox_tui(config.clone())?;
+        }
+
+        Commands::Starred { print } => {
+            if print {
+                print_starred(config)?;
+            } else {
+                tui::run_starred_tui(config.clone())?;
+            }
+        }
+
+        Commands::Tags { tag, print } => {
+            let tag = tag.map(|name| find_tag(&name)).transpose()?;
+            if print {
+                print_tags(config, tag.as_deref())?;
+            } else {
+                tui::run_tags_tui(config.clone(), tag)?;
+            }
+        }
+
+        Commands::Contacts { action } => match action {
+            None => {
+                tui::run_contacts_tui(config.clone())?;
+            }
+            Some(ContactsCommand::Alias { name, aliases }) => {
+                let target = config.add_aliases(&name, &aliases)?;
+                config.save()?;
+                info!(
+                    "{}",
+                    t!(
+                        "cli.added_aliases",
+                        name = target,
+                        aliases = aliases.join(", ")
+                    )
+                );
+            }
+            Some(ContactsCommand::Link { name, identifier }) => {
+                let formatted_id = format_phone_number(&identifier);
+                let target = config.link_identifier(&name, &formatted_id)?;
+                config.save()?;
+                info!(
+                    "{}",
+                    t!("cli.linked", identifier = formatted_id, name = target)
+                );
+            }
+            Some(ContactsCommand::Unlink { identifier }) => {
+                let formatted_id = format_phone_number(&identifier);
+                match config.unlink_identifier(&formatted_id) {
+                    Some(name) => {
+                        config.save()?;
+                        info!(
+                            "{}",
+                            t!("cli.unlinked", identifier = formatted_id, name = name)
                         );
-                    } else {
-                        println!("Removed contact '{}'", name);
                     }
+                    None => eprintln!("{}", t!("cli.not_linked", identifier = formatted_id)),
                 }
-            } else if config.remove_contact(&name) {
-                // Direct removal succeeded (unlikely to reach this after adding case-insensitive check)
-                config.save()?;
-                println!("Removed contact '{}'", name);
+            }
+            Some(ContactsCommand::Dedupe { yes }) => {
+                dedupe_contacts(config, yes)?;
+            }
+            Some(ContactsCommand::Undo) => {
+                let state = StateStore::open()?;
+                match state.last_removed_contact()? {
+                    Some(removed) => {
+                        let name = removed.entry.name.clone();
+                        config.restore_contact(removed)?;
+                        config.save()?;
+                        state.drop_last_removed_contact()?;
+                        info!("{}", t!("cli.restored_contact", name = name));
+                    }
+                    None => eprintln!("{}", t!("cli.nothing_to_restore")),
+                }
+            }
+            Some(ContactsCommand::Verify) => {
+                ensure_permissions(verbose)?;
+                verify_contacts(config)?;
+            }
+            Some(ContactsCommand::Birthday { name, date, clear }) => {
+                birthday_command(config, &name, date, clear)?;
+            }
+            Some(ContactsCommand::Import { file }) => {
+                import_contacts(config, file.as_deref())?;
+            }
+            Some(ContactsCommand::Export { vcf, output }) => {
+                export_contacts(config, vcf, output.as_deref())?;
+            }
+            Some(ContactsCommand::ImportCsv {
+                file,
+                name_col,
+                phone_col,
+                yes,
+            }) => {
+                import_csv(config, &file, name_col, phone_col, yes)?;
+            }
+            Some(ContactsCommand::Unalias { alias }) => {
+                if config.remove_alias(&alias) {
+                    config.save()?;
+                    info!("{}", t!("cli.removed_alias", alias = alias));
+                } else {
+                    eprintln!("{}", t!("cli.alias_not_found", alias = alias));
+                }
+            }
+            Some(ContactsCommand::Quickreply { action }) => {
+                quick_reply_command(config, action)?;
+            }
+        },
+
+        Commands::Export {
+            contact,
+            format,
+            output,
+            incremental,
+        } => {
+            let (identifier, display_name) = contact_from_name(config, &contact, verbose)?;
+            let correspondent = export::Correspondent {
+                identifier: &identifier,
+                name: &display_name,
+            };
+
+            // Archives are remembered by their full path, and an archive that's gone is
+            // exported again from the start
+            let state = StateStore::open()?;
+            let after_rowid = match output.canonicalize() {
+                Ok(path) if incremental => state.last_exported(&identifier, &path)?,
+                _ => None,
+            };
+            let summary = export::export_conversation(
+                &MessageDB::open()?,
+                &correspondent,
+                format,
+                &output,
+                after_rowid,
+            )?;
+            if let Some(rowid) = summary.last_rowid {
+                state.mark_exported(&identifier, &output.canonicalize()?, rowid)?;
+            }
+
+            let key = if after_rowid.is_some() {
+                "cli.exported_new_messages"
             } else {
-                println!("Contact '{}' not found in configuration", name);
+                "cli.exported_messages"
+            };
+            info!(
+                "{}",
+                t!(
+                    key,
+                    count = summary.count,
+                    name = display_name,
+                    path = output.display()
+                )
+            );
+        }
+
+        Commands::Birthdays { notify } => {
+            let today = Local::now().date_naive();
+            if notify {
+                birthdays::remind(config, &StateStore::open()?, today)?;
+            } else {
+                print_birthdays(config, today);
+            }
+        }
+
+        Commands::Stats {
+            contact,
+            heatmap,
+            words,
+        } => {
+            let (identifier, display_name) = contact_from_name(config, &contact, verbose)?;
+            let handles = if config.chat().merge_identifiers {
+                config.identifiers_for(&identifier)
+            } else {
+                vec![identifier]
+            };
+
+            let db = MessageDB::open()?;
+            let today = Local::now().date_naive();
+            let activity = Activity::load(&db, &handles, today)?;
+            print_activity(&activity, &display_name, heatmap);
+
+            if words {
+                let messages = db.get_conversation_since(&handles, activity::year_start(today)?)?;
+                print_word_counts(&WordCounts::count(&messages));
+            }
+        }
+
+        Commands::OpenArchive { path, contact } => {
+            let contact = contact
+                .map(|contact| contact_from_name(config, &contact, verbose))
+                .transpose()?;
+            let archive = archive::open(
+                &path,
+                contact.as_ref().map(|(identifier, _)| identifier.as_str()),
+            )?;
+            let display_name = archive
+                .name
+                .clone()
+                .or(contact.map(|(_, display_name)| display_name))
+                .unwrap_or_else(|| format_display_number(&archive.identifier));
+
+            db::use_archive(archive.database.clone());
+            tui::run_chat_tui(archive.identifier.clone(), display_name)?;
+        }
+
+        Commands::Demo => {
+            let demo = demo::Demo::create()?;
+            let mut config = config.clone();
+            demo.add_contacts(&mut config);
+
+            db::use_archive(demo.database.clone());
+            tui::run_inbox_tui(config)?;
+        }
+
+        Commands::Summarize { contact, since } => {
+            let (identifier, display_name) = contact_from_name(config, &contact, verbose)?;
+            let handles = if config.chat().merge_identifiers {
+                config.identifiers_for(&identifier)
+            } else {
+                vec![identifier]
+            };
+            let since = since.map(search::local_midnight).transpose()?.unwrap_or(0);
+
+            let messages = MessageDB::open()?.get_conversation_since(&handles, since)?;
+            let transcript = summarize::transcript(&messages, &display_name);
+            if transcript.is_empty() {
+                info!("{}", t!("cli.nothing_to_summarize", name = display_name));
+            } else {
+                println!("{}", summarize::summarize(config.summarize(), &transcript)?);
+            }
+        }
+
+        Commands::Attachments {
+            action:
+                AttachmentsCommand::Export {
+                    contact,
+                    kind,
+                    since,
+                    out,
+                },
+        } => {
+            let (identifier, display_name) = contact_from_name(config, &contact, verbose)?;
+            let handles = if config.chat().merge_identifiers {
+                config.identifiers_for(&identifier)
+            } else {
+                vec![identifier]
+            };
+            let since = since.map(search::local_midnight).transpose()?.unwrap_or(0);
+
+            let summary =
+                attachments::export_attachments(&MessageDB::open()?, &handles, kind, since, &out)?;
+            info!(
+                "{}",
+                t!(
+                    "cli.exported_attachments",
+                    count = summary.copied,
+                    name = display_name,
+                    path = out.display()
+                )
+            );
+            if summary.missing > 0 {
+                eprintln!("{}", t!("cli.missing_attachments", count = summary.missing));
+            }
+        }
+
+        Commands::Attachments {
+            action: AttachmentsCommand::Usage { print },
+        } => {
+            let usage = attachments::attachment_usage(&MessageDB::open()?)?;
+            if print {
+                print_attachment_usage(config, &usage);
+            } else {
+                tui::run_attachment_usage_tui(config, usage)?;
             }
         }
 
-        Commands::Contacts => {
-            tui::run_contacts_tui(config.clone())?;
+        Commands::Accounts {
+            action: AccountsCommand::List,
+        } => {
+            let accounts = sender::list_accounts()?;
+            if accounts.is_empty() {
+                info!("{}", t!("accounts.none"));
+            }
+            let configured = config.sender().account.as_deref();
+            for account in &accounts {
+                let marker = if configured.is_some_and(|name| account.matches(name)) {
+                    "*"
+                } else {
+                    " "
+                };
+                let status = if account.enabled {
+                    String::new()
+                } else {
+                    t!("accounts.disabled")
+                };
+                println!(
+                    "{} {} {:<9} {}{}",
+                    marker,
+                    pad_to_width(&account.description, 32),
+                    account.service_type,
+                    account.id,
+                    status
+                );
+            }
+            if let Some(name) = configured {
+                if !accounts.iter().any(|account| account.matches(name)) {
+                    eprintln!("{}", t!("accounts.not_found", account = name));
+                }
+            }
+        }
+
+        Commands::Completions { shell } => {
+            completions::print_completions(shell);
+        }
+
+        Commands::CompleteContacts => {
+            completions::print_contact_names(config);
+        }
+
+        Commands::BenchDb {
+            conversations,
+            messages,
+            iterations,
+            db,
+            overwrite,
+        } => {
+            bench_db(conversations, messages, iterations, db, overwrite)?;
         }
 
-        Commands::Config => {
-            if let Some(path) = Config::config_path() {
-                println!("Configuration file location:");
-                println!("{}", path.display());
+        Commands::Query { sql, json } => {
+            let rows = MessageDB::open()?.run_query(&sql)?;
+            if json {
+                let json = serde_json::to_string_pretty(&query::to_json(&rows))
+                    .map_err(io::Error::from)?;
+                println!("{}", json);
             } else {
-                println!("Could not determine configuration file location.");
+                for line in query::to_lines(&rows) {
+                    println!("{}", line);
+                }
             }
         }
+
+        Commands::Status { format } => {
+            let summary = status::unread_summary()?;
+            let latest_sender = summary.latest_sender.as_ref().map(|handle| {
+                config
+                    .display_name_for(handle)
+                    .unwrap_or_else(|| format_display_number(handle))
+            });
+            println!(
+                "{}",
+                status::render(&format, &summary, latest_sender.as_deref())
+            );
+        }
+
+        Commands::Config { action } => config_command(action)?,
+
+        Commands::Paths { name } => print_paths(name.as_deref())?,
+    }
+
+    Ok(())
+}
+
+/// Write a commented configuration file, check the one there is, or show where it is
+fn config_command(action: Option<ConfigCommand>) -> Result<()> {
+    let Some(action) = action else {
+        if let Some(path) = Config::config_path() {
+            println!("{}", t!("cli.config_location"));
+            println!("{}", path.display());
+        } else {
+            println!("{}", t!("cli.config_location_unknown"));
+        }
+        return Ok(());
+    };
+    let path = paths::config_file().ok_or_else(|| Error::Generic(t!("config.no_path")))?;
+    match action {
+        ConfigCommand::Init { force } => {
+            // Loading the configuration writes a bare one, which has nothing worth keeping
+            let existing = fs::read_to_string(&path).ok();
+            let has_settings = existing.as_deref().is_some_and(|source| {
+                toml::from_str::<Config>(source)
+                    .map_or(true, |config| !config.same_as(&Config::default()))
+            });
+            if has_settings {
+                if !force {
+                    return Err(Error::Generic(t!("config.exists", path = path.display())));
+                }
+                let backup = path.with_extension("toml.bak");
+                fs::copy(&path, &backup)?;
+                info!("{}", t!("config.backed_up", path = backup.display()));
+            }
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, config_file::TEMPLATE)?;
+            info!("{}", t!("config.written", path = path.display()));
+        }
+        ConfigCommand::Validate => {
+            let source = fs::read_to_string(&path)?;
+            let problems = config_file::validate(&source);
+            if problems.is_empty() {
+                info!("{}", t!("config.valid", path = path.display()));
+                return Ok(());
+            }
+            for problem in &problems {
+                match problem.line {
+                    Some(line) => eprintln!(
+                        "{}",
+                        t!(
+                            "config.problem_at",
+                            path = path.display(),
+                            line = line,
+                            problem = problem.message
+                        )
+                    ),
+                    None => eprintln!(
+                        "{}",
+                        t!(
+                            "config.problem",
+                            path = path.display(),
+                            problem = problem.message
+                        )
+                    ),
+                }
+            }
+            return Err(Error::Generic(t!("config.invalid", count = problems.len())));
+        }
+    }
+    Ok(())
+}
+
+/// Print where im keeps its files, or only the path called `name`
+fn print_paths(name: Option<&str>) -> Result<()> {
+    let all = paths::all();
+    if let Some(name) = name {
+        let names: Vec<&str> = all.iter().map(|(name, _)| *name).collect();
+        let (_, path) = all
+            .iter()
+            .find(|(path_name, _)| *path_name == name)
+            .ok_or_else(|| {
+                Error::Generic(t!("paths.unknown", name = name, names = names.join(", ")))
+            })?;
+        let path = path
+            .as_ref()
+            .ok_or_else(|| Error::Generic(t!("paths.unavailable", name = name)))?;
+        println!("{}", path.display());
+        return Ok(());
     }
 
+    let width = all.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, path) in &all {
+        let path = path
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|| t!("paths.none"));
+        println!("{:width$}  {}", name, path, width = width);
+    }
     Ok(())
 }
 
+/// List, add, edit, or remove a contact's quick replies
+fn quick_reply_command(config: &mut Config, action: QuickReplyCommand) -> Result<()> {
+    match action {
+        QuickReplyCommand::List { name } => {
+            let Some((name, entry)) = config.resolve_contact(&name) else {
+                return Err(Error::ContactNotFound(name.to_string()));
+            };
+            if entry.quick_replies.is_empty() {
+                info!("{}", t!("cli.quick_replies_none", name = name));
+            }
+            for (i, text) in entry.quick_replies.iter().enumerate() {
+                println!("{}", t!("cli.quick_reply", number = i + 1, text = text));
+            }
+        }
+        QuickReplyCommand::Add { name, text } => {
+            let name = config.add_quick_reply(&name, &text)?;
+            config.save()?;
+            info!(
+                "{}",
+                t!("cli.quick_reply_added", name = name, text = text.trim())
+            );
+        }
+        QuickReplyCommand::Edit { name, number, text } => {
+            let name = config.edit_quick_reply(&name, number, &text)?;
+            config.save()?;
+            info!(
+                "{}",
+                t!(
+                    "cli.quick_reply_updated",
+                    number = number,
+                    name = name,
+                    text = text.trim()
+                )
+            );
+        }
+        QuickReplyCommand::Remove { name, number } => {
+            let (name, text) = config.remove_quick_reply(&name, number)?;
+            config.save()?;
+            info!(
+                "{}",
+                t!("cli.quick_reply_removed", name = name, text = text)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Interactively merge contacts whose identifiers normalize to the same handle
+fn dedupe_contacts(config: &mut Config, yes: bool) -> Result<()> {
+    let groups = config.find_duplicates();
+    if groups.is_empty() {
+        info!("{}", t!("dedupe.none"));
+        return Ok(());
+    }
+
+    let mut merged = 0;
+    for group in groups {
+        print_contact_group(config, &group);
+
+        let keep = if yes {
+            Some(group[0].clone())
+        } else {
+            prompt(&t!("dedupe.prompt", count = group.len()))?
+                .parse::<usize>()
+                .ok()
+                .and_then(|choice| group.get(choice.wrapping_sub(1)))
+                .cloned()
+        };
+
+        match keep {
+            Some(keep) => {
+                config.merge_contacts(&keep, &group)?;
+                info!("{}\n", t!("dedupe.merged_into", name = keep));
+                merged += 1;
+            }
+            None => info!("{}\n", t!("dedupe.skipped")),
+        }
+    }
+
+    if merged > 0 {
+        config.save()?;
+    }
+    info!("{}", t!("dedupe.merged", count = merged));
+
+    Ok(())
+}
+
+/// Check with the user before `im add` changes a saved contact or adds a second contact for
+/// a handle, showing what would change. Returns whether to go ahead.
+///
+/// Without a terminal to ask at, the change needs `--force`.
+fn confirm_contact_update(
+    config: &Config,
+    name: &str,
+    identifier: &str,
+    display_name: &Option<String>,
+) -> Result<bool> {
+    let changes = config.contact_changes(name, identifier, display_name.as_deref());
+    let taken_by = config.identifier_taken_by(identifier, name);
+    if changes.is_empty() && taken_by.is_none() {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        return Err(Error::Generic(t!(
+            "cli.contact_conflict_force",
+            name = name
+        )));
+    }
+
+    if !changes.is_empty() {
+        println!("{}", t!("cli.contact_exists", name = name));
+    }
+    for change in &changes {
+        let field = match change.field {
+            ContactField::Name => t!("cli.field_name"),
+            ContactField::Identifier => t!("cli.field_identifier"),
+            ContactField::DisplayName => t!("cli.field_display_name"),
+        };
+        if let Some(before) = &change.before {
+            println!("- {}: {}", field, before);
+        }
+        if let Some(after) = &change.after {
+            println!("+ {}: {}", field, after);
+        }
+    }
+    if let Some(other) = taken_by {
+        println!(
+            "{}",
+            t!(
+                "cli.identifier_taken",
+                identifier = identifier,
+                name = other
+            )
+        );
+    }
+
+    confirm(&t!("cli.update_contact_prompt", name = name))
+}
+
+/// Ask a question at the terminal and read the answer, trimmed
+fn prompt(question: &str) -> Result<String> {
+    print!("{}", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+/// Ask a yes-or-no question at the terminal, taking anything but `y` or `yes` as no
+fn confirm(question: &str) -> Result<bool> {
+    let answer = prompt(question)?;
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Show, set, or clear a contact's birthday
+fn birthday_command(
+    config: &mut Config,
+    name: &str,
+    date: Option<String>,
+    clear: bool,
+) -> Result<()> {
+    if date.is_none() && !clear {
+        let Some((name, entry)) = config.resolve_contact(name) else {
+            return Err(Error::ContactNotFound(name.to_string()));
+        };
+        match &entry.birthday {
+            Some(birthday) => println!("{}", t!("cli.birthday", name = name, date = birthday)),
+            None => println!("{}", t!("cli.birthday_none", name = name)),
+        }
+        return Ok(());
+    }
+
+    let birthday = date.as_deref().map(birthdays::parse_birthday).transpose()?;
+    let name = config.set_birthday(name, birthday.clone())?;
+    config.save()?;
+    match birthday {
+        Some(birthday) => info!("{}", t!("cli.birthday_set", name = name, date = birthday)),
+        None => info!("{}", t!("cli.birthday_cleared", name = name)),
+    }
+    Ok(())
+}
+
+/// Print contacts' birthdays in the coming `UPCOMING_BIRTHDAY_DAYS`, soonest first
+fn print_birthdays(config: &Config, today: NaiveDate) {
+    let mut upcoming: Vec<(i64, &String, &str)> = config
+        .list_contacts()
+        .into_iter()
+        .filter_map(|(name, entry)| {
+            let birthday = entry.birthday.as_deref()?;
+            let days = birthdays::days_until(birthday, today)?;
+            (days <= UPCOMING_BIRTHDAY_DAYS).then_some((days, name, birthday))
+        })
+        .collect();
+    upcoming.sort();
+
+    if upcoming.is_empty() {
+        info!("{}", t!("cli.no_birthdays", days = UPCOMING_BIRTHDAY_DAYS));
+    }
+    for (days, name, birthday) in upcoming {
+        let line = match days {
+            0 => t!("cli.birthday_today", name = name),
+            1 => t!("cli.birthday_tomorrow", name = name),
+            _ => t!("cli.birthday_in", name = name, days = days, date = birthday),
+        };
+        println!("{}", line);
+    }
+}
+
+/// Time the main queries against a synthetic chat.db of `conversations` chats with `messages`
+/// messages each
+fn bench_db(
+    conversations: usize,
+    messages: usize,
+    iterations: usize,
+    path: Option<PathBuf>,
+    overwrite: bool,
+) -> Result<()> {
+    if conversations == 0 || messages == 0 {
+        return Err(Error::Generic(t!("cli.bench_empty")));
+    }
+    // The default database is im's own, so it's replaced; one given with --db only on request
+    let overwrite = overwrite || path.is_none();
+    let path = path.unwrap_or_else(|| env::temp_dir().join("im-bench-chat.db"));
+    if !overwrite && path.exists() {
+        return Err(Error::Generic(t!(
+            "cli.bench_exists",
+            path = path.display()
+        )));
+    }
+    info!(
+        "{}",
+        t!(
+            "cli.bench_generating",
+            count = conversations * messages,
+            conversations = conversations,
+            path = path.display()
+        )
+    );
+    let chat = bench::create_synthetic_db(&path, conversations, messages, overwrite)?;
+    let db = MessageDB::open_at(&path)?;
+
+    for result in bench::run(&db, &chat, iterations)? {
+        println!(
+            "{}",
+            t!(
+                "cli.bench_result",
+                name = format!("{:<26}", result.name),
+                mean = format!("{:>9.3}", result.mean.as_secs_f64() * 1000.0),
+                min = format!("{:.3}", result.min.as_secs_f64() * 1000.0),
+                max = format!("{:.3}", result.max.as_secs_f64() * 1000.0),
+                rows = result.rows
+            )
+        );
+    }
+    Ok(())
+}
+
+/// Add people from Contacts.app, or from a vCard file, as contacts, and give existing contacts
+/// their initials and avatar colors
+fn import_contacts(config: &mut Config, file: Option<&Path>) -> Result<()> {
+    let people = match file {
+        Some(file) => vcard::parse_vcards(&fs::read_to_string(file)?),
+        None => address_book::list_people()?,
+    };
+    let (mut added, mut updated, mut skipped) = (0, 0, 0);
+    for person in people {
+        match config.import_person(&person) {
+            ImportedPerson::Added => added += 1,
+            ImportedPerson::Updated => updated += 1,
+            ImportedPerson::Unchanged => {}
+            ImportedPerson::Skipped => skipped += 1,
+        }
+    }
+    if added + updated > 0 {
+        config.save()?;
+    }
+
+    let source = match file {
+        Some(file) => file.display().to_string(),
+        None => t!("cli.contacts_app"),
+    };
+    info!(
+        "{}",
+        t!(
+            "cli.imported_contacts",
+            added = added,
+            updated = updated,
+            source = source
+        )
+    );
+    if skipped > 0 {
+        eprintln!("{}", t!("cli.import_skipped", count = skipped));
+    }
+    Ok(())
+}
+
+/// Write every contact as a vCard, or as a CSV line of name, identifier, and display name
+fn export_contacts(config: &Config, vcf: bool, output: Option<&Path>) -> Result<()> {
+    let contacts = config.list_contacts();
+    let mut text = String::new();
+    if !vcf {
+        text.push_str("name,identifier,display_name\n");
+    }
+    for (name, entry) in &contacts {
+        if vcf {
+            text.push_str(&vcard::format_vcard(entry));
+        } else {
+            let display_name = entry.display_name.as_deref().unwrap_or_default();
+            text.push_str(&csv_import::format_csv_row(&[
+                name,
+                &entry.identifier,
+                display_name,
+            ]));
+            text.push('\n');
+        }
+    }
+
+    match output {
+        Some(path) => {
+            fs::write(path, text)?;
+            info!(
+                "{}",
+                t!(
+                    "cli.exported_contacts",
+                    count = contacts.len(),
+                    path = path.display()
+                )
+            );
+        }
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+/// Add contacts from a CSV file, after showing which rows would be added and which skipped
+fn import_csv(
+    config: &mut Config,
+    file: &Path,
+    name_col: usize,
+    phone_col: usize,
+    yes: bool,
+) -> Result<()> {
+    let text = fs::read_to_string(file)?;
+    let plan = csv_import::plan_import(config, &text, name_col, phone_col)?;
+
+    let (mut additions, mut duplicates, mut invalid) = (Vec::new(), 0, 0);
+    for row in plan {
+        match row {
+            CsvRow::Add { name, identifier } => {
+                println!(
+                    "{}",
+                    t!("csv_import.add", name = name, identifier = identifier)
+                );
+                additions.push((name, identifier));
+            }
+            CsvRow::Duplicate { name, existing } => {
+                println!(
+                    "{}",
+                    t!("csv_import.duplicate", name = name, existing = existing)
+                );
+                duplicates += 1;
+            }
+            CsvRow::Invalid { row, value } => {
+                println!("{}", t!("csv_import.invalid", row = row, value = value));
+                invalid += 1;
+            }
+        }
+    }
+
+    if additions.is_empty() {
+        info!("{}", t!("csv_import.nothing"));
+    } else if !yes {
+        if !io::stdin().is_terminal() {
+            return Err(Error::Generic(t!("csv_import.needs_yes")));
+        }
+        if !confirm(&t!("csv_import.prompt", count = additions.len()))? {
+            info!("{}", t!("csv_import.cancelled"));
+            return Ok(());
+        }
+    }
+
+    let added = additions.len();
+    for (name, identifier) in additions {
+        config.add_contact(name, identifier, None);
+    }
+    if added > 0 {
+        config.save()?;
+    }
+    info!(
+        "{}",
+        t!(
+            "csv_import.summary",
+            added = added,
+            duplicates = duplicates,
+            invalid = invalid
+        )
+    );
+    Ok(())
+}
+
+/// Check each contact's identifier against the handles in chat.db, flagging
+/// contacts with no message history and suggesting handles they may have meant
+fn verify_contacts(config: &Config) -> Result<()> {
+    let mut contacts: Vec<(String, String)> = config
+        .list_contacts()
+        .into_iter()
+        .map(|(name, entry)| (name.clone(), entry.identifier.clone()))
+        .collect();
+    if let Some(default) = config.default_contact() {
+        if config.find_contact_by_identifier(&default).is_none() {
+            contacts.push((t!("verify.default_contact"), default));
+        }
+    }
+    if contacts.is_empty() {
+        info!("{}", t!("verify.no_contacts"));
+        return Ok(());
+    }
+
+    let db = MessageDB::open()?;
+    let counts: HashMap<String, usize> = db.get_handle_message_counts()?.into_iter().collect();
+
+    let mut problems = 0;
+    for (name, identifier) in &contacts {
+        match counts.get(identifier) {
+            Some(count) if *count > 0 => {
+                println!(
+                    "{}",
+                    t!(
+                        "verify.ok",
+                        name = name,
+                        identifier = identifier,
+                        count = count
+                    )
+                );
+            }
+            _ => {
+                problems += 1;
+                println!(
+                    "{}",
+                    t!("verify.missing", name = name, identifier = identifier)
+                );
+                for (handle, count) in db
+                    .find_similar_handles(identifier)?
+                    .into_iter()
+                    .take(MAX_SUGGESTIONS)
+                {
+                    println!(
+                        "{}",
+                        t!("verify.suggestion", handle = handle, count = count)
+                    );
+                }
+            }
+        }
+    }
+
+    if problems == 0 {
+        println!("{}", t!("verify.all_ok", count = contacts.len()));
+    } else {
+        println!(
+            "{}",
+            t!(
+                "verify.problems",
+                problems = problems,
+                count = contacts.len()
+            )
+        );
+    }
+    Ok(())
+}
+
+/// Print a group of contacts side by side
+fn print_contact_group(config: &Config, group: &[String]) {
+    let columns: Vec<Vec<String>> = group
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let entry = config.get_contact(name);
+            let aliases: Vec<&str> = config
+                .aliases_for(name)
+                .iter()
+                .map(|a| a.as_str())
+                .collect();
+            vec![
+                format!("{}) {}", i + 1, name),
+                entry.map(|e| e.identifier.clone()).unwrap_or_default(),
+                entry
+                    .and_then(|e| e.display_name.clone())
+                    .unwrap_or_else(|| "-".to_string()),
+                if aliases.is_empty() {
+                    "-".to_string()
+                } else {
+                    aliases.join(", ")
+                },
+            ]
+        })
+        .collect();
+
+    let labels = [
+        String::new(),
+        t!("dedupe.identifier"),
+        t!("dedupe.display_name"),
+        t!("dedupe.aliases"),
+    ];
+    let widths: Vec<usize> = columns
+        .iter()
+        .map(|column| {
+            column
+                .iter()
+                .map(|cell| display_width(cell))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    println!("{}", t!("dedupe.heading"));
+    for (row, label) in labels.iter().enumerate() {
+        let cells: Vec<String> = columns
+            .iter()
+            .zip(&widths)
+            .map(|(column, &width)| pad_to_width(&column[row], width))
+            .collect();
+        println!("  {}  {}", pad_to_width(label, 12), cells.join("  |  "));
+    }
+}
+
+/// Print the columns and indexes chat.db lacks and the chat queries whose plans read a whole
+/// table
+fn print_query_plans(handles: &[String]) -> Result<()> {
+    let mut db = MessageDB::open()?;
+    let missing = db.missing_indexes()?;
+    let scans = db.find_full_scans(handles)?;
+
+    for column in db.missing_columns() {
+        eprintln!("{}", t!("cli.missing_column", column = column));
+    }
+    for index in &missing {
+        eprintln!("{}", t!("cli.missing_index", index = index));
+    }
+    for scan in &scans {
+        eprintln!(
+            "{}",
+            t!("cli.full_scan", query = scan.query, detail = scan.detail)
+        );
+    }
+    if missing.is_empty() && scans.is_empty() {
+        eprintln!("{}", t!("cli.query_plans_ok"));
+    }
+    Ok(())
+}
+
+/// Show the onboarding screen on first run, or whenever chat.db can't be read
+fn ensure_permissions(verbose: bool) -> Result<()> {
+    let state = StateStore::open()?;
+    let onboarded = state.get_setting(ONBOARDED_SETTING)?.is_some();
+    if onboarded && permissions::has_full_disk_access() {
+        return Ok(());
+    }
+
+    if verbose {
+        eprintln!("{}", t!("cli.checking_permissions"));
+    }
+
+    if tui::run_onboarding_tui()? {
+        state.set_setting(ONBOARDED_SETTING, "true")?;
+    }
+    Ok(())
+}
+
+/// Send a message from the command line, retrying while Messages.app is unavailable.
+///
+/// Messages sent by scripts are rate limited; ones over the limit are spooled until it allows.
+/// Replies to a message in a group chat go to the group, named by its guid in `chat`.
+fn send_reply(config: &Config, handle: &str, chat: Option<String>, text: &str) -> Result<()> {
+    let name = config
+        .display_name_for(handle)
+        .unwrap_or_else(|| format_display_number(handle));
+    let name = match chat {
+        Some(_) => t!("cli.group_with", name = name),
+        None => name,
+    };
+    let automated = sent_by_script();
+    let limit = RateLimit::new(config.sender());
+    let mut slot = None;
+    if automated {
+        let state = StateStore::open()?;
+        let reserved = limit.reserve(&state)?;
+        if let RateSlot::Full { send_at } = reserved {
+            state.spool_message(handle, chat.as_deref(), text, true)?;
+            let time = Local
+                .timestamp_opt(send_at, 0)
+                .single()
+                .map(|time| time.format("%H:%M:%S").to_string())
+                .unwrap_or_default();
+            return Err(Error::Queued(t!(
+                "cli.rate_limited",
+                name = name,
+                time = time
+            )));
+        }
+        slot = Some(reserved);
+    }
+
+    let sender = Sender::from_config(config, handle.to_string()).with_chat(chat.clone());
+    let policy = RetryPolicy::new(config.sender());
+    let sent = send_with_retry(&sender, text, &policy, |attempt, delay, error| {
+        eprintln!(
+            "{}",
+            t!(
+                "cli.retrying",
+                error = error,
+                seconds = delay.as_secs(),
+                attempt = attempt,
+                max = policy.max_attempts
+            )
+        );
+    });
+
+    if let (Some(slot), Err(_)) = (slot, &sent) {
+        limit.release(&StateStore::open()?, slot)?;
+    }
+    match sent {
+        Ok(()) if sender::is_sandbox() => {
+            let path = paths::sandbox_log().unwrap_or_default();
+            info!(
+                "{}",
+                t!("cli.sent_sandbox", name = name, path = path.display())
+            );
+            Ok(())
+        }
+        Ok(()) => {
+            info!("{}", t!("cli.sent", name = name));
+            Ok(())
+        }
+        // Keep it for when Messages.app is back
+        Err(error) if error.is_transient() => {
+            StateStore::open()?.spool_message(handle, chat.as_deref(), text, automated)?;
+            Err(Error::Queued(t!("cli.spooled", error = error, name = name)))
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Whether im was run by a script, such as a hook or a cron job, rather than typed at a
+/// terminal, so what it sends counts against the rate limit. Scripts say so with
+/// `--automated` or `IM_AUTOMATED`.
+fn sent_by_script() -> bool {
+    env::var_os(AUTOMATED_VAR).is_some()
+}
+
+/// Get the handle and message of the most recent incoming message
+fn latest_incoming(db: &MessageDB) -> Result<(String, Message)> {
+    db.get_latest_incoming()?
+        .ok_or_else(|| Error::Generic(t!("cli.no_incoming")))
+}
+
+/// Print all starred messages, newest first
+fn print_starred(config: &Config) -> Result<()> {
+    let starred = StateStore::open()?.starred()?;
+    if starred.is_empty() {
+        info!("{}", t!("cli.no_starred"));
+        return Ok(());
+    }
+
+    for message in starred {
+        print_saved_message(config, message);
+    }
+
+    Ok(())
+}
+
+/// Find the tag a name given on the command line refers to
+fn find_tag(name: &str) -> Result<String> {
+    let tags: Vec<String> = StateStore::open()?
+        .tags()?
+        .into_iter()
+        .map(|(tag, _)| tag)
+        .collect();
+    tags::find_tag(name, &tags)
+        .cloned()
+        .ok_or_else(|| Error::Generic(t!("cli.unknown_tag", tag = name)))
+}
+
+/// Print the messages with a tag, newest first, or every tag with its count
+fn print_tags(config: &Config, tag: Option<&str>) -> Result<()> {
+    let state = StateStore::open()?;
+    let Some(tag) = tag else {
+        let tags = state.tags()?;
+        if tags.is_empty() {
+            info!("{}", t!("cli.no_tags"));
+        }
+        for (tag, count) in tags {
+            println!("#{} ({})", tag, count);
+        }
+        return Ok(());
+    };
+
+    for message in state.tagged(tag)? {
+        print_saved_message(config, message);
+    }
+    Ok(())
+}
+
+/// Print a starred or tagged message on one line, with its date and sender
+fn print_saved_message(config: &Config, message: StarredMessage) {
+    let sender = if message.is_from_me {
+        t!("cli.me")
+    } else {
+        config
+            .display_name_for(&message.conversation)
+            .unwrap_or_else(|| format_display_number(&message.conversation))
+    };
+    println!(
+        "{}  {}: {}",
+        message.date.format("%Y-%m-%d %H:%M"),
+        sender,
+        message.text.unwrap_or_default()
+    );
+}
+
+/// Print a conversation's message counts over the last year, with a heatmap if asked
+fn print_activity(activity: &Activity, name: &str, heatmap: bool) {
+    println!(
+        "{}",
+        t!("cli.activity_total", name = name, count = activity.total())
+    );
+    if let Some((day, count)) = activity.busiest_day() {
+        println!(
+            "{}",
+            t!(
+                "cli.activity_busiest_day",
+                date = day.format("%Y-%m-%d"),
+                count = count
+            )
+        );
+    }
+    if let Some(hour) = activity.busiest_hour() {
+        println!("{}", t!("cli.activity_busiest_hour", hour = hour));
+        println!(
+            "{}",
+            t!("cli.activity_hours", sparkline = activity.sparkline())
+        );
+    }
+
+    if heatmap {
+        println!();
+        println!("    {}", activity.month_labels(HEATMAP_WEEKS));
+        for (label, row) in WEEKDAY_LABELS.iter().zip(activity.heatmap(HEATMAP_WEEKS)) {
+            let cells: String = row
+                .into_iter()
+                .map(|level| level.map_or(' ', |level| HEATMAP_CELLS[level]))
+                .collect();
+            println!("{:<4}{}", label, cells);
+        }
+    }
+}
+
+/// Print the words and emoji used most in a conversation, most frequent first
+fn print_word_counts(counts: &WordCounts) {
+    println!();
+    let words = counts.top_words(TOP_WORDS);
+    if words.is_empty() {
+        info!("{}", t!("cli.no_words"));
+    }
+    for (rank, (word, count)) in words.iter().enumerate() {
+        println!("{:>3}. {} {}", rank + 1, pad_to_width(word, 20), count);
+    }
+
+    let emoji = counts.top_emoji(TOP_EMOJI);
+    if !emoji.is_empty() {
+        let emoji: Vec<String> = emoji
+            .iter()
+            .map(|(emoji, count)| format!("{} {}", emoji, count))
+            .collect();
+        println!();
+        println!("{}", t!("cli.top_emoji", emoji = emoji.join("  ")));
+    }
+}
+
+/// Print each conversation's attachment usage, largest first
+fn print_attachment_usage(config: &Config, usage: &[AttachmentUsage]) {
+    if usage.is_empty() {
+        info!("{}", t!("cli.no_attachments"));
+    }
+    for conversation in usage {
+        let mut line = t!(
+            "cli.attachment_usage",
+            size = format!("{:>9}", format_size(conversation.bytes)),
+            count = conversation.count,
+            name = conversation.name(config)
+        );
+        if conversation.missing > 0 {
+            line.push_str(&t!(
+                "cli.attachment_usage_missing",
+                count = conversation.missing
+            ));
+        }
+        println!("{}", line);
+    }
+}
+
 /// Get contact information based on command-line arguments and configuration
 fn get_contact_info(args: &Cli, config: &Config, verbose: bool) -> Result<(String, String)> {
     // Priority:
@@ -185,61 +1638,32 @@ fn get_contact_info(args: &Cli, config: &Config, verbose: bool) -> Result<(Strin
     if let Some(cli_contact) = &args.contact {
         let formatted = format_phone_number(cli_contact);
         if verbose && formatted != *cli_contact {
-            println!(
-                "Note: Formatted contact identifier from '{}' to '{}'",
-                cli_contact, formatted
+            eprintln!(
+                "{}",
+                t!(
+                    "cli.formatted_identifier",
+                    input = cli_contact,
+                    identifier = formatted
+                )
             );
         }
 
-        let display = format_display_number(&formatted);
+        let display = config
+            .display_name_for(&formatted)
+            .unwrap_or_else(|| format_display_number(&formatted));
         return Ok((formatted, display));
     }
 
     if let Some(contact_name) = &args.contact_name {
-        // Try case-insensitive lookup first
-        if let Some((actual_name, entry)) = config.get_contact_case_insensitive(contact_name) {
-            let display = match &entry.display_name {
-                Some(name) => name.clone(),
-                None => format_display_number(&entry.identifier),
-            };
-
-            if verbose {
-                if actual_name != contact_name {
-                    println!(
-                        "Using contact '{}' (matched '{}' case-insensitively)",
-                        actual_name, contact_name
-                    );
-                } else {
-                    println!("Using contact '{}'", actual_name);
-                }
-            }
-
-            return Ok((entry.identifier.clone(), display));
-        } else {
-            // Fallback to case-sensitive lookup for backward compatibility
-            if let Some(entry) = config.get_contact(contact_name) {
-                let display = match &entry.display_name {
-                    Some(name) => name.clone(),
-                    None => format_display_number(&entry.identifier),
-                };
-
-                if verbose {
-                    println!("Using contact '{}'", contact_name);
-                }
-
-                return Ok((entry.identifier.clone(), display));
-            } else {
-                return Err(Error::Generic(format!(
-                    "Contact '{}' not found in configuration",
-                    contact_name
-                )));
-            }
-        }
+        return contact_from_name(config, contact_name, verbose);
     }
 
     if let Some(default_contact) = config.default_contact() {
         if verbose {
-            println!("Using default contact: {}", default_contact);
+            eprintln!(
+                "{}",
+                t!("cli.using_default_contact", identifier = default_contact)
+            );
         }
 
         let display = match config.default_display_name() {
@@ -252,3 +1676,48 @@ fn get_contact_info(args: &Cli, config: &Config, verbose: bool) -> Result<(Strin
 
     Err(Error::NoContact)
 }
+
+/// Resolve a contact name, alias, phone number, or email to an identifier and display name
+fn contact_from_name(
+    config: &Config,
+    contact_name: &str,
+    verbose: bool,
+) -> Result<(String, String)> {
+    // Look up by name or alias first
+    if let Some((actual_name, entry)) = config.resolve_contact(contact_name) {
+        let display = match &entry.display_name {
+            Some(name) => name.clone(),
+            None => format_display_number(&entry.identifier),
+        };
+
+        if verbose {
+            if actual_name != contact_name {
+                eprintln!(
+                    "{}",
+                    t!(
+                        "cli.using_contact_matched",
+                        name = actual_name,
+                        input = contact_name
+                    )
+                );
+            } else {
+                eprintln!("{}", t!("cli.using_contact", name = actual_name));
+            }
+        }
+
+        Ok((entry.identifier.clone(), display))
+    } else if looks_like_identifier(contact_name) {
+        // Use an unsaved phone number or email directly
+        let formatted = normalize_identifier(contact_name);
+        if verbose {
+            eprintln!("{}", t!("cli.using_identifier", identifier = formatted));
+        }
+
+        let display = config
+            .display_name_for(&formatted)
+            .unwrap_or_else(|| format_display_number(&formatted));
+        Ok((formatted, display))
+    } else {
+        Err(Error::ContactNotFound(contact_name.to_string()))
+    }
+}
@@ -0,0 +1,109 @@
+use crate::config::Config;
+use crate::db::{Message, MessageDB};
+use crate::error::Result;
+use crate::formatter::{format_display_number, truncate_graphemes};
+use crate::watcher::DbWatcher;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Posts a notification with the title and body passed as arguments.
+const NOTIFY_SCRIPT: &str = r#"
+on run {notificationTitle, notificationBody}
+    display notification notificationBody with title notificationTitle
+end run
+"#;
+
+/// Longest notification body, in graphemes.
+const MAX_BODY_CHARS: usize = 200;
+
+/// Posts desktop notifications for messages that arrive while a view is open.
+pub struct Notifier {
+    watcher: DbWatcher,
+    /// ROWID of the newest incoming message already seen
+    last_rowid: i64,
+}
+
+impl Notifier {
+    /// Start watching for messages newer than the latest one in chat.db.
+    pub fn new(db: &MessageDB) -> Result<Self> {
+        let last_rowid = db.get_latest_rowid()?;
+        let mut watcher = DbWatcher::new()?;
+        // The first check always reports a change; nothing is new yet
+        watcher.has_changed();
+        Ok(Self {
+            watcher,
+            last_rowid,
+        })
+    }
+
+    /// Notify about messages that arrived since the last check.
+    ///
+    /// Messages from `visible` handles are skipped while the terminal is `focused`, since
+    /// they're already on screen.
+    pub fn check(
+        &mut self,
+        db: &MessageDB,
+        config: &Config,
+        visible: &[String],
+        focused: bool,
+    ) -> Result<()> {
+        if !self.watcher.has_changed() {
+            return Ok(());
+        }
+
+        for (handle, message) in db.get_incoming_since(self.last_rowid)? {
+            self.last_rowid = self.last_rowid.max(message.rowid);
+            if should_notify(&handle, visible, focused) {
+                let title = config
+                    .display_name_for(&handle)
+                    .unwrap_or_else(|| format_display_number(&handle));
+                notify(&title, &notification_body(&message))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Whether a message from `handle` needs a notification.
+fn should_notify(handle: &str, visible: &[String], focused: bool) -> bool {
+    !focused || !visible.iter().any(|visible| visible == handle)
+}
+
+/// The text shown in a notification: the message, or what was sent when it has no text.
+fn notification_body(message: &Message) -> String {
+    let text = message
+        .text
+        .as_deref()
+        .filter(|text| !text.trim().is_empty())
+        .or(message.message_type.as_deref())
+        .unwrap_or("New message");
+    truncate_graphemes(text, MAX_BODY_CHARS)
+}
+
+/// Post a desktop notification without waiting for it to show.
+pub fn notify(title: &str, body: &str) -> Result<()> {
+    let mut child = Command::new("osascript")
+        .arg("-e")
+        .arg(NOTIFY_SCRIPT)
+        .arg(title)
+        .arg(body)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_notify() {
+        let visible = vec!["+15551234567".to_string(), "mom@example.com".to_string()];
+        assert!(!should_notify("+15551234567", &visible, true));
+        assert!(should_notify("+15551234567", &visible, false));
+        assert!(should_notify("+15557654321", &visible, true));
+        assert!(should_notify("+15557654321", &[], false));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::error::{Error, Result};
+use crate::i18n::t;
+use crate::APP_NAME;
+use directories::ProjectDirs;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command, Stdio};
+
+/// File in the data directory that holds app state.
+const STATE_DB: &str = "state.db";
+
+/// Directory inside the data directory that holds conversation transcripts.
+const TRANSCRIPT_DIR: &str = "transcripts";
+
+/// Directory inside the cache directory that holds attachment previews.
+const THUMBNAIL_DIR: &str = "thumbnails";
+
+/// Directory inside the cache directory that holds downloaded GIFs.
+const GIF_DIR: &str = "gifs";
+
+/// Directory inside the cache directory that holds voice recordings before they're sent.
+const VOICE_DIR: &str = "voice";
+
+/// Directory inside the cache directory that holds long messages being sent as text files.
+const TEXT_DIR: &str = "text";
+
+/// Directory inside the cache directory that holds the databases queried in place of chat.db:
+/// snapshots of it, and the ones built for opened archives and the demo.
+const DATABASE_DIR: &str = "databases";
+
+/// File in the cache directory that holds the last unread summary for `im status`.
+const STATUS_CACHE: &str = "status.json";
+
+/// File in the state directory that sends are written to in the sandbox.
+const SANDBOX_LOG: &str = "sandbox.log";
+
+/// The platform's directories for the app: `~/Library/Application Support/rs.im` and
+/// `~/Library/Caches/rs.im` on macOS, and the XDG base directories (`$XDG_DATA_HOME/im` and so
+/// on) elsewhere.
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("rs", "", APP_NAME)
+}
+
+/// Get the configuration file.
+pub fn config_file() -> Option<PathBuf> {
+    confy::get_configuration_file_path(APP_NAME, None).ok()
+}
+
+/// Get the directory for files worth keeping, like the state store and transcripts.
+pub fn data_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+/// Get the directory for files that can be made again, like thumbnails.
+pub fn cache_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.cache_dir().to_path_buf())
+}
+
+/// Get the directory for logs: `$XDG_STATE_HOME/im` where there is one, otherwise the data
+/// directory, as on macOS.
+pub fn state_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.state_dir().unwrap_or(dirs.data_dir()).to_path_buf())
+}
+
+/// Get the state database.
+pub fn state_db() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join(STATE_DB))
+}
+
+/// Get the directory conversation transcripts are written to.
+pub fn transcripts() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join(TRANSCRIPT_DIR))
+}
+
+/// Get the directory attachment previews are cached in.
+pub fn thumbnails() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join(THUMBNAIL_DIR))
+}
+
+/// Get the directory GIFs are downloaded to.
+pub fn gifs() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join(GIF_DIR))
+}
+
+/// Get the directory voice messages are recorded to.
+pub fn voice() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join(VOICE_DIR))
+}
+
+/// Get the directory long messages are written to, to send as text files.
+pub fn text_files() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join(TEXT_DIR))
+}
+
+/// Get the directory snapshots of chat.db and the databases built for archives and the demo
+/// are kept in.
+pub fn databases() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join(DATABASE_DIR))
+}
+
+/// Get a database in `databases()` for this process alone, named after `prefix` and the
+/// process id, creating the directory if needed.
+pub fn process_database(prefix: &str) -> Result<PathBuf> {
+    let dir = databases().ok_or_else(|| Error::Generic(t!("paths.no_cache_dir")))?;
+    fs::create_dir_all(&dir)?;
+    Ok(dir.join(format!("{}-{}.db", prefix, process::id())))
+}
+
+/// Delete the databases in `databases()` left behind by processes that are no longer running,
+/// such as an `im` that was killed or whose terminal was closed, along with their journals.
+pub fn remove_stale_databases() {
+    let Some(entries) = databases().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return;
+    };
+    let mut running = HashMap::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(pid) = name.to_str().and_then(database_owner) else {
+            continue;
+        };
+        if pid == process::id() {
+            continue;
+        }
+        let alive = *running.entry(pid).or_insert_with(|| is_running(pid));
+        if !alive {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// The id of the process a file from `process_database` belongs to: `chat-snapshot-123.db`,
+/// or its journal, `chat-snapshot-123.db-wal`.
+fn database_owner(file_name: &str) -> Option<u32> {
+    let (stem, _) = file_name.split_once(".db")?;
+    let (_, pid) = stem.rsplit_once('-')?;
+    pid.parse().ok()
+}
+
+/// Whether a process with the id `pid` is running.
+fn is_running(pid: u32) -> bool {
+    // Signal 0 checks that the process exists without disturbing it
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Get the file the last unread summary is cached in.
+pub fn status_cache() -> Option<PathBuf> {
+    cache_dir().map(|dir| dir.join(STATUS_CACHE))
+}
+
+/// Get the log sandboxed messages are written to.
+pub fn sandbox_log() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join(SANDBOX_LOG))
+}
+
+/// Everything `im paths` prints, as a name and the path, or `None` if the platform has no
+/// directory for it.
+pub fn all() -> Vec<(&'static str, Option<PathBuf>)> {
+    vec![
+        ("config", config_file()),
+        ("data", data_dir()),
+        ("cache", cache_dir()),
+        ("state", state_dir()),
+        ("state_db", state_db()),
+        ("transcripts", transcripts()),
+        ("thumbnails", thumbnails()),
+        ("gifs", gifs()),
+        ("voice", voice()),
+        ("text", text_files()),
+        ("databases", databases()),
+        ("status_cache", status_cache()),
+        ("sandbox_log", sandbox_log()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all() {
+        let all = all();
+        let (data, cache, state) = (data_dir(), cache_dir(), state_dir());
+        for (name, path) in &all {
+            let Some(path) = path else { continue };
+            let inside =
+                |dir: &Option<PathBuf>| dir.as_ref().is_some_and(|dir| path.starts_with(dir));
+            assert!(
+                *name == "config" || inside(&data) || inside(&cache) || inside(&state),
+                "{} is outside the app's directories",
+                name
+            );
+        }
+        assert_eq!(state_db(), data.map(|dir| dir.join(STATE_DB)));
+    }
+
+    #[test]
+    fn test_database_owner() {
+        assert_eq!(database_owner("chat-snapshot-123.db"), Some(123));
+        assert_eq!(database_owner("demo-45.db-wal"), Some(45));
+        assert_eq!(database_owner("chat-snapshot.db"), None);
+        assert_eq!(database_owner("notes.txt"), None);
+        assert!(is_running(process::id()));
+    }
+}
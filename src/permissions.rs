@@ -0,0 +1,64 @@
+use crate::db::MessageDB;
+use crate::error::Result;
+use std::process::{Command, Stdio};
+
+/// System Settings deep link for the Full Disk Access pane.
+const FULL_DISK_ACCESS_URL: &str =
+    "x-apple.systempreferences:com.apple.preference.security?Privacy_AllFiles";
+
+/// System Settings deep link for the Automation pane.
+const AUTOMATION_URL: &str =
+    "x-apple.systempreferences:com.apple.preference.security?Privacy_Automation";
+
+/// A macOS privacy permission `im` needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Reading ~/Library/Messages/chat.db
+    FullDiskAccess,
+    /// Controlling Messages.app through AppleScript to send messages
+    Automation,
+}
+
+impl Permission {
+    /// Check whether the permission is granted.
+    pub fn is_granted(self) -> bool {
+        match self {
+            Permission::FullDiskAccess => has_full_disk_access(),
+            Permission::Automation => has_automation_access(),
+        }
+    }
+
+    /// Open the System Settings pane where the permission is granted.
+    pub fn open_settings(self) -> Result<()> {
+        let url = match self {
+            Permission::FullDiskAccess => FULL_DISK_ACCESS_URL,
+            Permission::Automation => AUTOMATION_URL,
+        };
+
+        Command::new("open")
+            .arg(url)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()?;
+        Ok(())
+    }
+}
+
+/// Check whether the Messages database can be read.
+pub fn has_full_disk_access() -> bool {
+    MessageDB::open().and_then(|db| db.check_access()).is_ok()
+}
+
+/// Check whether Messages.app accepts AppleScript commands from this terminal.
+///
+/// The first check triggers the system prompt asking the user to allow access.
+fn has_automation_access() -> bool {
+    Command::new("osascript")
+        .arg("-e")
+        .arg(r#"tell application "Messages" to get name"#)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
@@ -0,0 +1,94 @@
+use crate::db::QueryRows;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rusqlite::types::Value;
+use serde_json::{Map, Number, Value as Json};
+
+/// Rows as tab-separated lines under a header of column names, for reading or piping into
+/// `cut` and `awk`.
+///
+/// NULLs are left empty, and blobs, which wouldn't print, are shown by size.
+pub fn to_lines(rows: &QueryRows) -> Vec<String> {
+    let mut lines = vec![rows.columns.join("\t")];
+    lines.extend(rows.rows.iter().map(|row| {
+        row.iter()
+            .map(|value| match value {
+                Value::Null => String::new(),
+                Value::Integer(n) => n.to_string(),
+                Value::Real(n) => n.to_string(),
+                // Keep one row per line
+                Value::Text(text) => text.replace(['\t', '\n'], " "),
+                Value::Blob(bytes) => format!("<{} bytes>", bytes.len()),
+            })
+            .collect::<Vec<_>>()
+            .join("\t")
+    }));
+    lines
+}
+
+/// Rows as a JSON array with an object per row, keyed by column name. Blobs are base64.
+pub fn to_json(rows: &QueryRows) -> Json {
+    Json::Array(
+        rows.rows
+            .iter()
+            .map(|row| {
+                let object: Map<String, Json> = rows
+                    .columns
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().map(json_value))
+                    .collect();
+                Json::Object(object)
+            })
+            .collect(),
+    )
+}
+
+/// Convert an SQLite value to JSON.
+fn json_value(value: &Value) -> Json {
+    match value {
+        Value::Null => Json::Null,
+        Value::Integer(n) => Json::from(*n),
+        Value::Real(n) => Number::from_f64(*n).map_or(Json::Null, Json::Number),
+        Value::Text(text) => Json::String(text.clone()),
+        Value::Blob(bytes) => Json::String(STANDARD.encode(bytes)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rows() -> QueryRows {
+        QueryRows {
+            columns: vec!["id".to_string(), "text".to_string(), "extra".to_string()],
+            rows: vec![
+                vec![
+                    Value::Integer(1),
+                    Value::Text("hi\tthere".to_string()),
+                    Value::Null,
+                ],
+                vec![Value::Real(1.5), Value::Null, Value::Blob(vec![1, 2, 3])],
+            ],
+        }
+    }
+
+    #[test]
+    fn test_to_lines() {
+        assert_eq!(
+            to_lines(&rows()),
+            vec!["id\ttext\textra", "1\thi there\t", "1.5\t\t<3 bytes>"]
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        assert_eq!(
+            to_json(&rows()),
+            json!([
+                {"id": 1, "text": "hi\tthere", "extra": null},
+                {"id": 1.5, "text": null, "extra": "AQID"},
+            ])
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+/// `associated_message_type` of the first tapback; the rest follow in `Tapback` order.
+const FIRST_TAPBACK: i64 = 2000;
+
+/// `associated_message_type` of the removal of the first tapback.
+const FIRST_REMOVAL: i64 = 3000;
+
+/// A classic tapback, in the order chat.db numbers them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Tapback {
+    Love,
+    Like,
+    Dislike,
+    Laugh,
+    Emphasize,
+    Question,
+}
+
+impl Tapback {
+    const ALL: [Tapback; 6] = [
+        Tapback::Love,
+        Tapback::Like,
+        Tapback::Dislike,
+        Tapback::Laugh,
+        Tapback::Emphasize,
+        Tapback::Question,
+    ];
+
+    /// How the tapback is shown under the message.
+    pub fn emoji(self) -> &'static str {
+        match self {
+            Tapback::Love => "❤️",
+            Tapback::Like => "👍",
+            Tapback::Dislike => "👎",
+            Tapback::Laugh => "😂",
+            Tapback::Emphasize => "‼️",
+            Tapback::Question => "❓",
+        }
+    }
+}
+
+/// What a tapback row in chat.db does to the message it points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapbackChange {
+    /// Reacted, replacing the sender's earlier reaction to the message
+    Added(Tapback),
+    /// Took the reaction back
+    Removed(Tapback),
+}
+
+impl TapbackChange {
+    /// Decode a message's `associated_message_type`. Emoji and sticker tapbacks, which this
+    /// doesn't show, and ordinary messages give `None`.
+    pub fn from_type(associated_type: i64) -> Option<Self> {
+        let index = |first: i64| {
+            usize::try_from(associated_type - first)
+                .ok()
+                .and_then(|i| Tapback::ALL.get(i).copied())
+        };
+        index(FIRST_TAPBACK)
+            .map(TapbackChange::Added)
+            .or_else(|| index(FIRST_REMOVAL).map(TapbackChange::Removed))
+    }
+}
+
+/// A reaction someone currently has on a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reaction {
+    pub tapback: Tapback,
+    /// Handle of whoever reacted, or `None` for the user.
+    pub sender: Option<String>,
+}
+
+/// A tapback row from chat.db, as read for `associate`.
+#[derive(Debug, Clone)]
+pub struct TapbackRow {
+    /// Guid of the message reacted to.
+    pub target: String,
+    pub change: TapbackChange,
+    /// Handle of whoever reacted, or `None` for the user.
+    pub sender: Option<String>,
+}
+
+/// Work out the reactions each message has now from its tapback rows, oldest first.
+///
+/// Everyone has at most one reaction on a message, as in Messages.app: a new tapback replaces
+/// the sender's earlier one, and a removal only takes back the tapback it names, so a removal
+/// recorded after the replacement doesn't drop the new reaction.
+pub fn associate(rows: impl IntoIterator<Item = TapbackRow>) -> HashMap<String, Vec<Reaction>> {
+    let mut reactions: HashMap<String, Vec<Reaction>> = HashMap::new();
+    for row in rows {
+        let current = reactions.entry(row.target).or_default();
+        let existing = current.iter().position(|r| r.sender == row.sender);
+        match (row.change, existing) {
+            (TapbackChange::Added(tapback), Some(i)) => current[i].tapback = tapback,
+            (TapbackChange::Added(tapback), None) => current.push(Reaction {
+                tapback,
+                sender: row.sender,
+            }),
+            (TapbackChange::Removed(tapback), Some(i)) if current[i].tapback == tapback => {
+                current.remove(i);
+            }
+            (TapbackChange::Removed(_), _) => {}
+        }
+    }
+    reactions.retain(|_, current| !current.is_empty());
+    reactions
+}
+
+/// Summarize reactions as each tapback's emoji, with a count when more than one person used
+/// it, e.g. "❤️ 2 👍".
+pub fn summary(reactions: &[Reaction]) -> String {
+    Tapback::ALL
+        .iter()
+        .filter_map(
+            |&tapback| match reactions.iter().filter(|r| r.tapback == tapback).count() {
+                0 => None,
+                1 => Some(tapback.emoji().to_string()),
+                count => Some(format!("{} {}", tapback.emoji(), count)),
+            },
+        )
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(sender: Option<&str>, associated_type: i64) -> TapbackRow {
+        TapbackRow {
+            target: "A".to_string(),
+            change: TapbackChange::from_type(associated_type).unwrap(),
+            sender: sender.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_from_type() {
+        assert_eq!(
+            TapbackChange::from_type(2001),
+            Some(TapbackChange::Added(Tapback::Like))
+        );
+        assert_eq!(
+            TapbackChange::from_type(3005),
+            Some(TapbackChange::Removed(Tapback::Question))
+        );
+        assert_eq!(TapbackChange::from_type(0), None);
+        assert_eq!(TapbackChange::from_type(2006), None);
+    }
+
+    #[test]
+    fn test_associate() {
+        let reactions = associate([
+            row(Some("mom"), 2000),
+            row(None, 2003),
+            // Changing a love to a like, with the removal of the love recorded afterwards
+            row(Some("mom"), 2001),
+            row(Some("mom"), 3000),
+            row(Some("sam"), 2001),
+        ]);
+        assert_eq!(summary(&reactions["A"]), "👍 2 😂");
+
+        // Taking back every reaction leaves nothing to show
+        let reactions = associate([row(None, 2004), row(None, 3004)]);
+        assert!(reactions.is_empty());
+    }
+}
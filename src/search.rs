@@ -0,0 +1,128 @@
+use crate::error::{Error, Result};
+use crate::i18n::t;
+use chrono::{Local, NaiveDate, TimeZone};
+
+/// Date format accepted by `before:` and `after:`.
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Who sent a message, for `from:` filters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Author {
+    Me,
+    Them,
+}
+
+/// A parsed in-chat search.
+///
+/// Plain words and "quoted phrases" must all appear in the message text. Filters narrow the
+/// results further: `from:me` or `from:them`, `has:attachment`, `has:link`, and
+/// `before:YYYY-MM-DD` or `after:YYYY-MM-DD`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SearchQuery {
+    /// Text that must appear in the message, ignoring case.
+    pub terms: Vec<String>,
+    pub from: Option<Author>,
+    pub has_attachment: bool,
+    pub has_link: bool,
+    /// Only messages sent before the start of this day.
+    pub before: Option<NaiveDate>,
+    /// Only messages sent on or after this day.
+    pub after: Option<NaiveDate>,
+}
+
+impl SearchQuery {
+    /// Parse a search typed by the user.
+    pub fn parse(query: &str) -> Result<Self> {
+        let mut search = SearchQuery::default();
+
+        for token in tokens(query) {
+            let Some((key, value)) = token.split_once(':').filter(|(_, value)| !value.is_empty())
+            else {
+                search.terms.push(token);
+                continue;
+            };
+
+            match (key.to_lowercase().as_str(), value.to_lowercase().as_str()) {
+                ("from", "me") => search.from = Some(Author::Me),
+                ("from", "them") => search.from = Some(Author::Them),
+                ("has", "attachment") => search.has_attachment = true,
+                ("has", "link") => search.has_link = true,
+                ("before", _) => search.before = Some(parse_date(value)?),
+                ("after", _) => search.after = Some(parse_date(value)?),
+                ("from" | "has", _) => {
+                    return Err(Error::Generic(t!("search.unknown_filter", filter = token)));
+                }
+                // Not a filter, e.g. a URL or a time like 10:30
+                _ => search.terms.push(token),
+            }
+        }
+
+        Ok(search)
+    }
+
+    /// Whether the search matches every message.
+    pub fn is_empty(&self) -> bool {
+        *self == SearchQuery::default()
+    }
+}
+
+/// Unix time of the start of a day in the local time zone.
+pub fn local_midnight(date: NaiveDate) -> Result<i64> {
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|midnight| Local.from_local_datetime(&midnight).earliest())
+        .map(|midnight| midnight.timestamp())
+        .ok_or_else(|| Error::Generic(t!("search.invalid_date", date = date)))
+}
+
+/// Parse a `before:`/`after:` date.
+fn parse_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value, DATE_FORMAT)
+        .map_err(|_| Error::Generic(t!("search.date_format", value = value)))
+}
+
+/// Split a query on whitespace, keeping "quoted phrases" together.
+fn tokens(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in query.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let search =
+            SearchQuery::parse(r#"dinner "see you" from:me has:link before:2023-01-01"#).unwrap();
+        assert_eq!(search.terms, vec!["dinner", "see you"]);
+        assert_eq!(search.from, Some(Author::Me));
+        assert!(search.has_link && !search.has_attachment);
+        assert_eq!(search.before, NaiveDate::from_ymd_opt(2023, 1, 1));
+        assert_eq!(search.after, None);
+
+        let search = SearchQuery::parse("https://example.com at 10:30 HAS:Attachment").unwrap();
+        assert_eq!(search.terms, vec!["https://example.com", "at", "10:30"]);
+        assert!(search.has_attachment);
+
+        assert!(SearchQuery::parse("").unwrap().is_empty());
+        assert!(SearchQuery::parse("has:photos").is_err());
+        assert!(SearchQuery::parse("after:yesterday").is_err());
+    }
+}
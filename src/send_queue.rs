@@ -0,0 +1,805 @@
+use crate::config::{Config, SenderConfig};
+use crate::error::{Error, Result};
+use crate::i18n::t;
+use crate::sender::{messages_available, Sender};
+use crate::state::{RateSlot, SpoolClaim, SpooledMessage, StateStore};
+use chrono::Local;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Longest wait between attempts, however many there have been.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+/// How often to check whether Messages.app is back while messages are spooled.
+const OFFLINE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Seconds in the windows automated messages are counted over.
+const MINUTE_SECS: i64 = 60;
+const HOUR_SECS: i64 = 60 * 60;
+
+/// How often to retry a message that failed for a reason that may pass, like Messages.app
+/// not running yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Most times to try sending a message, including the first.
+    pub max_attempts: u32,
+    /// Wait before the first retry; each later retry waits twice as long.
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Read the policy from the `[sender]` settings.
+    pub fn new(config: &SenderConfig) -> Self {
+        Self {
+            max_attempts: config.max_attempts.max(1),
+            delay: Duration::from_secs(config.retry_delay_secs),
+        }
+    }
+
+    /// How long to wait after attempt number `attempt` (counting from 1) failed.
+    pub fn delay_after(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.delay.saturating_mul(factor).min(MAX_RETRY_DELAY)
+    }
+
+    /// Whether to try again after attempt number `attempt` failed with `error`.
+    pub fn should_retry(&self, error: &Error, attempt: u32) -> bool {
+        error.is_transient() && attempt < self.max_attempts
+    }
+}
+
+/// Send a message right away, waiting out the policy's retries.
+///
+/// `on_retry` is called with the failed attempt number, the wait before the next one, and the
+/// error, before waiting.
+pub fn send_with_retry(
+    sender: &Sender,
+    text: &str,
+    policy: &RetryPolicy,
+    mut on_retry: impl FnMut(u32, Duration, &Error),
+) -> Result<()> {
+    let mut attempt = 1;
+    loop {
+        match sender.send_message(text) {
+            Err(error) if policy.should_retry(&error, attempt) => {
+                let delay = policy.delay_after(attempt);
+                on_retry(attempt, delay, &error);
+                thread::sleep(delay);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Caps how many automated messages, sent by scripts rather than typed, go out a minute and
+/// an hour, so runaway automation can't flood anyone. Messages over the limit wait in the
+/// spool instead of being dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Most automated messages in any minute; zero is no limit.
+    pub per_minute: u32,
+    /// Most automated messages in any hour; zero is no limit.
+    pub per_hour: u32,
+}
+
+impl RateLimit {
+    /// Read the limits from the `[sender]` settings.
+    pub fn new(config: &SenderConfig) -> Self {
+        Self {
+            per_minute: config.automated_per_minute,
+            per_hour: config.automated_per_hour,
+        }
+    }
+
+    /// Count an automated message about to be sent against the limit, or find out when it
+    /// may go if it's over. A reserved slot is released with `release` if the message isn't
+    /// sent after all.
+    pub fn reserve(&self, state: &StateStore) -> Result<RateSlot> {
+        let now = Local::now().timestamp();
+        state.reserve_automated_send(now, now - HOUR_SECS, |recent| {
+            self.next_allowed(recent, now)
+        })
+    }
+
+    /// Stop counting a reserved slot whose message wasn't sent.
+    pub fn release(&self, state: &StateStore, slot: RateSlot) -> Result<()> {
+        match slot {
+            RateSlot::Reserved(id) => state.release_automated_send(id),
+            RateSlot::Full { .. } => Ok(()),
+        }
+    }
+
+    /// When the next message may be sent at `now`, given when `recent` ones were, oldest first.
+    fn next_allowed(&self, recent: &[i64], now: i64) -> Option<i64> {
+        [(self.per_minute, MINUTE_SECS), (self.per_hour, HOUR_SECS)]
+            .into_iter()
+            .filter(|&(limit, _)| limit > 0)
+            .filter_map(|(limit, window)| {
+                let counted: Vec<i64> = recent
+                    .iter()
+                    .copied()
+                    .filter(|&sent| sent > now - window)
+                    .collect();
+                // Once the oldest send that puts it at the limit leaves the window
+                let over = counted.len().checked_sub(limit as usize)?;
+                Some(counted[over] + window)
+            })
+            .max()
+    }
+}
+
+/// Notices when the same text is sent twice in quick succession, as from pressing Enter twice
+/// in a laggy terminal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateGuard {
+    /// How soon after a message the same text counts as a duplicate; zero turns the guard off.
+    window: Duration,
+    /// The last message sent, and when.
+    last: Option<(String, Instant)>,
+}
+
+impl DuplicateGuard {
+    /// Read the window from the `[sender]` settings.
+    pub fn new(config: &SenderConfig) -> Self {
+        Self {
+            window: Duration::from_secs(config.duplicate_window_secs),
+            last: None,
+        }
+    }
+
+    /// Remember a message sent at `now`.
+    fn record(&mut self, text: &str, now: Instant) {
+        self.last = Some((text.trim().to_string(), now));
+    }
+
+    /// Whether sending `text` at `now` would repeat the last message within the window.
+    fn is_duplicate(&self, text: &str, now: Instant) -> bool {
+        self.last.as_ref().is_some_and(|(last, at)| {
+            last == text.trim() && now.saturating_duration_since(*at) < self.window
+        })
+    }
+}
+
+/// Where a queued message is on its way to Messages.app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendStatus {
+    /// Waiting for earlier messages to be sent.
+    Queued,
+    /// Attempt number `attempt` is in progress.
+    Sending { attempt: u32 },
+    /// Attempt number `attempt` failed with `error`; the next one starts at `retry_at`.
+    Retrying {
+        attempt: u32,
+        retry_at: Instant,
+        error: String,
+    },
+    /// An automated message over the rate limit, waiting in the spool until `send_at`.
+    RateLimited { send_at: Instant },
+    /// Retries ran out while Messages.app was unavailable. The message is spooled to disk and
+    /// sent once Messages.app is back.
+    Offline,
+    /// Sending gave up after `attempt` attempts.
+    Failed {
+        attempt: u32,
+        error: String,
+        /// What the user can do about the error.
+        suggestion: Option<String>,
+    },
+    /// Messages.app accepted the message.
+    Sent,
+}
+
+/// A message handed to the queue that hasn't been sent yet.
+#[derive(Debug, Clone)]
+pub struct Outgoing {
+    pub id: u64,
+    pub text: String,
+    /// Whether a script sent the message, so it counts against the rate limit.
+    pub automated: bool,
+    pub status: SendStatus,
+}
+
+/// Instructions for the worker thread.
+enum Command {
+    Send {
+        id: u64,
+        text: String,
+        automated: bool,
+        /// Id of the message in the spool, if it was loaded from there.
+        spool_id: Option<i64>,
+    },
+    /// Skip the wait before the next attempt.
+    RetryNow(u64),
+}
+
+/// A message the worker is responsible for.
+struct Job {
+    id: u64,
+    text: String,
+    /// Attempts made so far.
+    attempt: u32,
+    /// When the next attempt, or check for Messages.app, may start.
+    due: Instant,
+    /// Id of the message in the spool, once it has been saved there.
+    spool_id: Option<i64>,
+    /// Whether the message counts against the rate limit.
+    automated: bool,
+    /// Whether the message is waiting for Messages.app to come back.
+    offline: bool,
+}
+
+/// Outgoing messages sent one at a time, in order, on a worker thread.
+///
+/// A message that fails with a transient error is retried with exponential backoff, holding
+/// later messages back so they arrive in the order they were written. When the retries run
+/// out, the message and everything behind it is spooled to the state store and sent once
+/// Messages.app is back. Automated messages over the rate limit wait their turn the same way.
+/// Views add messages with `push`, pick up progress with `poll`, and show `outgoing` until
+/// each is sent.
+pub struct SendQueue {
+    commands: mpsc::Sender<Command>,
+    updates: Receiver<(u64, SendStatus)>,
+    /// Errors the worker ran into outside of sending, like spooling a message
+    errors: Receiver<Error>,
+    /// Messages not yet sent, oldest first
+    outgoing: Vec<Outgoing>,
+    next_id: u64,
+    policy: RetryPolicy,
+    duplicates: DuplicateGuard,
+}
+
+impl SendQueue {
+    /// Start the worker thread.
+    ///
+    /// Dropping the queue doesn't cancel messages that were already pushed: the worker finishes
+    /// sending them, including any retries, before it exits. Messages spooled while offline
+    /// stay in the state store for `deliver_spooled`.
+    pub fn new(
+        sender: Sender,
+        policy: RetryPolicy,
+        duplicates: DuplicateGuard,
+        limit: RateLimit,
+    ) -> Self {
+        let (commands, worker_commands) = mpsc::channel();
+        let (worker_updates, updates) = mpsc::channel();
+        let (worker_errors, errors) = mpsc::channel();
+        thread::spawn(move || {
+            Worker {
+                sender,
+                policy,
+                limit,
+                updates: worker_updates,
+                errors: worker_errors,
+                jobs: Vec::new(),
+                state: None,
+            }
+            .run(&worker_commands)
+        });
+
+        Self {
+            commands,
+            updates,
+            errors,
+            outgoing: Vec::new(),
+            next_id: 0,
+            policy,
+            duplicates,
+        }
+    }
+
+    /// Queue a message to be sent after any earlier ones.
+    pub fn push(&mut self, text: String) {
+        self.duplicates.record(&text, Instant::now());
+        self.enqueue(text, false, None, SendStatus::Queued);
+    }
+
+    /// Whether `text` is the message just pushed, so recently that sending it again is likely
+    /// a mistake.
+    pub fn is_duplicate(&self, text: &str) -> bool {
+        self.duplicates.is_duplicate(text, Instant::now())
+    }
+
+    /// Queue a message that was spooled in an earlier session.
+    pub fn push_spooled(&mut self, message: SpooledMessage) {
+        self.enqueue(
+            message.text,
+            message.automated,
+            Some(message.id),
+            SendStatus::Offline,
+        );
+    }
+
+    /// Hand a message to the worker, showing it with `status` until the worker reports.
+    fn enqueue(
+        &mut self,
+        text: String,
+        automated: bool,
+        spool_id: Option<i64>,
+        status: SendStatus,
+    ) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let status = match self.commands.send(Command::Send {
+            id,
+            text: text.clone(),
+            automated,
+            spool_id,
+        }) {
+            Ok(()) => status,
+            Err(_) => SendStatus::Failed {
+                attempt: 0,
+                error: "The send queue stopped".to_string(),
+                suggestion: None,
+            },
+        };
+        self.outgoing.push(Outgoing {
+            id,
+            text,
+            automated,
+            status,
+        });
+    }
+
+    /// Try every waiting or failed message again now. Returns whether there were any.
+    pub fn retry_now(&mut self) -> bool {
+        let mut retried = false;
+        for outgoing in &mut self.outgoing {
+            let command = match outgoing.status {
+                SendStatus::Retrying { .. }
+                | SendStatus::Offline
+                | SendStatus::RateLimited { .. } => Command::RetryNow(outgoing.id),
+                SendStatus::Failed { .. } => {
+                    outgoing.status = SendStatus::Queued;
+                    Command::Send {
+                        id: outgoing.id,
+                        text: outgoing.text.clone(),
+                        automated: outgoing.automated,
+                        spool_id: None,
+                    }
+                }
+                _ => continue,
+            };
+            retried |= self.commands.send(command).is_ok();
+        }
+        retried
+    }
+
+    /// Pick up progress from the worker, returning each status change in order. Sent
+    /// messages leave `outgoing`.
+    pub fn poll(&mut self) -> Vec<SendStatus> {
+        let mut changes = Vec::new();
+        while let Ok((id, status)) = self.updates.try_recv() {
+            if status == SendStatus::Sent {
+                self.outgoing.retain(|outgoing| outgoing.id != id);
+            } else if let Some(outgoing) = self.outgoing.iter_mut().find(|o| o.id == id) {
+                outgoing.status = status.clone();
+            }
+            changes.push(status);
+        }
+        changes
+    }
+
+    /// Take an error the worker ran into outside of sending a message, if any.
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.errors.try_recv().ok()
+    }
+
+    /// Messages that haven't been sent yet, oldest first.
+    pub fn outgoing(&self) -> &[Outgoing] {
+        &self.outgoing
+    }
+
+    /// The retry policy messages are sent with.
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+}
+
+/// Send messages spooled in earlier sessions, oldest first, if Messages.app is back. Returns
+/// how many were sent.
+///
+/// Messages that fail for good are dropped from the spool with an error printed; a transient
+/// failure, or an automated message over the rate limit, leaves the rest for next time. Each
+/// message is claimed before it's sent, so ones another `im` is sending are skipped.
+pub fn deliver_spooled(config: &Config, state: &StateStore) -> Result<usize> {
+    let spooled = state.spooled_messages(None)?;
+    if spooled.is_empty() || !messages_available() {
+        return Ok(0);
+    }
+
+    let limit = RateLimit::new(config.sender());
+    let mut delivered = 0;
+    for message in spooled {
+        if state.claim_spooled(message.id)? != SpoolClaim::Claimed {
+            continue;
+        }
+        let slot = if message.automated {
+            match limit.reserve(state)? {
+                RateSlot::Full { .. } => {
+                    state.release_spooled(message.id)?;
+                    break;
+                }
+                slot => Some(slot),
+            }
+        } else {
+            None
+        };
+        let sender =
+            Sender::from_config(config, message.conversation.clone()).with_chat(message.chat);
+        let sent = sender.send_message(&message.text);
+        if let (Some(slot), Err(_)) = (slot, &sent) {
+            limit.release(state, slot)?;
+        }
+        match sent {
+            Ok(()) => delivered += 1,
+            Err(error) if error.is_transient() => {
+                state.release_spooled(message.id)?;
+                break;
+            }
+            Err(error) => eprintln!(
+                "{}",
+                t!(
+                    "cli.spooled_failed",
+                    handle = message.conversation,
+                    error = error
+                )
+            ),
+        }
+        state.unspool_message(message.id)?;
+    }
+    Ok(delivered)
+}
+
+/// The worker thread's side of a `SendQueue`.
+struct Worker {
+    sender: Sender,
+    policy: RetryPolicy,
+    limit: RateLimit,
+    updates: mpsc::Sender<(u64, SendStatus)>,
+    /// Where errors go, since the view may be drawing over the terminal
+    errors: mpsc::Sender<Error>,
+    /// Messages not yet sent, oldest first
+    jobs: Vec<Job>,
+    /// Where offline messages are spooled, opened the first time one is
+    state: Option<StateStore>,
+}
+
+impl Worker {
+    /// Send queued messages in order until the queue is dropped and every message is either
+    /// sent, failed, or spooled.
+    fn run(mut self, commands: &Receiver<Command>) {
+        let mut open = true;
+
+        while open || self.jobs.iter().any(|job| !job.offline) {
+            // Wait for a command, or until the next message is due
+            let wait = self
+                .jobs
+                .first()
+                .map(|job| job.due.saturating_duration_since(Instant::now()));
+            let command = if open {
+                let received = match wait {
+                    Some(wait) => commands.recv_timeout(wait),
+                    None => commands.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match received {
+                    Ok(command) => Some(command),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        open = false;
+                        None
+                    }
+                }
+            } else {
+                thread::sleep(wait.unwrap_or_default());
+                None
+            };
+
+            match command {
+                Some(Command::Send {
+                    id,
+                    text,
+                    automated,
+                    spool_id,
+                }) => {
+                    let mut job = Job {
+                        id,
+                        text,
+                        attempt: 0,
+                        due: Instant::now(),
+                        spool_id,
+                        automated,
+                        offline: spool_id.is_some(),
+                    };
+                    // Nothing gets past a message waiting for Messages.app, so wait with it
+                    if self.jobs.first().is_some_and(|first| first.offline) {
+                        self.take_offline(&mut job);
+                    }
+                    self.jobs.push(job);
+                }
+                Some(Command::RetryNow(id)) => {
+                    if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+                        job.due = Instant::now();
+                    }
+                }
+                None => {}
+            }
+
+            self.send_due();
+        }
+
+        // Let another im send what's left once Messages.app is back
+        let spooled: Vec<i64> = self.jobs.iter().filter_map(|job| job.spool_id).collect();
+        for spool_id in spooled {
+            if let Err(err) = self
+                .state()
+                .and_then(|state| state.release_spooled(spool_id))
+            {
+                self.report(err);
+            }
+        }
+    }
+
+    /// Pass an error on to the view.
+    fn report(&self, error: Error) {
+        let _ = self.errors.send(error);
+    }
+
+    /// Send messages from the front of the queue until one has to wait.
+    fn send_due(&mut self) {
+        while self
+            .jobs
+            .first()
+            .is_some_and(|job| job.due <= Instant::now())
+        {
+            let job = &mut self.jobs[0];
+            if job.offline {
+                if !messages_available() {
+                    job.due = Instant::now() + OFFLINE_CHECK_INTERVAL;
+                    return;
+                }
+                job.offline = false;
+                job.attempt = 0;
+            }
+            if !self.claim_front() {
+                continue;
+            }
+            let slot = if self.jobs[0].automated {
+                match self.reserve_slot() {
+                    Some(slot) => Some(slot),
+                    None => return,
+                }
+            } else {
+                None
+            };
+            let job = &mut self.jobs[0];
+
+            job.attempt += 1;
+            let _ = self.updates.send((
+                job.id,
+                SendStatus::Sending {
+                    attempt: job.attempt,
+                },
+            ));
+
+            let status = match self.sender.send_message(&job.text) {
+                Ok(()) => SendStatus::Sent,
+                Err(error) if self.policy.should_retry(&error, job.attempt) => {
+                    job.due = Instant::now() + self.policy.delay_after(job.attempt);
+                    SendStatus::Retrying {
+                        attempt: job.attempt,
+                        retry_at: job.due,
+                        error: error.to_string(),
+                    }
+                }
+                Err(error) if error.is_transient() => {
+                    // Out of retries: wait for Messages.app, along with everything behind
+                    if let Some(spool_id) = job.spool_id {
+                        self.release(spool_id);
+                    }
+                    if let Some(slot) = slot {
+                        self.release_slot(slot);
+                    }
+                    let mut jobs = std::mem::take(&mut self.jobs);
+                    for job in &mut jobs {
+                        self.take_offline(job);
+                    }
+                    self.jobs = jobs;
+                    return;
+                }
+                Err(error) => SendStatus::Failed {
+                    attempt: job.attempt,
+                    error: error.to_string(),
+                    suggestion: match &error {
+                        Error::Send(error) => error.suggestion(),
+                        _ => None,
+                    },
+                },
+            };
+
+            let id = job.id;
+            if let Some(slot) = slot.filter(|_| status != SendStatus::Sent) {
+                self.release_slot(slot);
+            }
+            if !matches!(status, SendStatus::Retrying { .. }) {
+                let job = self.jobs.remove(0);
+                if let Some(spool_id) = job.spool_id {
+                    self.unspool(spool_id);
+                }
+            }
+            let _ = self.updates.send((id, status));
+        }
+    }
+
+    /// Count the automated message at the front of the queue against the rate limit, or hold
+    /// it back if it's over, returning `None` when it has to wait.
+    fn reserve_slot(&mut self) -> Option<RateSlot> {
+        let limit = self.limit;
+        let send_at = match self.state().and_then(|state| limit.reserve(state)) {
+            Ok(RateSlot::Full { send_at }) => send_at,
+            Ok(slot) => return Some(slot),
+            Err(err) => {
+                // Sending anyway beats holding messages back on a broken state database.
+                // Nothing was counted, so releasing this slot does nothing.
+                self.report(err);
+                return Some(RateSlot::Full { send_at: 0 });
+            }
+        };
+        let wait = (send_at - Local::now().timestamp()).max(1) as u64;
+        let send_at = Instant::now() + Duration::from_secs(wait);
+        let job = &mut self.jobs[0];
+        job.due = send_at;
+        let _ = self
+            .updates
+            .send((job.id, SendStatus::RateLimited { send_at }));
+        None
+    }
+
+    /// Stop counting a slot whose message wasn't sent, or won't be yet.
+    fn release_slot(&mut self, slot: RateSlot) {
+        let limit = self.limit;
+        if let Err(err) = self.state().and_then(|state| limit.release(state, slot)) {
+            self.report(err);
+        }
+    }
+
+    /// Spool a message to disk, if it isn't already, to send once Messages.app is back.
+    fn take_offline(&mut self, job: &mut Job) {
+        if job.spool_id.is_none() {
+            let contact = self.sender.contact().to_string();
+            let chat = self.sender.chat().map(str::to_string);
+            job.spool_id = self
+                .state()
+                .and_then(|state| {
+                    state.spool_message(&contact, chat.as_deref(), &job.text, job.automated)
+                })
+                .map_err(|err| self.report(err))
+                .ok();
+        }
+        job.offline = true;
+        job.due = Instant::now() + OFFLINE_CHECK_INTERVAL;
+        let _ = self.updates.send((job.id, SendStatus::Offline));
+    }
+
+    /// Claim the spooled message at the front of the queue, if it was loaded from the spool,
+    /// before sending it. Returns false if it can't be sent now: another im is sending it, in
+    /// which case it waits, or has sent it, in which case it leaves the queue.
+    fn claim_front(&mut self) -> bool {
+        let Some(spool_id) = self.jobs[0].spool_id else {
+            return true;
+        };
+        let claim = match self.state().and_then(|state| state.claim_spooled(spool_id)) {
+            Ok(claim) => claim,
+            Err(err) => {
+                self.report(err);
+                SpoolClaim::Busy
+            }
+        };
+        match claim {
+            SpoolClaim::Claimed => true,
+            SpoolClaim::Busy => {
+                self.jobs[0].due = Instant::now() + OFFLINE_CHECK_INTERVAL;
+                false
+            }
+            SpoolClaim::Gone => {
+                let job = self.jobs.remove(0);
+                let _ = self.updates.send((job.id, SendStatus::Sent));
+                false
+            }
+        }
+    }
+
+    /// Give up the claim on a spooled message that wasn't sent yet.
+    fn release(&mut self, spool_id: i64) {
+        if let Err(err) = self
+            .state()
+            .and_then(|state| state.release_spooled(spool_id))
+        {
+            self.report(err);
+        }
+    }
+
+    /// Remove a message that was sent or failed for good from the spool.
+    fn unspool(&mut self, spool_id: i64) {
+        if let Err(err) = self
+            .state()
+            .and_then(|state| state.unspool_message(spool_id))
+        {
+            self.report(err);
+        }
+    }
+
+    /// The state store, opening it the first time.
+    fn state(&mut self) -> Result<&StateStore> {
+        if self.state.is_none() {
+            self.state = Some(StateStore::open()?);
+        }
+        Ok(self.state.as_ref().expect("state store was just opened"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::SendError;
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            delay: Duration::from_secs(2),
+        };
+        assert_eq!(policy.delay_after(1), Duration::from_secs(2));
+        assert_eq!(policy.delay_after(3), Duration::from_secs(8));
+        assert_eq!(policy.delay_after(40), MAX_RETRY_DELAY);
+
+        let unavailable = Error::Send(SendError::MessagesUnavailable);
+        assert!(policy.should_retry(&unavailable, 2));
+        assert!(!policy.should_retry(&unavailable, 3));
+        assert!(!policy.should_retry(&Error::Send(SendError::AutomationDenied), 1));
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let limit = RateLimit {
+            per_minute: 2,
+            per_hour: 3,
+        };
+        let now = 10_000;
+        assert_eq!(limit.next_allowed(&[], now), None);
+        assert_eq!(limit.next_allowed(&[now - 30], now), None);
+        // Two in the last minute: the next waits for the older one to be a minute old
+        assert_eq!(
+            limit.next_allowed(&[now - 30, now - 10], now),
+            Some(now + 30)
+        );
+        // Three in the last hour, though only one in the last minute
+        assert_eq!(
+            limit.next_allowed(&[now - 3000, now - 2000, now - 10], now),
+            Some(now + 600)
+        );
+
+        let unlimited = RateLimit {
+            per_minute: 0,
+            per_hour: 0,
+        };
+        assert_eq!(unlimited.next_allowed(&[now; 100], now), None);
+    }
+
+    #[test]
+    fn test_duplicate_guard() {
+        let mut guard = DuplicateGuard::new(&SenderConfig::default());
+        let sent = Instant::now();
+        assert!(!guard.is_duplicate("on my way", sent));
+
+        guard.record("on my way", sent);
+        assert!(guard.is_duplicate("on my way ", sent + Duration::from_secs(1)));
+        assert!(!guard.is_duplicate("on my way!", sent + Duration::from_secs(1)));
+        assert!(!guard.is_duplicate("on my way", sent + Duration::from_secs(5)));
+
+        // A zero window turns the guard off
+        guard.window = Duration::ZERO;
+        assert!(!guard.is_duplicate("on my way", sent));
+    }
+}
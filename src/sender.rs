@@ -1,51 +1,440 @@
-use crate::error::{Error, Result};
+use crate::config::Config;
+use crate::db::{MessageDB, MessageDBPool};
+use crate::error::{Error, Result, SendError};
+use crate::formatter::normalize_identifier;
+use crate::i18n::t;
+use crate::paths;
+use chrono::{DateTime, Local};
+use std::fs::{self, OpenOptions};
 use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether sends go to the sandbox log instead of Messages.app, set by `use_sandbox`.
+static SANDBOX: AtomicBool = AtomicBool::new(false);
+
+/// Separates fields in the account list printed by `LIST_ACCOUNTS_SCRIPT`.
+const FIELD_SEPARATOR: char = '\t';
+
+/// Prints one line per Messages account: id, description, service type, and whether it's enabled.
+const LIST_ACCOUNTS_SCRIPT: &str = r#"
+    set output to ""
+    tell application "Messages"
+        repeat with acct in accounts
+            set output to output & (id of acct) & tab & (description of acct) & tab & ((service type of acct) as text) & tab & ((enabled of acct) as text) & linefeed
+        end repeat
+    end tell
+    return output
+"#;
+
+/// Starts a group chat with the handles after the script's first three arguments, names it if
+/// Messages.app allows, and sends the first message to it.
+const START_GROUP_SCRIPT: &str = r#"
+    on run argv
+        set textBody to item 1 of argv
+        set groupName to item 2 of argv
+        set accountName to item 3 of argv
+        tell application "Messages"
+            if accountName is "" then
+                set targetService to first service whose service type = iMessage
+            else
+                set targetService to first account whose id is accountName or description is accountName
+            end if
+            set members to {}
+            repeat with handle in items 4 thru -1 of argv
+                set end of members to participant (handle as text) of targetService
+            end repeat
+            set newChat to make new text chat with properties {participants:members}
+            if groupName is not "" then
+                -- Some versions of Messages.app only allow naming a group in the app
+                try
+                    set name of newChat to groupName
+                end try
+            end if
+            send textBody to newChat
+        end tell
+    end run
+"#;
+
+/// AppleScript error numbers for Messages.app not running, not responding, or timing out.
+const UNAVAILABLE_ERRORS: &[&str] = &["(-600)", "(-609)", "(-1712)"];
+
+/// An account Messages.app can send from, such as an Apple ID or SMS forwarding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    /// Unique id of the account.
+    pub id: String,
+    /// The account's name, usually the Apple ID email or "SMS".
+    pub description: String,
+    /// "iMessage", "SMS", or another service type.
+    pub service_type: String,
+    /// Whether the account is turned on in Messages settings.
+    pub enabled: bool,
+}
+
+impl Account {
+    /// Whether `name` from the configuration refers to this account.
+    pub fn matches(&self, name: &str) -> bool {
+        self.id == name || self.description.eq_ignore_ascii_case(name)
+    }
+}
+
+/// Write every message to the sandbox log instead of sending it, for the rest of the process.
+pub fn use_sandbox() {
+    SANDBOX.store(true, Ordering::Relaxed);
+}
+
+/// Whether messages are written to the sandbox log rather than sent.
+pub fn is_sandbox() -> bool {
+    SANDBOX.load(Ordering::Relaxed)
+}
+
+/// Append a message that would have been sent to `recipient` to the sandbox log.
+fn write_sandboxed(recipient: &str, text: &str) -> Result<()> {
+    let path = paths::sandbox_log().ok_or_else(|| Error::Generic(t!("paths.no_state_dir")))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    file.write_all(sandbox_entry(Local::now(), recipient, text).as_bytes())?;
+    Ok(())
+}
+
+/// Format a sandboxed message as a log entry, indenting the lines after its first.
+fn sandbox_entry(time: DateTime<Local>, recipient: &str, text: &str) -> String {
+    format!(
+        "{} to {}: {}\n",
+        time.format("%Y-%m-%d %H:%M:%S"),
+        recipient,
+        text.replace('\n', "\n    ")
+    )
+}
+
+/// Whether Messages.app is running and answering scripts, so messages can be sent.
+pub fn messages_available() -> bool {
+    if is_sandbox() {
+        return true;
+    }
+    matches!(
+        run_script(r#"return application "Messages" is running"#, &[]),
+        Ok(Ok(output)) if output.trim() == "true"
+    )
+}
+
+/// List the accounts signed in to Messages.app.
+pub fn list_accounts() -> Result<Vec<Account>> {
+    let output = run_script(LIST_ACCOUNTS_SCRIPT, &[])?
+        .map_err(|error| translate_error(&error, "", None))?;
+    Ok(parse_accounts(&output))
+}
+
+/// Start a group chat with `participants`, named `name` unless it's empty, by sending it its
+/// first message.
+pub fn start_group(config: &Config, participants: &[String], name: &str, text: &str) -> Result<()> {
+    if is_sandbox() {
+        let recipient = if name.is_empty() {
+            participants.join(", ")
+        } else {
+            format!("{} ({})", name, participants.join(", "))
+        };
+        return write_sandboxed(&recipient, text);
+    }
+    let account = config.sender().account.as_deref();
+    let mut args = vec![text, name, account.unwrap_or("")];
+    args.extend(participants.iter().map(String::as_str));
+    run_script(START_GROUP_SCRIPT, &args)?
+        .map_err(|error| translate_error(&error, &participants.join(", "), account))?;
+    Ok(())
+}
+
+/// Find the user's own phone number or email, which notes to self are sent to: `sender.me`
+/// if it's set, otherwise the address most chats were sent to.
+pub fn own_handle(config: &Config, db: &MessageDB) -> Result<Option<String>> {
+    if let Some(me) = &config.sender().me {
+        return Ok(Some(normalize_identifier(me)));
+    }
+    Ok(db
+        .get_own_handle()?
+        .map(|handle| normalize_identifier(&handle)))
+}
+
+/// Parse the output of `LIST_ACCOUNTS_SCRIPT`.
+fn parse_accounts(output: &str) -> Vec<Account> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(FIELD_SEPARATOR);
+            Some(Account {
+                id: fields.next()?.to_string(),
+                description: fields.next()?.to_string(),
+                service_type: fields.next()?.to_string(),
+                enabled: fields.next()? == "true",
+            })
+        })
+        .collect()
+}
+
+/// Turn what osascript printed when a script failed into a typed error.
+///
+/// `recipient` and `account` are who the message was for and the account it was sent from.
+fn translate_error(stderr: &str, recipient: &str, account: Option<&str>) -> SendError {
+    // Messages.app uses typographic apostrophes ("Can’t get buddy")
+    let error = stderr.replace('’', "'");
+    if error.contains("(-1743)") || error.contains("Not authorized to send Apple events") {
+        SendError::AutomationDenied
+    } else if UNAVAILABLE_ERRORS.iter().any(|code| error.contains(code))
+        || error.contains("isn't running")
+    {
+        SendError::MessagesUnavailable
+    } else if error.contains("Can't get account") || error.contains("Can't get service") {
+        SendError::AccountNotFound(account.unwrap_or("iMessage").to_string())
+    } else if error.contains("Can't get buddy")
+        || error.contains("Can't get participant")
+        || error.contains("Can't get chat")
+        || error.contains("(-1728)")
+    {
+        SendError::NotReachable(recipient.to_string())
+    } else {
+        SendError::Other(stderr.trim().to_string())
+    }
+}
+
+/// Run an AppleScript with arguments, returning its output, or its error text if it failed.
+pub fn run_script(script: &str, args: &[&str]) -> Result<std::result::Result<String, String>> {
+    let mut child = std::process::Command::new("osascript")
+        .arg("-")
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+
+    // Write the script to stdin
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(script.as_bytes())?;
+    }
+
+    // Wait for the process to complete and check its output
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Ok(Err(String::from_utf8_lossy(&output.stderr).into_owned()));
+    }
+
+    Ok(Ok(String::from_utf8_lossy(&output.stdout).into_owned()))
+}
 
 pub struct Sender {
     contact: String,
+    /// Id or description of the account to send from, or `None` for the first iMessage account.
+    account: Option<String>,
+    /// Own phone number or email to send from, when the Apple ID has more than one.
+    send_as: Option<String>,
+    /// Guid of a group chat to send to instead of the contact alone.
+    chat: Option<String>,
 }
 
 impl Sender {
     pub fn new(contact: String) -> Self {
-        Self { contact }
+        Self {
+            contact,
+            account: None,
+            send_as: None,
+            chat: None,
+        }
+    }
+
+    /// Create a sender for a contact with the account and address the configuration chooses.
+    pub fn from_config(config: &Config, contact: String) -> Self {
+        let account = config.account_for(&contact);
+        let send_as = config.send_as_for(&contact);
+        Self::new(contact)
+            .with_account(account)
+            .with_send_as(send_as)
+    }
+
+    /// The handle messages are sent to.
+    pub fn contact(&self) -> &str {
+        &self.contact
+    }
+
+    /// Send from a specific account (see `im accounts list`) instead of the first iMessage one.
+    pub fn with_account(mut self, account: Option<String>) -> Self {
+        self.account = account;
+        self
+    }
+
+    /// Send from one of the Apple ID's phone numbers or emails instead of the default one.
+    pub fn with_send_as(mut self, send_as: Option<String>) -> Self {
+        self.send_as = send_as;
+        self
+    }
+
+    /// Send to the group chat with guid `chat`, such as one a message being replied to was
+    /// sent in, rather than to the contact alone.
+    pub fn with_chat(mut self, chat: Option<String>) -> Self {
+        self.chat = chat;
+        self
+    }
+
+    /// The group chat messages are sent to, if any.
+    pub fn chat(&self) -> Option<&str> {
+        self.chat.as_deref()
+    }
+
+    /// Find the existing chat with the contact that was started from `send_as`, if it's set.
+    ///
+    /// Messages.app doesn't let scripts pick the address a new conversation is sent from, but
+    /// replies in an existing chat keep its address. Without such a chat the message would go
+    /// out from the wrong address, so that's an error.
+    fn send_as_chat(&self) -> Result<Option<String>> {
+        let Some(send_as) = &self.send_as else {
+            return Ok(None);
+        };
+        let alias = normalize_identifier(send_as);
+        let db = MessageDBPool::shared().get()?;
+        match db.get_chat_from_alias(&self.contact, &alias)? {
+            Some(chat) => Ok(Some(chat)),
+            None => Err(SendError::NoChatFrom(self.contact.clone(), send_as.clone()).into()),
+        }
     }
 
     pub fn send_message(&self, text: &str) -> Result<()> {
+        if is_sandbox() {
+            return write_sandboxed(self.recipient(), text);
+        }
+        self.send("textBody", text)
+    }
+
+    /// Send a file, such as an image, as an attachment.
+    pub fn send_file(&self, path: &Path) -> Result<()> {
+        let path = path
+            .to_str()
+            .ok_or_else(|| Error::Generic(t!("sender.cant_send_file", path = path.display())))?;
+        if is_sandbox() {
+            return write_sandboxed(self.recipient(), &format!("[attachment] {}", path));
+        }
+        self.send("POSIX file textBody", path)
+    }
+
+    /// Who messages go to: the group chat, if there is one, or else the contact.
+    fn recipient(&self) -> &str {
+        self.chat.as_deref().unwrap_or(&self.contact)
+    }
+
+    /// Send `payload`, an AppleScript expression of the script's `textBody` argument.
+    fn send(&self, payload: &str, text: &str) -> Result<()> {
         // Create the AppleScript command
         let script = format!(
             r#"
-            on run {{textBody}}
+            on run {{textBody, accountName, chatId, handle}}
                 tell application "Messages"
-                    set targetService to first service whose service type = iMessage
-                    set targetBuddy to buddy "{}" of targetService
-                    send textBody to targetBuddy
+                    set payload to {}
+                    if chatId is not "" then
+                        send payload to chat id chatId
+                        return
+                    end if
+                    if accountName is "" then
+                        set targetService to first service whose service type = iMessage
+                    else
+                        set targetService to first account whose id is accountName or description is accountName
+                    end if
+                    if exists buddy handle of targetService then
+                        send payload to buddy handle of targetService
+                    else
+                        -- Handles never messaged before have no buddy yet, but a chat with
+                        -- them as its participant can be made
+                        set newChat to make new text chat with properties {{participants:{{participant handle of targetService}}}}
+                        send payload to newChat
+                    end if
                 end tell
             end run
             "#,
-            self.contact
+            payload
         );
 
         // Execute the AppleScript
-        let mut child = std::process::Command::new("osascript")
-            .arg("-")
-            .arg(text)
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()?;
-
-        // Write the script to stdin
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(script.as_bytes())?;
-        }
-
-        // Wait for the process to complete and check its output
-        let output = child.wait_with_output()?;
-        if !output.status.success() {
-            let error = String::from_utf8_lossy(&output.stderr);
-            return Err(Error::Generic(format!("Failed to send message: {}", error)));
-        }
+        let chat = match &self.chat {
+            Some(chat) => chat.clone(),
+            None => self.send_as_chat()?.unwrap_or_default(),
+        };
+        run_script(
+            &script,
+            &[
+                text,
+                self.account.as_deref().unwrap_or(""),
+                &chat,
+                &self.contact,
+            ],
+        )?
+        .map_err(|error| translate_error(&error, self.recipient(), self.account.as_deref()))?;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_error() {
+        let translate = |stderr| translate_error(stderr, "+15551234567", Some("me@work.com"));
+        assert_eq!(
+            translate("execution error: Messages got an error: Can’t get buddy id \"+15551234567\" of service 1. (-1728)"),
+            SendError::NotReachable("+15551234567".to_string())
+        );
+        assert_eq!(
+            translate("execution error: Not authorized to send Apple events to Messages. (-1743)"),
+            SendError::AutomationDenied
+        );
+        assert_eq!(
+            translate("execution error: Messages got an error: Application isn’t running. (-600)"),
+            SendError::MessagesUnavailable
+        );
+        assert_eq!(
+            translate("execution error: Messages got an error: Can’t get account 1 whose id = \"me@work.com\". (-1728)"),
+            SendError::AccountNotFound("me@work.com".to_string())
+        );
+        assert_eq!(
+            translate("  syntax error: Expected end of line. (-2741)\n"),
+            SendError::Other("syntax error: Expected end of line. (-2741)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_sandbox_entry() {
+        use chrono::TimeZone;
+        let time = Local.with_ymd_and_hms(2024, 3, 9, 14, 5, 0).unwrap();
+        assert_eq!(
+            sandbox_entry(time, "+15551234567", "running late\nsorry!"),
+            "2024-03-09 14:05:00 to +15551234567: running late\n    sorry!\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_accounts() {
+        let accounts = parse_accounts(
+            "E1F2-A3\tme@icloud.com\tiMessage\ttrue\nB4C5-D6\tSMS\tSMS\tfalse\nmalformed\n",
+        );
+        assert_eq!(
+            accounts,
+            vec![
+                Account {
+                    id: "E1F2-A3".to_string(),
+                    description: "me@icloud.com".to_string(),
+                    service_type: "iMessage".to_string(),
+                    enabled: true,
+                },
+                Account {
+                    id: "B4C5-D6".to_string(),
+                    description: "SMS".to_string(),
+                    service_type: "SMS".to_string(),
+                    enabled: false,
+                },
+            ]
+        );
+        assert!(accounts[0].matches("Me@iCloud.com"));
+        assert!(accounts[1].matches("B4C5-D6"));
+        assert!(!accounts[1].matches("me@icloud.com"));
+    }
+}
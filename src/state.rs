@@ -0,0 +1,1464 @@
+use crate::config::{ContactEntry, OpenAt, RemovedContact};
+use crate::db::{message_from_row, Message};
+use crate::error::{Error, Result};
+use crate::i18n::t;
+use crate::paths;
+use crate::timestamps::TimestampStyle;
+use chrono::{DateTime, Duration, Local, TimeZone};
+use rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How long a removed contact can be restored for (hours).
+const UNDO_HOURS: i64 = 24;
+
+/// Most removed contacts kept for undo.
+const UNDO_LIMIT: i64 = 10;
+
+/// Settings key holding the last session, as JSON.
+const SESSION_KEY: &str = "session";
+
+/// Seconds after which a claim on a spooled message is taken to belong to an `im` that
+/// stopped before finishing the send, so another may send it.
+const CLAIM_TIMEOUT_SECS: i64 = 5 * 60;
+
+/// Schema for the state database.
+const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS seen (
+        conversation TEXT PRIMARY KEY,
+        last_seen_rowid INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS pins (
+        conversation TEXT PRIMARY KEY,
+        position INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS drafts (
+        conversation TEXT PRIMARY KEY,
+        text TEXT NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS snoozes (
+        conversation TEXT PRIMARY KEY,
+        until INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS archived (
+        conversation TEXT PRIMARY KEY,
+        archived_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS stars (
+        guid TEXT PRIMARY KEY,
+        conversation TEXT NOT NULL,
+        rowid INTEGER NOT NULL,
+        text TEXT,
+        date INTEGER NOT NULL,
+        is_from_me INTEGER NOT NULL,
+        starred_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS pinned_messages (
+        guid TEXT PRIMARY KEY,
+        conversation TEXT NOT NULL,
+        rowid INTEGER NOT NULL,
+        text TEXT,
+        date INTEGER NOT NULL,
+        is_from_me INTEGER NOT NULL,
+        pinned_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS tags (
+        guid TEXT NOT NULL,
+        tag TEXT NOT NULL,
+        conversation TEXT NOT NULL,
+        rowid INTEGER NOT NULL,
+        text TEXT,
+        date INTEGER NOT NULL,
+        is_from_me INTEGER NOT NULL,
+        tagged_at INTEGER NOT NULL,
+        PRIMARY KEY (guid, tag)
+    );
+    CREATE INDEX IF NOT EXISTS tags_tag ON tags (tag);
+    CREATE INDEX IF NOT EXISTS tags_conversation ON tags (conversation);
+    CREATE TABLE IF NOT EXISTS message_cache (
+        conversation TEXT NOT NULL,
+        rowid INTEGER NOT NULL,
+        guid TEXT NOT NULL,
+        text TEXT,
+        date INTEGER NOT NULL,
+        message_type TEXT,
+        is_from_me INTEGER NOT NULL,
+        PRIMARY KEY (conversation, rowid)
+    );
+    CREATE TABLE IF NOT EXISTS spooled_messages (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        conversation TEXT NOT NULL,
+        text TEXT NOT NULL,
+        queued_at INTEGER NOT NULL,
+        automated INTEGER NOT NULL DEFAULT 0,
+        chat TEXT,
+        claimed_by INTEGER,
+        claimed_at INTEGER
+    );
+    CREATE TABLE IF NOT EXISTS automated_sends (
+        sent_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS translations (
+        guid TEXT PRIMARY KEY,
+        text TEXT NOT NULL,
+        translated_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS exports (
+        conversation TEXT NOT NULL,
+        path TEXT NOT NULL,
+        last_rowid INTEGER NOT NULL,
+        exported_at INTEGER NOT NULL,
+        PRIMARY KEY (conversation, path)
+    );
+    CREATE TABLE IF NOT EXISTS transcribed (
+        guid TEXT PRIMARY KEY,
+        conversation TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS transcribed_conversation ON transcribed (conversation);
+    CREATE TABLE IF NOT EXISTS removed_contacts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        identifier TEXT NOT NULL,
+        display_name TEXT,
+        other_identifiers TEXT NOT NULL DEFAULT '',
+        aliases TEXT NOT NULL,
+        removed_at INTEGER NOT NULL,
+        account TEXT,
+        send_as TEXT,
+        quick_replies TEXT NOT NULL DEFAULT '',
+        translate INTEGER NOT NULL DEFAULT 0,
+        initials TEXT,
+        color TEXT,
+        birthday TEXT,
+        careful INTEGER NOT NULL DEFAULT 0,
+        open_at TEXT
+    );
+"#;
+
+/// Columns added to existing tables after they were first created, as (table, column, type).
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("message_cache", "event", "TEXT"),
+    (
+        "spooled_messages",
+        "automated",
+        "INTEGER NOT NULL DEFAULT 0",
+    ),
+    ("spooled_messages", "chat", "TEXT"),
+    ("spooled_messages", "claimed_by", "INTEGER"),
+    ("spooled_messages", "claimed_at", "INTEGER"),
+    (
+        "removed_contacts",
+        "other_identifiers",
+        "TEXT NOT NULL DEFAULT ''",
+    ),
+    ("removed_contacts", "account", "TEXT"),
+    ("removed_contacts", "send_as", "TEXT"),
+    (
+        "removed_contacts",
+        "quick_replies",
+        "TEXT NOT NULL DEFAULT ''",
+    ),
+    (
+        "removed_contacts",
+        "translate",
+        "INTEGER NOT NULL DEFAULT 0",
+    ),
+    ("removed_contacts", "initials", "TEXT"),
+    ("removed_contacts", "color", "TEXT"),
+    ("removed_contacts", "birthday", "TEXT"),
+    ("removed_contacts", "careful", "INTEGER NOT NULL DEFAULT 0"),
+    ("removed_contacts", "open_at", "TEXT"),
+];
+
+/// A message the user starred or pinned, with enough context to list it without chat.db.
+#[derive(Debug, Clone)]
+pub struct StarredMessage {
+    /// The message guid in chat.db.
+    pub guid: String,
+    /// The conversation the message belongs to.
+    pub conversation: String,
+    /// ROWID of the message in chat.db.
+    pub rowid: i64,
+    /// The message text (or placeholder) when it was starred.
+    pub text: Option<String>,
+    /// When the message was sent or received.
+    pub date: DateTime<Local>,
+    /// Whether the message was sent by the local user.
+    pub is_from_me: bool,
+}
+
+/// Where the user left off in the chat view, restored the next time `im` runs without arguments.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// The handle of the open conversation.
+    pub conversation: String,
+    pub display_name: String,
+    /// Guid of the message at the top of the screen, or `None` when following the latest
+    /// messages.
+    pub top_message: Option<String>,
+    /// Whether the pin board was collapsed.
+    pub pins_collapsed: bool,
+    /// Whether times were relative or absolute.
+    pub time_style: TimestampStyle,
+}
+
+/// A message waiting on disk for Messages.app to become available.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpooledMessage {
+    pub id: i64,
+    /// The handle the message is for.
+    pub conversation: String,
+    pub text: String,
+    /// Whether a script sent the message rather than the user, so it's rate limited.
+    pub automated: bool,
+    /// Guid of the group chat the message is for, when it replies to one.
+    pub chat: Option<String>,
+}
+
+/// Whether a spooled message could be claimed for sending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpoolClaim {
+    /// This process may send it.
+    Claimed,
+    /// Another `im` is sending it.
+    Busy,
+    /// It was already sent, or given up on.
+    Gone,
+}
+
+/// Whether an automated message could be counted against the rate limit before sending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateSlot {
+    /// It was counted, as this row; release it if the message isn't sent.
+    Reserved(i64),
+    /// The limit is reached until `send_at` (Unix seconds).
+    Full { send_at: i64 },
+}
+
+/// App-owned state that can't be stored in chat.db, which is managed by Messages.app.
+pub struct StateStore {
+    conn: Connection,
+}
+
+impl StateStore {
+    /// Open the state database, creating it if needed.
+    pub fn open() -> Result<Self> {
+        let path = Self::path().ok_or_else(|| Error::Generic(t!("paths.no_data_dir")))?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Open a state database that lives only as long as the store, for views that shouldn't
+    /// leave anything behind.
+    pub fn in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    /// Get the path to the state database.
+    pub fn path() -> Option<PathBuf> {
+        paths::state_db()
+    }
+
+    /// Initialize the schema on an open connection.
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        for (table, column, kind) in ADDED_COLUMNS {
+            let exists: bool = conn.query_row(
+                &format!(
+                    "SELECT COUNT(*) > 0 FROM pragma_table_info('{}') WHERE name = ?",
+                    table
+                ),
+                params![column],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                conn.execute_batch(&format!(
+                    "ALTER TABLE {} ADD COLUMN {} {}",
+                    table, column, kind
+                ))?;
+            }
+        }
+        Ok(Self { conn })
+    }
+
+    /// Get an app setting.
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT value FROM settings WHERE key = ?",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Set an app setting.
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    /// Get the last session, if one was saved and is still readable.
+    pub fn session(&self) -> Result<Option<Session>> {
+        Ok(self
+            .get_setting(SESSION_KEY)?
+            .and_then(|json| serde_json::from_str(&json).ok()))
+    }
+
+    /// Remember where the user left off.
+    pub fn save_session(&self, session: &Session) -> Result<()> {
+        let json = serde_json::to_string(session).map_err(std::io::Error::from)?;
+        self.set_setting(SESSION_KEY, &json)
+    }
+
+    /// Get the ROWID of the last message seen in a conversation.
+    pub fn last_seen(&self, conversation: &str) -> Result<Option<i64>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT last_seen_rowid FROM seen WHERE conversation = ?",
+                params![conversation],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Get the seen watermarks for all conversations.
+    pub fn seen_watermarks(&self) -> Result<HashMap<String, i64>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT conversation, last_seen_rowid FROM seen")?;
+        let watermarks = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(watermarks)
+    }
+
+    /// Mark a conversation as seen up to a message ROWID.
+    ///
+    /// The watermark never moves backwards.
+    pub fn mark_seen(&self, conversation: &str, rowid: i64) -> Result<()> {
+        self.conn.execute(
+            r#"
+            INSERT INTO seen (conversation, last_seen_rowid) VALUES (?1, ?2)
+            ON CONFLICT(conversation) DO UPDATE
+                SET last_seen_rowid = MAX(last_seen_rowid, excluded.last_seen_rowid)
+            "#,
+            params![conversation, rowid],
+        )?;
+        Ok(())
+    }
+
+    /// Get the ROWID of the last message of a conversation exported to an archive.
+    pub fn last_exported(&self, conversation: &str, path: &Path) -> Result<Option<i64>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT last_rowid FROM exports WHERE conversation = ?1 AND path = ?2",
+                params![conversation, path.to_string_lossy()],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Remember the last message of a conversation exported to an archive.
+    pub fn mark_exported(&self, conversation: &str, path: &Path, rowid: i64) -> Result<()> {
+        self.conn.execute(
+            r#"
+            INSERT OR REPLACE INTO exports (conversation, path, last_rowid, exported_at)
+            VALUES (?1, ?2, ?3, ?4)
+            "#,
+            params![
+                conversation,
+                path.to_string_lossy(),
+                rowid,
+                Local::now().timestamp()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get the guids of the messages of a conversation already written to its transcript.
+    pub fn transcribed(&self, conversation: &str) -> Result<HashSet<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT guid FROM transcribed WHERE conversation = ?")?;
+        let guids = stmt
+            .query_map(params![conversation], |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<String>>>()?;
+        Ok(guids)
+    }
+
+    /// Remember that messages of a conversation were written to its transcript.
+    pub fn mark_transcribed(&self, conversation: &str, guids: &[&str]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO transcribed (guid, conversation) VALUES (?1, ?2)",
+            )?;
+            for guid in guids {
+                stmt.execute(params![guid, conversation])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Get pinned conversations in pin order.
+    pub fn pinned(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT conversation FROM pins ORDER BY position")?;
+        let pinned = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(pinned)
+    }
+
+    /// Pin a conversation after any already pinned ones.
+    pub fn pin(&self, conversation: &str) -> Result<()> {
+        self.conn.execute(
+            r#"
+            INSERT OR IGNORE INTO pins (conversation, position)
+            SELECT ?1, COALESCE(MAX(position), -1) + 1 FROM pins
+            "#,
+            params![conversation],
+        )?;
+        Ok(())
+    }
+
+    /// Unpin a conversation.
+    pub fn unpin(&self, conversation: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM pins WHERE conversation = ?",
+            params![conversation],
+        )?;
+        Ok(())
+    }
+
+    /// Get the unsent draft for a conversation.
+    pub fn draft(&self, conversation: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT text FROM drafts WHERE conversation = ?",
+                params![conversation],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Save the unsent draft for a conversation. An empty draft clears it.
+    pub fn save_draft(&self, conversation: &str, text: &str) -> Result<()> {
+        if text.is_empty() {
+            self.conn.execute(
+                "DELETE FROM drafts WHERE conversation = ?",
+                params![conversation],
+            )?;
+        } else {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO drafts (conversation, text, updated_at) VALUES (?1, ?2, ?3)",
+                params![conversation, text, Local::now().timestamp()],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Snooze a conversation until a given time.
+    pub fn snooze(&self, conversation: &str, until: DateTime<Local>) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO snoozes (conversation, until) VALUES (?1, ?2)",
+            params![conversation, until.timestamp()],
+        )?;
+        Ok(())
+    }
+
+    /// Get conversations that are still snoozed, clearing expired snoozes.
+    pub fn active_snoozes(&self) -> Result<HashMap<String, DateTime<Local>>> {
+        let now = Local::now().timestamp();
+        self.conn
+            .execute("DELETE FROM snoozes WHERE until <= ?", params![now])?;
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT conversation, until FROM snoozes")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+
+        let mut snoozes = HashMap::new();
+        for row in rows {
+            let (conversation, until) = row?;
+            if let chrono::LocalResult::Single(until) = Local.timestamp_opt(until, 0) {
+                snoozes.insert(conversation, until);
+            }
+        }
+        Ok(snoozes)
+    }
+
+    /// Archive a conversation, hiding it from the main inbox.
+    pub fn archive(&self, conversation: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO archived (conversation, archived_at) VALUES (?1, ?2)",
+            params![conversation, Local::now().timestamp()],
+        )?;
+        Ok(())
+    }
+
+    /// Return an archived conversation to the main inbox.
+    pub fn unarchive(&self, conversation: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM archived WHERE conversation = ?",
+            params![conversation],
+        )?;
+        Ok(())
+    }
+
+    /// Get all archived conversations.
+    pub fn archived(&self) -> Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare("SELECT conversation FROM archived")?;
+        let archived = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(archived)
+    }
+
+    /// Star a message.
+    pub fn star(&self, message: &StarredMessage) -> Result<()> {
+        self.conn.execute(
+            r#"
+            INSERT OR REPLACE INTO stars (guid, conversation, rowid, text, date, is_from_me, starred_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            "#,
+            params![
+                message.guid,
+                message.conversation,
+                message.rowid,
+                message.text,
+                message.date.timestamp(),
+                message.is_from_me,
+                Local::now().timestamp()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Remove the star from a message.
+    pub fn unstar(&self, guid: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM stars WHERE guid = ?", params![guid])?;
+        Ok(())
+    }
+
+    /// Get the guids of starred messages in a conversation.
+    pub fn starred_guids(&self, conversation: &str) -> Result<HashSet<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT guid FROM stars WHERE conversation = ?")?;
+        let guids = stmt
+            .query_map(params![conversation], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(guids)
+    }
+
+    /// Get all starred messages across conversations, newest first.
+    pub fn starred(&self) -> Result<Vec<StarredMessage>> {
+        self.saved_messages(
+            "SELECT guid, conversation, rowid, text, date, is_from_me FROM stars ORDER BY date DESC",
+            [],
+        )
+    }
+
+    /// Pin a message to the top of its conversation.
+    pub fn pin_message(&self, message: &StarredMessage) -> Result<()> {
+        self.conn.execute(
+            r#"
+            INSERT OR REPLACE INTO pinned_messages (guid, conversation, rowid, text, date, is_from_me, pinned_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            "#,
+            params![
+                message.guid,
+                message.conversation,
+                message.rowid,
+                message.text,
+                message.date.timestamp(),
+                message.is_from_me,
+                Local::now().timestamp()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Unpin a message.
+    pub fn unpin_message(&self, guid: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM pinned_messages WHERE guid = ?", params![guid])?;
+        Ok(())
+    }
+
+    /// Get the pinned messages in a conversation, oldest first.
+    pub fn pinned_messages(&self, conversation: &str) -> Result<Vec<StarredMessage>> {
+        self.saved_messages(
+            r#"
+            SELECT guid, conversation, rowid, text, date, is_from_me
+            FROM pinned_messages
+            WHERE conversation = ?
+            ORDER BY date ASC
+            "#,
+            params![conversation],
+        )
+    }
+
+    /// Replace a message's tags, removing them all if `tags` is empty.
+    pub fn set_tags(&self, message: &StarredMessage, tags: &[String]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM tags WHERE guid = ?", params![message.guid])?;
+        {
+            let mut stmt = tx.prepare(
+                r#"
+                INSERT INTO tags (guid, tag, conversation, rowid, text, date, is_from_me, tagged_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                "#,
+            )?;
+            for tag in tags {
+                stmt.execute(params![
+                    message.guid,
+                    tag,
+                    message.conversation,
+                    message.rowid,
+                    message.text,
+                    message.date.timestamp(),
+                    message.is_from_me,
+                    Local::now().timestamp()
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Get the tags of the messages in a conversation, by guid, in alphabetical order.
+    pub fn message_tags(&self, conversation: &str) -> Result<HashMap<String, Vec<String>>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT guid, tag FROM tags WHERE conversation = ? ORDER BY tag")?;
+        let rows = stmt.query_map(params![conversation], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+        for row in rows {
+            let (guid, tag) = row?;
+            tags.entry(guid).or_default().push(tag);
+        }
+        Ok(tags)
+    }
+
+    /// Get every tag in use and how many messages have it, in alphabetical order.
+    pub fn tags(&self) -> Result<Vec<(String, usize)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT tag, COUNT(*) FROM tags GROUP BY tag ORDER BY tag")?;
+        let tags = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(tags)
+    }
+
+    /// Get the messages with a tag across conversations, newest first.
+    pub fn tagged(&self, tag: &str) -> Result<Vec<StarredMessage>> {
+        self.saved_messages(
+            r#"
+            SELECT guid, conversation, rowid, text, date, is_from_me
+            FROM tags
+            WHERE tag = ?
+            ORDER BY date DESC
+            "#,
+            params![tag],
+        )
+    }
+
+    /// Remove one tag from a message.
+    pub fn untag(&self, guid: &str, tag: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM tags WHERE guid = ? AND tag = ?",
+            params![guid, tag],
+        )?;
+        Ok(())
+    }
+
+    /// Run a query selecting starred or pinned message columns and collect the messages.
+    fn saved_messages(
+        &self,
+        query: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<StarredMessage>> {
+        let mut stmt = self.conn.prepare(query)?;
+        let rows = stmt.query_map(params, |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, bool>(5)?,
+            ))
+        })?;
+
+        let mut messages = Vec::new();
+        for row in rows {
+            let (guid, conversation, rowid, text, date, is_from_me) = row?;
+            if let chrono::LocalResult::Single(date) = Local.timestamp_opt(date, 0) {
+                messages.push(StarredMessage {
+                    guid,
+                    conversation,
+                    rowid,
+                    text,
+                    date,
+                    is_from_me,
+                });
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Get the cached translation of a message.
+    pub fn translation(&self, guid: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT text FROM translations WHERE guid = ?",
+                params![guid],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Cache the translation of a message.
+    pub fn save_translation(&self, guid: &str, text: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO translations (guid, text, translated_at) VALUES (?1, ?2, ?3)",
+            params![guid, text, Local::now().timestamp()],
+        )?;
+        Ok(())
+    }
+
+    /// Keep a message that couldn't be sent until Messages.app is back, or until the rate
+    /// limit allows if it's `automated`, returning its id. Messages for a group chat name it
+    /// in `chat`.
+    pub fn spool_message(
+        &self,
+        conversation: &str,
+        chat: Option<&str>,
+        text: &str,
+        automated: bool,
+    ) -> Result<i64> {
+        self.conn.execute(
+            r#"
+            INSERT INTO spooled_messages (conversation, chat, text, queued_at, automated)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            "#,
+            params![
+                conversation,
+                chat,
+                text,
+                Local::now().timestamp(),
+                automated
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Claim a spooled message for this process to send, so two `im`s running at once don't
+    /// both send it. Release the claim with `release_spooled` if the send doesn't happen.
+    pub fn claim_spooled(&self, id: i64) -> Result<SpoolClaim> {
+        self.claim_spooled_as(id, std::process::id(), Local::now().timestamp())
+    }
+
+    /// Claim a spooled message for the process `owner` at `now` (Unix seconds).
+    fn claim_spooled_as(&self, id: i64, owner: u32, now: i64) -> Result<SpoolClaim> {
+        // Immediate, so no other process can read the claim between the check and the update
+        let tx = Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate)?;
+        let claim: Option<(Option<u32>, Option<i64>)> = tx
+            .query_row(
+                "SELECT claimed_by, claimed_at FROM spooled_messages WHERE id = ?",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        let result = match claim {
+            None => SpoolClaim::Gone,
+            Some((Some(other), Some(at))) if other != owner && at > now - CLAIM_TIMEOUT_SECS => {
+                SpoolClaim::Busy
+            }
+            Some(_) => {
+                tx.execute(
+                    "UPDATE spooled_messages SET claimed_by = ?2, claimed_at = ?3 WHERE id = ?1",
+                    params![id, owner, now],
+                )?;
+                SpoolClaim::Claimed
+            }
+        };
+        tx.commit()?;
+        Ok(result)
+    }
+
+    /// Give up a claim on a spooled message that couldn't be sent yet, so any `im` may try it.
+    pub fn release_spooled(&self, id: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE spooled_messages SET claimed_by = NULL, claimed_at = NULL WHERE id = ?",
+            params![id],
+        )?;
+        Ok(())
+    }
+
+    /// Forget a spooled message, once it has been sent or given up on.
+    pub fn unspool_message(&self, id: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM spooled_messages WHERE id = ?", params![id])?;
+        Ok(())
+    }
+
+    /// Get spooled messages, oldest first, for one conversation or all of them.
+    pub fn spooled_messages(&self, conversation: Option<&str>) -> Result<Vec<SpooledMessage>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT id, conversation, text, automated, chat
+            FROM spooled_messages
+            WHERE ?1 IS NULL OR conversation = ?1
+            ORDER BY id ASC
+            "#,
+        )?;
+        let messages = stmt
+            .query_map(params![conversation], |row| {
+                Ok(SpooledMessage {
+                    id: row.get(0)?,
+                    conversation: row.get(1)?,
+                    text: row.get(2)?,
+                    automated: row.get(3)?,
+                    chat: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(messages)
+    }
+
+    /// Count an automated message about to be sent at `now` (Unix seconds), if
+    /// `next_allowed`, given when the ones since `forget_before` were sent oldest first, says
+    /// it may go now. Older sends are forgotten.
+    ///
+    /// Checking and counting happen in one transaction, so two `im`s can't both take the
+    /// last slot under the limit.
+    pub fn reserve_automated_send(
+        &self,
+        now: i64,
+        forget_before: i64,
+        next_allowed: impl FnOnce(&[i64]) -> Option<i64>,
+    ) -> Result<RateSlot> {
+        let tx = Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate)?;
+        tx.execute(
+            "DELETE FROM automated_sends WHERE sent_at < ?",
+            params![forget_before],
+        )?;
+        let sends = tx
+            .prepare("SELECT sent_at FROM automated_sends ORDER BY sent_at ASC")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<i64>>>()?;
+        let slot = match next_allowed(&sends) {
+            Some(send_at) => RateSlot::Full { send_at },
+            None => {
+                tx.execute(
+                    "INSERT INTO automated_sends (sent_at) VALUES (?)",
+                    params![now],
+                )?;
+                RateSlot::Reserved(tx.last_insert_rowid())
+            }
+        };
+        tx.commit()?;
+        Ok(slot)
+    }
+
+    /// Stop counting a reserved automated send that didn't go out.
+    pub fn release_automated_send(&self, slot: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM automated_sends WHERE rowid = ?", params![slot])?;
+        Ok(())
+    }
+
+    /// Remember a removed contact so the removal can be undone.
+    ///
+    /// Only the most recent removals are kept.
+    pub fn push_removed_contact(&self, removed: &RemovedContact) -> Result<()> {
+        self.conn.execute(
+            r#"
+            INSERT INTO removed_contacts
+                (name, identifier, display_name, other_identifiers, aliases, removed_at,
+                 account, send_as, quick_replies, translate, initials, color,
+                 birthday, careful, open_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+            "#,
+            params![
+                removed.entry.name,
+                removed.entry.identifier,
+                removed.entry.display_name,
+                removed.entry.other_identifiers.join("\n"),
+                removed.aliases.join("\n"),
+                Local::now().timestamp(),
+                removed.entry.account,
+                removed.entry.send_as,
+                removed.entry.quick_replies.join("\n"),
+                removed.entry.translate,
+                removed.entry.initials,
+                removed.entry.color,
+                removed.entry.birthday,
+                removed.entry.careful,
+                removed.entry.open_at.map(OpenAt::as_str)
+            ],
+        )?;
+        self.conn.execute(
+            r#"
+            DELETE FROM removed_contacts WHERE id NOT IN
+                (SELECT id FROM removed_contacts ORDER BY id DESC LIMIT ?)
+            "#,
+            params![UNDO_LIMIT],
+        )?;
+        Ok(())
+    }
+
+    /// Get the most recently removed contact that can still be restored,
+    /// clearing expired ones.
+    pub fn last_removed_contact(&self) -> Result<Option<RemovedContact>> {
+        let expired = (Local::now() - Duration::hours(UNDO_HOURS)).timestamp();
+        self.conn.execute(
+            "DELETE FROM removed_contacts WHERE removed_at <= ?",
+            params![expired],
+        )?;
+
+        Ok(self
+            .conn
+            .query_row(
+                r#"
+                SELECT name, identifier, display_name, other_identifiers, aliases, account, send_as,
+                       quick_replies, translate, initials, color, birthday, careful,
+                       open_at
+                FROM removed_contacts
+                ORDER BY id DESC
+                LIMIT 1
+                "#,
+                [],
+                |row| {
+                    let other_identifiers: String = row.get(3)?;
+                    let aliases: String = row.get(4)?;
+                    let quick_replies: String = row.get(7)?;
+                    let open_at: Option<String> = row.get(13)?;
+                    Ok(RemovedContact {
+                        entry: ContactEntry {
+                            name: row.get(0)?,
+                            identifier: row.get(1)?,
+                            display_name: row.get(2)?,
+                            other_identifiers: split_lines(&other_identifiers),
+                            account: row.get(5)?,
+                            send_as: row.get(6)?,
+                            quick_replies: split_lines(&quick_replies),
+                            translate: row.get(8)?,
+                            initials: row.get(9)?,
+                            color: row.get(10)?,
+                            birthday: row.get(11)?,
+                            careful: row.get(12)?,
+                            open_at: open_at.as_deref().and_then(OpenAt::from_name),
+                        },
+                        aliases: split_lines(&aliases),
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    /// Forget the most recently removed contact, once it has been restored.
+    pub fn drop_last_removed_contact(&self) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM removed_contacts WHERE id = (SELECT MAX(id) FROM removed_contacts)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Replace the cached messages for a conversation.
+    pub fn cache_messages(&self, conversation: &str, messages: &[Message]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "DELETE FROM message_cache WHERE conversation = ?",
+            params![conversation],
+        )?;
+        {
+            let mut stmt = tx.prepare(
+                r#"
+                INSERT OR REPLACE INTO message_cache
+                    (conversation, rowid, guid, text, date, message_type, is_from_me, event)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                "#,
+            )?;
+            for message in messages {
+                stmt.execute(params![
+                    conversation,
+                    message.rowid,
+                    message.guid,
+                    message.text,
+                    message.date.timestamp(),
+                    message.message_type,
+                    message.is_from_me,
+                    message
+                        .event
+                        .as_ref()
+                        .and_then(|event| serde_json::to_string(event).ok())
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Get the cached messages for a conversation, oldest first.
+    ///
+    /// The service and tapbacks aren't cached, so they're filled in by the next load from
+    /// chat.db.
+    pub fn cached_messages(&self, conversation: &str) -> Result<Vec<Message>> {
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT rowid, guid, text, date, message_type, is_from_me, NULL as service,
+                   0, 0, NULL, NULL, NULL, event
+            FROM message_cache
+            WHERE conversation = ?
+            ORDER BY date ASC, rowid ASC
+            "#,
+        )?;
+        let mut rows = stmt.query(params![conversation])?;
+
+        let mut messages = Vec::new();
+        while let Some(row) = rows.next()? {
+            let mut message = message_from_row(row, 0)?;
+            // The cache doesn't keep the columns group events are decoded from
+            let event: Option<String> = row.get(12)?;
+            message.event = event.and_then(|event| serde_json::from_str(&event).ok());
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+}
+
+/// Split a newline-joined list stored in a single column.
+fn split_lines(joined: &str) -> Vec<String> {
+    joined
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{GroupChange, GroupEvent};
+
+    fn memory_store() -> StateStore {
+        StateStore::in_memory().unwrap()
+    }
+
+    #[test]
+    fn test_mark_seen() {
+        let store = memory_store();
+        assert_eq!(store.last_seen("+15551234567").unwrap(), None);
+
+        store.mark_seen("+15551234567", 10).unwrap();
+        assert_eq!(store.last_seen("+15551234567").unwrap(), Some(10));
+
+        // Watermark doesn't move backwards
+        store.mark_seen("+15551234567", 5).unwrap();
+        assert_eq!(store.last_seen("+15551234567").unwrap(), Some(10));
+
+        store.mark_seen("+15551234567", 12).unwrap();
+        assert_eq!(store.seen_watermarks().unwrap()["+15551234567"], 12);
+    }
+
+    #[test]
+    fn test_exports() {
+        let store = memory_store();
+        let mbox = Path::new("/tmp/mom.mbox");
+        assert_eq!(store.last_exported("mom", mbox).unwrap(), None);
+
+        store.mark_exported("mom", mbox, 10).unwrap();
+        store
+            .mark_exported("mom", Path::new("/tmp/maildir"), 4)
+            .unwrap();
+        assert_eq!(store.last_exported("mom", mbox).unwrap(), Some(10));
+        assert_eq!(store.last_exported("dad", mbox).unwrap(), None);
+
+        store.mark_exported("mom", mbox, 12).unwrap();
+        assert_eq!(store.last_exported("mom", mbox).unwrap(), Some(12));
+    }
+
+    #[test]
+    fn test_pins_keep_order() {
+        let store = memory_store();
+        store.pin("b").unwrap();
+        store.pin("a").unwrap();
+        store.pin("b").unwrap();
+        assert_eq!(store.pinned().unwrap(), vec!["b", "a"]);
+
+        store.unpin("b").unwrap();
+        store.pin("b").unwrap();
+        assert_eq!(store.pinned().unwrap(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_drafts() {
+        let store = memory_store();
+        store.save_draft("a", "hello").unwrap();
+        assert_eq!(store.draft("a").unwrap().as_deref(), Some("hello"));
+
+        store.save_draft("a", "").unwrap();
+        assert_eq!(store.draft("a").unwrap(), None);
+    }
+
+    #[test]
+    fn test_pinned_messages() {
+        let store = memory_store();
+        let message = |guid: &str, date: i64| StarredMessage {
+            guid: guid.to_string(),
+            conversation: "a".to_string(),
+            rowid: date,
+            text: Some("door code 1234".to_string()),
+            date: Local.timestamp_opt(date, 0).unwrap(),
+            is_from_me: false,
+        };
+
+        store.pin_message(&message("later", 200)).unwrap();
+        store.pin_message(&message("earlier", 100)).unwrap();
+        let pinned = store.pinned_messages("a").unwrap();
+        assert_eq!(
+            pinned.iter().map(|m| m.guid.as_str()).collect::<Vec<_>>(),
+            vec!["earlier", "later"]
+        );
+        assert!(store.pinned_messages("b").unwrap().is_empty());
+
+        store.unpin_message("earlier").unwrap();
+        assert_eq!(store.pinned_messages("a").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_tags() {
+        let store = memory_store();
+        let message = |guid: &str, conversation: &str, date: i64| StarredMessage {
+            guid: guid.to_string(),
+            conversation: conversation.to_string(),
+            rowid: date,
+            text: Some("total $42.10".to_string()),
+            date: Local.timestamp_opt(date, 0).unwrap(),
+            is_from_me: false,
+        };
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+
+        store
+            .set_tags(&message("A", "mom", 100), &tags(&["receipt", "address"]))
+            .unwrap();
+        store
+            .set_tags(&message("B", "dad", 200), &tags(&["receipt"]))
+            .unwrap();
+        assert_eq!(
+            store.tags().unwrap(),
+            vec![("address".to_string(), 1), ("receipt".to_string(), 2)]
+        );
+        let tagged = store.tagged("receipt").unwrap();
+        assert_eq!(
+            tagged.iter().map(|m| m.guid.as_str()).collect::<Vec<_>>(),
+            vec!["B", "A"]
+        );
+        assert_eq!(
+            store.message_tags("mom").unwrap()["A"],
+            tags(&["address", "receipt"])
+        );
+
+        // Setting tags replaces the old ones
+        store
+            .set_tags(&message("A", "mom", 100), &tags(&["address"]))
+            .unwrap();
+        assert_eq!(store.tagged("receipt").unwrap().len(), 1);
+        store.untag("A", "address").unwrap();
+        assert!(store.message_tags("mom").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_session() {
+        let store = memory_store();
+        assert_eq!(store.session().unwrap(), None);
+
+        let session = Session {
+            conversation: "+15551234567".to_string(),
+            display_name: "Mom".to_string(),
+            top_message: Some("p:0/ABC".to_string()),
+            pins_collapsed: true,
+            time_style: TimestampStyle::Relative,
+        };
+        store.save_session(&session).unwrap();
+        assert_eq!(store.session().unwrap(), Some(session));
+
+        // A session saved by an incompatible version is ignored
+        store
+            .set_setting(SESSION_KEY, "{\"conversation\": 1}")
+            .unwrap();
+        assert_eq!(store.session().unwrap(), None);
+    }
+
+    #[test]
+    fn test_spooled_messages() {
+        let store = memory_store();
+        let first = store
+            .spool_message("+15551234567", None, "on my way", false)
+            .unwrap();
+        store
+            .spool_message(
+                "mom@example.com",
+                Some("iMessage;+;chat123"),
+                "hi mom",
+                true,
+            )
+            .unwrap();
+        store
+            .spool_message("+15551234567", None, "running late", false)
+            .unwrap();
+
+        let texts = |conversation| {
+            store
+                .spooled_messages(conversation)
+                .unwrap()
+                .into_iter()
+                .map(|message| message.text)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            texts(Some("+15551234567")),
+            vec!["on my way", "running late"]
+        );
+        assert_eq!(texts(None).len(), 3);
+        let group = &store.spooled_messages(Some("mom@example.com")).unwrap()[0];
+        assert!(group.automated);
+        assert_eq!(group.chat.as_deref(), Some("iMessage;+;chat123"));
+
+        store.unspool_message(first).unwrap();
+        assert_eq!(texts(Some("+15551234567")), vec!["running late"]);
+    }
+
+    #[test]
+    fn test_claim_spooled() {
+        let store = memory_store();
+        let id = store
+            .spool_message("+15551234567", None, "on my way", false)
+            .unwrap();
+
+        let claim = |owner, now| store.claim_spooled_as(id, owner, now).unwrap();
+        assert_eq!(claim(1, 1000), SpoolClaim::Claimed);
+        // The owner can claim it again before retrying, but no one else can
+        assert_eq!(claim(1, 1010), SpoolClaim::Claimed);
+        assert_eq!(claim(2, 1020), SpoolClaim::Busy);
+        // Claims of an im that stopped run out
+        assert_eq!(claim(2, 1010 + CLAIM_TIMEOUT_SECS), SpoolClaim::Claimed);
+
+        store.release_spooled(id).unwrap();
+        assert_eq!(claim(1, 2000), SpoolClaim::Claimed);
+        store.unspool_message(id).unwrap();
+        assert_eq!(claim(1, 2000), SpoolClaim::Gone);
+    }
+
+    #[test]
+    fn test_reserve_automated_send() {
+        let store = memory_store();
+        // Room for two sends in the hour
+        let next_allowed =
+            |sends: &[i64]| (sends.len() >= 2).then(|| sends[sends.len() - 2] + 3600);
+        let reserve = |now| {
+            store
+                .reserve_automated_send(now, now - 3600, next_allowed)
+                .unwrap()
+        };
+
+        assert!(matches!(reserve(1000), RateSlot::Reserved(_)));
+        let RateSlot::Reserved(second) = reserve(1010) else {
+            panic!("expected a slot");
+        };
+        assert_eq!(reserve(1020), RateSlot::Full { send_at: 4600 });
+
+        // A send that didn't go out frees its slot
+        store.release_automated_send(second).unwrap();
+        assert!(matches!(reserve(1030), RateSlot::Reserved(_)));
+        // Sends older than the window are forgotten
+        assert!(matches!(reserve(5000), RateSlot::Reserved(_)));
+    }
+
+    #[test]
+    fn test_translations() {
+        let store = memory_store();
+        assert_eq!(store.translation("guid").unwrap(), None);
+        store.save_translation("guid", "hello").unwrap();
+        store.save_translation("guid", "hi").unwrap();
+        assert_eq!(store.translation("guid").unwrap().as_deref(), Some("hi"));
+    }
+
+    #[test]
+    fn test_added_columns() {
+        // A database created before `other_identifiers` was added to removed_contacts
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE removed_contacts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                identifier TEXT NOT NULL,
+                display_name TEXT,
+                aliases TEXT NOT NULL,
+                removed_at INTEGER NOT NULL
+            );
+            "#,
+        )
+        .unwrap();
+        let store = StateStore::with_connection(conn).unwrap();
+
+        let entry = ContactEntry {
+            name: "boss".to_string(),
+            identifier: "+15551234567".to_string(),
+            display_name: None,
+            other_identifiers: vec!["boss@work.com".to_string()],
+            account: Some("me@work.com".to_string()),
+            send_as: Some("me@work.com".to_string()),
+            quick_replies: vec!["On my way".to_string()],
+            translate: true,
+            initials: Some("JB".to_string()),
+            color: Some("cyan".to_string()),
+            birthday: Some("03-31".to_string()),
+            careful: true,
+            open_at: Some(OpenAt::FirstUnread),
+        };
+        store
+            .push_removed_contact(&RemovedContact {
+                entry,
+                aliases: Vec::new(),
+            })
+            .unwrap();
+        let restored = store.last_removed_contact().unwrap().unwrap();
+        assert_eq!(restored.entry.other_identifiers, vec!["boss@work.com"]);
+        assert_eq!(restored.entry.account.as_deref(), Some("me@work.com"));
+        assert_eq!(restored.entry.send_as.as_deref(), Some("me@work.com"));
+        assert_eq!(restored.entry.quick_replies, vec!["On my way"]);
+        assert!(restored.entry.translate);
+        assert_eq!(restored.entry.initials.as_deref(), Some("JB"));
+        assert_eq!(restored.entry.color.as_deref(), Some("cyan"));
+        assert_eq!(restored.entry.birthday.as_deref(), Some("03-31"));
+        assert!(restored.entry.careful);
+        assert_eq!(restored.entry.open_at, Some(OpenAt::FirstUnread));
+    }
+
+    #[test]
+    fn test_removed_contacts() {
+        let store = memory_store();
+        assert!(store.last_removed_contact().unwrap().is_none());
+
+        let removed = |name: &str, aliases: &[&str]| RemovedContact {
+            entry: ContactEntry {
+                name: name.to_string(),
+                identifier: "+15551234567".to_string(),
+                display_name: None,
+                other_identifiers: Vec::new(),
+                account: None,
+                send_as: None,
+                quick_replies: Vec::new(),
+                translate: false,
+                initials: None,
+                color: None,
+                birthday: None,
+                careful: false,
+                open_at: None,
+            },
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+        };
+        store.push_removed_contact(&removed("mom", &[])).unwrap();
+        store
+            .push_removed_contact(&removed("dad", &["father", "pop"]))
+            .unwrap();
+
+        // Undo restores the most recent removal first
+        let last = store.last_removed_contact().unwrap().unwrap();
+        assert_eq!(last.entry.name, "dad");
+        assert_eq!(last.aliases, vec!["father", "pop"]);
+        store.drop_last_removed_contact().unwrap();
+        assert_eq!(
+            store.last_removed_contact().unwrap().unwrap().entry.name,
+            "mom"
+        );
+
+        // Only the most recent removals are kept
+        for i in 0..UNDO_LIMIT {
+            store
+                .push_removed_contact(&removed(&i.to_string(), &[]))
+                .unwrap();
+        }
+        for _ in 0..UNDO_LIMIT {
+            store.drop_last_removed_contact().unwrap();
+        }
+        assert!(store.last_removed_contact().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_message_cache() {
+        let store = memory_store();
+        let message = |rowid: i64, date: i64| Message {
+            rowid,
+            guid: format!("guid-{}", rowid),
+            text: Some(format!("message {}", rowid)),
+            date: Local.timestamp_opt(date, 0).unwrap(),
+            message_type: None,
+            is_from_me: rowid % 2 == 0,
+            service: None,
+            reactions: Vec::new(),
+            event: None,
+        };
+
+        let event = GroupEvent {
+            actor: Some("alice@example.com".to_string()),
+            change: GroupChange::Renamed(Some("Ski Trip".to_string())),
+        };
+        let renamed = Message {
+            event: Some(event.clone()),
+            ..message(2, 200)
+        };
+        store
+            .cache_messages("a", &[renamed, message(1, 100)])
+            .unwrap();
+        let cached = store.cached_messages("a").unwrap();
+        assert_eq!(
+            cached.iter().map(|m| m.rowid).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert!(cached[1].is_from_me);
+        assert_eq!(cached[1].event, Some(event));
+
+        // Caching again replaces the previous messages
+        store.cache_messages("a", &[message(3, 300)]).unwrap();
+        assert_eq!(store.cached_messages("a").unwrap().len(), 1);
+        assert!(store.cached_messages("b").unwrap().is_empty());
+    }
+}
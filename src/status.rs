@@ -0,0 +1,103 @@
+use crate::db::{MessageDB, UnreadSummary};
+use crate::error::Result;
+use crate::paths;
+use crate::watcher::{DbWatcher, FileStamp};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What `im status` prints when no format is given.
+pub const DEFAULT_FORMAT: &str = "#{unread_total}";
+
+/// An unread summary and the state of chat.db when it was taken.
+#[derive(Serialize, Deserialize)]
+struct CachedStatus {
+    stamps: Vec<FileStamp>,
+    summary: UnreadSummary,
+}
+
+/// Get the unread summary for a status line.
+///
+/// Status lines run this every few seconds, so the last summary is kept on disk and reused
+/// until Messages.app writes to chat.db, which is checked from file metadata alone.
+pub fn unread_summary() -> Result<UnreadSummary> {
+    let stamps = DbWatcher::new()?.stamps();
+    let path = cache_path();
+    if let Some(cached) = path.as_deref().and_then(read_cache) {
+        if cached.stamps == stamps {
+            return Ok(cached.summary);
+        }
+    }
+
+    // Query chat.db itself even in snapshot mode, since copying it takes far too long here
+    let summary = MessageDB::open_at(&MessageDB::path()?)?.get_unread_summary()?;
+    if let Some(path) = path {
+        let cached = CachedStatus {
+            stamps,
+            summary: summary.clone(),
+        };
+        // A status line is better off stale than failing
+        let _ = write_cache(&path, &cached);
+    }
+    Ok(summary)
+}
+
+/// Fill in a status line format: `#{unread_total}`, `#{unread_chats}`, and `#{latest_sender}`
+/// are replaced, and everything else is kept as written.
+pub fn render(format: &str, summary: &UnreadSummary, latest_sender: Option<&str>) -> String {
+    format
+        .replace("#{unread_total}", &summary.total.to_string())
+        .replace("#{unread_chats}", &summary.chats.to_string())
+        .replace("#{latest_sender}", latest_sender.unwrap_or_default())
+}
+
+/// Where the last unread summary is cached.
+fn cache_path() -> Option<PathBuf> {
+    paths::status_cache()
+}
+
+/// Read the cached summary, if there is a readable one.
+fn read_cache(path: &Path) -> Option<CachedStatus> {
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+/// Replace the cached summary.
+fn write_cache(path: &Path, cached: &CachedStatus) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_vec(cached).map_err(io::Error::from)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let summary = UnreadSummary {
+            total: 5,
+            chats: 2,
+            latest_sender: Some("+15551234567".to_string()),
+        };
+        assert_eq!(
+            render(
+                "#[fg=red]#{unread_total} in #{unread_chats} (#{latest_sender})",
+                &summary,
+                Some("Mom")
+            ),
+            "#[fg=red]5 in 2 (Mom)"
+        );
+        assert_eq!(
+            render(
+                "#{unread_total} #{latest_sender}",
+                &UnreadSummary::default(),
+                None
+            ),
+            "0 "
+        );
+        assert_eq!(render("#{other}", &summary, None), "#{other}");
+    }
+}
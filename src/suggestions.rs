@@ -0,0 +1,148 @@
+use crate::config::{SuggestionConfig, MAX_QUICK_REPLIES};
+use crate::db::Message;
+use crate::error::{Error, Result};
+use crate::hooks::run_command;
+use serde::Serialize;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+/// Longest suggestion kept, in characters; longer lines are dropped.
+const MAX_SUGGESTION_CHARS: usize = 200;
+
+/// What the suggestion command is given on stdin.
+#[derive(Debug, Serialize)]
+struct SuggestionRequest<'a> {
+    /// Name of the person the conversation is with.
+    contact: &'a str,
+    /// The latest messages, oldest first.
+    messages: Vec<SuggestionMessage<'a>>,
+}
+
+/// A message as given to the suggestion command.
+#[derive(Debug, Serialize)]
+struct SuggestionMessage<'a> {
+    from_me: bool,
+    text: &'a str,
+    /// When the message was sent, in RFC 3339.
+    date: String,
+}
+
+/// Asks the configured command for reply suggestions in the background.
+pub struct Suggester {
+    command: String,
+    context_messages: usize,
+    timeout: Duration,
+    /// Guid of the message suggestions were last asked for, so each is asked about once
+    asked_for: Option<String>,
+    pending: Option<Receiver<Result<Vec<String>>>>,
+}
+
+impl Suggester {
+    /// Create a suggester, or `None` when no command is configured.
+    pub fn new(config: &SuggestionConfig) -> Option<Self> {
+        let command = config.command.clone().filter(|c| !c.trim().is_empty())?;
+        Some(Self {
+            command,
+            context_messages: config.context_messages.max(1),
+            timeout: Duration::from_secs(config.timeout_secs),
+            asked_for: None,
+            pending: None,
+        })
+    }
+
+    /// Ask for suggestions if the latest message is a new one from the other person.
+    ///
+    /// Returns whether a request was started.
+    pub fn request(&mut self, contact: &str, messages: &[Message]) -> Result<bool> {
+        let Some(latest) = messages.last().filter(|message| !message.is_from_me) else {
+            return Ok(false);
+        };
+        if self.asked_for.as_deref() == Some(latest.guid.as_str()) {
+            return Ok(false);
+        }
+        self.asked_for = Some(latest.guid.clone());
+
+        let start = messages.len().saturating_sub(self.context_messages);
+        let request = SuggestionRequest {
+            contact,
+            messages: messages[start..]
+                .iter()
+                .filter_map(|message| {
+                    Some(SuggestionMessage {
+                        from_me: message.is_from_me,
+                        text: message.text.as_deref()?,
+                        date: message.date.to_rfc3339(),
+                    })
+                })
+                .collect(),
+        };
+        let input = serde_json::to_vec(&request).map_err(|e| Error::Generic(e.to_string()))?;
+
+        let (tx, rx) = mpsc::channel();
+        let command = self.command.clone();
+        let timeout = self.timeout;
+        thread::spawn(move || {
+            let result = run_command(&command, &input, timeout, "suggestion")
+                .map(|out| parse_suggestions(&out));
+            let _ = tx.send(result);
+        });
+        // A newer request replaces one still running
+        self.pending = Some(rx);
+        Ok(true)
+    }
+
+    /// Pick up suggestions once the command finishes.
+    pub fn poll(&mut self) -> Option<Result<Vec<String>>> {
+        let rx = self.pending.as_ref()?;
+        match rx.try_recv() {
+            Ok(result) => {
+                self.pending = None;
+                Some(result)
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.pending = None;
+                None
+            }
+        }
+    }
+}
+
+/// Read suggestions from the command's output: one per line, up to one for each Alt+number.
+///
+/// Blank lines, duplicates, overly long lines, and lines with control characters such as
+/// terminal escape codes are skipped.
+fn parse_suggestions(output: &str) -> Vec<String> {
+    let mut suggestions: Vec<String> = Vec::new();
+    for line in output.lines() {
+        let line = line.trim();
+        if line.is_empty()
+            || line.chars().any(char::is_control)
+            || line.chars().count() > MAX_SUGGESTION_CHARS
+            || suggestions.iter().any(|s| s == line)
+        {
+            continue;
+        }
+        suggestions.push(line.to_string());
+        if suggestions.len() == MAX_QUICK_REPLIES {
+            break;
+        }
+    }
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_suggestions() {
+        let output = "Sounds good!\n\n  On my way \nSounds good!\n\x1b[31mSure\n";
+        assert_eq!(parse_suggestions(output), vec!["Sounds good!", "On my way"]);
+        assert!(parse_suggestions("").is_empty());
+
+        let many: String = (0..20).map(|i| format!("{}\n", i)).collect();
+        assert_eq!(parse_suggestions(&many).len(), MAX_QUICK_REPLIES);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::config::SummarizeConfig;
+use crate::db::Message;
+use crate::error::{Error, Result};
+use crate::hooks::run_command;
+use crate::i18n::t;
+use std::time::Duration;
+
+/// How many times long summaries are summarized again before giving up on shortening them.
+const MAX_ROUNDS: usize = 3;
+
+/// Write messages as a plain-text transcript, one line per message, for the summarizer.
+///
+/// Messages without text are noted by their type, like "[Image]".
+pub fn transcript(messages: &[Message], name: &str) -> String {
+    messages
+        .iter()
+        .filter_map(|message| {
+            let sender = if message.is_from_me {
+                t!("chat.me")
+            } else {
+                name.to_string()
+            };
+            let text = match (&message.text, &message.message_type) {
+                (Some(text), _) if !text.trim().is_empty() => text.replace('\n', " "),
+                (_, Some(kind)) => format!("[{}]", kind),
+                _ => return None,
+            };
+            Some(format!(
+                "{} {}: {}\n",
+                message.date.format("%Y-%m-%d %H:%M"),
+                sender,
+                text
+            ))
+        })
+        .collect()
+}
+
+/// Summarize a transcript with the configured command.
+///
+/// Transcripts longer than `chunk_chars` are split into parts at line breaks and each part is
+/// summarized; the joined summaries are then summarized the same way until they fit.
+pub fn summarize(config: &SummarizeConfig, transcript: &str) -> Result<String> {
+    let Some(command) = config.command.as_deref().filter(|c| !c.trim().is_empty()) else {
+        return Err(Error::Generic(t!("summarize.not_configured")));
+    };
+    let timeout = Duration::from_secs(config.timeout_secs);
+    let max_chars = config.chunk_chars.max(1);
+
+    let mut text = transcript.to_string();
+    for _ in 0..MAX_ROUNDS {
+        let parts = chunks(&text, max_chars);
+        let summaries = parts
+            .iter()
+            .map(|part| {
+                run_command(command, part.as_bytes(), timeout, "summarize")
+                    .map(|summary| summary.trim().to_string())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let done = parts.len() == 1;
+        text = summaries.join("\n\n");
+        if done {
+            break;
+        }
+    }
+    Ok(text)
+}
+
+/// Split text into parts of at most `max_chars` characters at line breaks.
+///
+/// A single line longer than `max_chars` becomes a part of its own.
+fn chunks(text: &str, max_chars: usize) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut length = 0;
+    let mut end = 0;
+
+    for line in text.split_inclusive('\n') {
+        let line_chars = line.chars().count();
+        if length > 0 && length + line_chars > max_chars {
+            parts.push(&text[start..end]);
+            start = end;
+            length = 0;
+        }
+        length += line_chars;
+        end += line.len();
+    }
+    if end > start {
+        parts.push(&text[start..end]);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunks() {
+        let text = "aaaa\nbbbb\ncc\nddddddddddd\ne";
+        assert_eq!(
+            chunks(text, 10),
+            vec!["aaaa\nbbbb\n", "cc\n", "ddddddddddd\n", "e"]
+        );
+        assert_eq!(chunks(text, 100), vec![text]);
+        assert!(chunks("", 10).is_empty());
+    }
+
+    #[test]
+    fn test_summarize() {
+        let config = SummarizeConfig {
+            command: Some("head -c 3".to_string()),
+            chunk_chars: 8,
+            timeout_secs: 5,
+        };
+        // Each part is cut to three characters, then the joined parts are summarized again
+        assert_eq!(summarize(&config, "abcdef\nghijkl\n").unwrap(), "abc");
+
+        let config = SummarizeConfig {
+            command: None,
+            ..config
+        };
+        assert!(summarize(&config, "hi").is_err());
+    }
+}
@@ -0,0 +1,57 @@
+/// Read the tags typed for a message, like "#receipt, address": separated by spaces or
+/// commas, with or without `#`, in lowercase, without repeats, in alphabetical order.
+pub fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = input
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .map(|tag| tag.trim_start_matches('#').to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Find the tag `name` refers to among the tags in use, allowing a leading `#` and the
+/// plural, so `receipts` finds `receipt`.
+pub fn find_tag<'a>(name: &str, tags: &'a [String]) -> Option<&'a String> {
+    let name = name.trim_start_matches('#').to_lowercase();
+    tags.iter().find(|tag| **tag == name).or_else(|| {
+        let singular = name.strip_suffix('s')?;
+        tags.iter().find(|tag| *tag == singular)
+    })
+}
+
+/// Show tags the way they're written in messages, like "#address #receipt".
+pub fn format_tags(tags: &[String]) -> String {
+    tags.iter()
+        .map(|tag| format!("#{}", tag))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(
+            parse_tags("#Receipt, address  #receipt"),
+            vec!["address", "receipt"]
+        );
+        assert!(parse_tags(" # , ").is_empty());
+        assert_eq!(
+            format_tags(&parse_tags("receipt address")),
+            "#address #receipt"
+        );
+    }
+
+    #[test]
+    fn test_find_tag() {
+        let tags = vec!["address".to_string(), "receipt".to_string()];
+        assert_eq!(find_tag("#receipt", &tags), Some(&tags[1]));
+        assert_eq!(find_tag("Receipts", &tags), Some(&tags[1]));
+        assert_eq!(find_tag("addres", &tags), None);
+        assert_eq!(find_tag("recipes", &tags), None);
+    }
+}
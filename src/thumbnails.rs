@@ -0,0 +1,402 @@
+use crate::attachments::AttachmentKind;
+use crate::db::Attachment;
+use crate::error::{Error, Result};
+use crate::i18n::t;
+use crate::paths;
+use image::{imageops::FilterType, DynamicImage, RgbImage};
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+/// Longest edge of a cached thumbnail, in pixels.
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// Size of an inline preview, in terminal cells. Each cell shows two pixels stacked vertically.
+pub const PREVIEW_COLUMNS: u32 = 32;
+pub const PREVIEW_ROWS: u32 = 8;
+
+/// An attachment waiting for its thumbnail.
+struct Request {
+    guid: String,
+    source: PathBuf,
+    kind: AttachmentKind,
+}
+
+/// What can be shown inline for an image or video attachment.
+#[derive(Debug, Default)]
+pub struct Preview {
+    /// The image, or a video's poster frame, if it could be decoded.
+    pub image: Option<RgbImage>,
+    /// Length of a video, in seconds.
+    pub duration: Option<u64>,
+}
+
+/// Image and video previews generated on a worker thread.
+///
+/// Decoding a multi-megabyte photo takes far longer than a frame, so attachments are
+/// downscaled once in the background and cached on disk, keyed by attachment guid and the
+/// file's modification time. Views queue attachments with `request`, pick up finished
+/// previews with `poll`, and draw whatever `get` has ready.
+pub struct ThumbnailCache {
+    requests: Sender<Request>,
+    responses: Receiver<(String, Preview)>,
+    /// Previews by attachment guid
+    previews: HashMap<String, Preview>,
+    pending: HashSet<String>,
+}
+
+impl ThumbnailCache {
+    /// Start the worker thread.
+    pub fn new() -> Self {
+        let (requests, worker_requests) = mpsc::channel::<Request>();
+        let (worker_responses, responses) = mpsc::channel();
+        let cache_dir = Self::dir();
+
+        thread::spawn(move || {
+            for request in worker_requests {
+                let cache_dir = cache_dir.as_deref();
+                let preview = match request.kind {
+                    AttachmentKind::Video => Preview {
+                        image: load_preview(
+                            cache_dir,
+                            &request.guid,
+                            &request.source,
+                            poster_frame,
+                        )
+                        .ok(),
+                        duration: video_duration(&request.source),
+                    },
+                    _ => Preview {
+                        image: load_preview(cache_dir, &request.guid, &request.source, decode).ok(),
+                        duration: None,
+                    },
+                };
+                if worker_responses.send((request.guid, preview)).is_err() {
+                    // The view closed
+                    break;
+                }
+            }
+        });
+
+        Self {
+            requests,
+            responses,
+            previews: HashMap::new(),
+            pending: HashSet::new(),
+        }
+    }
+
+    /// Get the directory thumbnails are cached in.
+    pub fn dir() -> Option<PathBuf> {
+        paths::thumbnails()
+    }
+
+    /// Queue an attachment for a preview, unless it already has one or is on its way.
+    pub fn request(&mut self, attachment: &Attachment) {
+        let Some(source) = &attachment.path else {
+            return;
+        };
+        if self.previews.contains_key(&attachment.guid) || self.pending.contains(&attachment.guid) {
+            return;
+        }
+
+        let request = Request {
+            guid: attachment.guid.clone(),
+            source: source.clone(),
+            kind: AttachmentKind::of(attachment),
+        };
+        if self.requests.send(request).is_ok() {
+            self.pending.insert(attachment.guid.clone());
+        }
+    }
+
+    /// Whether any requested previews haven't been picked up yet.
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Pick up previews the worker has finished. Returns whether any arrived.
+    pub fn poll(&mut self) -> bool {
+        let mut arrived = false;
+        loop {
+            match self.responses.try_recv() {
+                Ok((guid, preview)) => {
+                    self.pending.remove(&guid);
+                    arrived |= preview.image.is_some() || preview.duration.is_some();
+                    self.previews.insert(guid, preview);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.pending.clear();
+                    break;
+                }
+            }
+        }
+        arrived
+    }
+
+    /// Get an attachment's preview, if it's ready.
+    pub fn get(&self, guid: &str) -> Option<&Preview> {
+        self.previews.get(guid)
+    }
+}
+
+impl Default for ThumbnailCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Load the preview image for an attachment, generating its thumbnail with `decode` and caching
+/// it if needed.
+fn load_preview(
+    cache_dir: Option<&Path>,
+    guid: &str,
+    source: &Path,
+    decode: fn(&Path) -> Result<DynamicImage>,
+) -> Result<RgbImage> {
+    let cached = cache_dir
+        .map(|dir| cache_path(dir, guid, source))
+        .transpose()?;
+
+    let thumbnail = match cached.as_deref().map(image::open) {
+        Some(Ok(thumbnail)) => thumbnail,
+        _ => {
+            let thumbnail = decode(source)?.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+            if let Some(path) = &cached {
+                // A thumbnail that can't be cached is still worth showing
+                if let Some(dir) = path.parent() {
+                    let _ = fs::create_dir_all(dir);
+                }
+                let _ = thumbnail.save(path);
+            }
+            thumbnail
+        }
+    };
+
+    // Square pixels fit the preview because each cell shows two of them stacked
+    Ok(thumbnail
+        .resize(PREVIEW_COLUMNS, PREVIEW_ROWS * 2, FilterType::Triangle)
+        .to_rgb8())
+}
+
+/// Decode an image file straight into an inline preview, without caching a thumbnail.
+pub fn preview_file(source: &Path) -> Result<RgbImage> {
+    Ok(decode(source)?
+        .resize(PREVIEW_COLUMNS, PREVIEW_ROWS * 2, FilterType::Triangle)
+        .to_rgb8())
+}
+
+/// Decode an image file, converting formats the `image` crate can't read (like the HEIC photos
+/// iPhones take) with `sips`.
+fn decode(source: &Path) -> Result<DynamicImage> {
+    match image::open(source) {
+        Ok(image) => Ok(image),
+        Err(error @ image::ImageError::IoError(_)) => Err(image_error(error)),
+        Err(_) => convert_with_sips(source),
+    }
+}
+
+/// Convert an image to a downscaled PNG with macOS's `sips`, which decodes everything Photos
+/// can, and read it back.
+fn convert_with_sips(source: &Path) -> Result<DynamicImage> {
+    let output = temp_path("png");
+
+    let status = Command::new("sips")
+        .args(["-s", "format", "png", "-Z"])
+        .arg(THUMBNAIL_SIZE.to_string())
+        .arg(source)
+        .arg("--out")
+        .arg(&output)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(Error::Generic(t!(
+            "thumbnails.convert_failed",
+            path = source.display()
+        )));
+    }
+
+    let image = image::open(&output).map_err(image_error);
+    let _ = fs::remove_file(&output);
+    image
+}
+
+/// Extract the first frame of a video with `ffmpeg` if it's installed, or with Quick Look.
+fn poster_frame(source: &Path) -> Result<DynamicImage> {
+    let output = temp_path("png");
+    let extracted = Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-i"])
+        .arg(source)
+        .args(["-frames:v", "1", "-vf"])
+        .arg(format!("scale={}:-2", THUMBNAIL_SIZE))
+        .arg(&output)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if extracted {
+        let image = image::open(&output).map_err(image_error);
+        let _ = fs::remove_file(&output);
+        return image;
+    }
+
+    // Quick Look writes `<file name>.png` into the output directory
+    let output_dir = temp_path("d");
+    fs::create_dir_all(&output_dir)?;
+    let status = Command::new("qlmanage")
+        .arg("-t")
+        .args(["-s", &THUMBNAIL_SIZE.to_string(), "-o"])
+        .arg(&output_dir)
+        .arg(source)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+
+    let mut name = source.file_name().unwrap_or_default().to_os_string();
+    name.push(".png");
+    let image = match status {
+        Ok(status) if status.success() => image::open(output_dir.join(name)).map_err(image_error),
+        Ok(_) => Err(Error::Generic(t!(
+            "thumbnails.no_frame",
+            path = source.display()
+        ))),
+        Err(error) => Err(error.into()),
+    };
+    let _ = fs::remove_dir_all(&output_dir);
+    image
+}
+
+/// Read a video's length in seconds with `ffprobe` if it's installed, or from Spotlight.
+fn video_duration(source: &Path) -> Option<u64> {
+    let probes: [(&str, &[&str]); 2] = [
+        (
+            "ffprobe",
+            &[
+                "-v",
+                "error",
+                "-show_entries",
+                "format=duration",
+                "-of",
+                "default=noprint_wrappers=1:nokey=1",
+            ],
+        ),
+        ("mdls", &["-raw", "-name", "kMDItemDurationSeconds"]),
+    ];
+
+    probes.iter().find_map(|(program, args)| {
+        let output = Command::new(program)
+            .args(*args)
+            .arg(source)
+            .stderr(Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        parse_duration(&String::from_utf8_lossy(&output.stdout))
+    })
+}
+
+/// Parse a duration in (fractional) seconds, rounded to the nearest second.
+fn parse_duration(output: &str) -> Option<u64> {
+    let seconds: f64 = output.trim().parse().ok()?;
+    (seconds.is_finite() && seconds >= 0.0).then(|| seconds.round() as u64)
+}
+
+/// A unique temporary path for an intermediate file.
+fn temp_path(extension: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    env::temp_dir().join(format!(
+        "im-{}-{}.{}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        extension
+    ))
+}
+
+/// Where the thumbnail for an attachment is cached. The file's modification time is part of
+/// the name so re-downloaded attachments get a fresh thumbnail.
+fn cache_path(cache_dir: &Path, guid: &str, source: &Path) -> Result<PathBuf> {
+    let modified = fs::metadata(source)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|age| age.as_secs())
+        .unwrap_or_default();
+    Ok(cache_dir.join(cache_file_name(guid, modified)))
+}
+
+/// Name of a cached thumbnail, with anything unsafe in a file name replaced.
+fn cache_file_name(guid: &str, modified: u64) -> String {
+    let guid: String = guid
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}-{}.png", guid, modified)
+}
+
+/// Convert an image decoding or encoding error.
+fn image_error(error: image::ImageError) -> Error {
+    Error::Generic(t!("thumbnails.image", error = error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_file_name() {
+        assert_eq!(
+            cache_file_name("at_0_8F2C-11AB", 1_700_000_000),
+            "at_0_8F2C-11AB-1700000000.png"
+        );
+        assert_eq!(cache_file_name("../a/b", 0), "___a_b-0.png");
+    }
+
+    #[test]
+    fn test_load_preview() {
+        let dir = std::env::temp_dir().join(format!("im-thumbnails-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("photo.png");
+        RgbImage::from_pixel(400, 100, image::Rgb([200, 10, 10]))
+            .save(&source)
+            .unwrap();
+
+        let preview = load_preview(Some(&dir.join("cache")), "at_0_A", &source, decode).unwrap();
+        assert_eq!(preview.dimensions(), (PREVIEW_COLUMNS, PREVIEW_COLUMNS / 4));
+        assert_eq!(preview.get_pixel(0, 0).0, [200, 10, 10]);
+        assert!(cache_path(&dir.join("cache"), "at_0_A", &source)
+            .unwrap()
+            .is_file());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("42.516000\n"), Some(43));
+        assert_eq!(parse_duration("0"), Some(0));
+        assert_eq!(parse_duration("(null)"), None);
+        assert_eq!(parse_duration("-1"), None);
+    }
+
+    #[test]
+    fn test_decode_unreadable() {
+        let source = std::env::temp_dir().join(format!("im-broken-{}.heic", std::process::id()));
+        fs::write(&source, b"not a photo").unwrap();
+        assert!(decode(&source).is_err());
+        assert!(decode(&source.with_extension("missing")).is_err());
+        fs::remove_file(&source).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+use crate::config::TimeConfig;
+use chrono::{DateTime, Local, Locale};
+use serde::{Deserialize, Serialize};
+use std::env;
+
+/// Environment variables that name the user's locale, most specific first.
+const LOCALE_VARIABLES: &[&str] = &["LC_ALL", "LC_TIME", "LANG"];
+
+/// How message times are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampStyle {
+    /// Clock times, like "14:05"
+    Absolute,
+    /// Times relative to now, like "2m ago" or "yesterday"
+    Relative,
+}
+
+/// Formats message times in the configured style, format, and locale.
+#[derive(Debug, Clone)]
+pub struct TimeFormatter {
+    style: TimestampStyle,
+    time_format: String,
+    date_format: String,
+    locale: Locale,
+}
+
+impl TimeFormatter {
+    /// Create a formatter from the timestamp settings.
+    pub fn new(config: &TimeConfig) -> Self {
+        let locale = match &config.locale {
+            Some(name) => parse_locale(name),
+            None => LOCALE_VARIABLES
+                .iter()
+                .filter_map(|var| env::var(var).ok())
+                .find(|value| !value.is_empty())
+                .and_then(|value| parse_locale(&value)),
+        };
+
+        Self {
+            style: config.style,
+            time_format: config.time_format.clone(),
+            date_format: config.date_format.clone(),
+            locale: locale.unwrap_or(Locale::POSIX),
+        }
+    }
+
+    /// The style times are shown in.
+    pub fn style(&self) -> TimestampStyle {
+        self.style
+    }
+
+    /// Show times in `style`.
+    pub fn set_style(&mut self, style: TimestampStyle) {
+        self.style = style;
+    }
+
+    /// Switch between relative and absolute times.
+    pub fn toggle(&mut self) {
+        self.style = match self.style {
+            TimestampStyle::Absolute => TimestampStyle::Relative,
+            TimestampStyle::Relative => TimestampStyle::Absolute,
+        };
+    }
+
+    /// Format a message time as of `now`.
+    pub fn format(&self, date: &DateTime<Local>, now: &DateTime<Local>) -> String {
+        match self.style {
+            TimestampStyle::Absolute => self.localized(date, &self.time_format),
+            TimestampStyle::Relative => self.relative(date, now),
+        }
+    }
+
+    /// Describe how long ago a time was, falling back to a date after a week.
+    fn relative(&self, date: &DateTime<Local>, now: &DateTime<Local>) -> String {
+        let elapsed = now.signed_duration_since(*date);
+        let days = (now.date_naive() - date.date_naive()).num_days();
+
+        if elapsed.num_minutes() < 1 {
+            "now".to_string()
+        } else if elapsed.num_hours() < 1 {
+            format!("{}m ago", elapsed.num_minutes())
+        } else if days == 0 {
+            format!("{}h ago", elapsed.num_hours())
+        } else if days == 1 {
+            "yesterday".to_string()
+        } else if days < 7 {
+            self.localized(date, "%A")
+        } else {
+            self.localized(date, &self.date_format)
+        }
+    }
+
+    fn localized(&self, date: &DateTime<Local>, format: &str) -> String {
+        date.format_localized(format, self.locale).to_string()
+    }
+}
+
+/// Parse a locale name like "fr_FR", "de-DE", or "en_GB.UTF-8".
+fn parse_locale(name: &str) -> Option<Locale> {
+    let name = name.split(['.', '@']).next()?.replace('-', "_");
+    Locale::try_from(name.as_str()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn formatter(style: TimestampStyle, locale: &str) -> TimeFormatter {
+        TimeFormatter::new(&TimeConfig {
+            style,
+            locale: Some(locale.to_string()),
+            ..TimeConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_relative() {
+        let formatter = formatter(TimestampStyle::Relative, "en_US");
+        let now = Local.with_ymd_and_hms(2024, 3, 15, 18, 0, 0).unwrap();
+        let ago = |minutes| formatter.format(&(now - chrono::Duration::minutes(minutes)), &now);
+
+        assert_eq!(ago(0), "now");
+        assert_eq!(ago(2), "2m ago");
+        assert_eq!(ago(3 * 60), "3h ago");
+        assert_eq!(ago(20 * 60), "yesterday");
+        assert_eq!(ago(3 * 24 * 60), "Tuesday");
+        assert_eq!(ago(30 * 24 * 60), "Feb 14");
+    }
+
+    #[test]
+    fn test_absolute_and_locale() {
+        let date = Local.with_ymd_and_hms(2024, 3, 10, 9, 5, 0).unwrap();
+        let mut formatter = formatter(TimestampStyle::Absolute, "fr_FR.UTF-8");
+        assert_eq!(formatter.format(&date, &date), "09:05");
+
+        formatter.toggle();
+        let now = date + chrono::Duration::days(3);
+        assert_eq!(formatter.format(&date, &now), "dimanche");
+
+        assert!(parse_locale("not a locale").is_none());
+        assert_eq!(parse_locale("de-DE"), Some(Locale::de_DE));
+    }
+}
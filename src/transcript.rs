@@ -0,0 +1,101 @@
+use crate::db::Message;
+use crate::error::{Error, Result};
+use crate::i18n::t;
+use crate::paths;
+use crate::state::StateStore;
+use crate::summarize;
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+/// A plain-text log of a conversation's messages, appended to as a chat shows them, so a
+/// record survives even if the messages are later deleted from chat.db.
+///
+/// Each message is written once, in the order the chat first showed it, in the same format as
+/// transcripts given to the summarizer.
+pub struct TranscriptLog {
+    conversation: String,
+    name: String,
+    path: PathBuf,
+    /// Guids of the messages already in the log
+    written: HashSet<String>,
+}
+
+impl TranscriptLog {
+    /// Open the log of the conversation with `conversation`, whose messages are written
+    /// under `name`.
+    pub fn open(state: &StateStore, conversation: &str, name: &str) -> Result<Self> {
+        let dir = Self::dir().ok_or_else(|| Error::Generic(t!("paths.no_data_dir")))?;
+
+        Ok(Self {
+            conversation: conversation.to_string(),
+            name: name.to_string(),
+            path: dir.join(file_name(conversation)),
+            written: state.transcribed(conversation)?,
+        })
+    }
+
+    /// Get the directory the transcripts are written to.
+    pub fn dir() -> Option<PathBuf> {
+        paths::transcripts()
+    }
+
+    /// Append the messages that aren't in the log yet.
+    pub fn record(&mut self, state: &StateStore, messages: &[Message]) -> Result<()> {
+        let new: Vec<Message> = messages
+            .iter()
+            .filter(|message| !self.written.contains(&message.guid))
+            .cloned()
+            .collect();
+        if new.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Only the user can read what they've said to people
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(&self.path)?;
+        file.write_all(summarize::transcript(&new, &self.name).as_bytes())?;
+
+        let guids: Vec<&str> = new.iter().map(|message| message.guid.as_str()).collect();
+        state.mark_transcribed(&self.conversation, &guids)?;
+        self.written
+            .extend(new.into_iter().map(|message| message.guid));
+        Ok(())
+    }
+}
+
+/// Name of the log file for a conversation, with characters that don't belong in file names
+/// replaced.
+fn file_name(conversation: &str) -> String {
+    let safe: String = conversation
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || "+@.-_".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.log", safe.trim_start_matches('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name("+15551234567"), "+15551234567.log");
+        assert_eq!(file_name("mom@example.com"), "mom@example.com.log");
+        assert_eq!(file_name("../chat;1/2"), "_chat_1_2.log");
+    }
+}
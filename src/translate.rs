@@ -0,0 +1,162 @@
+use crate::config::TranslateConfig;
+use crate::error::{Error, Result};
+use crate::hooks::run_command;
+use crate::state::StateStore;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+/// A message waiting for its translation.
+struct Request {
+    guid: String,
+    text: String,
+}
+
+/// Translations of incoming messages made on a worker thread with the configured command.
+///
+/// Translations are cached in the state store by message guid, so each message is only run
+/// through the command once. Views queue the messages on screen with `request`, pick up
+/// finished translations with `poll`, and draw whatever `get` has ready.
+pub struct Translator {
+    requests: Sender<Request>,
+    responses: Receiver<(String, Result<String>)>,
+    /// Translations by message guid
+    translations: HashMap<String, String>,
+    pending: HashSet<String>,
+    /// Why the last failed translation failed, until the view shows it
+    error: Option<Error>,
+}
+
+impl Translator {
+    /// Start the worker thread, or `None` when no command is configured.
+    pub fn new(config: &TranslateConfig) -> Option<Self> {
+        let command = config.command.clone().filter(|c| !c.trim().is_empty())?;
+        let timeout = Duration::from_secs(config.timeout_secs);
+        let (requests, worker_requests) = mpsc::channel::<Request>();
+        let (worker_responses, responses) = mpsc::channel();
+
+        thread::spawn(move || {
+            // Translations that can't be cached are still worth showing
+            let state = StateStore::open().ok();
+            for request in worker_requests {
+                let cached = state
+                    .as_ref()
+                    .and_then(|state| state.translation(&request.guid).ok().flatten());
+                let result = match cached {
+                    Some(translation) => Ok(translation),
+                    None => translate(&command, &request.text, timeout).inspect(|translation| {
+                        if let Some(state) = &state {
+                            let _ = state.save_translation(&request.guid, translation);
+                        }
+                    }),
+                };
+                if worker_responses.send((request.guid, result)).is_err() {
+                    // The view closed
+                    break;
+                }
+            }
+        });
+
+        Some(Self {
+            requests,
+            responses,
+            translations: HashMap::new(),
+            pending: HashSet::new(),
+            error: None,
+        })
+    }
+
+    /// Whether a message already has a translation, or one on its way.
+    pub fn is_requested(&self, guid: &str) -> bool {
+        self.translations.contains_key(guid) || self.pending.contains(guid)
+    }
+
+    /// Queue a message for translation, unless it already has one or is on its way.
+    pub fn request(&mut self, guid: &str, text: &str) {
+        if self.is_requested(guid) {
+            return;
+        }
+        let request = Request {
+            guid: guid.to_string(),
+            text: text.to_string(),
+        };
+        if self.requests.send(request).is_ok() {
+            self.pending.insert(guid.to_string());
+        }
+    }
+
+    /// Whether any requested translations haven't been picked up yet.
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Pick up translations the worker has finished. Returns whether any arrived.
+    ///
+    /// Messages that failed aren't retried until the view is reopened.
+    pub fn poll(&mut self) -> bool {
+        let mut arrived = false;
+        loop {
+            match self.responses.try_recv() {
+                Ok((guid, result)) => {
+                    self.pending.remove(&guid);
+                    match result {
+                        Ok(translation) => {
+                            arrived |= !translation.is_empty();
+                            self.translations.insert(guid, translation);
+                        }
+                        Err(e) => {
+                            self.translations.insert(guid, String::new());
+                            self.error = Some(e);
+                        }
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.pending.clear();
+                    break;
+                }
+            }
+        }
+        arrived
+    }
+
+    /// Take the error from the last failed translation, if any.
+    pub fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+
+    /// Get a message's translation, if it's ready and says something the original doesn't.
+    pub fn get(&self, guid: &str) -> Option<&str> {
+        self.translations
+            .get(guid)
+            .map(String::as_str)
+            .filter(|translation| !translation.is_empty())
+    }
+}
+
+/// Run a message's text through the translation command.
+///
+/// A translation that matches the original, as when the message is already in the target
+/// language, comes back empty so it isn't shown twice.
+fn translate(command: &str, text: &str, timeout: Duration) -> Result<String> {
+    let translation = run_command(command, text.as_bytes(), timeout, "translate")?;
+    let translation = translation.trim();
+    if translation == text.trim() {
+        Ok(String::new())
+    } else {
+        Ok(translation.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate() {
+        let timeout = Duration::from_secs(5);
+        assert_eq!(translate("tr a-z A-Z", "hola\n", timeout).unwrap(), "HOLA");
+        assert_eq!(translate("cat", "hello", timeout).unwrap(), "");
+    }
+}
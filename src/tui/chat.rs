@@ -1,235 +1,3069 @@
-use crate::db::MessageDB;
-use crate::error::Result;
+use crate::activity::{self, Activity, HEATMAP_CELLS, HEATMAP_WEEKS, WEEKDAY_LABELS};
+use crate::attachments::{format_size, open_attachment, AttachmentKind};
+use crate::birthdays::BirthdayReminder;
+use crate::config::{CarefulSend, Config, OpenAt, SendKey, MAX_QUICK_REPLIES};
+use crate::db::{self, Attachment, Message, MessageDB, MessageDBPool, PooledMessageDB};
+use crate::error::{Error, Result};
+use crate::formatter::{
+    display_width, format_display_number, format_duration, looks_like_code, message_parts,
+    pad_to_width, sms_length, split_message, truncate_graphemes,
+};
+use crate::gifs::{self, gif_query, Gif};
+use crate::i18n::t;
+use crate::notifications::Notifier;
+use crate::paths;
+use crate::reactions;
+use crate::search::SearchQuery;
+use crate::send_queue::{DuplicateGuard, RateLimit, RetryPolicy, SendQueue, SendStatus};
 use crate::sender::Sender;
-use crate::tui::common::{run_terminal, TuiResult};
+use crate::state::{Session, StarredMessage, StateStore};
+use crate::suggestions::Suggester;
+use crate::summarize;
+use crate::tags::{format_tags, parse_tags};
+use crate::thumbnails::{ThumbnailCache, PREVIEW_ROWS};
+use crate::timestamps::TimeFormatter;
+use crate::transcript::TranscriptLog;
+use crate::translate::Translator;
+use crate::tui::common::{
+    avatar_span, focus_style, keyboard_enhanced, run_terminal, sandbox_banner, FocusRing,
+    Focusable, TuiResult,
+};
+use crate::tui::input::{enter_action, EnterAction, TextInput};
+use crate::tui::jumps::JumpList;
+use crate::tui::markdown::{render_markdown, render_plain};
+use crate::tui::minimap::{minimap_lines, Mark};
+use crate::tui::modal::{Modal, ModalResult};
+use crate::tui::paging::{MessageWindow, PAGE_SIZE};
+use crate::tui::preview::image_lines;
+use crate::tui::refresh::RefreshRate;
+use crate::tui::switcher::{Switcher, SwitcherAction};
+use crate::tui::toast::Toast;
+use crate::voice::{is_voice_command, Recorder};
+use crate::watcher::{ConfigWatcher, DbWatcher};
+use crate::words::WordCounts;
 use chrono::{DateTime, Local};
-use crossterm::event::{Event, KeyCode, KeyModifiers};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, Paragraph},
+    widgets::{block::Title, Block, Borders, Paragraph, Wrap},
 };
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
 use std::time::{Duration, Instant};
 
-/// UI update rate (milliseconds)
-const TICK_RATE_MS: u64 = 100;
+/// Rows taken up by the title and input boxes
+const CHROME_HEIGHT: u16 = 6;
+
+/// Rows taken up by the input box
+const INPUT_HEIGHT: u16 = 3;
+
+/// How many of the latest messages are cached for the next time the chat opens
+const CACHED_MESSAGES: usize = 50;
+
+/// Most pinned messages shown before the pin board scrolls off
+const MAX_PINNED_ROWS: u16 = 5;
+
+/// Columns of the activity panel given to the words used most, when there's room
+const ACTIVITY_WORDS_WIDTH: u16 = 36;
+
+/// Most search results shown
+const MAX_SEARCH_RESULTS: usize = 200;
+
+/// Most similar handles offered when a chat has no history
+const MAX_SIMILAR_HANDLES: usize = 5;
+
+/// Frames of the loading spinner
+const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+/// How long each spinner frame is shown (milliseconds)
+const SPINNER_FRAME_MS: u128 = 80;
+
+/// Result of opening chat.db and running the first query in the background
+type InitialLoad = Result<(PooledMessageDB, Vec<Message>)>;
+
+/// Stands in for a guid on lines of messages that are still in the send queue.
+const OUTGOING_GUID: &str = "outgoing";
+
+/// A line in the message area
+struct ChatLine<'a> {
+    /// Guid of the message the line belongs to, `OUTGOING_GUID` for queued messages, or `None`
+    /// for the new messages divider
+    guid: Option<&'a str>,
+    line: Line<'static>,
+}
+
+/// Panes of the chat view that can hold focus
+#[derive(Clone, Copy, PartialEq)]
+enum ChatPane {
+    /// The composer
+    Input,
+    /// The message list, where messages can be selected
+    Messages,
+}
+
+impl Focusable for ChatPane {
+    const ORDER: &'static [Self] = &[ChatPane::Input, ChatPane::Messages];
+}
+
+/// Sides of a split chat window
+#[derive(Clone, Copy, PartialEq)]
+enum SplitPane {
+    Left,
+    Right,
+}
+
+impl Focusable for SplitPane {
+    const ORDER: &'static [Self] = &[SplitPane::Left, SplitPane::Right];
+}
+
+/// What an open modal in the chat view is asking about
+#[derive(Clone)]
+enum ChatModal {
+    /// Name to save the contact as
+    SaveContact,
+    /// Whether to point the contact saved under this name at the conversation, or keep it
+    UpdateContact(String),
+    /// Whether to save the conversation under this name although another contact has its
+    /// handle
+    SaveContactAgain(String),
+    /// Whether to throw away the composer contents
+    ClearDraft,
+    /// Which of these handles to use instead of the contact's identifier
+    SimilarHandle(Vec<String>),
+    /// Whether to send the same text as the last message again so soon: the composer contents,
+    /// or a quick reply
+    SendAgain(Option<String>),
+    /// Whether to send a message to a careful contact: the composer contents, or a quick reply
+    SendCarefully(Option<String>),
+    /// Whether to send an attachment to a careful contact
+    SendAttachmentCarefully(PendingAttachment),
+    /// Whether to send a message that takes more than one SMS segment
+    SendLong,
+    /// Whether to send a message over `large_message_bytes` as a text file: the composer
+    /// contents, or pasted text
+    SendLarge(Option<String>),
+    /// What to search the conversation for
+    Search,
+    /// Tags for the message with this guid
+    Tag(String),
+    /// A summary of the conversation, shown until dismissed
+    Summary,
+}
+
+/// An attachment ready to send once a careful contact's confirmation is answered
+#[derive(Clone)]
+enum PendingAttachment {
+    /// The GIF being previewed
+    Gif,
+    /// The voice memo being reviewed
+    VoiceMemo,
+    /// Text over `large_message_bytes` as a text file: the composer contents, or pasted text
+    TextFile(Option<String>),
+}
 
-/// How often to check for new messages (milliseconds)
-const POLL_INTERVAL_MS: u64 = 500;
+impl PendingAttachment {
+    /// What the attachment is called in prompts and notices
+    fn name(&self) -> &'static str {
+        match self {
+            PendingAttachment::Gif => "GIF",
+            PendingAttachment::VoiceMemo => "voice memo",
+            PendingAttachment::TextFile(_) => "text file",
+        }
+    }
+}
 
 /// The chat view for messaging with a contact
 pub struct ChatView {
-    messages: Vec<(Option<String>, DateTime<Local>, Option<String>, bool)>,
-    input: String,
+    /// Connection to the Messages database, once the background load has opened it
+    db: Option<PooledMessageDB>,
+    /// Receives the first load of messages while it runs in the background
+    loading: Option<Receiver<InitialLoad>>,
+    /// Detects when Messages.app writes to chat.db
+    watcher: DbWatcher,
+    messages: Vec<Message>,
+    /// Bounds `messages` and tracks whether older ones can be loaded
+    window: MessageWindow,
+    input: TextInput,
     scroll: usize,
     contact: String,
+    /// Every identifier whose messages are shown, `contact` first
+    handles: Vec<String>,
     display_name: String,
+    /// The contact's initials and avatar color, if imported from Contacts.app
+    avatar: Option<(String, String)>,
     should_reset_scroll: bool,
-    sender: Sender,
+    /// Messages on their way to Messages.app, retried while it's unavailable
+    outbox: SendQueue,
+    state: StateStore,
+    /// Seen watermark from when the view was opened
+    seen_at_open: Option<i64>,
+    /// Which pane keys go to
+    focus: FocusRing<ChatPane>,
+    /// Guid of the selected message while the message list has focus
+    selected: Option<String>,
+    /// Guids of starred messages in this conversation
+    starred: HashSet<String>,
+    /// Tags of messages in this conversation, by guid
+    tags: HashMap<String, Vec<String>>,
+    /// Messages pinned to the top of this conversation, oldest first
+    pinned: Vec<StarredMessage>,
+    /// Whether the pin board is collapsed to a single line
+    pins_collapsed: bool,
+    /// Message and word counts shown in the activity panel while it's open
+    activity: Option<(Activity, WordCounts)>,
+    /// Message to show context around instead of following the latest messages
+    anchor: Option<i64>,
+    /// Search whose results are shown instead of the conversation
+    search: Option<String>,
+    /// Guids of the results of the last search, marked on the minimap after jumping to one
+    search_hits: HashSet<String>,
+    /// Unix time of the last query, to find messages whose status changed since
+    last_checked: i64,
+    /// Whether to render Markdown-style formatting in messages
+    markdown: bool,
+    /// Which key sends the composer contents
+    send_key: SendKey,
+    /// How messages are confirmed before they're sent, if the contact is marked careful
+    careful: Option<CarefulSend>,
+    /// The contact's canned replies, sent with Alt+1 to Alt+9
+    quick_replies: Vec<String>,
+    /// Runs the reply suggestion command, if one is configured
+    suggester: Option<Suggester>,
+    /// Replies suggested for the latest message, offered after the quick replies
+    suggestions: Vec<String>,
+    /// Receives the summary of the conversation while the summarize command runs
+    summary: Option<Receiver<Result<String>>>,
+    /// Translates the contact's messages, if they have `translate` on
+    translator: Option<Translator>,
+    /// Log the messages shown are appended to, if transcripts are on
+    transcript: Option<TranscriptLog>,
+    /// Formats message times
+    times: TimeFormatter,
+    /// Whether the contact isn't saved in the configuration
+    unsaved: bool,
+    /// Whether the conversation comes from an archive, so nothing can be sent
+    read_only: bool,
+    /// Conversation switcher popup, when open
+    switcher: Option<Switcher>,
+    /// Pasted code waiting for the user to choose how to send it
+    pasted_code: Option<String>,
+    /// Search started with `/gif`, running in the background
+    gif_search: Option<Receiver<Result<Gif>>>,
+    /// A found GIF shown in place of the composer until it's sent or dismissed
+    gif: Option<Gif>,
+    /// A voice memo started with `/voice`, shown in place of the composer while it's recorded
+    /// and until it's sent or discarded
+    recording: Option<Recorder>,
+    /// A confirmed GIF or voice memo being sent in the background
+    attachment_send: Option<Receiver<Result<()>>>,
+    /// Dialog over the view, when open
+    modal: Option<Modal<ChatModal>>,
+    /// Whether a search for handles similar to the contact found nothing
+    no_similar_handles: bool,
+    /// Service of the latest message with the contact (e.g., "iMessage" or "SMS")
+    service: Option<String>,
+    /// Whether to show image attachments inline
+    previews: bool,
+    /// Image and video attachments of loaded messages, by message rowid
+    media: HashMap<i64, Vec<Attachment>>,
+    /// Downscaled previews of `media`
+    thumbnails: ThumbnailCache,
+    /// Notice shown over the messages, such as why a message failed to send
+    toast: Option<Toast>,
+    /// Message to scroll to the top of the screen once messages load, from a restored session
+    restore_top: Option<String>,
+    /// Whether to scroll to the first unread message once messages load, rather than
+    /// following the latest
+    open_at_unread: bool,
+    /// Rows of messages on screen as of the last frame
+    visible_height: usize,
+    /// Another conversation shown side by side with this one
+    split: Option<Box<ChatView>>,
+    /// Which side of the split keys go to
+    split_focus: FocusRing<SplitPane>,
+    /// Switcher choosing the conversation to show in the split
+    split_picker: Option<Switcher>,
     last_refresh: Instant,
+    /// Configuration when the view opened, for naming the people group events mention
+    config: Config,
 }
 
 impl ChatView {
     /// Create a new chat view for a contact
-    pub fn new(contact: String, display_name: String) -> Self {
-        Self {
+    pub fn new(contact: String, display_name: String) -> Result<Self> {
+        // Archives are browsed without touching the drafts, caches, and seen messages of
+        // live conversations
+        let read_only = db::is_archive();
+        let state = if read_only {
+            StateStore::in_memory()?
+        } else {
+            StateStore::open()?
+        };
+        let input = TextInput::with_value(state.draft(&contact)?.unwrap_or_default());
+        let seen_at_open = state.last_seen(&contact)?;
+        let starred = state.starred_guids(&contact)?;
+        let tags = state.message_tags(&contact)?;
+        let pinned = state.pinned_messages(&contact)?;
+        let config = Config::load()?;
+        let unsaved = !read_only && !config.is_saved_identifier(&contact);
+        let transcript = if config.transcript().enabled && !read_only {
+            Some(TranscriptLog::open(&state, &contact, &display_name)?)
+        } else {
+            None
+        };
+        let careful = config
+            .careful_for(&contact)
+            .then_some(config.chat().careful_send);
+        let handles = if config.chat().merge_identifiers {
+            config.identifiers_for(&contact)
+        } else {
+            vec![contact.clone()]
+        };
+        // Messages spooled while Messages.app was unavailable go out first. Replies to group
+        // chats are left for `deliver_spooled`, which sends them to the group.
+        let mut outbox = SendQueue::new(
+            Sender::from_config(&config, contact.clone()),
+            RetryPolicy::new(config.sender()),
+            DuplicateGuard::new(config.sender()),
+            RateLimit::new(config.sender()),
+        );
+        for message in state.spooled_messages(Some(&contact))? {
+            if message.chat.is_none() {
+                outbox.push_spooled(message);
+            }
+        }
+
+        Ok(Self {
+            db: None,
+            loading: None,
+            watcher: DbWatcher::new()?,
             messages: Vec::new(),
-            input: String::new(),
+            window: MessageWindow::new(config.chat().max_messages),
+            input,
             scroll: 0,
             contact: contact.clone(),
+            handles,
             display_name,
+            avatar: config
+                .avatar_for(&contact)
+                .map(|(initials, color)| (initials.to_string(), color.to_string())),
             should_reset_scroll: true,
-            sender: Sender::new(contact),
+            outbox,
+            state,
+            seen_at_open,
+            focus: FocusRing::new(),
+            selected: None,
+            starred,
+            tags,
+            pinned,
+            pins_collapsed: false,
+            activity: None,
+            anchor: None,
+            search: None,
+            search_hits: HashSet::new(),
+            last_checked: 0,
+            markdown: config.chat().markdown,
+            send_key: match careful {
+                Some(CarefulSend::CtrlEnter) => SendKey::CtrlEnter,
+                _ => config.chat().send_key,
+            },
+            careful,
+            quick_replies: config.quick_replies_for(&contact),
+            suggester: Suggester::new(config.suggestions()),
+            suggestions: Vec::new(),
+            summary: None,
+            translator: if config.translate_for(&contact) {
+                Translator::new(config.translate())
+            } else {
+                None
+            },
+            transcript,
+            times: TimeFormatter::new(config.time()),
+            unsaved,
+            read_only,
+            switcher: None,
+            pasted_code: None,
+            gif_search: None,
+            gif: None,
+            recording: None,
+            attachment_send: None,
+            modal: None,
+            no_similar_handles: false,
+            service: None,
+            previews: config.chat().previews,
+            media: HashMap::new(),
+            thumbnails: ThumbnailCache::new(),
+            toast: None,
+            restore_top: None,
+            open_at_unread: config.open_at_for(&contact) == OpenAt::FirstUnread,
+            visible_height: 0,
+            split: None,
+            split_focus: FocusRing::new(),
+            split_picker: None,
             last_refresh: Instant::now(),
+            config,
+        })
+    }
+
+    /// Open the view around a specific message, with it selected
+    pub fn jump_to(&mut self, rowid: i64) {
+        self.anchor = Some(rowid);
+    }
+
+    /// Show cached messages and start loading fresh ones in the background
+    fn start_loading(&mut self) -> Result<()> {
+        if self.anchor.is_none() {
+            self.messages = self.state.cached_messages(&self.contact)?;
         }
+
+        let (tx, rx) = mpsc::channel();
+        let handles = self.handles.clone();
+        let anchor = self.anchor;
+        thread::spawn(move || {
+            let result = MessageDBPool::shared().get().and_then(|db| {
+                let messages = fetch_messages(&db, &handles, anchor)?;
+                Ok((db, messages))
+            });
+            // The view may have closed before the load finished
+            let _ = tx.send(result);
+        });
+
+        self.loading = Some(rx);
+        Ok(())
+    }
+
+    /// Apply the background load if it has finished
+    fn poll_loading(&mut self) -> Result<()> {
+        let Some(rx) = &self.loading else {
+            return Ok(());
+        };
+
+        let (db, messages) = match rx.try_recv() {
+            Ok(result) => {
+                self.loading = None;
+                result?
+            }
+            Err(TryRecvError::Empty) => return Ok(()),
+            Err(TryRecvError::Disconnected) => {
+                self.loading = None;
+                return Ok(());
+            }
+        };
+
+        // Only used for the length counter, so a failed lookup isn't fatal
+        self.service = db.get_handle_service(&self.contact).ok().flatten();
+        self.db = Some(db);
+        // Cached messages were a placeholder, so scroll to the fresh latest message
+        self.should_reset_scroll = self.anchor.is_none();
+        self.apply_messages(messages)
     }
 
     /// Load messages from the database
     pub fn load_messages(&mut self) -> Result<()> {
-        let db = MessageDB::open()?;
-        let mut messages = db.get_messages(&self.contact)?;
-        // Reverse the messages so oldest are at the top
-        messages.reverse();
+        let Some(db) = &self.db else {
+            // Still loading in the background
+            return Ok(());
+        };
+
+        let messages = fetch_messages(db, &self.handles, self.anchor)?;
+        self.apply_messages(messages)
+    }
 
-        // Check if we need to auto-scroll when new messages arrive
-        if !self.messages.is_empty() && messages.len() > self.messages.len() {
+    /// Fetch only messages that arrived or changed since the last query
+    fn refresh_messages(&mut self) -> Result<()> {
+        let Some(db) = self.db.as_ref().filter(|_| self.search.is_none()) else {
+            return Ok(());
+        };
+        let (Some(oldest), Some(newest)) = (
+            self.messages.iter().map(|m| m.rowid).min(),
+            self.messages.iter().map(|m| m.rowid).max(),
+        ) else {
+            return self.load_messages();
+        };
+
+        let checked_at = Local::now().timestamp();
+        let changed = db.get_messages_since(&self.handles, newest, oldest, self.last_checked)?;
+        self.last_checked = checked_at;
+        self.last_refresh = Instant::now();
+
+        let mut appended = false;
+        for message in changed {
+            if let Some(existing) = self.messages.iter_mut().find(|m| m.guid == message.guid) {
+                *existing = message;
+            } else if self.anchor.is_none() {
+                // Anchored views only show context around a message, so new ones wait for End
+                self.messages.push(message);
+                appended = true;
+            }
+        }
+
+        if appended {
+            // Reading back through history keeps the messages being read
+            if self.selected.is_none() {
+                let evicted = self.window.evict_oldest(&mut self.messages);
+                if let Some(first) = self.messages.first().filter(|_| evicted > 0) {
+                    let first = first.rowid;
+                    self.media.retain(|rowid, _| *rowid >= first);
+                }
+            }
             self.should_reset_scroll = true;
+            if let Some(last) = self.messages.last() {
+                self.state.mark_seen(&self.contact, last.rowid)?;
+            }
+        }
+        self.load_media()
+    }
+
+    /// Load the page of messages before the first one held, keeping the same lines on screen.
+    ///
+    /// Past `chat.max_messages`, the newest messages are dropped and new ones wait until `End`
+    /// returns to the latest, as when jumping to a message.
+    fn load_older(&mut self) -> Result<()> {
+        let Some(db) = self
+            .db
+            .as_ref()
+            .filter(|_| self.search.is_none() && self.window.has_older())
+        else {
+            return Ok(());
+        };
+        let Some(first) = self.messages.first() else {
+            return Ok(());
+        };
+
+        let mut older = db.get_messages_before(&self.handles, first.rowid, PAGE_SIZE)?;
+        self.window.older_loaded(older.len());
+        if older.is_empty() {
+            return Ok(());
+        }
+        older.reverse();
+        let lines_before = self.lines().len();
+        older.append(&mut self.messages);
+        self.messages = older;
+        if self.window.evict_newest(&mut self.messages) > 0 && self.anchor.is_none() {
+            self.anchor = self.messages.last().map(|m| m.rowid);
+        }
+        self.scroll += self.lines().len().saturating_sub(lines_before);
+        self.should_reset_scroll = false;
+
+        self.load_media()
+    }
+
+    /// Queue the contact's messages on screen for translation, so scrolled-back history isn't
+    /// run through the command until it's read
+    fn request_translations(&mut self, visible_height: usize) {
+        let Some(translator) = &self.translator else {
+            return;
+        };
+        let lines = self.lines();
+        let end = (self.scroll + visible_height).min(lines.len());
+        let visible: HashSet<&str> = lines
+            .get(self.scroll..end)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|line| line.guid)
+            .collect();
+        let requests: Vec<(String, String)> = self
+            .messages
+            .iter()
+            .filter(|m| !m.is_from_me && visible.contains(m.guid.as_str()))
+            .filter(|m| !translator.is_requested(&m.guid))
+            .filter_map(|m| {
+                let text = m.text.as_deref().filter(|t| !t.trim().is_empty())?;
+                Some((m.guid.clone(), text.to_string()))
+            })
+            .collect();
+        drop(lines);
+
+        let translator = self.translator.as_mut().expect("translator is set");
+        for (guid, text) in requests {
+            translator.request(&guid, &text);
+        }
+    }
+
+    /// Look up image and video attachments of loaded messages and queue their previews
+    fn load_media(&mut self) -> Result<()> {
+        let Some(db) = self.db.as_ref().filter(|_| self.previews) else {
+            return Ok(());
+        };
+
+        for message in &self.messages {
+            if !matches!(message.message_type.as_deref(), Some("Image" | "Video"))
+                || self.media.contains_key(&message.rowid)
+            {
+                continue;
+            }
+
+            let media: Vec<Attachment> = db
+                .get_attachments(message.rowid)?
+                .into_iter()
+                .filter(|attachment| {
+                    matches!(
+                        AttachmentKind::of(attachment),
+                        AttachmentKind::Image | AttachmentKind::Video
+                    )
+                })
+                .collect();
+            for attachment in &media {
+                self.thumbnails.request(attachment);
+            }
+            self.media.insert(message.rowid, media);
+        }
+        Ok(())
+    }
+
+    /// Replace the shown messages with freshly loaded ones, oldest first
+    fn apply_messages(&mut self, messages: Vec<Message>) -> Result<()> {
+        // Select the anchored message the first time it's loaded
+        if let Some(rowid) = self.anchor {
+            if self.selected.is_none() {
+                self.selected = messages
+                    .iter()
+                    .find(|m| m.rowid == rowid)
+                    .map(|m| m.guid.clone());
+                if self.selected.is_some() {
+                    self.focus.focus(ChatPane::Messages);
+                }
+                self.should_reset_scroll = false;
+            }
+        }
+
+        // Everything loaded is on screen, so mark it as seen
+        if let Some(last) = messages.last() {
+            self.state.mark_seen(&self.contact, last.rowid)?;
         }
 
+        self.window
+            .reset(self.anchor.is_some() || messages.len() >= PAGE_SIZE);
         self.messages = messages;
+        self.last_checked = Local::now().timestamp();
         self.last_refresh = Instant::now();
 
+        self.load_media()
+    }
+
+    /// Index of the first message received since the chat was last open, which the new
+    /// messages divider goes above
+    fn first_unread(&self) -> Option<usize> {
+        self.seen_at_open.and_then(|seen| {
+            self.messages
+                .iter()
+                .position(|m| !m.is_from_me && m.rowid > seen)
+        })
+    }
+
+    /// Build the lines shown in the message area, including the new messages divider
+    fn lines(&self) -> Vec<ChatLine<'_>> {
+        let divider_idx = self.first_unread();
+
+        // Relative times are recomputed every frame, so they stay current
+        let now = Local::now();
+        let mut lines = Vec::with_capacity(self.messages.len() + 1);
+        for (idx, message) in self.messages.iter().enumerate() {
+            if Some(idx) == divider_idx {
+                lines.push(ChatLine {
+                    guid: None,
+                    line: Line::styled("── New Messages ──", Style::default().fg(Color::Red))
+                        .alignment(Alignment::Center),
+                });
+            }
+            lines.extend(
+                self.message_lines(message, &now)
+                    .into_iter()
+                    .map(|line| ChatLine {
+                        guid: Some(message.guid.as_str()),
+                        line,
+                    }),
+            );
+        }
+        if self.anchor.is_none() && self.search.is_none() {
+            lines.extend(self.outgoing_lines().into_iter().map(|line| ChatLine {
+                guid: Some(OUTGOING_GUID),
+                line,
+            }));
+        }
+        lines
+    }
+
+    /// Render a message as one or more lines, prefixed with its time
+    fn message_lines(&self, message: &Message, now: &DateTime<Local>) -> Vec<Line<'static>> {
+        // Changes to a group chat are a line of their own in the middle
+        if let Some(event) = &message.event {
+            let mut style = Style::default().fg(Color::DarkGray);
+            if self.selected.as_ref() == Some(&message.guid) {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            let text = event.describe(|handle| {
+                self.config
+                    .display_name_for(handle)
+                    .unwrap_or_else(|| format_display_number(handle))
+            });
+            return vec![Line::styled(text, style).alignment(Alignment::Center)];
+        }
+
+        let alignment = if message.is_from_me {
+            Alignment::Right
+        } else {
+            Alignment::Left
+        };
+
+        let mut style = if message.is_from_me {
+            Style::default().fg(Color::Blue)
+        } else {
+            Style::default().fg(Color::Green)
+        };
+        if self.selected.as_ref() == Some(&message.guid) {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+
+        let star = if self.starred.contains(&message.guid) {
+            "★ "
+        } else {
+            ""
+        };
+        // Merged timelines tag each message with the service it went over
+        let service = match &message.service {
+            Some(service) if self.handles.len() > 1 => format!(" [{}]", service),
+            _ => String::new(),
+        };
+        let tags = match self.tags.get(&message.guid) {
+            Some(tags) => format!(" {}", format_tags(tags)),
+            None => String::new(),
+        };
+        let prefix = format!(
+            "{}{}{}{}: ",
+            star,
+            self.times.format(&message.date, now),
+            service,
+            tags
+        );
+        let indent = " ".repeat(display_width(&prefix));
+
+        let content = message_content(message);
+        let mut lines = if self.markdown {
+            render_markdown(&content, style)
+        } else {
+            render_plain(&content, style)
+        };
+        for (i, line) in lines.iter_mut().enumerate() {
+            let lead = if i == 0 {
+                prefix.clone()
+            } else {
+                indent.clone()
+            };
+            line.spans.insert(0, Span::styled(lead, style));
+            line.alignment = Some(alignment);
+        }
+
+        if !message.reactions.is_empty() {
+            let mut line = Line::styled(
+                format!("{}{}", indent, reactions::summary(&message.reactions)),
+                Style::default().fg(Color::DarkGray),
+            );
+            line.alignment = Some(alignment);
+            lines.push(line);
+        }
+
+        // Translations go beneath the original, dimmed
+        if let Some(translation) = self
+            .translator
+            .as_ref()
+            .and_then(|translator| translator.get(&message.guid))
+        {
+            let dimmed = Style::default()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::ITALIC);
+            for mut line in render_plain(translation, dimmed) {
+                line.spans.insert(0, Span::raw(indent.clone()));
+                line.alignment = Some(alignment);
+                lines.push(line);
+            }
+        }
+
+        let previews = self
+            .media
+            .get(&message.rowid)
+            .into_iter()
+            .flatten()
+            .filter_map(|attachment| self.thumbnails.get(&attachment.guid));
+        for preview in previews {
+            let mut preview_lines = preview.image.as_ref().map(image_lines).unwrap_or_default();
+            if let Some(duration) = preview.duration {
+                preview_lines.push(Line::styled(
+                    format!("▶ {}", format_duration(duration)),
+                    style,
+                ));
+            }
+            for mut line in preview_lines {
+                line.spans.insert(0, Span::raw(indent.clone()));
+                line.alignment = Some(alignment);
+                lines.push(line);
+            }
+        }
+        lines
+    }
+
+    /// Index of the selected message, if any
+    fn selected_index(&self) -> Option<usize> {
+        let guid = self.selected.as_ref()?;
+        self.messages.iter().position(|m| &m.guid == guid)
+    }
+
+    /// Move the selection by `delta` messages, entering selection mode at the latest message
+    fn move_selection(&mut self, delta: isize) {
+        let idx = match self.selected_index() {
+            Some(idx) => idx.saturating_add_signed(delta),
+            None => self.messages.len().saturating_sub(1),
+        };
+        self.selected = self
+            .messages
+            .get(idx.min(self.messages.len().saturating_sub(1)))
+            .map(|m| m.guid.clone());
+    }
+
+    /// Move focus to the next pane, selecting the latest message when the list gets focus
+    fn cycle_focus(&mut self) {
+        self.focus.next();
+        match self.focus.current() {
+            ChatPane::Messages => self.move_selection(0),
+            ChatPane::Input => self.selected = None,
+        }
+    }
+
+    /// Scroll so the selected message is within the visible rows
+    fn scroll_to_selection(&mut self, visible_rows: usize) {
+        let Some(guid) = self.selected.as_deref() else {
+            return;
+        };
+        let lines = self.lines();
+        let Some(first) = lines.iter().position(|l| l.guid == Some(guid)) else {
+            return;
+        };
+        let last = lines
+            .iter()
+            .rposition(|l| l.guid == Some(guid))
+            .unwrap_or(first);
+
+        if first < self.scroll {
+            self.scroll = first;
+        } else if last >= self.scroll + visible_rows {
+            // Show as much of a long message as fits, starting from its first line
+            self.scroll = (last + 1).saturating_sub(visible_rows).min(first);
+        }
+    }
+
+    /// Star or unstar the selected message
+    fn toggle_selected_star(&mut self) -> Result<()> {
+        let Some(message) = self.selected_index().map(|idx| &self.messages[idx]) else {
+            return Ok(());
+        };
+
+        if self.starred.remove(&message.guid) {
+            self.state.unstar(&message.guid)?;
+        } else {
+            self.state.star(&StarredMessage {
+                guid: message.guid.clone(),
+                conversation: self.contact.clone(),
+                rowid: message.rowid,
+                text: Some(message_content(message)),
+                date: message.date,
+                is_from_me: message.is_from_me,
+            })?;
+            self.starred.insert(message.guid.clone());
+        }
         Ok(())
     }
 
-    /// Send a message to the contact
-    pub fn send_message(&mut self, text: &str) -> Result<()> {
-        self.sender.send_message(text)?;
-        // Reload messages to show the sent message
-        self.load_messages()?;
+    /// Ask for the selected message's tags, starting from the ones it has
+    fn open_tag_prompt(&mut self) {
+        let Some(message) = self.selected_index().map(|idx| &self.messages[idx]) else {
+            return;
+        };
+        let tags = self
+            .tags
+            .get(&message.guid)
+            .map(|tags| format!("{} ", format_tags(tags)))
+            .unwrap_or_default();
+        self.modal = Some(Modal::prompt(
+            ChatModal::Tag(message.guid.clone()),
+            t!("chat.tags_title"),
+            t!("chat.tags_prompt"),
+            TextInput::with_value(tags),
+        ));
+    }
+
+    /// Replace the tags of the message with `guid`
+    fn tag_message(&mut self, guid: &str, text: &str) -> Result<()> {
+        let Some(message) = self.messages.iter().find(|m| m.guid == guid) else {
+            return Ok(());
+        };
+        let tags = parse_tags(text);
+        self.state.set_tags(
+            &StarredMessage {
+                guid: message.guid.clone(),
+                conversation: self.contact.clone(),
+                rowid: message.rowid,
+                text: Some(message_content(message)),
+                date: message.date,
+                is_from_me: message.is_from_me,
+            },
+            &tags,
+        )?;
+        if tags.is_empty() {
+            self.tags.remove(guid);
+        } else {
+            self.tags.insert(guid.to_string(), tags);
+        }
         Ok(())
     }
 
-    /// Run the chat view
-    pub fn run(&mut self) -> Result<()> {
-        run_terminal(|terminal| self.run_ui(terminal))
+    /// Pin or unpin the selected message
+    fn toggle_selected_pin(&mut self) -> Result<()> {
+        let Some(message) = self.selected_index().map(|idx| &self.messages[idx]) else {
+            return Ok(());
+        };
+
+        if let Some(idx) = self.pinned.iter().position(|p| p.guid == message.guid) {
+            self.state.unpin_message(&message.guid)?;
+            self.pinned.remove(idx);
+        } else {
+            let pinned = StarredMessage {
+                guid: message.guid.clone(),
+                conversation: self.contact.clone(),
+                rowid: message.rowid,
+                text: Some(message_content(message)),
+                date: message.date,
+                is_from_me: message.is_from_me,
+            };
+            self.state.pin_message(&pinned)?;
+            self.pinned.push(pinned);
+            self.pinned.sort_by_key(|p| p.date);
+            self.pins_collapsed = false;
+        }
+        Ok(())
     }
 
-    /// Handle the UI loop
-    fn run_ui(
-        &mut self,
-        terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
-    ) -> TuiResult<()> {
-        // Load messages
-        self.load_messages()?;
+    /// Open the selected message's first photo or video, videos in QuickTime
+    fn open_selected_media(&mut self) {
+        let attachment = self
+            .selected_index()
+            .and_then(|idx| self.media.get(&self.messages[idx].rowid))
+            .and_then(|media| media.first());
+        if let Some(attachment) = attachment {
+            if let Err(e) = open_attachment(attachment) {
+                self.toast = Some(Toast::new(
+                    t!("chat.open_attachment_failed"),
+                    Some(e.to_string()),
+                ));
+            }
+        }
+    }
 
-        let tick_rate = Duration::from_millis(TICK_RATE_MS);
-        let poll_interval = Duration::from_millis(POLL_INTERVAL_MS);
-        let mut last_tick = Instant::now();
+    /// Rows taken up by the pin board
+    fn pin_board_height(&self) -> u16 {
+        if self.pinned.is_empty() {
+            0
+        } else if self.pins_collapsed {
+            1
+        } else {
+            (self.pinned.len() as u16).min(MAX_PINNED_ROWS) + 2
+        }
+    }
 
-        loop {
-            // Check if it's time to refresh messages
-            if self.last_refresh.elapsed() >= poll_interval {
-                // Check for new messages
-                if let Err(e) = self.load_messages() {
-                    eprintln!("Error loading messages: {}", e);
-                }
+    /// Rows taken up by the activity panel: month labels, a row per weekday, the hourly
+    /// sparkline, and borders
+    fn activity_height(&self) -> u16 {
+        if self.activity.is_some() {
+            11
+        } else {
+            0
+        }
+    }
+
+    /// Open the activity panel with this year's message and word counts, or close it
+    fn toggle_activity(&mut self) -> Result<()> {
+        self.activity = match self.activity {
+            Some(_) => None,
+            None => {
+                let db = MessageDBPool::shared().get()?;
+                let today = Local::now().date_naive();
+                let messages =
+                    db.get_conversation_since(&self.handles, activity::year_start(today)?)?;
+                Some((
+                    Activity::load(&db, &self.handles, today)?,
+                    WordCounts::count(&messages),
+                ))
             }
+        };
+        Ok(())
+    }
 
-            // Reset scroll position if needed
-            if self.should_reset_scroll && !self.messages.is_empty() {
-                let size = terminal.size()?;
-                let visible_messages = self.messages.len().min((size.height - 6) as usize);
-                self.scroll = self.messages.len().saturating_sub(visible_messages);
-                self.should_reset_scroll = false;
+    /// Rows taken up by the quick replies above the composer
+    fn quick_reply_height(&self) -> u16 {
+        if self.quick_replies.is_empty() && self.suggestions.is_empty() {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Rows for the input box, or the GIF preview that replaces it
+    fn input_height(&self) -> u16 {
+        if self.gif.is_some() {
+            PREVIEW_ROWS as u16 + 2
+        } else {
+            INPUT_HEIGHT
+        }
+    }
+
+    /// The contact's quick replies followed by suggested replies, one for each Alt+number
+    fn reply_chips(&self) -> impl Iterator<Item = &String> {
+        self.quick_replies
+            .iter()
+            .chain(&self.suggestions)
+            .take(MAX_QUICK_REPLIES)
+    }
+
+    /// Send the quick or suggested reply for Alt+`digit`, leaving any draft in the composer
+    fn send_quick_reply(&mut self, digit: char) {
+        let Some(text) = digit
+            .to_digit(10)
+            .and_then(|number| self.reply_chips().nth((number as usize).checked_sub(1)?))
+            .cloned()
+        else {
+            return;
+        };
+        if self.read_only {
+            self.toast = Some(Toast::error(&read_only_error()));
+        } else if self.careful.is_some() {
+            self.confirm_careful_send(Some(text));
+        } else if self.outbox.is_duplicate(&text) {
+            self.confirm_send_again(Some(text));
+        } else {
+            self.send_reply(text);
+        }
+    }
+
+    /// Send a quick or suggested reply
+    fn send_reply(&mut self, text: String) {
+        self.outbox.push(text);
+        self.suggestions.clear();
+        self.should_reset_scroll = true;
+        if self.anchor.take().is_some() {
+            if let Err(e) = self.load_messages() {
+                self.toast = Some(Toast::error(&e));
             }
+        }
+    }
 
-            // Draw UI
-            terminal.draw(|f| self.render(f))?;
+    /// Save the composer contents as a draft and the latest messages for next time
+    fn save_on_exit(&self) -> Result<()> {
+        self.state.save_draft(&self.contact, self.input.value())?;
+        self.state.save_session(&self.session())?;
+        if self.anchor.is_none() && self.search.is_none() && self.db.is_some() {
+            let start = self.messages.len().saturating_sub(CACHED_MESSAGES);
+            self.state
+                .cache_messages(&self.contact, &self.messages[start..])?;
+        }
+        Ok(())
+    }
 
-            // Handle events with timeout
-            let timeout = tick_rate
-                .checked_sub(last_tick.elapsed())
-                .unwrap_or_else(|| Duration::from_secs(0));
+    /// Where the user is in the conversation, to pick up from next time
+    fn session(&self) -> Session {
+        let lines = self.lines();
+        let following = self.scroll + self.visible_height >= lines.len();
+        let top_message = if following || self.anchor.is_some() || self.search.is_some() {
+            None
+        } else {
+            lines
+                .iter()
+                .skip(self.scroll)
+                .find_map(|line| line.guid)
+                .filter(|guid| *guid != OUTGOING_GUID)
+                .map(str::to_string)
+        };
 
-            if let Some(event) = crate::tui::common::poll_event(timeout.as_millis() as u64)? {
-                if let Event::Key(key) = event {
-                    match key.code {
-                        KeyCode::Esc => {
-                            return Ok(());
-                        }
-                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            return Ok(());
-                        }
-                        KeyCode::Char(c) => {
-                            self.input.push(c);
-                        }
-                        KeyCode::Backspace => {
-                            self.input.pop();
-                        }
-                        KeyCode::Enter => {
-                            if !self.input.is_empty() {
-                                let input = self.input.clone();
-                                if let Err(e) = self.send_message(&input) {
-                                    eprintln!("Error sending message: {}", e);
-                                }
-                                self.input.clear();
-                            }
-                        }
-                        KeyCode::Up => {
-                            if self.scroll > 0 {
-                                self.scroll -= 1;
-                            }
-                        }
-                        KeyCode::Down => {
-                            let size = terminal.size()?;
-                            let visible_messages =
-                                self.messages.len().min((size.height - 6) as usize);
-                            let max_scroll = self.messages.len().saturating_sub(visible_messages);
-                            if self.scroll < max_scroll {
-                                self.scroll += 1;
-                            }
-                        }
-                        _ => {}
-                    }
+        Session {
+            conversation: self.contact.clone(),
+            display_name: self.display_name.clone(),
+            top_message,
+            pins_collapsed: self.pins_collapsed,
+            time_style: self.times.style(),
+        }
+    }
+
+    /// Create a chat view that picks up where a previous session left off
+    pub fn resume(session: Session) -> Result<Self> {
+        let mut chat = ChatView::new(session.conversation.clone(), session.display_name.clone())?;
+        chat.restore(session);
+        Ok(chat)
+    }
+
+    /// Pick up where a previous session left off
+    fn restore(&mut self, session: Session) {
+        self.pins_collapsed = session.pins_collapsed;
+        self.times.set_style(session.time_style);
+        self.restore_top = session.top_message;
+        self.open_at_unread = false;
+    }
+
+    /// Scroll the new messages divider to the top of the screen once messages have loaded,
+    /// for chats that open at the first unread message
+    fn scroll_to_unread(&mut self, visible_height: usize) {
+        if !self.open_at_unread || self.loading.is_some() || self.db.is_none() {
+            return;
+        }
+        self.open_at_unread = false;
+        if self.anchor.is_some() {
+            return;
+        }
+        let Some(idx) = self.first_unread() else {
+            return;
+        };
+        let guid = self.messages[idx].guid.as_str();
+        let lines = self.lines();
+        if let Some(line) = lines.iter().position(|line| line.guid == Some(guid)) {
+            // The divider is the line above the message; a short conversation stays at the
+            // bottom of the screen
+            let bottom = lines.len().saturating_sub(visible_height);
+            self.scroll = line.saturating_sub(1).min(bottom);
+            self.should_reset_scroll = false;
+        }
+    }
+
+    /// Scroll the restored session's top message back into place once messages have loaded
+    fn restore_scroll(&mut self) {
+        if self.loading.is_some() || self.db.is_none() {
+            return;
+        }
+        let Some(guid) = self.restore_top.take() else {
+            return;
+        };
+        if let Some(idx) = self
+            .lines()
+            .iter()
+            .position(|line| line.guid == Some(guid.as_str()))
+        {
+            self.scroll = idx;
+            self.should_reset_scroll = false;
+        }
+    }
+
+    /// Replace this view with a chat with another contact
+    fn switch_to(&mut self, contact: String, display_name: String) -> Result<()> {
+        JumpList::shared().record(self.session());
+        self.replace_with(ChatView::new(contact, display_name)?)?;
+        self.start_loading()
+    }
+
+    /// Replace this view with a position from the jump list
+    fn jump_back_to(&mut self, session: Session) -> Result<()> {
+        self.replace_with(ChatView::resume(session)?)?;
+        self.start_loading()
+    }
+
+    /// Save this conversation and replace the view with another
+    fn replace_with(&mut self, chat: ChatView) -> Result<()> {
+        self.save_on_exit()?;
+        // The conversation next to this one stays open
+        let split = self.split.take();
+        let split_focus = std::mem::take(&mut self.split_focus);
+        *self = chat;
+        self.split = split;
+        self.split_focus = split_focus;
+        Ok(())
+    }
+
+    /// Save the contact under `name`, keeping the prompt open if an alias has that name, and
+    /// asking first if another contact has the conversation's handle, written in any format
+    fn save_contact(&mut self, name: &str) -> Result<()> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Ok(());
+        }
+
+        let config = Config::load()?;
+        if config.get_contact(&name).is_none() {
+            if let Some((existing, _)) = config.resolve_contact(&name) {
+                if let Some(modal) = &mut self.modal {
+                    modal.set_error(t!("chat.already_contact", name = existing));
                 }
+                return Ok(());
             }
+        }
+        if let Some(other) = config.identifier_taken_by(&self.contact, &name) {
+            self.modal = Some(Modal::picker(
+                ChatModal::SaveContactAgain(name.clone()),
+                t!("chat.handle_saved_title"),
+                t!(
+                    "chat.handle_saved_as",
+                    identifier = format_display_number(&self.contact),
+                    name = other
+                ),
+                vec![
+                    t!("chat.save_contact_too", name = name),
+                    t!("chat.keep_contact", name = other),
+                ],
+            ));
+            return Ok(());
+        }
+        self.save_contact_as(name)
+    }
 
-            if last_tick.elapsed() >= tick_rate {
-                last_tick = Instant::now();
-            }
+    /// Save the contact under `name`, asking first if a contact has that name
+    fn save_contact_as(&mut self, name: String) -> Result<()> {
+        let config = Config::load()?;
+        if let Some(existing) = config.get_contact(&name) {
+            self.modal = Some(Modal::picker(
+                ChatModal::UpdateContact(existing.name.clone()),
+                t!("chat.contact_exists_title"),
+                t!(
+                    "chat.contact_saved_as",
+                    name = existing.name,
+                    identifier = format_display_number(&existing.identifier)
+                ),
+                vec![
+                    t!("chat.update_contact", name = existing.name),
+                    t!("chat.keep_contact", name = existing.name),
+                ],
+            ));
+            return Ok(());
         }
+        self.add_as_contact(name, None)
     }
 
-    /// Render the UI
-    fn render(&self, f: &mut Frame) {
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(3), // Title
-                Constraint::Min(0),    // Messages
-                Constraint::Length(3), // Input
-            ])
-            .split(f.size());
+    /// Save the conversation as the contact `name`, replacing one saved under that name
+    fn add_as_contact(&mut self, name: String, display_name: Option<String>) -> Result<()> {
+        let mut config = Config::load()?;
+        config.add_contact(name.clone(), self.contact.clone(), display_name.clone());
+        config.save()?;
 
-        // Title
-        let title = Paragraph::new(self.display_name.clone())
-            .block(Block::default().borders(Borders::ALL))
-            .alignment(Alignment::Center);
-        f.render_widget(title, chunks[0]);
+        self.display_name = display_name.unwrap_or(name);
+        self.unsaved = false;
+        self.modal = None;
+        Ok(())
+    }
 
-        // Messages
-        let messages_area = chunks[1];
-        let visible_messages = self.messages.len().min(messages_area.height as usize);
-        let start_idx = self.scroll;
-        let end_idx = (start_idx + visible_messages).min(self.messages.len());
+    /// Whether chat.db has no messages at all with the contact
+    fn has_no_history(&self) -> bool {
+        self.db.is_some()
+            && self.loading.is_none()
+            && self.search.is_none()
+            && self.messages.is_empty()
+            && self.outbox.outgoing().is_empty()
+    }
 
-        let messages_chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Length(1); visible_messages])
-            .split(messages_area);
-
-        // Calculate the visible range of messages
-        let visible_range = start_idx..end_idx;
-
-        for (i, idx) in visible_range.enumerate() {
-            let (text, time, msg_type, is_from_me) = &self.messages[idx];
-            let content = if let Some(text) = text {
-                text.clone()
-            } else if let Some(msg_type) = msg_type {
-                format!("[{}]", msg_type)
-            } else {
-                "<empty message>".to_string()
-            };
+    /// Ask what to search the conversation for
+    fn open_search(&mut self) {
+        self.modal = Some(Modal::prompt(
+            ChatModal::Search,
+            t!("chat.search_title"),
+            t!("chat.search_prompt"),
+            TextInput::with_value(self.search.clone().unwrap_or_default()),
+        ));
+    }
 
-            let alignment = if *is_from_me {
-                Alignment::Right
-            } else {
-                Alignment::Left
-            };
+    /// Show messages matching a search, keeping the prompt open if it can't be run
+    fn run_search(&mut self, text: &str) -> Result<()> {
+        let search = match SearchQuery::parse(text) {
+            Ok(search) if search.is_empty() => {
+                self.modal = None;
+                return self.clear_search();
+            }
+            Ok(search) => search,
+            Err(e) => {
+                if let Some(modal) = &mut self.modal {
+                    modal.set_error(e.to_string());
+                }
+                return Ok(());
+            }
+        };
+        let Some(db) = &self.db else {
+            if let Some(modal) = &mut self.modal {
+                modal.set_error(t!("chat.still_loading"));
+            }
+            return Ok(());
+        };
 
-            let style = if *is_from_me {
-                Style::default().fg(Color::Blue)
-            } else {
-                Style::default().fg(Color::Green)
-            };
+        self.messages = db.search_messages(&self.handles, &search, MAX_SEARCH_RESULTS)?;
+        self.search = Some(text.trim().to_string());
+        self.modal = None;
+        self.selected = None;
+        self.should_reset_scroll = true;
+        if !self.messages.is_empty() {
+            self.focus.focus(ChatPane::Messages);
+            self.move_selection(0);
+        }
+        self.load_media()
+    }
+
+    /// Leave search results and return to the conversation
+    fn clear_search(&mut self) -> Result<()> {
+        if self.search.take().is_none() {
+            return Ok(());
+        }
+        self.search_hits.clear();
+        self.focus.focus(ChatPane::Input);
+        self.selected = None;
+        self.should_reset_scroll = true;
+        self.load_messages()
+    }
+
+    /// Leave search results and show the conversation around the selected result
+    fn jump_to_selected_result(&mut self) -> Result<()> {
+        let Some(idx) = self.selected_index() else {
+            return Ok(());
+        };
+        self.anchor = Some(self.messages[idx].rowid);
+        self.search = None;
+        self.search_hits = self.messages.iter().map(|m| m.guid.clone()).collect();
+        // Let the anchored load select the result again
+        self.selected = None;
+        self.load_messages()
+    }
 
-            let message = Paragraph::new(format!("{}: {}", time.format("%H:%M"), content))
-                .style(style)
-                .alignment(alignment)
-                .block(Block::default().borders(Borders::NONE));
+    /// Offer handles from chat.db that look like a mistyped or differently formatted contact
+    fn search_similar_handles(&mut self) -> Result<()> {
+        let Some(db) = &self.db else {
+            return Ok(());
+        };
 
-            f.render_widget(message, messages_chunks[i]);
+        let similar: Vec<(String, usize)> = db
+            .find_similar_handles(&self.contact)?
+            .into_iter()
+            .take(MAX_SIMILAR_HANDLES)
+            .collect();
+        if similar.is_empty() {
+            self.no_similar_handles = true;
+            return Ok(());
         }
 
-        // Input
-        let input = Paragraph::new(Text::from(self.input.as_str()))
-            .block(Block::default().title("Input").borders(Borders::ALL));
-        f.render_widget(input, chunks[2]);
+        let items = similar
+            .iter()
+            .map(|(handle, count)| t!("chat.similar_handle", handle = handle, count = count))
+            .collect();
+        let similar = similar.into_iter().map(|(handle, _)| handle).collect();
+        self.modal = Some(Modal::picker(
+            ChatModal::SimilarHandle(similar),
+            t!("chat.similar_title"),
+            t!("chat.similar_prompt"),
+            items,
+        ));
+        Ok(())
+    }
+
+    /// Switch to `handle`, updating any saved contact that pointed at the old identifier
+    fn use_handle(&mut self, handle: String) -> Result<()> {
+        let mut config = Config::load()?;
+        if config.replace_identifier(&self.contact, &handle) {
+            config.save()?;
+        }
+        let display_name = config
+            .display_name_for(&handle)
+            .unwrap_or_else(|| format_display_number(&handle));
+        self.switch_to(handle, display_name)
     }
-}
 
-/// Convenience function to run the chat TUI
-pub fn run_chat_tui(contact: String, display_name: String) -> Result<()> {
-    let mut chat = ChatView::new(contact, display_name);
-    chat.run()
+    /// Whether messages with the contact go over SMS
+    fn is_sms(&self) -> bool {
+        self.service.as_deref() == Some("SMS")
+    }
+
+    /// Send the composer contents, first asking about repeats of the last message and SMS
+    /// messages that take several segments
+    fn confirm_send(&mut self) {
+        if let Some(query) = gif_query(self.input.value()) {
+            let query = query.to_string();
+            self.start_gif_search(query);
+            return;
+        }
+        if is_voice_command(self.input.value()) {
+            self.start_recording();
+            return;
+        }
+
+        if self.is_large(self.input.value()) {
+            self.confirm_large_send(None);
+        } else {
+            self.confirm_message_send();
+        }
+    }
+
+    /// Send the composer contents as a message, first asking about careful contacts, repeats
+    /// of the last message, and SMS messages that take several segments
+    fn confirm_message_send(&mut self) {
+        if self.asks_before_send() {
+            self.confirm_careful_send(None);
+        } else if self.outbox.is_duplicate(self.input.value()) {
+            self.confirm_send_again(None);
+        } else {
+            self.confirm_long_send();
+        }
+    }
+
+    /// Whether every message has to be confirmed with `y`: always for careful contacts set to
+    /// ask, and for those set to Ctrl+Enter in terminals that can't tell it from Enter
+    fn asks_before_send(&self) -> bool {
+        match self.careful {
+            Some(CarefulSend::Ask) => true,
+            Some(CarefulSend::CtrlEnter) => !keyboard_enhanced(),
+            None => false,
+        }
+    }
+
+    /// Ask whether to send a message to a careful contact, showing it in full
+    fn confirm_careful_send(&mut self, quick_reply: Option<String>) {
+        let text = quick_reply.as_deref().unwrap_or(self.input.value());
+        let message = t!("chat.confirm_send", name = self.display_name, text = text);
+        self.modal = Some(Modal::confirm_explicitly(
+            ChatModal::SendCarefully(quick_reply),
+            t!("chat.confirm_send_title"),
+            message,
+        ));
+    }
+
+    /// Whether text is too long to send comfortably as a message
+    fn is_large(&self, text: &str) -> bool {
+        let limit = self.config.chat().large_message_bytes;
+        limit > 0 && text.len() > limit
+    }
+
+    /// Ask whether to send large text as a text file: the composer contents, or a paste
+    fn confirm_large_send(&mut self, pasted: Option<String>) {
+        let size = pasted.as_deref().unwrap_or(self.input.value()).len();
+        let other = if pasted.is_some() {
+            t!("chat.paste_into_message")
+        } else {
+            t!("chat.send_as_message")
+        };
+        self.modal = Some(Modal::picker(
+            ChatModal::SendLarge(pasted),
+            t!("chat.large_title"),
+            t!("chat.large_prompt", size = format_size(size as u64)),
+            vec![t!("chat.send_text_file"), other],
+        ));
+    }
+
+    /// Send text as an attached text file, clearing the composer if it came from there
+    fn send_as_text_file(&mut self, pasted: Option<String>) -> Result<()> {
+        match pasted {
+            // Whatever is being written around the paste stays in the composer
+            Some(text) => {
+                self.should_reset_scroll = true;
+                self.send_file("text file", move || write_text_file(&text))
+            }
+            None => {
+                let text = self.input.value().to_string();
+                self.send_attachment("text file", move || write_text_file(&text))
+            }
+        }
+    }
+
+    /// Ask whether to send the same text as the last message again
+    fn confirm_send_again(&mut self, quick_reply: Option<String>) {
+        self.modal = Some(Modal::confirm(
+            ChatModal::SendAgain(quick_reply),
+            t!("chat.send_again_title"),
+            t!("chat.send_again"),
+        ));
+    }
+
+    /// Send the composer contents, first asking about SMS messages that take several segments
+    fn confirm_long_send(&mut self) {
+        let segments = sms_length(self.input.value()).segments;
+        if self.is_sms() && segments > 1 {
+            self.modal = Some(Modal::confirm(
+                ChatModal::SendLong,
+                t!("chat.long_sms_title"),
+                t!("chat.long_sms", segments = segments),
+            ));
+        } else {
+            self.send_input();
+        }
+    }
+
+    /// Send the composer contents and clear it, keeping them if sending failed. Long messages
+    /// go out as several when `split_messages_at` is set.
+    fn send_input(&mut self) {
+        let parts = split_message(self.input.value(), self.config.chat().split_messages_at);
+        match parts.iter().try_for_each(|part| self.send_message(part)) {
+            Ok(()) => self.input.clear(),
+            Err(e) => self.toast = Some(Toast::error(&e)),
+        }
+    }
+
+    /// Where the composer contents will be split into several messages: the characters
+    /// left out between parts, or the first character of a part that had to be cut
+    fn split_marks(&self) -> Vec<Range<usize>> {
+        let parts = message_parts(self.input.value(), self.config.chat().split_messages_at);
+        parts
+            .windows(2)
+            .map(|pair| {
+                if pair[0].end < pair[1].start {
+                    pair[0].end..pair[1].start
+                } else {
+                    pair[1].start..pair[1].start + 1
+                }
+            })
+            .collect()
+    }
+
+    /// Queue a message to the contact
+    pub fn send_message(&mut self, text: &str) -> Result<()> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        self.outbox.push(text.to_string());
+        self.suggestions.clear();
+        self.state.save_draft(&self.contact, "")?;
+        // Return to the latest messages to show the message being sent
+        self.should_reset_scroll = true;
+        if self.anchor.take().is_some() {
+            self.load_messages()?;
+        }
+        Ok(())
+    }
+
+    /// Search for a GIF in the background, to preview before sending
+    fn start_gif_search(&mut self, query: String) {
+        if self.gif_search.is_some() {
+            return;
+        }
+        let config = match Config::load() {
+            Ok(config) => config.gif().clone(),
+            Err(e) => {
+                self.toast = Some(Toast::error(&e));
+                return;
+            }
+        };
+
+        self.toast = Some(Toast::new(
+            format!("Searching for a GIF of {}…", query),
+            None,
+        ));
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(gifs::search(&config, &query));
+        });
+        self.gif_search = Some(rx);
+    }
+
+    /// Start recording a voice memo from the default microphone
+    fn start_recording(&mut self) {
+        match Config::load().and_then(|config| Recorder::start(config.voice())) {
+            Ok(recorder) => self.recording = Some(recorder),
+            Err(e) => self.toast = Some(Toast::error(&e)),
+        }
+    }
+
+    /// Send an attachment, first asking about careful contacts
+    fn confirm_attachment_send(&mut self, attachment: PendingAttachment) -> Result<()> {
+        if self.careful.is_none() {
+            return self.send_pending_attachment(attachment);
+        }
+        let message = t!(
+            "chat.confirm_send_attachment",
+            what = attachment.name(),
+            name = self.display_name
+        );
+        self.modal = Some(Modal::confirm_explicitly(
+            ChatModal::SendAttachmentCarefully(attachment),
+            t!("chat.confirm_send_title"),
+            message,
+        ));
+        Ok(())
+    }
+
+    /// Send the GIF being previewed, the voice memo being reviewed, or text as a text file
+    fn send_pending_attachment(&mut self, attachment: PendingAttachment) -> Result<()> {
+        let what = attachment.name();
+        match attachment {
+            PendingAttachment::Gif => {
+                if let Some(gif) = self.gif.take() {
+                    self.send_attachment(what, move || Ok(gif.path))?;
+                }
+            }
+            PendingAttachment::VoiceMemo => {
+                if let Some(recorder) = self.recording.take() {
+                    self.send_attachment(what, move || recorder.finish())?;
+                }
+            }
+            PendingAttachment::TextFile(pasted) => self.send_as_text_file(pasted)?,
+        }
+        Ok(())
+    }
+
+    /// Send a file in the background, clearing the composer command that made it
+    ///
+    /// `file` runs on the background thread, so it can take a while to produce the file.
+    fn send_attachment(
+        &mut self,
+        what: &str,
+        file: impl FnOnce() -> Result<PathBuf> + Send + 'static,
+    ) -> Result<()> {
+        self.send_file(what, file)?;
+        self.input.clear();
+        self.suggestions.clear();
+        self.state.save_draft(&self.contact, "")?;
+        self.should_reset_scroll = true;
+        Ok(())
+    }
+
+    /// Send a file in the background, leaving the composer as it is
+    fn send_file(
+        &mut self,
+        what: &str,
+        file: impl FnOnce() -> Result<PathBuf> + Send + 'static,
+    ) -> Result<()> {
+        if self.read_only {
+            return Err(read_only_error());
+        }
+        let contact = self.contact.clone();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let sent = file().and_then(|path| {
+                let config = Config::load()?;
+                let sent = Sender::from_config(&config, contact).send_file(&path);
+                // Text files are written only to be sent, and Messages keeps its own copy
+                if path.parent() == paths::text_files().as_deref() {
+                    let _ = fs::remove_file(&path);
+                }
+                sent
+            });
+            let _ = tx.send(sent);
+        });
+        self.attachment_send = Some(rx);
+        self.toast = Some(Toast::new(t!("chat.sending_file", what = what), None));
+        Ok(())
+    }
+
+    /// Pick up a finished GIF search, recording, or attachment send
+    fn poll_attachments(&mut self) {
+        if let Some(result) = self.gif_search.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.gif_search = None;
+            match result {
+                Ok(gif) => {
+                    self.toast = None;
+                    self.gif = Some(gif);
+                }
+                Err(e) => self.toast = Some(Toast::error(&e)),
+            }
+        }
+        if let Some(Err(e)) = self.recording.as_mut().map(Recorder::check) {
+            self.toast = Some(Toast::error(&e));
+            if let Some(recorder) = self.recording.take() {
+                recorder.discard();
+            }
+        }
+        if let Some(result) = self
+            .attachment_send
+            .as_ref()
+            .and_then(|rx| rx.try_recv().ok())
+        {
+            self.attachment_send = None;
+            self.toast = match result {
+                Ok(()) => None,
+                Err(e) => Some(Toast::error(&e)),
+            };
+        }
+    }
+
+    /// Handle a key while previewing a GIF
+    fn handle_gif_key(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Enter => self.confirm_attachment_send(PendingAttachment::Gif)?,
+            KeyCode::Esc => self.gif = None,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handle a key while recording or reviewing a voice memo
+    fn handle_recording_key(&mut self, key: KeyEvent) -> Result<()> {
+        let Some(recorder) = &mut self.recording else {
+            return Ok(());
+        };
+        match key.code {
+            KeyCode::Enter if recorder.is_recording() => {
+                if let Err(e) = recorder.stop() {
+                    self.toast = Some(Toast::error(&e));
+                    if let Some(recorder) = self.recording.take() {
+                        recorder.discard();
+                    }
+                }
+            }
+            KeyCode::Enter => self.confirm_attachment_send(PendingAttachment::VoiceMemo)?,
+            KeyCode::Esc => {
+                if let Some(recorder) = self.recording.take() {
+                    recorder.discard();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Pick up progress on queued messages, showing sent ones and explaining failures
+    fn poll_outbox(&mut self, visible_height: usize) -> Result<()> {
+        let following = self.scroll + visible_height >= self.lines().len();
+        let mut sent = false;
+        let mut changed = false;
+        if let Some(e) = self.outbox.take_error() {
+            self.toast = Some(Toast::error(&e));
+        }
+        for status in self.outbox.poll() {
+            changed = true;
+            match status {
+                SendStatus::Sent => sent = true,
+                SendStatus::Offline => {
+                    self.toast = Some(Toast::new(
+                        t!("chat.offline"),
+                        Some(t!("chat.offline_queued")),
+                    ))
+                }
+                SendStatus::Failed {
+                    error, suggestion, ..
+                } => self.toast = Some(Toast::new(error, suggestion)),
+                _ => {}
+            }
+        }
+        if changed && following && self.selected.is_none() {
+            self.should_reset_scroll = true;
+        }
+        if sent {
+            self.refresh_messages()?;
+        }
+        Ok(())
+    }
+
+    /// Lines for messages waiting in the send queue, each followed by its status
+    fn outgoing_lines(&self) -> Vec<Line<'static>> {
+        let max_attempts = self.outbox.policy().max_attempts;
+        let mut lines = Vec::new();
+        for outgoing in self.outbox.outgoing() {
+            let (status, color) = match &outgoing.status {
+                SendStatus::Queued => (t!("chat.status_queued"), Color::DarkGray),
+                SendStatus::Sending { attempt: 1 } => (t!("chat.status_sending"), Color::DarkGray),
+                SendStatus::Sending { attempt } => (
+                    t!(
+                        "chat.status_sending_attempt",
+                        attempt = attempt,
+                        max = max_attempts
+                    ),
+                    Color::Yellow,
+                ),
+                SendStatus::Retrying {
+                    attempt,
+                    retry_at,
+                    error,
+                } => (
+                    t!(
+                        "chat.status_retrying",
+                        error = error,
+                        seconds = retry_at.saturating_duration_since(Instant::now()).as_secs() + 1,
+                        attempt = attempt,
+                        max = max_attempts
+                    ),
+                    Color::Yellow,
+                ),
+                SendStatus::RateLimited { send_at } => (
+                    t!(
+                        "chat.status_rate_limited",
+                        seconds = send_at.saturating_duration_since(Instant::now()).as_secs() + 1
+                    ),
+                    Color::Yellow,
+                ),
+                SendStatus::Offline => (t!("chat.status_offline"), Color::Yellow),
+                SendStatus::Failed { error, .. } => {
+                    (t!("chat.status_failed", error = error), Color::Red)
+                }
+                SendStatus::Sent => continue,
+            };
+
+            let style = Style::default().fg(Color::Blue).add_modifier(Modifier::DIM);
+            lines.extend(
+                render_plain(&outgoing.text, style)
+                    .into_iter()
+                    .map(|line| line.alignment(Alignment::Right)),
+            );
+            lines
+                .push(Line::styled(status, Style::default().fg(color)).alignment(Alignment::Right));
+        }
+        lines
+    }
+
+    /// Run the chat view
+    pub fn run(&mut self) -> Result<()> {
+        run_terminal(|terminal| self.run_ui(terminal))
+    }
+
+    /// Handle the UI loop
+    pub(super) fn run_ui(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    ) -> TuiResult<()> {
+        // Show cached messages right away while the database loads
+        self.start_loading()?;
+
+        let mut config = Config::load()?;
+        let mut config_watcher = ConfigWatcher::new();
+        let mut refresh = RefreshRate::new(config.refresh());
+        let mut notifier = if config.notifications().enabled {
+            let db = MessageDBPool::shared().get()?;
+            Some(Notifier::new(&db)?)
+        } else {
+            None
+        };
+        let mut birthdays = config.notifications().enabled.then(BirthdayReminder::new);
+        let mut last_tick = Instant::now();
+        let mut last_poll = Instant::now();
+
+        loop {
+            // Only query for new messages when Messages.app wrote to chat.db
+            let poll = last_poll.elapsed() >= refresh.poll_interval();
+            if poll {
+                last_poll = Instant::now();
+            }
+            // Edits in another terminal are picked up as often as new messages
+            let reloaded = if poll {
+                config_watcher.reload(&config)
+            } else {
+                None
+            };
+            if let Some(reloaded) = reloaded {
+                match reloaded {
+                    Ok(reloaded) => {
+                        refresh = RefreshRate::new(reloaded.refresh());
+                        if let Some(split) = &mut self.split {
+                            split.apply_config(reloaded.clone());
+                        }
+                        self.apply_config(reloaded.clone());
+                        config = reloaded;
+                        self.toast = Some(Toast::new(t!("chat.config_reloaded"), None));
+                    }
+                    Err(e) => {
+                        self.toast = Some(Toast::new(
+                            t!("chat.config_reload_failed"),
+                            Some(e.to_string()),
+                        ))
+                    }
+                }
+            }
+            let height = terminal.size()?.height;
+            let mut changed = self.update(height, poll);
+            if let Some(split) = &mut self.split {
+                changed |= split.update(height, poll);
+            }
+            if changed {
+                refresh.record_activity();
+            }
+            if let Some(notifier) = notifier.as_mut().filter(|_| poll) {
+                if let Err(e) = self.notify(notifier, &config, refresh.is_focused()) {
+                    self.toast = Some(Toast::new(t!("chat.notify_failed"), Some(e.to_string())));
+                }
+            }
+            if let Some(birthdays) = birthdays.as_mut().filter(|_| poll) {
+                if let Err(e) = birthdays.check(&config, &self.state) {
+                    self.toast = Some(Toast::new(t!("chat.birthdays_failed"), Some(e.to_string())));
+                }
+            }
+
+            // Draw UI
+            terminal.draw(|f| self.render_window(f))?;
+
+            // Handle events with timeout
+            let tick_rate = refresh.tick_rate();
+            let timeout = tick_rate
+                .checked_sub(last_tick.elapsed())
+                .unwrap_or_else(|| Duration::from_secs(0));
+
+            let event = crate::tui::common::poll_event(timeout.as_millis() as u64)?;
+            if let Some(event) = &event {
+                refresh.observe(event);
+            }
+            if let Some(Event::Paste(text)) = event {
+                self.focused_pane().handle_paste(text);
+            } else if let Some(Event::Key(key)) = event {
+                if !self.handle_window_key(key)? {
+                    return Ok(());
+                }
+            }
+
+            if last_tick.elapsed() >= tick_rate {
+                last_tick = Instant::now();
+            }
+        }
+    }
+
+    /// Pick up an edited configuration: contact names and avatars, quick replies, and chat,
+    /// time, suggestion, and translation settings. Sending settings apply to chats opened
+    /// afterwards, since messages may be on their way.
+    fn apply_config(&mut self, config: Config) {
+        if let Some(name) = config.display_name_for(&self.contact) {
+            self.display_name = name;
+        }
+        self.avatar = config
+            .avatar_for(&self.contact)
+            .map(|(initials, color)| (initials.to_string(), color.to_string()));
+        self.unsaved = !self.read_only && !config.is_saved_identifier(&self.contact);
+        self.careful = config
+            .careful_for(&self.contact)
+            .then_some(config.chat().careful_send);
+        self.send_key = match self.careful {
+            Some(CarefulSend::CtrlEnter) => SendKey::CtrlEnter,
+            _ => config.chat().send_key,
+        };
+        self.markdown = config.chat().markdown;
+        self.previews = config.chat().previews;
+        self.quick_replies = config.quick_replies_for(&self.contact);
+        self.suggester = Suggester::new(config.suggestions());
+        self.translator = if config.translate_for(&self.contact) {
+            Translator::new(config.translate())
+        } else {
+            None
+        };
+        self.times = TimeFormatter::new(config.time());
+        self.config = config;
+    }
+
+    /// Bring the view up to date before drawing a frame `height` rows tall, checking for new
+    /// messages if `poll` is set. Returns whether chat.db changed.
+    fn update(&mut self, height: u16, poll: bool) -> bool {
+        if let Err(e) = self.poll_loading() {
+            self.toast = Some(Toast::error(&e));
+        }
+
+        // The first load failed, so cached messages are still shown: try again
+        if poll && self.db.is_none() && self.loading.is_none() {
+            if let Err(e) = self.start_loading() {
+                self.toast = Some(Toast::error(&e));
+            }
+        }
+
+        let changed = poll && self.watcher.has_changed();
+        if changed {
+            if let Err(e) = self.refresh_messages() {
+                self.toast = Some(Toast::error(&e));
+            }
+        }
+
+        let visible_height = height.saturating_sub(
+            CHROME_HEIGHT
+                + self.pin_board_height()
+                + self.activity_height()
+                + self.quick_reply_height()
+                - INPUT_HEIGHT
+                + self.input_height(),
+        ) as usize;
+
+        if let Err(e) = self.poll_outbox(visible_height) {
+            self.toast = Some(Toast::error(&e));
+        }
+
+        if let Some(transcript) = &mut self.transcript {
+            if let Err(e) = transcript.record(&self.state, &self.messages) {
+                // Stop trying rather than failing on every frame
+                self.toast = Some(Toast::error(&e));
+                self.transcript = None;
+            }
+        }
+
+        // Translations make messages taller too
+        if self
+            .translator
+            .as_ref()
+            .is_some_and(Translator::has_pending)
+        {
+            let following = self.scroll + visible_height >= self.lines().len();
+            let translator = self.translator.as_mut().expect("translator is set");
+            let arrived = translator.poll();
+            if let Some(e) = translator.take_error() {
+                self.toast = Some(Toast::error(&e));
+            }
+            if arrived && following && self.selected.is_none() {
+                self.should_reset_scroll = true;
+            }
+        }
+
+        // Keep following the latest messages as previews make them taller
+        if self.thumbnails.has_pending() {
+            let following = self.scroll + visible_height >= self.lines().len();
+            if self.thumbnails.poll() && following && self.selected.is_none() {
+                self.should_reset_scroll = true;
+            }
+        }
+
+        // Reset scroll position if needed
+        if self.should_reset_scroll && !self.messages.is_empty() {
+            let line_count = self.lines().len();
+            let visible_rows = line_count.min(visible_height);
+            self.scroll = line_count.saturating_sub(visible_rows);
+            self.should_reset_scroll = false;
+        }
+        self.restore_scroll();
+        self.scroll_to_unread(visible_height);
+        self.scroll_to_selection(visible_height);
+        self.visible_height = visible_height;
+        self.request_translations(visible_height);
+
+        self.update_suggestions();
+        self.poll_summary();
+        self.poll_attachments();
+
+        if self.toast.as_ref().is_some_and(Toast::is_expired) {
+            self.toast = None;
+        }
+        changed
+    }
+
+    /// Post notifications for new messages, except in conversations on screen while the
+    /// terminal has focus
+    fn notify(&self, notifier: &mut Notifier, config: &Config, focused: bool) -> Result<()> {
+        let mut visible = self.handles.clone();
+        if let Some(split) = &self.split {
+            visible.extend(split.handles.iter().cloned());
+        }
+        let db = MessageDBPool::shared().get()?;
+        notifier.check(&db, config, &visible, focused)
+    }
+
+    /// Summarize the loaded messages in the background with the summarize command
+    fn start_summary(&mut self) -> Result<()> {
+        let config = Config::load()?.summarize().clone();
+        let transcript = summarize::transcript(&self.messages, &self.display_name);
+        if transcript.is_empty() {
+            self.toast = Some(Toast::new(t!("chat.nothing_to_summarize"), None));
+            return Ok(());
+        }
+
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(summarize::summarize(&config, &transcript));
+        });
+        self.summary = Some(rx);
+        self.toast = Some(Toast::new(t!("chat.summarizing"), None));
+        Ok(())
+    }
+
+    /// Show the summary once the summarize command finishes
+    fn poll_summary(&mut self) {
+        let Some(rx) = &self.summary else {
+            return;
+        };
+        let result = match rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                self.summary = None;
+                return;
+            }
+        };
+        self.summary = None;
+        self.toast = None;
+        match result {
+            Ok(summary) => {
+                self.modal = Some(Modal::notice(
+                    ChatModal::Summary,
+                    t!("chat.summary_title", name = self.display_name),
+                    summary,
+                ));
+            }
+            Err(e) => self.toast = Some(Toast::error(&e)),
+        }
+    }
+
+    /// Ask for reply suggestions when a new message arrives, and pick up ones that are ready
+    fn update_suggestions(&mut self) {
+        let Some(suggester) = &mut self.suggester else {
+            return;
+        };
+        // Only the latest messages are worth replying to
+        if self.loading.is_none() && self.anchor.is_none() && self.search.is_none() {
+            match suggester.request(&self.display_name, &self.messages) {
+                Ok(true) => self.suggestions.clear(),
+                Ok(false) => {}
+                Err(e) => self.toast = Some(Toast::error(&e)),
+            }
+        }
+        match suggester.poll() {
+            Some(Ok(suggestions)) => self.suggestions = suggestions,
+            Some(Err(e)) => self.toast = Some(Toast::error(&e)),
+            None => {}
+        }
+    }
+
+    /// The conversation keys go to: the split one when it has focus, otherwise this one
+    fn focused_pane(&mut self) -> &mut ChatView {
+        if self.split.is_some() && self.split_focus.is_focused(SplitPane::Right) {
+            self.split.as_deref_mut().expect("split is open")
+        } else {
+            self
+        }
+    }
+
+    /// Handle a key for the window, passing it on to the focused conversation. Returns false
+    /// when the window should close.
+    fn handle_window_key(&mut self, key: KeyEvent) -> Result<bool> {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        if key.code == KeyCode::Char('c') && control {
+            if let Some(split) = &self.split {
+                split.save_on_exit()?;
+            }
+            self.save_on_exit()?;
+            return Ok(false);
+        }
+
+        if let Some(picker) = &mut self.split_picker {
+            match picker.handle_key(key) {
+                // Only the inbox chooses several people
+                SwitcherAction::None | SwitcherAction::Choose(_) => {}
+                SwitcherAction::Close => self.split_picker = None,
+                SwitcherAction::Open(contact, display_name) => {
+                    self.split_picker = None;
+                    self.open_split(contact, display_name)?;
+                }
+            }
+            return Ok(true);
+        }
+
+        // Window keys only apply while nothing in the focused conversation is asking for input
+        let pane = self.focused_pane();
+        let idle = pane.switcher.is_none()
+            && pane.modal.is_none()
+            && pane.pasted_code.is_none()
+            && pane.gif.is_none()
+            && pane.recording.is_none();
+        match key.code {
+            KeyCode::Char('n') if control && idle => {
+                let db = MessageDBPool::shared().get()?;
+                self.split_picker = Some(Switcher::new(&Config::load()?, &db)?);
+                return Ok(true);
+            }
+            KeyCode::Char('o') if control && idle && self.split.is_some() => {
+                self.split_focus.next();
+                return Ok(true);
+            }
+            KeyCode::Char('w') if control && idle && self.split.is_some() => {
+                self.close_focused_split()?;
+                return Ok(true);
+            }
+            // With a split open, Ctrl+O moves between the sides instead
+            KeyCode::Char('o') if control && idle => {
+                let jump = JumpList::shared().back(Some(self.session()));
+                if let Some(session) = jump {
+                    self.jump_back_to(session)?;
+                }
+                return Ok(true);
+            }
+            KeyCode::Char('i') if control && idle && self.split.is_none() => {
+                let jump = JumpList::shared().forward(Some(self.session()));
+                if let Some(session) = jump {
+                    self.jump_back_to(session)?;
+                }
+                return Ok(true);
+            }
+            _ => {}
+        }
+
+        if self.focused_pane().handle_key(key)? {
+            Ok(true)
+        } else if self.split.is_some() {
+            // Closing one side of a split leaves the other
+            self.close_focused_split()?;
+            Ok(true)
+        } else {
+            self.save_on_exit()?;
+            JumpList::shared().record(self.session());
+            Ok(false)
+        }
+    }
+
+    /// Show another conversation next to this one, replacing any already shown
+    fn open_split(&mut self, contact: String, display_name: String) -> Result<()> {
+        if let Some(split) = &self.split {
+            split.save_on_exit()?;
+        }
+        let mut split = ChatView::new(contact, display_name)?;
+        split.start_loading()?;
+        self.split = Some(Box::new(split));
+        self.split_focus = FocusRing::new();
+        self.split_focus.next();
+        Ok(())
+    }
+
+    /// Close the focused side of a split, leaving the other conversation full width
+    fn close_focused_split(&mut self) -> Result<()> {
+        let Some(split) = self.split.take() else {
+            return Ok(());
+        };
+        if self.split_focus.is_focused(SplitPane::Right) {
+            split.save_on_exit()?;
+        } else {
+            self.save_on_exit()?;
+            *self = *split;
+        }
+        self.split_focus = FocusRing::new();
+        Ok(())
+    }
+
+    /// Handle a key for this conversation. Returns false when it should close.
+    fn handle_key(&mut self, key: KeyEvent) -> Result<bool> {
+        if key.code == KeyCode::Char('k') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            let db = MessageDBPool::shared().get()?;
+            self.switcher = Some(Switcher::new(&Config::load()?, &db)?);
+            return Ok(true);
+        }
+
+        if let Some(switcher) = &mut self.switcher {
+            match switcher.handle_key(key) {
+                SwitcherAction::None | SwitcherAction::Choose(_) => {}
+                SwitcherAction::Close => self.switcher = None,
+                SwitcherAction::Open(contact, display_name) => {
+                    self.switch_to(contact, display_name)?;
+                }
+            }
+            Ok(true)
+        } else if self.pasted_code.is_some() {
+            self.handle_pasted_code_key(key)?;
+            Ok(true)
+        } else if self.modal.is_some() {
+            // Before the GIF and voice memo, which a modal can ask about sending
+            self.handle_modal_key(key)?;
+            Ok(true)
+        } else if self.gif.is_some() {
+            self.handle_gif_key(key)?;
+            Ok(true)
+        } else if self.recording.is_some() {
+            self.handle_recording_key(key)?;
+            Ok(true)
+        } else if self.focus.is_focused(ChatPane::Messages) {
+            self.handle_selection_key(key)?;
+            Ok(true)
+        } else {
+            self.handle_composer_key(key, self.visible_height)
+        }
+    }
+
+    /// Handle a key while typing in the composer. Returns false when the view should close.
+    fn handle_composer_key(&mut self, key: KeyEvent, visible_height: usize) -> Result<bool> {
+        match key.code {
+            KeyCode::Esc if self.search.is_some() => {
+                self.clear_search()?;
+            }
+            KeyCode::Esc => {
+                return Ok(false);
+            }
+            KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.open_search();
+            }
+            KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.times.toggle();
+            }
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.outbox.retry_now();
+            }
+            KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.toggle_activity()?;
+            }
+            KeyCode::Char('g')
+                if key.modifiers.contains(KeyModifiers::CONTROL) && self.summary.is_none() =>
+            {
+                self.start_summary()?;
+            }
+            KeyCode::Tab if !self.messages.is_empty() => {
+                self.cycle_focus();
+            }
+            KeyCode::Char(digit @ '1'..='9') if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.send_quick_reply(digit);
+            }
+            KeyCode::Char('s') if self.unsaved && key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.modal = Some(Modal::prompt(
+                    ChatModal::SaveContact,
+                    t!("chat.save_contact_title"),
+                    t!("chat.save_contact_prompt", identifier = self.contact),
+                    TextInput::new(),
+                ));
+            }
+            // A control key, so `s` still types the first letter of a message
+            KeyCode::Char('l')
+                if self.has_no_history() && key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                self.search_similar_handles()?;
+            }
+            KeyCode::Char('u')
+                if !self.input.is_empty() && key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                self.modal = Some(Modal::confirm(
+                    ChatModal::ClearDraft,
+                    t!("chat.clear_draft_title"),
+                    t!("chat.clear_draft"),
+                ));
+            }
+            KeyCode::Enter => match enter_action(key, self.send_key, keyboard_enhanced()) {
+                Some(EnterAction::Newline) => self.input.insert_str("\n"),
+                _ if self.input.is_empty() => {}
+                _ => self.confirm_send(),
+            },
+            KeyCode::Up if self.scroll > 0 => {
+                self.scroll -= 1;
+            }
+            KeyCode::Up => {
+                self.load_older()?;
+                self.scroll = self.scroll.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                let line_count = self.lines().len();
+                let visible_rows = line_count.min(visible_height);
+                let max_scroll = line_count.saturating_sub(visible_rows);
+                if self.scroll < max_scroll {
+                    self.scroll += 1;
+                }
+            }
+            KeyCode::End if self.anchor.is_some() => {
+                self.anchor = None;
+                self.search_hits.clear();
+                self.should_reset_scroll = true;
+                self.load_messages()?;
+            }
+            _ => {
+                self.input.handle_key(key);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Handle pasted text, offering to send code as a code block
+    fn handle_paste(&mut self, text: String) {
+        if let Some(modal) = &mut self.modal {
+            modal.handle_paste(&text);
+        } else if self.focus.is_focused(ChatPane::Messages) {
+            // Nothing to paste into while selecting messages
+        } else if self.is_large(&text) {
+            self.confirm_large_send(Some(text));
+        } else if looks_like_code(&text) {
+            self.pasted_code = Some(text);
+        } else {
+            self.input.insert_str(&text);
+        }
+    }
+
+    /// Handle a key while deciding how to send pasted code
+    fn handle_pasted_code_key(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(code) = self.pasted_code.take() {
+                    if let Err(e) = self.send_message(&fence_code(&code)) {
+                        self.toast = Some(Toast::error(&e));
+                    }
+                }
+            }
+            KeyCode::Char('t') => {
+                if let Some(code) = self.pasted_code.take() {
+                    self.input.insert_str(&code);
+                }
+            }
+            KeyCode::Esc => {
+                self.pasted_code = None;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handle a key while a modal is open
+    fn handle_modal_key(&mut self, key: KeyEvent) -> Result<()> {
+        let Some(modal) = &mut self.modal else {
+            return Ok(());
+        };
+
+        match (modal.action(), modal.handle_key(key)) {
+            (_, ModalResult::Pending) => {}
+            (_, ModalResult::Cancel) => self.modal = None,
+            (ChatModal::SaveContact, ModalResult::Submit(name)) => self.save_contact(&name)?,
+            (ChatModal::SaveContactAgain(name), ModalResult::Pick(0)) => {
+                let name = name.clone();
+                self.modal = None;
+                self.save_contact_as(name)?;
+            }
+            (ChatModal::UpdateContact(name), ModalResult::Pick(0)) => {
+                let name = name.clone();
+                // The contact keeps its display name; only what it points at changes
+                let display_name = Config::load()?
+                    .get_contact(&name)
+                    .and_then(|entry| entry.display_name.clone());
+                self.add_as_contact(name, display_name)?;
+            }
+            (ChatModal::ClearDraft, ModalResult::Confirm) => {
+                self.input.clear();
+                self.state.save_draft(&self.contact, "")?;
+                self.modal = None;
+            }
+            // Being careful doesn't skip the checks that come after it
+            (ChatModal::SendCarefully(None), ModalResult::Confirm) => {
+                self.modal = None;
+                if self.outbox.is_duplicate(self.input.value()) {
+                    self.confirm_send_again(None);
+                } else {
+                    self.confirm_long_send();
+                }
+            }
+            (ChatModal::SendCarefully(Some(text)), ModalResult::Confirm) => {
+                self.modal = None;
+                if self.outbox.is_duplicate(&text) {
+                    self.confirm_send_again(Some(text));
+                } else {
+                    self.send_reply(text);
+                }
+            }
+            (ChatModal::SendAgain(Some(text)), ModalResult::Confirm) => {
+                self.modal = None;
+                self.send_reply(text);
+            }
+            (ChatModal::SendAttachmentCarefully(attachment), ModalResult::Confirm) => {
+                self.modal = None;
+                if let Err(e) = self.send_pending_attachment(attachment) {
+                    self.toast = Some(Toast::error(&e));
+                }
+            }
+            (ChatModal::SendLarge(pasted), ModalResult::Pick(0)) => {
+                self.modal = None;
+                if let Err(e) = self.confirm_attachment_send(PendingAttachment::TextFile(pasted)) {
+                    self.toast = Some(Toast::error(&e));
+                }
+            }
+            (ChatModal::SendLarge(Some(text)), ModalResult::Pick(_)) => {
+                self.modal = None;
+                self.input.insert_str(&text);
+            }
+            (ChatModal::SendLarge(None), ModalResult::Pick(_)) => {
+                self.modal = None;
+                self.confirm_message_send();
+            }
+            (ChatModal::SendAgain(None), ModalResult::Confirm) => {
+                self.modal = None;
+                self.confirm_long_send();
+            }
+            (ChatModal::SendLong, ModalResult::Confirm) => {
+                self.modal = None;
+                self.send_input();
+            }
+            (ChatModal::Search, ModalResult::Submit(text)) => self.run_search(&text)?,
+            (ChatModal::Tag(guid), ModalResult::Submit(text)) => {
+                let guid = guid.clone();
+                self.modal = None;
+                self.tag_message(&guid, &text)?;
+            }
+            (ChatModal::SimilarHandle(handles), ModalResult::Pick(index)) => {
+                self.modal = None;
+                self.use_handle(handles[index].clone())?;
+            }
+            _ => self.modal = None,
+        }
+        Ok(())
+    }
+
+    /// Handle a key in message-selection mode
+    fn handle_selection_key(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Esc if self.search.is_some() => {
+                self.clear_search()?;
+            }
+            KeyCode::Enter if self.search.is_some() => {
+                self.jump_to_selected_result()?;
+            }
+            KeyCode::Esc | KeyCode::Tab => {
+                self.cycle_focus();
+            }
+            KeyCode::Char('/') => {
+                self.open_search();
+            }
+            KeyCode::Up => {
+                if self.selected_index() == Some(0) {
+                    self.load_older()?;
+                }
+                self.move_selection(-1);
+            }
+            KeyCode::Down => {
+                self.move_selection(1);
+            }
+            KeyCode::Char('s') => {
+                self.toggle_selected_star()?;
+            }
+            KeyCode::Char('#') => {
+                self.open_tag_prompt();
+            }
+            KeyCode::Char('p') => {
+                self.toggle_selected_pin()?;
+            }
+            KeyCode::Char('P') => {
+                self.pins_collapsed = !self.pins_collapsed;
+            }
+            KeyCode::Char('o') => {
+                self.open_selected_media();
+            }
+            KeyCode::Char('t') => {
+                self.times.toggle();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Render the UI
+    fn render_window(&self, f: &mut Frame) {
+        match &self.split {
+            Some(split) => {
+                let panes = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                    .split(f.size());
+                let right_focused = self.split_focus.is_focused(SplitPane::Right);
+                self.render(f, panes[0], !right_focused, true);
+                split.render(f, panes[1], right_focused, true);
+            }
+            None => self.render(f, f.size(), true, false),
+        }
+        if let Some(picker) = &self.split_picker {
+            picker.render(f);
+        }
+    }
+
+    /// Render the conversation in `area`, with its input focused if `active`. In a `split` window
+    /// the title is highlighted on the side keys go to.
+    fn render(&self, f: &mut Frame, area: Rect, active: bool, split: bool) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),                         // Title
+                Constraint::Length(self.pin_board_height()),   // Pinned messages
+                Constraint::Length(self.activity_height()),    // Activity panel
+                Constraint::Min(0),                            // Messages
+                Constraint::Length(self.quick_reply_height()), // Quick replies
+                Constraint::Length(self.input_height()),       // Input
+            ])
+            .split(area);
+
+        // Title
+        let mut title_spans = Vec::new();
+        if let Some(banner) = sandbox_banner() {
+            title_spans.push(banner);
+            title_spans.push(Span::raw(" "));
+        }
+        if let Some((initials, color)) = &self.avatar {
+            title_spans.push(avatar_span(initials, color));
+            title_spans.push(Span::raw(" "));
+        }
+        title_spans.push(Span::raw(self.display_name.clone()));
+        if self.loading.is_some() {
+            let frame = (self.last_refresh.elapsed().as_millis() / SPINNER_FRAME_MS) as usize;
+            title_spans.push(Span::styled(
+                format!("  {} Loading", SPINNER_FRAMES[frame % SPINNER_FRAMES.len()]),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+        if self.read_only {
+            title_spans.push(Span::styled(
+                "  (archive, read-only)",
+                Style::default().fg(Color::Gray),
+            ));
+        }
+        if self.unsaved {
+            title_spans.push(Span::styled(
+                "  (unsaved, Ctrl+S to save as contact)",
+                Style::default().fg(Color::Gray),
+            ));
+        }
+        if self.handles.len() > 1 {
+            title_spans.push(Span::styled(
+                format!("  (merged: {})", self.handles.join(", ")),
+                Style::default().fg(Color::Gray),
+            ));
+        }
+        if let Some(search) = &self.search {
+            let plural = if self.messages.len() == 1 { "" } else { "s" };
+            title_spans.push(Span::styled(
+                format!(
+                    "  Search: {} ({} result{}, Enter to jump, Esc to exit)",
+                    search,
+                    self.messages.len(),
+                    plural
+                ),
+                Style::default().fg(Color::Yellow),
+            ));
+        }
+        let title = Paragraph::new(Line::from(title_spans))
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(if split {
+                        focus_style(active)
+                    } else {
+                        Style::default()
+                    }),
+            )
+            .alignment(Alignment::Center);
+        f.render_widget(title, chunks[0]);
+
+        self.render_pin_board(f, chunks[1]);
+        self.render_activity(f, chunks[2]);
+
+        // Messages
+        let messages_area = chunks[3];
+        if self.has_no_history() {
+            self.render_no_history(f, messages_area);
+        } else {
+            let lines = self.lines();
+            let height = messages_area.height as usize;
+            let mut text_area = messages_area;
+            if lines.len() > height {
+                let marks: Vec<(usize, Mark)> = lines
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(idx, line)| match line.guid {
+                        None => Some((idx, Mark::Unread)),
+                        Some(guid) if self.search_hits.contains(guid) => {
+                            Some((idx, Mark::SearchHit))
+                        }
+                        Some(_) => None,
+                    })
+                    // Only the first line of a message marks it
+                    .filter(|(idx, _)| idx == &0 || lines[idx - 1].guid != lines[*idx].guid)
+                    .collect();
+                let panes = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Min(0), Constraint::Length(1)])
+                    .split(messages_area);
+                f.render_widget(
+                    Paragraph::new(minimap_lines(lines.len(), self.scroll, height, &marks)),
+                    panes[1],
+                );
+                text_area = panes[0];
+            }
+
+            let start_idx = self.scroll.min(lines.len());
+            let end_idx = (start_idx + height).min(lines.len());
+            let visible: Vec<Line> = lines
+                .into_iter()
+                .skip(start_idx)
+                .take(end_idx - start_idx)
+                .map(|l| l.line)
+                .collect();
+            f.render_widget(Paragraph::new(visible), text_area);
+        }
+
+        self.render_quick_replies(f, chunks[4]);
+        self.render_input(f, chunks[5], active);
+
+        if let Some(toast) = &self.toast {
+            toast.render(f, chunks[3]);
+        }
+        if let Some(switcher) = &self.switcher {
+            switcher.render(f);
+        }
+        if let Some(modal) = &self.modal {
+            modal.render(f);
+        }
+    }
+
+    /// Render the contact's quick replies and suggested replies as numbered chips above the
+    /// composer
+    fn render_quick_replies(&self, f: &mut Frame, area: Rect) {
+        let key_style = Style::default()
+            .fg(Color::Green)
+            .add_modifier(Modifier::BOLD);
+        let mut spans = Vec::new();
+        for (i, reply) in self.reply_chips().enumerate() {
+            if i > 0 {
+                spans.push(Span::raw("  "));
+            }
+            // Suggestions are told apart from the contact's own quick replies by color
+            let chip_style = if i < self.quick_replies.len() {
+                Style::default().bg(Color::DarkGray)
+            } else {
+                Style::default().fg(Color::Cyan).bg(Color::DarkGray)
+            };
+            spans.push(Span::styled(format!("Alt+{}", i + 1), key_style));
+            spans.push(Span::styled(format!(" {} ", reply), chip_style));
+        }
+        f.render_widget(Paragraph::new(Line::from(spans)), area);
+    }
+
+    /// Render the activity panel: a heatmap of messages per day over the last year, as many
+    /// weeks as fit, and a sparkline of messages per hour, with the words used most beside them
+    /// when there's room
+    fn render_activity(&self, f: &mut Frame, area: Rect) {
+        let Some((activity, words)) = &self.activity else {
+            return;
+        };
+
+        // Four columns for the weekday labels, two for the borders
+        let heatmap_width = HEATMAP_WEEKS as u16 + 6;
+        let area = if area.width >= heatmap_width + ACTIVITY_WORDS_WIDTH {
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Min(heatmap_width),
+                    Constraint::Length(ACTIVITY_WORDS_WIDTH),
+                ])
+                .split(area);
+            self.render_top_words(f, chunks[1], words);
+            chunks[0]
+        } else {
+            area
+        };
+        let weeks = (area.width as usize).saturating_sub(6).min(HEATMAP_WEEKS);
+        let dim = Style::default().fg(Color::DarkGray);
+        let mut lines = vec![Line::styled(
+            format!("    {}", activity.month_labels(weeks)),
+            dim,
+        )];
+        for (label, row) in WEEKDAY_LABELS.iter().zip(activity.heatmap(weeks)) {
+            let mut spans = vec![Span::styled(format!("{:<4}", label), dim)];
+            spans.extend(row.into_iter().map(|level| match level {
+                None => Span::raw(" "),
+                Some(0) => Span::styled(HEATMAP_CELLS[0].to_string(), dim),
+                Some(level) => Span::styled(
+                    HEATMAP_CELLS[level].to_string(),
+                    Style::default().fg(Color::Green),
+                ),
+            }));
+            lines.push(Line::from(spans));
+        }
+        lines.push(Line::from(vec![
+            Span::styled("Hrs ", dim),
+            Span::styled(activity.sparkline(), Style::default().fg(Color::Green)),
+            Span::styled("  midnight to 11pm", dim),
+        ]));
+
+        let title = format!(
+            " Activity: {} messages in the last year (Ctrl+Y to hide) ",
+            activity.total()
+        );
+        f.render_widget(
+            Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)),
+            area,
+        );
+    }
+
+    /// Render the words used most in the conversation, each with a bar, and the top emoji on
+    /// the last line
+    fn render_top_words(&self, f: &mut Frame, area: Rect, words: &WordCounts) {
+        // Leave the last row inside the borders for emoji
+        let rows = area.height.saturating_sub(3) as usize;
+        let top = words.top_words(rows);
+        let max = top.first().map_or(1, |&(_, count)| count);
+        let bar_width = (area.width as usize).saturating_sub(21);
+
+        let mut lines: Vec<Line> = top
+            .iter()
+            .map(|&(word, count)| {
+                Line::from(vec![
+                    Span::raw(format!(
+                        "{} ",
+                        pad_to_width(&truncate_graphemes(word, 12), 12)
+                    )),
+                    Span::styled(
+                        "▇".repeat((count * bar_width).div_ceil(max)),
+                        Style::default().fg(Color::Green),
+                    ),
+                    Span::styled(format!(" {}", count), Style::default().fg(Color::DarkGray)),
+                ])
+            })
+            .collect();
+        lines.resize(rows, Line::default());
+        let emoji: Vec<String> = words
+            .top_emoji(5)
+            .iter()
+            .map(|(emoji, count)| format!("{}{}", emoji, count))
+            .collect();
+        lines.push(Line::raw(emoji.join(" ")));
+
+        f.render_widget(
+            Paragraph::new(lines)
+                .block(Block::default().borders(Borders::ALL).title(" Top words ")),
+            area,
+        );
+    }
+
+    /// Render the pinned messages above the conversation
+    fn render_pin_board(&self, f: &mut Frame, area: Rect) {
+        if self.pinned.is_empty() {
+            return;
+        }
+
+        let count = self.pinned.len();
+        let plural = if count == 1 { "" } else { "s" };
+        if self.pins_collapsed {
+            let summary = format!("📌 {} pinned message{} (P to expand)", count, plural);
+            f.render_widget(
+                Paragraph::new(summary)
+                    .style(Style::default().fg(Color::Yellow))
+                    .alignment(Alignment::Center),
+                area,
+            );
+            return;
+        }
+
+        let me = t!("chat.me");
+        let lines: Vec<Line> = self
+            .pinned
+            .iter()
+            .rev()
+            .take(MAX_PINNED_ROWS as usize)
+            .rev()
+            .map(|message| {
+                let sender = if message.is_from_me {
+                    me.as_str()
+                } else {
+                    self.display_name.as_str()
+                };
+                let text = message.text.as_deref().unwrap_or_default();
+                Line::from(vec![
+                    Span::styled(
+                        format!("{} {}: ", message.date.format("%b %d %H:%M"), sender),
+                        Style::default().fg(Color::Gray),
+                    ),
+                    Span::raw(text.lines().next().unwrap_or_default().to_string()),
+                ])
+            })
+            .collect();
+        let title = format!("Pinned ({}) | P Collapse", count);
+        f.render_widget(
+            Paragraph::new(lines).block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Yellow)),
+            ),
+            area,
+        );
+    }
+
+    /// Render a hint in place of the messages when the contact has no history
+    fn render_no_history(&self, f: &mut Frame, area: Rect) {
+        let key_style = Style::default()
+            .fg(Color::Green)
+            .add_modifier(Modifier::BOLD);
+        let action = if self.no_similar_handles {
+            Line::from(t!("chat.no_similar_handles"))
+        } else {
+            Line::from(vec![
+                Span::raw(t!("chat.press")),
+                Span::styled("Ctrl+L", key_style),
+                Span::raw(t!("chat.to_search_similar")),
+            ])
+        };
+        let text = vec![
+            Line::from(t!("chat.no_history", contact = self.contact)),
+            Line::from(""),
+            Line::from(t!("chat.no_history_hint")),
+            action,
+        ];
+
+        // Leave room for the box's borders around the text
+        let height = (text.len() as u16 + 2).min(area.height);
+        let hint_area = Rect {
+            y: area.y + area.height.saturating_sub(height) / 2,
+            height,
+            ..area
+        };
+        let hint = Paragraph::new(text)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true })
+            .block(
+                Block::default()
+                    .title(t!("chat.no_history_title"))
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Yellow)),
+            );
+        f.render_widget(hint, hint_area);
+    }
+
+    /// Character count of the composer, with SMS segments when texting over SMS
+    fn length_counter(&self) -> Line<'static> {
+        let chars = self.input.value().chars().count();
+        let parts = self.split_marks().len() + 1;
+        if parts > 1 {
+            return Line::styled(
+                format!("{} chars | {} messages", chars, parts),
+                Style::default().fg(Color::Yellow),
+            );
+        }
+        if !self.is_sms() {
+            return Line::from(format!("{} chars", chars));
+        }
+
+        let length = sms_length(self.input.value());
+        let style = if length.segments > 1 {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        let encoding = if length.unicode { ", Unicode" } else { "" };
+        Line::styled(
+            format!(
+                "{} chars | SMS {} segment{}{}",
+                chars,
+                length.segments,
+                if length.segments == 1 { "" } else { "s" },
+                encoding
+            ),
+            style,
+        )
+    }
+
+    /// Render the input box, or the prompt that replaces it
+    fn render_input(&self, f: &mut Frame, area: Rect, active: bool) {
+        if let Some(code) = &self.pasted_code {
+            let summary = format!("Pasted {} lines of code", code.lines().count());
+            let prompt = Paragraph::new(summary).block(
+                Block::default()
+                    .title("Code: Enter Send as code block | t Paste as text | Esc Cancel")
+                    .borders(Borders::ALL),
+            );
+            f.render_widget(prompt, area);
+            return;
+        }
+        if let Some(gif) = &self.gif {
+            let block = Block::default()
+                .title(format!("GIF: {} | Enter Send | Esc Cancel", gif.query))
+                .borders(Borders::ALL)
+                .border_style(focus_style(active));
+            let preview = match &gif.preview {
+                Some(image) => Paragraph::new(image_lines(image)),
+                None => Paragraph::new(format!("No preview for {}", gif.path.display())),
+            };
+            f.render_widget(preview.block(block), area);
+            return;
+        }
+        if let Some(recorder) = &self.recording {
+            let length = format_duration(recorder.length().as_secs());
+            let (status, keys) = if recorder.is_recording() {
+                (
+                    Line::from(vec![
+                        Span::styled("● ", Style::default().fg(Color::Red)),
+                        Span::raw(t!("chat.recording", length = length)),
+                    ]),
+                    t!("chat.recording_keys"),
+                )
+            } else {
+                (
+                    Line::raw(t!("chat.recorded", length = length)),
+                    t!("chat.recorded_keys"),
+                )
+            };
+            let block = Block::default()
+                .title(keys)
+                .borders(Borders::ALL)
+                .border_style(focus_style(active));
+            f.render_widget(Paragraph::new(status).block(block), area);
+            return;
+        }
+
+        let input_focused = active && self.focus.is_focused(ChatPane::Input);
+        let input_title = if input_focused {
+            "Input"
+        } else if self.search.is_some() {
+            "Results: ↑/↓ Move | Enter Jump to message | / Search again | Esc Back"
+        } else {
+            "Select: ↑/↓ Move | s Star | # Tag | p Pin | P Collapse pins | o Open | t Times | / Search | Esc Back"
+        };
+        let input_title = if active || !self.focus.is_focused(ChatPane::Input) {
+            input_title
+        } else {
+            "Ctrl+O to switch here"
+        };
+        let mut block = Block::default()
+            .title(input_title)
+            .borders(Borders::ALL)
+            .border_style(focus_style(input_focused));
+        if input_focused && !self.input.is_empty() {
+            block = block.title(Title::from(self.length_counter()).alignment(Alignment::Right));
+        }
+        self.input.render_marked(
+            f,
+            area,
+            block,
+            // Popups over the view take the cursor
+            input_focused && self.modal.is_none() && self.switcher.is_none(),
+            &self.split_marks(),
+        );
+    }
+}
+
+/// Query messages with any of a contact's handles, oldest first
+fn fetch_messages(db: &MessageDB, handles: &[String], anchor: Option<i64>) -> Result<Vec<Message>> {
+    let mut messages = match anchor {
+        Some(rowid) => db.get_messages_around(handles, rowid)?,
+        None => db.get_messages(handles, PAGE_SIZE)?,
+    };
+    // Reverse the messages so oldest are at the top
+    messages.reverse();
+    Ok(messages)
+}
+
+/// Wrap code in a fence so it keeps its formatting when read
+fn fence_code(code: &str) -> String {
+    format!("```\n{}\n```", code.trim_end())
+}
+
+/// Write text to a file in the cache directory, to send as an attachment
+fn write_text_file(text: &str) -> Result<PathBuf> {
+    let dir = paths::text_files().ok_or_else(|| Error::Generic(t!("paths.no_cache_dir")))?;
+    fs::create_dir_all(&dir)?;
+    // Texts sent within the same second, or by another im, each get a file of their own
+    let stamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+    let mut number = 1;
+    loop {
+        let name = match number {
+            1 => format!("{}.txt", stamp),
+            _ => format!("{}-{}.txt", stamp, number),
+        };
+        let path = dir.join(name);
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                file.write_all(text.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => number += 1,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Get the text to display for a message
+pub(super) fn message_content(message: &Message) -> String {
+    if let Some(event) = &message.event {
+        event.describe(format_display_number)
+    } else if let Some(text) = &message.text {
+        text.clone()
+    } else if let Some(msg_type) = &message.message_type {
+        format!("[{}]", msg_type)
+    } else {
+        t!("chat.empty_message")
+    }
+}
+
+/// The error shown when trying to send to an archived conversation
+fn read_only_error() -> Error {
+    Error::Generic(t!("chat.read_only"))
+}
+
+/// Convenience function to run the chat TUI
+pub fn run_chat_tui(contact: String, display_name: String) -> Result<()> {
+    let mut chat = ChatView::new(contact, display_name)?;
+    chat.run()
+}
+
+/// Run the chat view where a previous session left off
+pub fn resume_chat_tui(session: Session) -> Result<()> {
+    ChatView::resume(session)?.run()
 }
@@ -1,15 +1,34 @@
 use crate::error::Result;
+use crate::i18n::t;
+use crate::sender;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event},
+    event::{
+        self, DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+        EnableFocusChange, EnableMouseCapture, Event, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{
+        disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement, EnterAlternateScreen,
+        LeaveAlternateScreen,
+    },
 };
 use ratatui::{prelude::*, Terminal};
 use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /// Type alias for TUI results
 pub type TuiResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// Whether the terminal reports modifiers on keys like Enter, set while a terminal UI runs
+static KEYBOARD_ENHANCED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the terminal uses the enhanced keyboard protocol, so Shift+Enter and Ctrl+Enter
+/// can be told apart from Enter
+pub fn keyboard_enhanced() -> bool {
+    KEYBOARD_ENHANCED.load(Ordering::Relaxed)
+}
+
 /// Run a terminal UI with proper setup and teardown
 pub fn run_terminal<F, T>(ui_func: F) -> Result<T>
 where
@@ -18,37 +37,155 @@ where
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste,
+        EnableFocusChange
+    )?;
+    // Terminals with the kitty keyboard protocol report modifiers on Enter and friends
+    let enhanced = supports_keyboard_enhancement().unwrap_or(false);
+    if enhanced {
+        execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+        )?;
+    }
+    KEYBOARD_ENHANCED.store(enhanced, Ordering::Relaxed);
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // Run the UI function
-    let result = match ui_func(&mut terminal) {
-        Ok(result) => {
-            // Restore terminal
-            disable_raw_mode()?;
-            execute!(
-                terminal.backend_mut(),
-                LeaveAlternateScreen,
-                DisableMouseCapture
-            )?;
-            terminal.show_cursor()?;
-            Ok(result)
-        }
-        Err(e) => {
-            // Restore terminal on error
-            disable_raw_mode()?;
-            execute!(
-                terminal.backend_mut(),
-                LeaveAlternateScreen,
-                DisableMouseCapture
-            )?;
-            terminal.show_cursor()?;
-            Err(crate::error::Error::Generic(format!("TUI error: {}", e)))
+    // Run the UI function, restoring the terminal either way
+    let result = ui_func(&mut terminal);
+    restore_terminal(&mut terminal)?;
+    result.map_err(|e| crate::error::Error::Generic(t!("error.tui", error = e)))
+}
+
+/// Undo the setup in `run_terminal`
+fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()> {
+    if KEYBOARD_ENHANCED.swap(false, Ordering::Relaxed) {
+        execute!(terminal.backend_mut(), PopKeyboardEnhancementFlags)?;
+    }
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste,
+        DisableFocusChange
+    )?;
+    terminal.show_cursor()
+}
+
+/// A widget or pane that can hold keyboard focus, listed in Tab order
+pub trait Focusable: Copy + PartialEq + 'static {
+    /// Every focusable item, in the order Tab visits them
+    const ORDER: &'static [Self];
+}
+
+/// Tracks which of a view's focusable items has keyboard focus
+pub struct FocusRing<T: Focusable> {
+    current: T,
+}
+
+impl<T: Focusable> FocusRing<T> {
+    /// Start with the first item in Tab order focused
+    pub fn new() -> Self {
+        Self {
+            current: T::ORDER[0],
         }
-    };
+    }
+
+    /// The focused item
+    pub fn current(&self) -> T {
+        self.current
+    }
+
+    /// Whether `item` has focus
+    pub fn is_focused(&self, item: T) -> bool {
+        self.current == item
+    }
+
+    /// Move focus to the next item, wrapping around
+    pub fn next(&mut self) {
+        let index = self.index();
+        self.current = T::ORDER[(index + 1) % T::ORDER.len()];
+    }
+
+    /// Move focus to the previous item, wrapping around
+    pub fn prev(&mut self) {
+        let index = self.index();
+        self.current = T::ORDER[(index + T::ORDER.len() - 1) % T::ORDER.len()];
+    }
+
+    /// Move focus to `item`
+    pub fn focus(&mut self, item: T) {
+        self.current = item;
+    }
 
-    result
+    /// Position of the focused item in Tab order
+    fn index(&self) -> usize {
+        T::ORDER
+            .iter()
+            .position(|&item| item == self.current)
+            .unwrap_or(0)
+    }
+}
+
+impl<T: Focusable> Default for FocusRing<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Border and title style that shows whether a pane has focus
+pub fn focus_style(focused: bool) -> Style {
+    if focused {
+        Style::default().fg(Color::Blue)
+    } else {
+        Style::default().fg(Color::Gray)
+    }
+}
+
+/// A contact's initials on their avatar color, like Messages.app shows for contacts without a
+/// photo. Colors ratatui can't parse fall back to gray.
+pub fn avatar_span(initials: &str, color: &str) -> Span<'static> {
+    let color = color.parse().unwrap_or(Color::Gray);
+    Span::styled(
+        format!(" {} ", initials),
+        Style::default()
+            .fg(Color::Black)
+            .bg(color)
+            .add_modifier(Modifier::BOLD),
+    )
+}
+
+/// A red banner for the title bar in the sandbox, so a demo can't be mistaken for real
+/// sending, or `None` when messages are really sent.
+pub fn sandbox_banner() -> Option<Span<'static>> {
+    sender::is_sandbox().then(|| {
+        Span::styled(
+            " SANDBOX ",
+            Style::default()
+                .fg(Color::White)
+                .bg(Color::Red)
+                .add_modifier(Modifier::BOLD),
+        )
+    })
+}
+
+/// Split `width` into two side-by-side panes, giving the first `percent` of it.
+///
+/// Each pane keeps at least its minimum width; if both don't fit, the first
+/// pane takes the whole width and the second collapses to zero.
+pub fn split_panes(width: u16, percent: u16, min_first: u16, min_second: u16) -> (u16, u16) {
+    if width < min_first + min_second {
+        return (width, 0);
+    }
+    let first = (width as u32 * percent.min(100) as u32 / 100) as u16;
+    let first = first.clamp(min_first, width - min_second);
+    (first, width - first)
 }
 
 /// Helper to poll for key events with a timeout
@@ -60,3 +197,44 @@ pub fn poll_event(timeout_ms: u64) -> io::Result<Option<Event>> {
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    enum Pane {
+        Left,
+        Middle,
+        Right,
+    }
+
+    impl Focusable for Pane {
+        const ORDER: &'static [Self] = &[Pane::Left, Pane::Middle, Pane::Right];
+    }
+
+    #[test]
+    fn test_focus_ring() {
+        let mut focus = FocusRing::<Pane>::new();
+        assert_eq!(focus.current(), Pane::Left);
+        focus.next();
+        assert!(focus.is_focused(Pane::Middle));
+        focus.next();
+        focus.next();
+        assert_eq!(focus.current(), Pane::Left);
+        focus.prev();
+        assert_eq!(focus.current(), Pane::Right);
+        focus.focus(Pane::Middle);
+        assert_eq!(focus.current(), Pane::Middle);
+    }
+
+    #[test]
+    fn test_split_panes() {
+        assert_eq!(split_panes(100, 40, 20, 30), (40, 60));
+        // Minimum sizes win over the ratio
+        assert_eq!(split_panes(100, 10, 20, 30), (20, 80));
+        assert_eq!(split_panes(100, 90, 20, 30), (70, 30));
+        // Too narrow for both: the second pane collapses
+        assert_eq!(split_panes(40, 40, 20, 30), (40, 0));
+    }
+}
@@ -1,25 +1,235 @@
 use crate::config::Config;
+use crate::db::{ContactSummary, MessageDBPool};
 use crate::error::Result;
-use crate::tui::common::{run_terminal, TuiResult};
-use crossterm::event::{Event, KeyCode, KeyModifiers};
+use crate::i18n::t;
+use crate::state::StateStore;
+use crate::tui::chat::message_content;
+use crate::tui::common::{focus_style, run_terminal, split_panes, TuiResult};
+use crate::tui::modal::{Modal, ModalResult};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
 };
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long the selection has to stay on a contact before its history is looked up, so
+/// scrolling through the list doesn't query every contact on the way
+const DETAIL_DELAY: Duration = Duration::from_millis(200);
+
+/// Share of the width the contact list takes when there's room for the detail pane (percent)
+const LIST_PERCENT: u16 = 55;
+
+/// Narrowest the contact list and detail panes can get (columns)
+const MIN_LIST_WIDTH: u16 = 40;
+const MIN_DETAIL_WIDTH: u16 = 30;
+
+/// Progress through merging groups of duplicate contacts
+struct MergeState {
+    groups: Vec<Vec<String>>,
+    group_index: usize,
+    /// Index within the current group of the contact to merge into
+    keep_index: usize,
+}
+
+/// What an open modal in the contacts view is asking about
+#[derive(Clone)]
+enum ContactsModal {
+    /// Whether to remove the named contact
+    Remove(String),
+}
 
 /// The contacts view for managing contacts
 pub struct ContactsView {
     config: Config,
+    /// Keeps removed contacts so they can be restored
+    state: StateStore,
     selected_index: usize,
+    merge: Option<MergeState>,
+    /// Dialog over the view, when open
+    modal: Option<Modal<ContactsModal>>,
+    /// Result of the last removal or undo
+    status: Option<String>,
+    /// Message history of the contacts shown in the detail pane so far, by name
+    details: HashMap<String, ContactSummary>,
+    /// When the selection last moved
+    selected_at: Instant,
 }
 
 impl ContactsView {
     /// Create a new contacts view
-    pub fn new(config: Config) -> Self {
-        Self {
+    pub fn new(config: Config) -> Result<Self> {
+        Ok(Self {
             config,
+            state: StateStore::open()?,
             selected_index: 0,
+            merge: None,
+            modal: None,
+            status: None,
+            details: HashMap::new(),
+            selected_at: Instant::now(),
+        })
+    }
+
+    /// Name of the selected contact
+    fn selected_name(&self) -> Option<&String> {
+        self.config
+            .list_contacts()
+            .get(self.selected_index)
+            .map(|(name, _)| *name)
+    }
+
+    /// Select the contact at `index`
+    fn select(&mut self, index: usize) {
+        self.selected_index = index;
+        self.selected_at = Instant::now();
+    }
+
+    /// Look up the selected contact's message history once the selection has settled
+    fn update_details(&mut self) -> Result<()> {
+        if self.selected_at.elapsed() < DETAIL_DELAY {
+            return Ok(());
+        }
+        let Some(name) = self.selected_name().cloned() else {
+            return Ok(());
+        };
+        if self.details.contains_key(&name) {
+            return Ok(());
+        }
+        let Some(entry) = self.config.get_contact(&name) else {
+            return Ok(());
+        };
+
+        let handles: Vec<String> = entry.identifiers().cloned().collect();
+        let summary = MessageDBPool::shared()
+            .get()?
+            .get_contact_summary(&handles)?;
+        self.details.insert(name, summary);
+        Ok(())
+    }
+
+    /// Start merging duplicate contacts, if there are any
+    fn start_merge(&mut self) {
+        let groups = self.config.find_duplicates();
+        if !groups.is_empty() {
+            self.merge = Some(MergeState {
+                groups,
+                group_index: 0,
+                keep_index: 0,
+            });
+        }
+    }
+
+    /// Move to the next duplicate group, leaving merge mode after the last one
+    fn next_merge_group(&mut self) {
+        if let Some(merge) = &mut self.merge {
+            merge.group_index += 1;
+            merge.keep_index = 0;
+            if merge.group_index >= merge.groups.len() {
+                self.merge = None;
+            }
+        }
+    }
+
+    /// Merge the current duplicate group into the chosen contact and save
+    fn merge_current_group(&mut self) -> Result<()> {
+        if let Some(merge) = &self.merge {
+            let group = &merge.groups[merge.group_index];
+            self.config
+                .merge_contacts(&group[merge.keep_index], group)?;
+            self.config.save()?;
+            // The contact kept has the others' identifiers now
+            self.details.remove(&group[merge.keep_index]);
         }
+        self.next_merge_group();
+        self.selected_index = self
+            .selected_index
+            .min(self.config.contact_count().saturating_sub(1));
+        Ok(())
+    }
+
+    /// Handle a key while merging duplicates
+    fn handle_merge_key(&mut self, code: KeyCode) -> Result<()> {
+        match code {
+            KeyCode::Esc => {
+                self.merge = None;
+            }
+            KeyCode::Left => {
+                if let Some(merge) = &mut self.merge {
+                    merge.keep_index = merge.keep_index.saturating_sub(1);
+                }
+            }
+            KeyCode::Right => {
+                if let Some(merge) = &mut self.merge {
+                    let group_len = merge.groups[merge.group_index].len();
+                    merge.keep_index = (merge.keep_index + 1).min(group_len - 1);
+                }
+            }
+            KeyCode::Enter => {
+                self.merge_current_group()?;
+            }
+            KeyCode::Char('n') => {
+                self.next_merge_group();
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Ask to remove the selected contact
+    fn confirm_remove_selected(&mut self) {
+        let Some(name) = self.selected_name().cloned() else {
+            return;
+        };
+        self.modal = Some(Modal::confirm(
+            ContactsModal::Remove(name.clone()),
+            t!("contacts.remove_title"),
+            t!("contacts.remove_prompt", name = name),
+        ));
+    }
+
+    /// Handle a key while a modal is open
+    fn handle_modal_key(&mut self, key: KeyEvent) -> Result<()> {
+        let Some(modal) = &mut self.modal else {
+            return Ok(());
+        };
+
+        match (modal.action(), modal.handle_key(key)) {
+            (_, ModalResult::Pending) => return Ok(()),
+            (ContactsModal::Remove(name), ModalResult::Confirm) => {
+                if let Some(removed) = self.config.take_contact(&name) {
+                    self.config.save()?;
+                    self.state.push_removed_contact(&removed)?;
+                    self.status = Some(t!("contacts.removed", name = name));
+                }
+                self.selected_index = self
+                    .selected_index
+                    .min(self.config.contact_count().saturating_sub(1));
+            }
+            _ => {}
+        }
+        self.modal = None;
+        Ok(())
+    }
+
+    /// Restore the most recently removed contact
+    fn undo_remove(&mut self) -> Result<()> {
+        let Some(removed) = self.state.last_removed_contact()? else {
+            self.status = Some(t!("contacts.nothing_to_undo"));
+            return Ok(());
+        };
+
+        let name = removed.entry.name.clone();
+        match self.config.restore_contact(removed) {
+            Ok(()) => {
+                self.config.save()?;
+                self.state.drop_last_removed_contact()?;
+                self.status = Some(t!("contacts.restored", name = name));
+            }
+            Err(e) => self.status = Some(e.to_string()),
+        }
+        Ok(())
     }
 
     /// Run the contacts view
@@ -33,32 +243,52 @@ impl ContactsView {
         terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
     ) -> TuiResult<()> {
         loop {
+            if let Err(e) = self.update_details() {
+                self.status = Some(e.to_string());
+                // Try again after another delay rather than on every tick
+                self.selected_at = Instant::now();
+            }
+
             // Draw UI
             terminal.draw(|f| self.render(f))?;
 
             // Handle events
-            if let Some(event) = crate::tui::common::poll_event(50)? {
-                if let Event::Key(key) = event {
-                    match key.code {
-                        KeyCode::Esc => {
-                            return Ok(());
-                        }
-                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            return Ok(());
-                        }
-                        KeyCode::Up => {
-                            if self.selected_index > 0 {
-                                self.selected_index -= 1;
-                            }
-                        }
-                        KeyCode::Down => {
-                            let contact_count = self.config.contact_count();
-                            if self.selected_index < contact_count.saturating_sub(1) {
-                                self.selected_index += 1;
-                            }
+            if let Some(Event::Key(key)) = crate::tui::common::poll_event(50)? {
+                if self.modal.is_some() {
+                    self.handle_modal_key(key)?;
+                    continue;
+                }
+                if self.merge.is_some() {
+                    self.handle_merge_key(key.code)?;
+                    continue;
+                }
+
+                match key.code {
+                    KeyCode::Esc => {
+                        return Ok(());
+                    }
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(());
+                    }
+                    KeyCode::Up if self.selected_index > 0 => {
+                        self.select(self.selected_index - 1);
+                    }
+                    KeyCode::Down => {
+                        let contact_count = self.config.contact_count();
+                        if self.selected_index < contact_count.saturating_sub(1) {
+                            self.select(self.selected_index + 1);
                         }
-                        _ => {}
                     }
+                    KeyCode::Char('m') => {
+                        self.start_merge();
+                    }
+                    KeyCode::Char('d') => {
+                        self.confirm_remove_selected();
+                    }
+                    KeyCode::Char('u') => {
+                        self.undo_remove()?;
+                    }
+                    _ => {}
                 }
             }
         }
@@ -75,11 +305,16 @@ impl ContactsView {
             .split(f.size());
 
         // Title
-        let title = Paragraph::new("Contacts")
+        let title = Paragraph::new(t!("contacts.title"))
             .block(Block::default().borders(Borders::ALL))
             .alignment(Alignment::Center);
         f.render_widget(title, chunks[0]);
 
+        if let Some(merge) = &self.merge {
+            self.render_merge(f, merge, chunks[1]);
+            return;
+        }
+
         // Content
         let content_chunks = Layout::default()
             .direction(Direction::Vertical)
@@ -96,12 +331,12 @@ impl ContactsView {
                 None => default.clone(),
             }
         } else {
-            "None".to_string()
+            t!("contacts.no_default")
         };
 
         let default_section = Paragraph::new(default_contact).block(
             Block::default()
-                .title("Default Contact")
+                .title(t!("contacts.default_title"))
                 .borders(Borders::ALL),
         );
         f.render_widget(default_section, content_chunks[0]);
@@ -112,32 +347,225 @@ impl ContactsView {
             .list_contacts()
             .into_iter()
             .map(|(name, entry)| {
+                let identifiers: Vec<&str> = entry.identifiers().map(|id| id.as_str()).collect();
+                let identifiers = identifiers.join(", ");
                 let display = match &entry.display_name {
-                    Some(display) => format!("{} ({})", display, entry.identifier),
-                    None => entry.identifier.clone(),
+                    Some(display) => format!("{} ({})", display, identifiers),
+                    None => identifiers,
                 };
-                ListItem::new(format!("{}: {}", name, display))
+                let aliases = self.config.aliases_for(name);
+                if aliases.is_empty() {
+                    ListItem::new(format!("{}: {}", name, display))
+                } else {
+                    let aliases: Vec<&str> = aliases.iter().map(|a| a.as_str()).collect();
+                    ListItem::new(t!(
+                        "contacts.with_aliases",
+                        name = name,
+                        aliases = aliases.join(", "),
+                        display = display
+                    ))
+                }
             })
             .collect();
 
+        let duplicate_count = self.config.find_duplicates().len();
+        let mut contacts_title = if duplicate_count > 0 {
+            t!("contacts.list_title_duplicates", count = duplicate_count)
+        } else {
+            t!("contacts.list_title")
+        };
+        if let Some(status) = &self.status {
+            contacts_title.push_str(&format!(" | {}", status));
+        }
+
         let contacts_list = List::new(contacts)
-            .block(
-                Block::default()
-                    .title("Named Contacts")
-                    .borders(Borders::ALL),
-            )
+            .block(Block::default().title(contacts_title).borders(Borders::ALL))
             .highlight_style(Style::default().add_modifier(Modifier::BOLD))
             .highlight_symbol("> ");
 
+        let (list_width, detail_width) = split_panes(
+            content_chunks[1].width,
+            LIST_PERCENT,
+            MIN_LIST_WIDTH,
+            MIN_DETAIL_WIDTH,
+        );
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Length(list_width),   // Contacts
+                Constraint::Length(detail_width), // Details
+            ])
+            .split(content_chunks[1]);
+
         let mut state = ListState::default();
         state.select(Some(self.selected_index));
 
-        f.render_stateful_widget(contacts_list, content_chunks[1], &mut state);
+        f.render_stateful_widget(contacts_list, panes[0], &mut state);
+
+        // The details collapse on narrow terminals
+        if detail_width > 0 {
+            self.render_details(f, panes[1]);
+        }
+
+        if let Some(modal) = &self.modal {
+            modal.render(f);
+        }
+    }
+
+    /// Render the selected contact's identifiers and message history
+    fn render_details(&self, f: &mut Frame, area: Rect) {
+        let Some(name) = self.selected_name() else {
+            f.render_widget(Block::default().borders(Borders::ALL), area);
+            return;
+        };
+        let identifiers: Vec<String> = self
+            .config
+            .get_contact(name)
+            .map(|entry| entry.identifiers().cloned().collect())
+            .unwrap_or_default();
+
+        let label = Style::default().add_modifier(Modifier::BOLD);
+        let field = |name: String, value: String| {
+            Line::from(vec![
+                Span::styled(format!("{}: ", name), label),
+                Span::raw(value),
+            ])
+        };
+        let mut lines = vec![field(t!("contacts.identifiers"), identifiers.join(", "))];
+        match self.details.get(name) {
+            Some(summary) => {
+                let last = summary.last_message.as_ref();
+                lines.push(field(
+                    t!("contacts.service"),
+                    last.and_then(|message| message.service.clone())
+                        .unwrap_or_else(|| "-".to_string()),
+                ));
+                lines.push(field(
+                    t!("contacts.messages"),
+                    summary.message_count.to_string(),
+                ));
+                lines.push(field(
+                    t!("contacts.first_message"),
+                    summary
+                        .first_date
+                        .map(|date| date.format("%Y-%m-%d").to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                ));
+                if let Some(message) = last {
+                    let sender = if message.is_from_me {
+                        t!("contacts.you")
+                    } else {
+                        name.clone()
+                    };
+                    lines.push(Line::from(""));
+                    lines.push(field(
+                        t!("contacts.last_message"),
+                        message.date.format("%Y-%m-%d %H:%M").to_string(),
+                    ));
+                    lines.push(Line::from(format!(
+                        "{}: {}",
+                        sender,
+                        message_content(message)
+                    )));
+                }
+            }
+            None => lines.push(Line::styled(
+                t!("contacts.loading"),
+                Style::default().fg(Color::DarkGray),
+            )),
+        }
+
+        let details = Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().title(name.as_str()).borders(Borders::ALL));
+        f.render_widget(details, area);
+    }
+
+    /// Render a group of duplicate contacts side by side
+    fn render_merge(&self, f: &mut Frame, merge: &MergeState, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(0),    // Duplicates
+                Constraint::Length(3), // Instructions
+            ])
+            .split(area);
+
+        let group = &merge.groups[merge.group_index];
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Ratio(1, group.len() as u32); group.len()])
+            .split(chunks[0]);
+
+        for (i, name) in group.iter().enumerate() {
+            let entry = self.config.get_contact(name);
+            let aliases: Vec<&str> = self
+                .config
+                .aliases_for(name)
+                .iter()
+                .map(|a| a.as_str())
+                .collect();
+
+            let text = vec![
+                Line::from(t!(
+                    "contacts.merge_identifier",
+                    identifier = entry.map(|e| e.identifier.as_str()).unwrap_or("")
+                )),
+                Line::from(t!(
+                    "contacts.merge_display_name",
+                    name = entry.and_then(|e| e.display_name.as_deref()).unwrap_or("-")
+                )),
+                Line::from(t!(
+                    "contacts.merge_aliases",
+                    aliases = if aliases.is_empty() {
+                        "-".to_string()
+                    } else {
+                        aliases.join(", ")
+                    }
+                )),
+            ];
+
+            let focused = i == merge.keep_index;
+            let title = if focused {
+                t!("contacts.keep", name = name)
+            } else {
+                name.clone()
+            };
+            let border_style = focus_style(focused);
+
+            let column = Paragraph::new(text).block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .border_style(border_style),
+            );
+            f.render_widget(column, columns[i]);
+        }
+
+        let key_style = |color| Style::default().fg(color).add_modifier(Modifier::BOLD);
+        let instructions = Paragraph::new(Line::from(vec![
+            Span::raw(t!(
+                "contacts.duplicate_of",
+                number = merge.group_index + 1,
+                count = merge.groups.len()
+            )),
+            Span::styled("←/→", key_style(Color::Blue)),
+            Span::raw(t!("contacts.choose_keep")),
+            Span::styled("Enter", key_style(Color::Green)),
+            Span::raw(t!("contacts.merge")),
+            Span::styled("n", key_style(Color::Blue)),
+            Span::raw(t!("contacts.skip")),
+            Span::styled("Esc", key_style(Color::Red)),
+            Span::raw(t!("contacts.cancel")),
+        ]))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL));
+        f.render_widget(instructions, chunks[1]);
     }
 }
 
 /// Convenience function to run the contacts TUI
 pub fn run_contacts_tui(config: Config) -> Result<()> {
-    let mut contacts_view = ContactsView::new(config);
+    let mut contacts_view = ContactsView::new(config)?;
     contacts_view.run()
 }
@@ -0,0 +1,1012 @@
+use crate::birthdays::{birthday_message, is_birthday, BirthdayReminder};
+use crate::config::Config;
+use crate::db::{self, Conversation, Message, MessageDBPool};
+use crate::error::Result;
+use crate::formatter::{display_width, format_display_number, normalize_identifier};
+use crate::i18n::t;
+use crate::sender::{own_handle, start_group, Sender};
+use crate::state::{Session, StateStore};
+use crate::tui::chat::{message_content, ChatView};
+use crate::tui::common::{
+    avatar_span, focus_style, run_terminal, sandbox_banner, split_panes, FocusRing, Focusable,
+    TuiResult,
+};
+use crate::tui::input::TextInput;
+use crate::tui::jumps::JumpList;
+use crate::tui::markdown::{render_markdown, render_plain};
+use crate::tui::modal::{Modal, ModalResult};
+use crate::tui::refresh::RefreshRate;
+use crate::tui::switcher::{Switcher, SwitcherAction};
+use crate::tui::toast::Toast;
+use crate::watcher::{ConfigWatcher, DbWatcher};
+use chrono::{Duration as ChronoDuration, Local};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Maximum number of conversations listed in the inbox
+const CONVERSATION_LIMIT: usize = 100;
+
+/// How often to reload even without writes, so expired snoozes reappear (seconds)
+const IDLE_REFRESH_SECS: u64 = 60;
+
+/// Number of messages shown in the preview pane
+const PREVIEW_MESSAGES: usize = 20;
+
+/// Narrowest the conversation list and preview panes can get (columns)
+const MIN_LIST_WIDTH: u16 = 30;
+const MIN_PREVIEW_WIDTH: u16 = 30;
+
+/// How much `<`/`>` resize the panes by (percent)
+const RESIZE_STEP: u16 = 5;
+
+/// How long a conversation can be snoozed for (label key, hours)
+const SNOOZE_OPTIONS: &[(&str, i64)] = &[
+    ("inbox.snooze_hour", 1),
+    ("inbox.snooze_hours", 4),
+    ("inbox.snooze_day", 24),
+    ("inbox.snooze_week", 24 * 7),
+];
+
+/// Which conversations the inbox is showing
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum InboxFilter {
+    /// Conversations with people
+    Main,
+    /// Short codes and automated senders
+    Other,
+    /// Conversations the user archived
+    Archived,
+}
+
+/// What an open modal in the inbox is asking about
+#[derive(Clone, Copy)]
+enum InboxModal {
+    /// How long to snooze the selected conversation
+    Snooze,
+    /// Whether to send the birthday message to the selected conversation
+    Birthday,
+    /// What to name the group being started
+    GroupName,
+    /// The first message of the group being started
+    GroupMessage,
+}
+
+/// A group chat being started from the inbox
+struct NewGroup {
+    /// Identifiers of the people in the group
+    participants: Vec<String>,
+    /// Name for the group, or empty to leave it unnamed
+    name: String,
+}
+
+/// Panes of the inbox that can hold focus
+#[derive(Clone, Copy, PartialEq)]
+enum InboxPane {
+    /// The conversation list
+    List,
+    /// The preview of the selected conversation, which Up/Down scroll
+    Preview,
+}
+
+impl Focusable for InboxPane {
+    const ORDER: &'static [Self] = &[InboxPane::List, InboxPane::Preview];
+}
+
+/// The inbox view listing recent conversations
+pub struct InboxView {
+    config: Config,
+    state: StateStore,
+    conversations: Vec<Conversation>,
+    unread_counts: HashMap<String, usize>,
+    pinned: Vec<String>,
+    /// The user's own phone number or email, whose conversation is Notes to Self
+    own_handle: Option<String>,
+    filter: InboxFilter,
+    selected_index: usize,
+    /// Latest messages of the selected conversation, oldest first
+    preview: Vec<Message>,
+    /// Identifier of the conversation `preview` was loaded for
+    preview_for: Option<String>,
+    /// Lines the preview is scrolled up from the latest message
+    preview_scroll: usize,
+    /// Which pane Up/Down go to
+    focus: FocusRing<InboxPane>,
+    /// Dialog over the view, when open
+    modal: Option<Modal<InboxModal>>,
+    /// Whether `g` was just pressed, so a second `g` jumps to the top
+    pending_g: bool,
+    /// Picker choosing the people in a new group, when open
+    group_picker: Option<Switcher>,
+    /// The group being started, once its people are chosen
+    new_group: Option<NewGroup>,
+    /// Detects when Messages.app writes to chat.db
+    watcher: DbWatcher,
+    /// Notice shown over the conversations, such as that the configuration was reloaded
+    toast: Option<Toast>,
+    last_refresh: Instant,
+}
+
+impl InboxView {
+    /// Create a new inbox view
+    pub fn new(config: Config) -> Result<Self> {
+        // Pins, snoozes, and seen messages belong to the live conversations, not an archive's
+        let state = if db::is_archive() {
+            StateStore::in_memory()?
+        } else {
+            StateStore::open()?
+        };
+
+        let own_handle = own_handle(&config, &*MessageDBPool::shared().get()?)?;
+
+        Ok(Self {
+            own_handle,
+            config,
+            state,
+            conversations: Vec::new(),
+            unread_counts: HashMap::new(),
+            pinned: Vec::new(),
+            filter: InboxFilter::Main,
+            selected_index: 0,
+            preview: Vec::new(),
+            preview_for: None,
+            preview_scroll: 0,
+            focus: FocusRing::new(),
+            modal: None,
+            pending_g: false,
+            group_picker: None,
+            new_group: None,
+            watcher: DbWatcher::new()?,
+            toast: None,
+            last_refresh: Instant::now(),
+        })
+    }
+
+    /// Pick up an edited configuration, showing contacts' new names and avatars.
+    fn apply_config(&mut self, config: Config) -> Result<()> {
+        self.own_handle = own_handle(&config, &*MessageDBPool::shared().get()?)?;
+        self.config = config;
+        self.load_conversations()
+    }
+
+    /// Load conversations from the database, applying local state from the state store
+    pub fn load_conversations(&mut self) -> Result<()> {
+        let db = MessageDBPool::shared().get()?;
+        let seen = self.state.seen_watermarks()?;
+        let snoozes = self.state.active_snoozes()?;
+        let archived = self.state.archived()?;
+        self.pinned = self.state.pinned()?;
+
+        let mut conversations = db.get_conversations(CONVERSATION_LIMIT)?;
+        let inbox_config = self.config.inbox();
+        conversations.retain(|c| {
+            if snoozes.contains_key(&c.identifier) {
+                return false;
+            }
+            let automated =
+                inbox_config.filter_automated && c.is_automated(&inbox_config.automated_senders);
+            match self.filter {
+                InboxFilter::Archived => archived.contains(&c.identifier),
+                InboxFilter::Other => !archived.contains(&c.identifier) && automated,
+                InboxFilter::Main => !archived.contains(&c.identifier) && !automated,
+            }
+        });
+
+        // Notes to Self comes first, then pinned conversations in pin order
+        conversations.sort_by_key(|c| {
+            let pin = self
+                .pinned
+                .iter()
+                .position(|p| *p == c.identifier)
+                .unwrap_or(self.pinned.len());
+            (!self.is_notes_to_self(c), pin)
+        });
+
+        self.unread_counts.clear();
+        for conversation in &conversations {
+            let count = match (
+                conversation.last_incoming_rowid,
+                seen.get(&conversation.identifier),
+            ) {
+                (Some(incoming), Some(&last_seen)) if incoming > last_seen => {
+                    db.count_incoming_since(&conversation.identifier, last_seen)?
+                }
+                (Some(_), Some(_)) => 0,
+                // Fall back to Messages.app's read flags until we've tracked the conversation
+                (Some(_), None) => conversation.unread_count,
+                (None, _) => 0,
+            };
+            self.unread_counts
+                .insert(conversation.identifier.clone(), count);
+        }
+
+        self.conversations = conversations;
+        self.selected_index = self
+            .selected_index
+            .min(self.conversations.len().saturating_sub(1));
+        self.last_refresh = Instant::now();
+
+        // Refresh the preview too, since the selected conversation may have new messages
+        self.preview_for = None;
+        Ok(())
+    }
+
+    /// Load the preview of the selected conversation if the selection changed.
+    /// The preview is read-only, so it doesn't mark anything as seen.
+    fn update_preview(&mut self) -> Result<()> {
+        let selected = self
+            .conversations
+            .get(self.selected_index)
+            .map(|c| c.identifier.clone());
+        if selected == self.preview_for {
+            return Ok(());
+        }
+
+        self.preview = match &selected {
+            Some(identifier) => {
+                let mut messages = MessageDBPool::shared()
+                    .get()?
+                    .get_chat_messages(identifier, PREVIEW_MESSAGES)?;
+                messages.reverse();
+                messages
+            }
+            None => Vec::new(),
+        };
+        self.preview_for = selected;
+        self.preview_scroll = 0;
+        Ok(())
+    }
+
+    /// Handle a key while a modal is open
+    fn handle_modal_key(&mut self, key: KeyEvent) -> Result<()> {
+        let Some(modal) = &mut self.modal else {
+            return Ok(());
+        };
+
+        match (modal.action(), modal.handle_key(key)) {
+            (_, ModalResult::Pending) => return Ok(()),
+            (InboxModal::Snooze, ModalResult::Pick(index)) => {
+                self.snooze_selected(SNOOZE_OPTIONS[index].1)?;
+            }
+            (InboxModal::Birthday, ModalResult::Confirm) => {
+                if let Err(error) = self.send_birthday_message() {
+                    if let Some(modal) = &mut self.modal {
+                        modal.set_error(error.to_string());
+                    }
+                    return Ok(());
+                }
+            }
+            (InboxModal::GroupName, ModalResult::Submit(name)) => {
+                if let Some(group) = &mut self.new_group {
+                    group.name = name.trim().to_string();
+                    self.modal = Some(Modal::prompt(
+                        InboxModal::GroupMessage,
+                        t!("inbox.group_title"),
+                        t!("inbox.group_message"),
+                        TextInput::new(),
+                    ));
+                    return Ok(());
+                }
+            }
+            (InboxModal::GroupMessage, ModalResult::Submit(text)) => {
+                let error = match &self.new_group {
+                    _ if text.trim().is_empty() => Some(t!("inbox.group_message_empty")),
+                    Some(group) => {
+                        start_group(&self.config, &group.participants, &group.name, &text)
+                            .err()
+                            .map(|error| error.to_string())
+                    }
+                    None => None,
+                };
+                if let Some(error) = error {
+                    if let Some(modal) = &mut self.modal {
+                        modal.set_error(error);
+                    }
+                    return Ok(());
+                }
+                // The new chat shows up once Messages.app writes it to chat.db
+                self.load_conversations()?;
+            }
+            _ => {}
+        }
+        self.modal = None;
+        self.new_group = None;
+        Ok(())
+    }
+
+    /// Handle a key in the picker choosing the people in a new group
+    fn handle_group_picker_key(&mut self, key: KeyEvent) {
+        let Some(picker) = &mut self.group_picker else {
+            return;
+        };
+        match picker.handle_key(key) {
+            SwitcherAction::None | SwitcherAction::Open(..) => {}
+            SwitcherAction::Close => self.group_picker = None,
+            SwitcherAction::Choose(participants) => {
+                self.group_picker = None;
+                self.new_group = Some(NewGroup {
+                    participants,
+                    name: String::new(),
+                });
+                self.modal = Some(Modal::prompt(
+                    InboxModal::GroupName,
+                    t!("inbox.group_title"),
+                    t!("inbox.group_name"),
+                    TextInput::new(),
+                ));
+            }
+        }
+    }
+
+    /// Whether today is the birthday of the contact a conversation is with
+    fn is_birthday_today(&self, conversation: &Conversation) -> bool {
+        self.config
+            .birthday_for(&conversation.identifier)
+            .is_some_and(|birthday| is_birthday(birthday, Local::now().date_naive()))
+    }
+
+    /// The birthday message for the selected conversation, if it's the contact's birthday
+    fn selected_birthday_message(&self) -> Option<String> {
+        let conversation = self
+            .conversations
+            .get(self.selected_index)
+            .filter(|conversation| self.is_birthday_today(conversation))?;
+        Some(birthday_message(
+            &self.config.birthdays().template,
+            &self.conversation_name(conversation),
+        ))
+    }
+
+    /// Send the birthday message to the selected conversation, spooling it while Messages.app
+    /// is unavailable
+    fn send_birthday_message(&self) -> Result<()> {
+        // An archive's or the demo's conversations aren't there to write to
+        if db::is_archive() {
+            return Ok(());
+        }
+        let (Some(conversation), Some(text)) = (
+            self.conversations.get(self.selected_index),
+            self.selected_birthday_message(),
+        ) else {
+            return Ok(());
+        };
+        let sender = Sender::from_config(&self.config, conversation.identifier.clone());
+        match sender.send_message(&text) {
+            Err(error) if error.is_transient() => {
+                self.state
+                    .spool_message(&conversation.identifier, None, &text, false)?;
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Widen or narrow the conversation list and save the new size
+    fn resize_list(&mut self, wider: bool) -> Result<()> {
+        let percent = self.config.layout().inbox_list_percent;
+        let percent = if wider {
+            (percent + RESIZE_STEP).min(100 - RESIZE_STEP)
+        } else {
+            percent.saturating_sub(RESIZE_STEP).max(RESIZE_STEP)
+        };
+        self.config.set_inbox_list_percent(percent)
+    }
+
+    /// Number of incoming messages the user hasn't seen in a conversation
+    fn unread_count(&self, conversation: &Conversation) -> usize {
+        self.unread_counts
+            .get(&conversation.identifier)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Whether a conversation has incoming messages the user hasn't seen
+    fn is_unread(&self, conversation: &Conversation) -> bool {
+        self.unread_count(conversation) > 0
+    }
+
+    /// Find the next (or previous) unread conversation, wrapping around the list
+    fn find_unread(&self, forward: bool) -> Option<usize> {
+        let len = self.conversations.len();
+        (1..=len)
+            .map(|offset| {
+                if forward {
+                    (self.selected_index + offset) % len
+                } else {
+                    (self.selected_index + len - offset) % len
+                }
+            })
+            .find(|&idx| self.is_unread(&self.conversations[idx]))
+    }
+
+    /// Mark the selected conversation as seen up to its latest message
+    fn mark_selected_seen(&mut self) -> Result<()> {
+        if let Some(conversation) = self.conversations.get(self.selected_index) {
+            self.state
+                .mark_seen(&conversation.identifier, conversation.last_rowid)?;
+            self.unread_counts
+                .insert(conversation.identifier.clone(), 0);
+        }
+        Ok(())
+    }
+
+    /// Pin or unpin the selected conversation
+    fn toggle_selected_pin(&mut self) -> Result<()> {
+        if let Some(conversation) = self.conversations.get(self.selected_index) {
+            if self.pinned.contains(&conversation.identifier) {
+                self.state.unpin(&conversation.identifier)?;
+            } else {
+                self.state.pin(&conversation.identifier)?;
+            }
+            self.load_conversations()?;
+        }
+        Ok(())
+    }
+
+    /// Hide the selected conversation for a while
+    fn snooze_selected(&mut self, hours: i64) -> Result<()> {
+        if let Some(conversation) = self.conversations.get(self.selected_index) {
+            let until = Local::now() + ChronoDuration::hours(hours);
+            self.state.snooze(&conversation.identifier, until)?;
+            self.load_conversations()?;
+        }
+        Ok(())
+    }
+
+    /// Archive the selected conversation, or unarchive it in the archived view
+    fn toggle_selected_archive(&mut self) -> Result<()> {
+        if let Some(conversation) = self.conversations.get(self.selected_index) {
+            if self.filter == InboxFilter::Archived {
+                self.state.unarchive(&conversation.identifier)?;
+            } else {
+                self.state.archive(&conversation.identifier)?;
+            }
+            self.load_conversations()?;
+        }
+        Ok(())
+    }
+
+    /// Switch to a filter, or back to the main inbox if it's already shown
+    fn toggle_filter(&mut self, filter: InboxFilter) -> Result<()> {
+        self.filter = if self.filter == filter {
+            InboxFilter::Main
+        } else {
+            filter
+        };
+        self.selected_index = 0;
+        self.load_conversations()
+    }
+
+    /// Get the name to show for a conversation
+    fn conversation_name(&self, conversation: &Conversation) -> String {
+        if self.is_notes_to_self(conversation) {
+            return t!("inbox.notes_to_self");
+        }
+        if let Some(name) = self.config.display_name_for(&conversation.identifier) {
+            return name;
+        }
+
+        match &conversation.display_name {
+            Some(name) => name.clone(),
+            None => format_display_number(&conversation.identifier),
+        }
+    }
+
+    /// Whether a conversation is with the user's own phone number or email
+    fn is_notes_to_self(&self, conversation: &Conversation) -> bool {
+        self.own_handle.as_deref() == Some(&normalize_identifier(&conversation.identifier))
+    }
+
+    /// Run the inbox view
+    pub fn run(&mut self) -> Result<()> {
+        run_terminal(|terminal| self.run_ui(terminal))
+    }
+
+    /// Handle the UI loop
+    fn run_ui(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    ) -> TuiResult<()> {
+        self.load_conversations()?;
+
+        let mut refresh = RefreshRate::new(self.config.refresh());
+        let mut birthdays = self
+            .config
+            .notifications()
+            .enabled
+            .then(BirthdayReminder::new);
+        let idle_refresh = Duration::from_secs(IDLE_REFRESH_SECS);
+        let mut last_poll = Instant::now();
+        let mut config_watcher = ConfigWatcher::new();
+
+        loop {
+            if last_poll.elapsed() >= refresh.poll_interval() {
+                last_poll = Instant::now();
+                match config_watcher.reload(&self.config) {
+                    Some(Ok(config)) => {
+                        refresh = RefreshRate::new(config.refresh());
+                        birthdays = config
+                            .notifications()
+                            .enabled
+                            .then(|| birthdays.take().unwrap_or_else(BirthdayReminder::new));
+                        self.toast = Some(match self.apply_config(config) {
+                            Ok(()) => Toast::new(t!("inbox.config_reloaded"), None),
+                            Err(e) => Toast::new(t!("inbox.load_failed"), Some(e.to_string())),
+                        });
+                    }
+                    Some(Err(e)) => {
+                        self.toast = Some(Toast::new(
+                            t!("inbox.config_reload_failed"),
+                            Some(e.to_string()),
+                        ))
+                    }
+                    None => {}
+                }
+                let changed = self.watcher.has_changed();
+                if changed {
+                    refresh.record_activity();
+                }
+                if changed || self.last_refresh.elapsed() >= idle_refresh {
+                    if let Err(e) = self.load_conversations() {
+                        self.toast = Some(Toast::new(t!("inbox.load_failed"), Some(e.to_string())));
+                    }
+                }
+                if let Some(birthdays) = &mut birthdays {
+                    if let Err(e) = birthdays.check(&self.config, &self.state) {
+                        self.toast = Some(Toast::new(
+                            t!("inbox.birthdays_failed"),
+                            Some(e.to_string()),
+                        ));
+                    }
+                }
+            }
+
+            if let Err(e) = self.update_preview() {
+                self.toast = Some(Toast::new(t!("inbox.preview_failed"), Some(e.to_string())));
+            }
+            if self.toast.as_ref().is_some_and(Toast::is_expired) {
+                self.toast = None;
+            }
+
+            // Draw UI
+            terminal.draw(|f| self.render(f))?;
+
+            // Handle events
+            let event = crate::tui::common::poll_event(refresh.tick_rate().as_millis() as u64)?;
+            if let Some(event) = &event {
+                refresh.observe(event);
+            }
+            if let Some(Event::Key(key)) = event {
+                if self.modal.is_some() {
+                    self.handle_modal_key(key)?;
+                    continue;
+                }
+                if self.group_picker.is_some() {
+                    self.handle_group_picker_key(key);
+                    continue;
+                }
+                let pending_g = std::mem::take(&mut self.pending_g);
+                match key.code {
+                    KeyCode::Esc => {
+                        return Ok(());
+                    }
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(());
+                    }
+                    KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        let jump = JumpList::shared().back(None);
+                        if let Some(session) = jump {
+                            self.open_jump(terminal, session)?;
+                        }
+                    }
+                    KeyCode::Char('i') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        let jump = JumpList::shared().forward(None);
+                        if let Some(session) = jump {
+                            self.open_jump(terminal, session)?;
+                        }
+                    }
+                    KeyCode::Left | KeyCode::Right => {
+                        self.focus.next();
+                    }
+                    KeyCode::Up if self.focus.is_focused(InboxPane::Preview) => {
+                        let max_scroll = self.preview_lines().len().saturating_sub(1);
+                        self.preview_scroll = (self.preview_scroll + 1).min(max_scroll);
+                    }
+                    KeyCode::Down if self.focus.is_focused(InboxPane::Preview) => {
+                        self.preview_scroll = self.preview_scroll.saturating_sub(1);
+                    }
+                    KeyCode::Up if self.selected_index > 0 => {
+                        self.selected_index -= 1;
+                    }
+                    KeyCode::Down
+                        if self.selected_index < self.conversations.len().saturating_sub(1) =>
+                    {
+                        self.selected_index += 1;
+                    }
+                    KeyCode::Char('g') if pending_g => {
+                        self.selected_index = 0;
+                    }
+                    KeyCode::Char('g') => {
+                        self.pending_g = true;
+                    }
+                    KeyCode::Char('G') => {
+                        self.selected_index = self.conversations.len().saturating_sub(1);
+                    }
+                    KeyCode::Char(digit @ '1'..='9') => {
+                        let index = digit as usize - '1' as usize;
+                        if index < self.conversations.len() {
+                            self.selected_index = index;
+                            self.open_selected(terminal)?;
+                        }
+                    }
+                    KeyCode::Tab => {
+                        if let Some(idx) = self.find_unread(true) {
+                            self.selected_index = idx;
+                        }
+                    }
+                    KeyCode::BackTab => {
+                        if let Some(idx) = self.find_unread(false) {
+                            self.selected_index = idx;
+                        }
+                    }
+                    KeyCode::Char('s') => {
+                        self.mark_selected_seen()?;
+                    }
+                    KeyCode::Char('p') => {
+                        self.toggle_selected_pin()?;
+                    }
+                    KeyCode::Char('z') if !self.conversations.is_empty() => {
+                        let options = SNOOZE_OPTIONS.iter().map(|(label, _)| t!(label)).collect();
+                        self.modal = Some(Modal::picker(
+                            InboxModal::Snooze,
+                            t!("inbox.snooze_title"),
+                            t!("inbox.snooze_prompt"),
+                            options,
+                        ));
+                    }
+                    KeyCode::Char('b') if !db::is_archive() => {
+                        if let Some(text) = self.selected_birthday_message() {
+                            self.modal = Some(Modal::confirm(
+                                InboxModal::Birthday,
+                                t!("inbox.birthday_title"),
+                                t!("inbox.birthday_prompt", text = text),
+                            ));
+                        }
+                    }
+                    KeyCode::Char('a') => {
+                        self.toggle_selected_archive()?;
+                    }
+                    KeyCode::Char('n') if !db::is_archive() => {
+                        let db = MessageDBPool::shared().get()?;
+                        self.group_picker = Some(Switcher::choose_several(&self.config, &db)?);
+                    }
+                    KeyCode::Char('A') => {
+                        self.toggle_filter(InboxFilter::Archived)?;
+                    }
+                    KeyCode::Char('o') => {
+                        self.toggle_filter(InboxFilter::Other)?;
+                    }
+                    KeyCode::Char('<') => {
+                        self.resize_list(false)?;
+                    }
+                    KeyCode::Char('>') => {
+                        self.resize_list(true)?;
+                    }
+                    KeyCode::Enter => {
+                        self.open_selected(terminal)?;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Open the selected conversation
+    fn open_selected(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    ) -> TuiResult<()> {
+        if let Some(conversation) = self.conversations.get(self.selected_index) {
+            let name = self.conversation_name(conversation);
+            let chat = ChatView::new(conversation.identifier.clone(), name)?;
+            self.open_chat(terminal, chat)?;
+        }
+        Ok(())
+    }
+
+    /// Open a chat over the inbox, marking the selected conversation as seen
+    fn open_chat(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+        mut chat: ChatView,
+    ) -> TuiResult<()> {
+        self.mark_selected_seen()?;
+        chat.run_ui(terminal)?;
+        self.load_conversations()?;
+        Ok(())
+    }
+
+    /// Open a conversation from the jump list where it was left, selecting it in the list
+    fn open_jump(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+        session: Session,
+    ) -> TuiResult<()> {
+        let index = self
+            .conversations
+            .iter()
+            .position(|c| c.identifier == session.conversation);
+        let mut chat = ChatView::resume(session)?;
+        match index {
+            Some(index) => {
+                self.selected_index = index;
+                self.open_chat(terminal, chat)
+            }
+            // Older than the conversations listed
+            None => {
+                chat.run_ui(terminal)?;
+                self.load_conversations()?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Render the UI
+    fn render(&self, f: &mut Frame) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3), // Title
+                Constraint::Min(0),    // Conversations
+                Constraint::Length(3), // Instructions
+            ])
+            .split(f.size());
+
+        // Title
+        let unread: usize = self
+            .conversations
+            .iter()
+            .map(|c| self.unread_count(c))
+            .sum();
+        let view_name = match self.filter {
+            InboxFilter::Main => "Inbox",
+            InboxFilter::Other => "Other",
+            InboxFilter::Archived => "Archived",
+        };
+        let mut title_spans = Vec::new();
+        if let Some(banner) = sandbox_banner() {
+            title_spans.push(banner);
+            title_spans.push(Span::raw(" "));
+        }
+        title_spans.push(Span::raw(format!("{} ({} unread)", view_name, unread)));
+        let title = Paragraph::new(Line::from(title_spans))
+            .block(Block::default().borders(Borders::ALL))
+            .alignment(Alignment::Center);
+        f.render_widget(title, chunks[0]);
+
+        // Conversations
+        let items: Vec<ListItem> = self
+            .conversations
+            .iter()
+            .enumerate()
+            .map(|(i, conversation)| {
+                let unread = self.unread_count(conversation);
+                let marker = if self.is_notes_to_self(conversation) {
+                    "📝"
+                } else if self.pinned.contains(&conversation.identifier) {
+                    "📌"
+                } else if unread > 0 {
+                    "● "
+                } else {
+                    "  "
+                };
+                let name_style = if unread > 0 {
+                    Style::default()
+                        .fg(Color::Blue)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                let preview = conversation
+                    .last_text
+                    .as_deref()
+                    .unwrap_or("")
+                    .replace('\n', " ");
+
+                let badge = if unread > 0 {
+                    format!(" ({})", unread)
+                } else {
+                    String::new()
+                };
+
+                // The first nine open with their number
+                let number = if i < 9 {
+                    format!("{} ", i + 1)
+                } else {
+                    "  ".to_string()
+                };
+                let mut spans = vec![
+                    Span::styled(number, Style::default().fg(Color::DarkGray)),
+                    Span::styled(marker, Style::default().fg(Color::Blue)),
+                ];
+                if let Some((initials, color)) = self.config.avatar_for(&conversation.identifier) {
+                    spans.push(avatar_span(initials, color));
+                    spans.push(Span::raw(" "));
+                }
+                spans.extend([
+                    Span::styled(self.conversation_name(conversation), name_style),
+                    Span::raw(if self.is_birthday_today(conversation) {
+                        " 🎂"
+                    } else {
+                        ""
+                    }),
+                    Span::styled(badge, Style::default().fg(Color::Blue)),
+                    Span::styled(
+                        format!("  {}  ", conversation.last_date.format("%m/%d %H:%M")),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::raw(preview),
+                ]);
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title("Conversations")
+                    .borders(Borders::ALL)
+                    .border_style(focus_style(self.focus.is_focused(InboxPane::List))),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+
+        let (list_width, preview_width) = split_panes(
+            chunks[1].width,
+            self.config.layout().inbox_list_percent,
+            MIN_LIST_WIDTH,
+            MIN_PREVIEW_WIDTH,
+        );
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Length(list_width),    // Conversations
+                Constraint::Length(preview_width), // Preview
+            ])
+            .split(chunks[1]);
+
+        let mut state = ListState::default();
+        if !self.conversations.is_empty() {
+            state.select(Some(self.selected_index));
+        }
+        f.render_stateful_widget(list, panes[0], &mut state);
+
+        // The preview collapses on narrow terminals
+        if preview_width > 0 {
+            self.render_preview(f, panes[1]);
+        }
+
+        // Instructions
+        let key_style = Style::default()
+            .fg(Color::Blue)
+            .add_modifier(Modifier::BOLD);
+        let mut instructions = vec![
+            Span::styled("Enter", key_style),
+            Span::raw(": Open | "),
+            Span::styled("1-9", key_style),
+            Span::raw(": Open by number | "),
+            Span::styled("gg/G", key_style),
+            Span::raw(": Top/bottom | "),
+            Span::styled("Tab/Shift+Tab", key_style),
+            Span::raw(": Next/prev unread | "),
+            Span::styled("s", key_style),
+            Span::raw(": Mark seen | "),
+            Span::styled("p", key_style),
+            Span::raw(": Pin | "),
+            Span::styled("z", key_style),
+            Span::raw(": Snooze | "),
+            Span::styled("n", key_style),
+            Span::raw(": New group | "),
+            Span::styled("a/A", key_style),
+            Span::raw(": Archive/Show archived | "),
+            Span::styled("o", key_style),
+            Span::raw(": Other | "),
+            Span::styled("←/→", key_style),
+            Span::raw(": Switch pane | "),
+            Span::styled("</>", key_style),
+            Span::raw(": Resize | "),
+            Span::styled("Esc", key_style),
+            Span::raw(": Quit"),
+        ];
+        if self.selected_birthday_message().is_some() {
+            instructions.splice(
+                2..2,
+                [
+                    Span::styled("b", key_style),
+                    Span::raw(": Send birthday message | "),
+                ],
+            );
+        }
+        let instructions = Paragraph::new(Line::from(instructions))
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL));
+        f.render_widget(instructions, chunks[2]);
+
+        if let Some(toast) = &self.toast {
+            toast.render(f, chunks[1]);
+        }
+
+        if let Some(picker) = &self.group_picker {
+            picker.render(f);
+        }
+        if let Some(modal) = &self.modal {
+            modal.render(f);
+        }
+    }
+}
+
+impl InboxView {
+    /// Lines of the preview, oldest first
+    fn preview_lines(&self) -> Vec<Line<'static>> {
+        let mut lines: Vec<Line> = Vec::new();
+        for message in &self.preview {
+            let (alignment, style) = if message.is_from_me {
+                (Alignment::Right, Style::default().fg(Color::Blue))
+            } else {
+                (Alignment::Left, Style::default().fg(Color::Green))
+            };
+            let content = message_content(message);
+            let mut message_lines = if self.config.chat().markdown {
+                render_markdown(&content, style)
+            } else {
+                render_plain(&content, style)
+            };
+            let prefix = format!("{}: ", message.date.format("%H:%M"));
+            let indent = " ".repeat(display_width(&prefix));
+            for (i, line) in message_lines.iter_mut().enumerate() {
+                let lead = if i == 0 {
+                    prefix.clone()
+                } else {
+                    indent.clone()
+                };
+                line.spans.insert(0, Span::styled(lead, style));
+                line.alignment = Some(alignment);
+            }
+            lines.extend(message_lines);
+        }
+        lines
+    }
+
+    /// Render the latest messages of the selected conversation, newest at the bottom
+    fn render_preview(&self, f: &mut Frame, area: Rect) {
+        let title = self
+            .conversations
+            .get(self.selected_index)
+            .map(|c| self.conversation_name(c))
+            .unwrap_or_default();
+
+        // Keep the latest messages in view unless scrolled up
+        let mut lines = self.preview_lines();
+        lines.truncate(lines.len() - self.preview_scroll.min(lines.len()));
+        let visible = area.height.saturating_sub(2) as usize;
+        let lines = lines.split_off(lines.len().saturating_sub(visible));
+
+        let preview = Paragraph::new(lines).block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(focus_style(self.focus.is_focused(InboxPane::Preview))),
+        );
+        f.render_widget(preview, area);
+    }
+}
+
+/// Convenience function to run the inbox TUI
+pub fn run_inbox_tui(config: Config) -> Result<()> {
+    let mut inbox = InboxView::new(config)?;
+    inbox.run()
+}
@@ -0,0 +1,438 @@
+use crate::config::SendKey;
+use crate::formatter::{display_width, grapheme_boundaries};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Paragraph},
+};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Character shown in place of each grapheme of a masked input
+const MASK_CHAR: char = '•';
+
+/// Character shown in place of line breaks, since the input is a single row
+const NEWLINE_CHAR: char = '↵';
+
+/// What an Enter key press does in a composer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnterAction {
+    Send,
+    Newline,
+}
+
+/// What `key` does under the `send_key` scheme, or `None` if it isn't Enter.
+///
+/// Without the enhanced keyboard protocol, terminals send Ctrl+Enter and Shift+Enter the same
+/// as Enter, so Enter always sends and only Alt+Enter inserts a line break.
+pub fn enter_action(key: KeyEvent, send_key: SendKey, enhanced: bool) -> Option<EnterAction> {
+    if key.code != KeyCode::Enter {
+        return None;
+    }
+    let control = key.modifiers.contains(KeyModifiers::CONTROL);
+    let newline_modifier = key
+        .modifiers
+        .intersects(KeyModifiers::SHIFT | KeyModifiers::ALT);
+
+    let send = match send_key {
+        SendKey::CtrlEnter if enhanced => control,
+        _ => !newline_modifier,
+    };
+    Some(if send {
+        EnterAction::Send
+    } else {
+        EnterAction::Newline
+    })
+}
+
+/// A single-line text input with a cursor, selection, and horizontal scrolling.
+///
+/// The cursor moves and deletes by grapheme, so emoji sequences and flags are edited whole,
+/// and scrolling is measured in terminal columns.
+#[derive(Default)]
+pub struct TextInput {
+    value: String,
+    /// Cursor position, in characters, always on a grapheme boundary
+    cursor: usize,
+    /// Other end of the selection, if any, in characters
+    anchor: Option<usize>,
+    /// Text shown dimmed while the input is empty
+    placeholder: String,
+    /// Hide the contents, e.g. for secrets
+    masked: bool,
+}
+
+impl TextInput {
+    /// Create an empty input
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an input holding `value`, with the cursor at the end
+    pub fn with_value(value: impl Into<String>) -> Self {
+        let mut input = Self::new();
+        input.set_value(value);
+        input
+    }
+
+    /// Set the text shown while the input is empty
+    pub fn placeholder(mut self, placeholder: impl Into<String>) -> Self {
+        self.placeholder = placeholder.into();
+        self
+    }
+
+    /// Show each grapheme as a dot instead of its value
+    #[allow(dead_code)]
+    pub fn masked(mut self, masked: bool) -> Self {
+        self.masked = masked;
+        self
+    }
+
+    /// The current text
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Whether the input is empty
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty()
+    }
+
+    /// Replace the text, moving the cursor to the end
+    pub fn set_value(&mut self, value: impl Into<String>) {
+        self.value = value.into();
+        self.cursor = self.len();
+        self.anchor = None;
+    }
+
+    /// Clear the text
+    pub fn clear(&mut self) {
+        self.set_value(String::new());
+    }
+
+    /// Insert text at the cursor, replacing the selection
+    pub fn insert_str(&mut self, text: &str) {
+        self.delete_selection();
+        let at = self.byte_index(self.cursor);
+        self.value.insert_str(at, text);
+        self.cursor += text.chars().count();
+    }
+
+    /// Handle a key press. Returns false if the key isn't an editing key.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Char('a') if control => {
+                self.anchor = Some(0);
+                self.cursor = self.len();
+            }
+            KeyCode::Char(c) if !control && !key.modifiers.contains(KeyModifiers::ALT) => {
+                let mut buf = [0; 4];
+                self.insert_str(c.encode_utf8(&mut buf));
+            }
+            KeyCode::Backspace => {
+                if !self.delete_selection() && self.cursor > 0 {
+                    let start = self.previous_boundary();
+                    self.remove_range(start, self.cursor);
+                    self.cursor = start;
+                }
+            }
+            KeyCode::Delete => {
+                if !self.delete_selection() && self.cursor < self.len() {
+                    self.remove_range(self.cursor, self.next_boundary());
+                }
+            }
+            KeyCode::Left => self.move_cursor(self.previous_boundary(), shift),
+            KeyCode::Right => self.move_cursor(self.next_boundary(), shift),
+            KeyCode::Home => self.move_cursor(0, shift),
+            KeyCode::End => self.move_cursor(self.len(), shift),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Render the input inside `block`, placing the terminal cursor when focused
+    pub fn render(&self, f: &mut Frame, area: Rect, block: Block, focused: bool) {
+        self.render_marked(f, area, block, focused, &[]);
+    }
+
+    /// Render the input with the characters in `marks` highlighted
+    pub fn render_marked(
+        &self,
+        f: &mut Frame,
+        area: Rect,
+        block: Block,
+        focused: bool,
+        marks: &[Range<usize>],
+    ) {
+        let inner = block.inner(area);
+
+        let line = if self.value.is_empty() {
+            Line::from(Span::styled(
+                self.placeholder.as_str(),
+                Style::default().fg(Color::DarkGray),
+            ))
+        } else {
+            let offset = self.scroll_offset(inner.width as usize);
+            let end = offset + inner.width as usize;
+            let selection = self.selection();
+            let mut column = 0;
+            let spans: Vec<Span> = self
+                .display_cells()
+                .into_iter()
+                .filter(|(_, cell)| {
+                    let start = column;
+                    column += display_width(cell);
+                    start >= offset && column <= end
+                })
+                .map(|(i, cell)| {
+                    let mut style = match selection {
+                        Some((start, end)) if i >= start && i < end => {
+                            Style::default().add_modifier(Modifier::REVERSED)
+                        }
+                        _ => Style::default(),
+                    };
+                    if marks.iter().any(|mark| mark.contains(&i)) {
+                        style = style.fg(Color::Black).bg(Color::Yellow);
+                    }
+                    Span::styled(cell, style)
+                })
+                .collect();
+            Line::from(spans)
+        };
+        f.render_widget(Paragraph::new(line).block(block), area);
+
+        if focused && inner.width > 0 && inner.height > 0 {
+            let column = self.cursor_column() - self.scroll_offset(inner.width as usize);
+            f.set_cursor(inner.x + column as u16, inner.y);
+        }
+    }
+
+    /// Number of characters in the text
+    fn len(&self) -> usize {
+        self.value.chars().count()
+    }
+
+    /// Byte index of the character at `index`
+    fn byte_index(&self, index: usize) -> usize {
+        self.value
+            .char_indices()
+            .nth(index)
+            .map(|(i, _)| i)
+            .unwrap_or(self.value.len())
+    }
+
+    /// Remove the characters from `start` up to `end`
+    fn remove_range(&mut self, start: usize, end: usize) {
+        let range = self.byte_index(start)..self.byte_index(end);
+        self.value.replace_range(range, "");
+    }
+
+    /// Start of the grapheme before the cursor
+    fn previous_boundary(&self) -> usize {
+        grapheme_boundaries(&self.value)
+            .into_iter()
+            .take_while(|&boundary| boundary < self.cursor)
+            .last()
+            .unwrap_or(0)
+    }
+
+    /// End of the grapheme after the cursor
+    fn next_boundary(&self) -> usize {
+        grapheme_boundaries(&self.value)
+            .into_iter()
+            .find(|&boundary| boundary > self.cursor)
+            .unwrap_or(self.cursor)
+    }
+
+    /// Move the cursor, extending the selection if `select` is set
+    fn move_cursor(&mut self, to: usize, select: bool) {
+        if select {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = to;
+    }
+
+    /// Selected character range, if the selection isn't empty
+    fn selection(&self) -> Option<(usize, usize)> {
+        let anchor = self.anchor?;
+        (anchor != self.cursor).then(|| (anchor.min(self.cursor), anchor.max(self.cursor)))
+    }
+
+    /// Delete the selected text. Returns false if nothing was selected.
+    fn delete_selection(&mut self) -> bool {
+        let selection = self.selection();
+        self.anchor = None;
+        let Some((start, end)) = selection else {
+            return false;
+        };
+        self.remove_range(start, end);
+        self.cursor = start;
+        true
+    }
+
+    /// Graphemes as displayed, with masking and line breaks applied, each with the index of
+    /// its first character
+    fn display_cells(&self) -> Vec<(usize, String)> {
+        let boundaries = grapheme_boundaries(&self.value);
+        self.value
+            .graphemes(true)
+            .zip(boundaries)
+            .map(|(grapheme, start)| {
+                let cell = match grapheme {
+                    _ if self.masked => MASK_CHAR.to_string(),
+                    "\n" | "\r\n" => NEWLINE_CHAR.to_string(),
+                    grapheme => grapheme.to_string(),
+                };
+                (start, cell)
+            })
+            .collect()
+    }
+
+    /// Column of the cursor, counting emoji and wide characters as two
+    fn cursor_column(&self) -> usize {
+        self.display_cells()
+            .iter()
+            .take_while(|(start, _)| *start < self.cursor)
+            .map(|(_, cell)| display_width(cell))
+            .sum()
+    }
+
+    /// First visible column, scrolling just far enough to keep the cursor in view without
+    /// showing half of a wide character
+    fn scroll_offset(&self, width: usize) -> usize {
+        let cursor = self.cursor_column();
+        let target = (cursor + 1).saturating_sub(width);
+        let mut column = 0;
+        for (_, cell) in self.display_cells() {
+            if column >= target {
+                return column;
+            }
+            column += display_width(&cell);
+        }
+        column
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn shift(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::SHIFT)
+    }
+
+    #[test]
+    fn test_enter_action() {
+        let enter = key(KeyCode::Enter);
+        let ctrl_enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::CONTROL);
+        let alt_enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT);
+
+        assert_eq!(
+            enter_action(enter, SendKey::Enter, true),
+            Some(EnterAction::Send)
+        );
+        assert_eq!(
+            enter_action(shift(KeyCode::Enter), SendKey::Enter, true),
+            Some(EnterAction::Newline)
+        );
+        assert_eq!(
+            enter_action(enter, SendKey::CtrlEnter, true),
+            Some(EnterAction::Newline)
+        );
+        assert_eq!(
+            enter_action(ctrl_enter, SendKey::CtrlEnter, true),
+            Some(EnterAction::Send)
+        );
+        assert_eq!(
+            enter_action(enter, SendKey::CtrlEnter, false),
+            Some(EnterAction::Send)
+        );
+        assert_eq!(
+            enter_action(alt_enter, SendKey::CtrlEnter, false),
+            Some(EnterAction::Newline)
+        );
+        assert_eq!(enter_action(key(KeyCode::Tab), SendKey::Enter, true), None);
+    }
+
+    #[test]
+    fn test_editing() {
+        let mut input = TextInput::with_value("héllo");
+        input.handle_key(key(KeyCode::Home));
+        input.handle_key(key(KeyCode::Right));
+        input.handle_key(key(KeyCode::Delete));
+        input.handle_key(key(KeyCode::Char('e')));
+        assert_eq!(input.value(), "hello");
+
+        input.handle_key(key(KeyCode::End));
+        input.handle_key(key(KeyCode::Backspace));
+        assert_eq!(input.value(), "hell");
+        assert!(!input.handle_key(key(KeyCode::Enter)));
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut input = TextInput::with_value("hello world");
+        input.handle_key(shift(KeyCode::Left));
+        input.handle_key(shift(KeyCode::Left));
+        assert_eq!(input.selection(), Some((9, 11)));
+
+        // Typing replaces the selection
+        input.handle_key(key(KeyCode::Char('k')));
+        assert_eq!(input.value(), "hello work");
+
+        input.handle_key(shift(KeyCode::Home));
+        input.handle_key(key(KeyCode::Backspace));
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn test_scroll_offset() {
+        let input = TextInput::with_value("abcdefghij");
+        assert_eq!(input.scroll_offset(20), 0);
+        // The cursor after the last character stays in view
+        assert_eq!(input.scroll_offset(5), 6);
+    }
+
+    #[test]
+    fn test_display_cells() {
+        let cells = |input: TextInput| -> Vec<String> {
+            input.display_cells().into_iter().map(|(_, c)| c).collect()
+        };
+        assert_eq!(
+            cells(TextInput::with_value("a\nb").masked(true)),
+            vec!["•", "•", "•"]
+        );
+        assert_eq!(cells(TextInput::with_value("a\nb")), vec!["a", "↵", "b"]);
+        assert_eq!(cells(TextInput::with_value("🇺🇸👍🏽")), vec!["🇺🇸", "👍🏽"]);
+    }
+
+    #[test]
+    fn test_emoji_editing() {
+        // A flag, a thumbs up with a skin tone, and a family joined with zero-width joiners
+        let mut input = TextInput::with_value("a🇺🇸👍🏽👨\u{200d}👩\u{200d}👧");
+        assert_eq!(input.cursor_column(), 7);
+
+        input.handle_key(key(KeyCode::Backspace));
+        assert_eq!(input.value(), "a🇺🇸👍🏽");
+        input.handle_key(key(KeyCode::Left));
+        assert_eq!(input.cursor_column(), 3);
+        input.handle_key(key(KeyCode::Backspace));
+        assert_eq!(input.value(), "a👍🏽");
+        input.handle_key(key(KeyCode::Delete));
+        assert_eq!(input.value(), "a");
+
+        // Scrolling never starts halfway through a wide character
+        let input = TextInput::with_value("日本語");
+        assert_eq!(input.scroll_offset(4), 4);
+        assert_eq!(input.scroll_offset(3), 4);
+    }
+}
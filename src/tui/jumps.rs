@@ -0,0 +1,135 @@
+use crate::state::Session;
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+
+/// Most positions kept in the jump list.
+const MAX_JUMPS: usize = 100;
+
+/// Conversations and positions left during this run, which Ctrl+O and Ctrl+I move back and
+/// forward through like vim's jump list.
+///
+/// Each conversation appears once, at the position it was last left at.
+#[derive(Debug, Default)]
+pub struct JumpList {
+    jumps: Vec<Session>,
+    /// Index into `jumps` of the current position, or `jumps.len()` when past the newest
+    position: usize,
+}
+
+impl JumpList {
+    /// Get the jump list shared by every view.
+    pub fn shared() -> MutexGuard<'static, JumpList> {
+        static JUMPS: OnceLock<Mutex<JumpList>> = OnceLock::new();
+        JUMPS
+            .get_or_init(Mutex::default)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Record leaving a position for somewhere new, dropping the positions ahead of it.
+    pub fn record(&mut self, left: Session) {
+        self.jumps.truncate(self.position);
+        self.jumps
+            .retain(|jump| jump.conversation != left.conversation);
+        self.jumps.push(left);
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.position = self.jumps.len();
+    }
+
+    /// Move back from `current`, the position being left if there is one, and get where to
+    /// go.
+    pub fn back(&mut self, current: Option<Session>) -> Option<Session> {
+        if self.position == 0 {
+            return None;
+        }
+        if let Some(current) = current {
+            // Remember where the jump started, so moving forward comes back to it
+            if self.position == self.jumps.len() {
+                self.jumps
+                    .retain(|jump| jump.conversation != current.conversation);
+                self.position = self.jumps.len();
+                if self.position == 0 {
+                    self.jumps.push(current);
+                    return None;
+                }
+                self.jumps.push(current);
+            } else {
+                self.jumps[self.position] = current;
+            }
+        }
+        self.position -= 1;
+        Some(self.jumps[self.position].clone())
+    }
+
+    /// Move forward from `current` after moving back, and get where to go.
+    pub fn forward(&mut self, current: Option<Session>) -> Option<Session> {
+        if self.position + 1 >= self.jumps.len() {
+            return None;
+        }
+        if let Some(current) = current {
+            self.jumps[self.position] = current;
+        }
+        self.position += 1;
+        Some(self.jumps[self.position].clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timestamps::TimestampStyle;
+
+    fn session(conversation: &str, top: Option<&str>) -> Session {
+        Session {
+            conversation: conversation.to_string(),
+            display_name: conversation.to_string(),
+            top_message: top.map(str::to_string),
+            pins_collapsed: false,
+            time_style: TimestampStyle::Relative,
+        }
+    }
+
+    fn conversation(jump: Option<Session>) -> Option<String> {
+        jump.map(|jump| jump.conversation)
+    }
+
+    #[test]
+    fn test_back_and_forward() {
+        let mut jumps = JumpList::default();
+        assert_eq!(jumps.back(Some(session("a", None))), None);
+
+        jumps.record(session("a", Some("A1")));
+        jumps.record(session("b", None));
+        let back = jumps.back(Some(session("c", Some("C1"))));
+        assert_eq!(conversation(back), Some("b".to_string()));
+        let back = jumps.back(Some(session("b", Some("B1")))).unwrap();
+        assert_eq!(back.top_message.as_deref(), Some("A1"));
+        assert_eq!(jumps.back(Some(session("a", None))), None);
+
+        // Moving forward returns to the positions moved back from
+        let forward = jumps.forward(Some(session("a", Some("A2")))).unwrap();
+        assert_eq!(forward.top_message.as_deref(), Some("B1"));
+        let forward = jumps.forward(None).unwrap();
+        assert_eq!(forward.top_message.as_deref(), Some("C1"));
+        assert_eq!(jumps.forward(None), None);
+    }
+
+    #[test]
+    fn test_record_drops_forward_history() {
+        let mut jumps = JumpList::default();
+        jumps.record(session("a", None));
+        jumps.record(session("b", None));
+        jumps.back(Some(session("c", None)));
+        jumps.back(None);
+        jumps.record(session("a", Some("A1")));
+        assert_eq!(jumps.forward(None), None);
+
+        // Each conversation is kept once, at its latest position
+        assert_eq!(jumps.jumps.len(), 1);
+        assert_eq!(
+            conversation(jumps.back(Some(session("d", None)))),
+            Some("a".to_string())
+        );
+    }
+}
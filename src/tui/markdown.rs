@@ -0,0 +1,203 @@
+use crate::formatter::{display_width, looks_like_code};
+use ratatui::prelude::*;
+
+/// Fence that opens and closes a code block.
+const CODE_FENCE: &str = "```";
+
+/// Render message text with basic Markdown-style formatting.
+///
+/// Supports fenced code blocks (kept verbatim), `-`/`*` bulleted lists,
+/// backtick code spans, `**bold**`, and `*emphasis*`/`_emphasis_`. Unfenced
+/// text that looks like code is shown as a code block.
+pub fn render_markdown(text: &str, style: Style) -> Vec<Line<'static>> {
+    if !text.contains(CODE_FENCE) && looks_like_code(text) {
+        return render_code_block(&text.lines().collect::<Vec<_>>(), style);
+    }
+
+    let mut lines = Vec::new();
+    let mut code_block: Option<Vec<&str>> = None;
+
+    for line in text.lines() {
+        if line.trim_start().starts_with(CODE_FENCE) {
+            match code_block.take() {
+                Some(block) => lines.extend(render_code_block(&block, style)),
+                None => code_block = Some(Vec::new()),
+            }
+            continue;
+        }
+
+        if let Some(block) = &mut code_block {
+            block.push(line);
+        } else if let Some((indent, item)) = bullet_item(line) {
+            let mut spans = vec![Span::styled(format!("{}• ", indent), style)];
+            spans.extend(inline_spans(item, style));
+            lines.push(Line::from(spans));
+        } else {
+            lines.push(Line::from(inline_spans(line, style)));
+        }
+    }
+
+    // An unclosed fence still starts a code block
+    if let Some(block) = code_block {
+        lines.extend(render_code_block(&block, style));
+    }
+
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled(String::new(), style)));
+    }
+    lines
+}
+
+/// Render lines of code verbatim inside a border, keeping their indentation.
+fn render_code_block(code: &[&str], style: Style) -> Vec<Line<'static>> {
+    let width = code
+        .iter()
+        .map(|line| display_width(line))
+        .max()
+        .unwrap_or(0);
+    let border_style = style.add_modifier(Modifier::DIM);
+
+    let mut lines = vec![Line::from(Span::styled(
+        format!("┌{}┐", "─".repeat(width + 2)),
+        border_style,
+    ))];
+    for line in code {
+        let padding = " ".repeat(width - display_width(line));
+        lines.push(Line::from(vec![
+            Span::styled("│ ", border_style),
+            Span::styled(format!("{}{}", line, padding), code_style(style)),
+            Span::styled(" │", border_style),
+        ]));
+    }
+    lines.push(Line::from(Span::styled(
+        format!("└{}┘", "─".repeat(width + 2)),
+        border_style,
+    )));
+    lines
+}
+
+/// Render message text as plain lines.
+pub fn render_plain(text: &str, style: Style) -> Vec<Line<'static>> {
+    let mut lines: Vec<Line> = text
+        .lines()
+        .map(|line| Line::from(Span::styled(line.to_string(), style)))
+        .collect();
+    if lines.is_empty() {
+        lines.push(Line::from(Span::styled(String::new(), style)));
+    }
+    lines
+}
+
+/// Style for code spans and blocks.
+fn code_style(style: Style) -> Style {
+    style.fg(Color::Yellow)
+}
+
+/// Split a bulleted list item into its indentation and text.
+fn bullet_item(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+    trimmed
+        .strip_prefix("- ")
+        .or_else(|| trimmed.strip_prefix("* "))
+        .map(|item| (indent, item))
+}
+
+/// Split a line into spans for code, bold, and emphasis markers.
+///
+/// Markers without a closing match are kept as literal text.
+fn inline_spans(line: &str, style: Style) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut plain = String::new();
+    let mut rest = line;
+    let mut prev: Option<char> = None;
+
+    while let Some(c) = rest.chars().next() {
+        let marker = match c {
+            '`' => Some(("`", code_style(style))),
+            '*' if rest.starts_with("**") => Some(("**", style.add_modifier(Modifier::BOLD))),
+            '*' | '_' => Some((&rest[..1], style.add_modifier(Modifier::ITALIC))),
+            _ => None,
+        };
+
+        if let Some((marker, marked_style)) = marker {
+            let after = &rest[marker.len()..];
+            // Emphasis must hug its text and start a word, so "2 * 3 * 4" and
+            // "snake_case_name" stay literal
+            let opens = marker == "`"
+                || (!after.starts_with(char::is_whitespace)
+                    && !prev.is_some_and(char::is_alphanumeric));
+            if let Some(end) = after.find(marker).filter(|&end| opens && end > 0) {
+                if !plain.is_empty() {
+                    spans.push(Span::styled(std::mem::take(&mut plain), style));
+                }
+                spans.push(Span::styled(after[..end].to_string(), marked_style));
+                rest = &after[end + marker.len()..];
+                prev = marker.chars().last();
+                continue;
+            }
+        }
+
+        plain.push(c);
+        prev = Some(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    if !plain.is_empty() || spans.is_empty() {
+        spans.push(Span::styled(plain, style));
+    }
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(line: &Line) -> Vec<String> {
+        line.spans.iter().map(|s| s.content.to_string()).collect()
+    }
+
+    #[test]
+    fn test_inline_spans() {
+        let style = Style::default();
+        let line = Line::from(inline_spans("run `cargo test` **now** or _later_", style));
+        assert_eq!(
+            contents(&line),
+            vec!["run ", "cargo test", " ", "now", " or ", "later"]
+        );
+        assert!(line.spans[3].style.add_modifier.contains(Modifier::BOLD));
+        assert!(line.spans[5].style.add_modifier.contains(Modifier::ITALIC));
+
+        // Unmatched or spaced markers stay literal
+        let line = Line::from(inline_spans("2 * 3 * 4 and a stray `", style));
+        assert_eq!(contents(&line), vec!["2 * 3 * 4 and a stray `"]);
+        let line = Line::from(inline_spans("snake_case_name", style));
+        assert_eq!(contents(&line), vec!["snake_case_name"]);
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let lines = render_markdown(
+            "list:\n- one\n  * two\n```\nfn main() {\n    x\n}\n```",
+            Style::default(),
+        );
+        let text: Vec<String> = lines.iter().map(|l| contents(l).concat()).collect();
+        assert_eq!(
+            text,
+            vec![
+                "list:",
+                "• one",
+                "  • two",
+                "┌─────────────┐",
+                "│ fn main() { │",
+                "│     x       │",
+                "│ }           │",
+                "└─────────────┘",
+            ]
+        );
+
+        // Unfenced code is detected and boxed too
+        let lines = render_markdown("if (x) {\n  y();\n}", Style::default());
+        assert_eq!(lines.len(), 5);
+    }
+}
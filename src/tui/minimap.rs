@@ -0,0 +1,88 @@
+use ratatui::prelude::*;
+
+/// Something worth finding in a long timeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    /// The new messages divider
+    Unread,
+    /// A message that matched the last search
+    SearchHit,
+}
+
+impl Mark {
+    fn span(self, thumb: Style) -> Span<'static> {
+        let color = match self {
+            Mark::Unread => Color::Red,
+            Mark::SearchHit => Color::Yellow,
+        };
+        Span::styled("◆", thumb.fg(color))
+    }
+}
+
+/// Draw a scrollbar `height` rows tall for `total` lines scrolled down by `scroll`, with
+/// `marks` (by line index) shown at their position in the timeline.
+///
+/// Each row stands for an equal share of the lines. Rows covering lines on screen make up the
+/// thumb; a row holding the divider shows it over anything else.
+pub fn minimap_lines(
+    total: usize,
+    scroll: usize,
+    height: usize,
+    marks: &[(usize, Mark)],
+) -> Vec<Line<'static>> {
+    if total == 0 || height == 0 {
+        return Vec::new();
+    }
+
+    let row_of = |line: usize| line * height / total;
+    let first_thumb = row_of(scroll.min(total - 1));
+    let last_thumb = row_of((scroll + height).min(total) - 1);
+
+    (0..height)
+        .map(|row| {
+            let thumb = if (first_thumb..=last_thumb).contains(&row) {
+                Style::default().bg(Color::DarkGray)
+            } else {
+                Style::default()
+            };
+            let mark = marks
+                .iter()
+                .filter(|(line, _)| row_of(*line) == row)
+                .map(|(_, mark)| *mark)
+                .min_by_key(|mark| *mark != Mark::Unread);
+
+            match mark {
+                Some(mark) => Line::from(mark.span(thumb)),
+                None if thumb != Style::default() => Line::styled(" ", thumb),
+                None => Line::styled("│", Style::default().fg(Color::DarkGray)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols(lines: &[Line]) -> String {
+        lines
+            .iter()
+            .map(|line| line.spans[0].content.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_minimap_lines() {
+        // 40 lines in 10 rows, four lines a row, scrolled to the bottom
+        let lines = minimap_lines(40, 30, 10, &[(5, Mark::SearchHit), (37, Mark::SearchHit)]);
+        assert_eq!(symbols(&lines), "│◆│││││  ◆");
+        assert_eq!(lines[6].spans[0].style.bg, None);
+        assert_eq!(lines[7].spans[0].style.bg, Some(Color::DarkGray));
+        assert_eq!(lines[9].spans[0].style.bg, Some(Color::DarkGray));
+
+        // The divider wins over a search hit in the same row
+        let lines = minimap_lines(40, 0, 10, &[(20, Mark::SearchHit), (21, Mark::Unread)]);
+        assert_eq!(lines[5].spans[0].style.fg, Some(Color::Red));
+        assert_eq!(symbols(&lines), "   ││◆││││");
+    }
+}
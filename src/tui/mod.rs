@@ -1,8 +1,27 @@
 mod chat;
 mod common;
 mod contacts;
+mod inbox;
+mod input;
+mod jumps;
+mod markdown;
+mod minimap;
+mod modal;
+mod onboarding;
+mod paging;
+mod preview;
+mod refresh;
+mod saved;
 mod setup;
+mod switcher;
+mod toast;
+mod usage;
 
-pub use chat::run_chat_tui;
+pub use chat::{resume_chat_tui, run_chat_tui};
 pub use contacts::run_contacts_tui;
+pub use inbox::run_inbox_tui;
+pub use onboarding::run_onboarding_tui;
+pub use paging::PAGE_SIZE;
+pub use saved::{run_starred_tui, run_tags_tui};
 pub use setup::run_setup_tui;
+pub use usage::run_attachment_usage_tui;
@@ -0,0 +1,422 @@
+use crate::formatter::display_width;
+use crate::tui::input::TextInput;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+};
+
+/// Widest a modal gets (columns)
+const MAX_WIDTH: u16 = 60;
+
+/// What a modal asks for
+enum ModalKind {
+    /// Yes or no, and whether Enter answers yes as well as `y`
+    Confirm { enter: bool },
+    /// A line of text
+    Prompt(TextInput),
+    /// One item from a list
+    Picker { items: Vec<String>, selected: usize },
+    /// Nothing; the message is shown until dismissed
+    Notice,
+}
+
+/// What happened to a modal after it handled a key
+pub enum ModalResult {
+    /// Still waiting for an answer
+    Pending,
+    /// Dismissed without an answer
+    Cancel,
+    /// A confirmation was accepted
+    Confirm,
+    /// A prompt was submitted with this text
+    Submit(String),
+    /// A picker item was chosen, by index
+    Pick(usize),
+}
+
+/// A dialog drawn over the current view that takes all keys until answered.
+///
+/// `action` records what the view should do with the answer.
+pub struct Modal<A> {
+    action: A,
+    title: String,
+    message: String,
+    error: Option<String>,
+    kind: ModalKind,
+    /// Rows of the message scrolled past, when it's too long for the screen
+    scroll: u16,
+}
+
+impl<A: Clone> Modal<A> {
+    /// Ask a yes/no question
+    pub fn confirm(action: A, title: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(
+            action,
+            title.into(),
+            message.into(),
+            ModalKind::Confirm { enter: true },
+        )
+    }
+
+    /// Ask a yes/no question that only `y` answers yes, so a stray Enter can't
+    pub fn confirm_explicitly(
+        action: A,
+        title: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        Self::new(
+            action,
+            title.into(),
+            message.into(),
+            ModalKind::Confirm { enter: false },
+        )
+    }
+
+    /// Ask for a line of text
+    pub fn prompt(
+        action: A,
+        title: impl Into<String>,
+        message: impl Into<String>,
+        input: TextInput,
+    ) -> Self {
+        Self::new(
+            action,
+            title.into(),
+            message.into(),
+            ModalKind::Prompt(input),
+        )
+    }
+
+    /// Ask to choose one of `items`
+    pub fn picker(
+        action: A,
+        title: impl Into<String>,
+        message: impl Into<String>,
+        items: Vec<String>,
+    ) -> Self {
+        Self::new(
+            action,
+            title.into(),
+            message.into(),
+            ModalKind::Picker { items, selected: 0 },
+        )
+    }
+
+    /// Show a longer message until dismissed
+    pub fn notice(action: A, title: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(action, title.into(), message.into(), ModalKind::Notice)
+    }
+
+    fn new(action: A, title: String, message: String, kind: ModalKind) -> Self {
+        Self {
+            action,
+            title,
+            message,
+            error: None,
+            kind,
+            scroll: 0,
+        }
+    }
+
+    /// What the answer is for
+    pub fn action(&self) -> A {
+        self.action.clone()
+    }
+
+    /// Show why the last answer was rejected, keeping the modal open
+    pub fn set_error(&mut self, error: impl Into<String>) {
+        self.error = Some(error.into());
+    }
+
+    /// Handle a key press
+    pub fn handle_key(&mut self, key: KeyEvent) -> ModalResult {
+        if key.code == KeyCode::Esc {
+            return ModalResult::Cancel;
+        }
+
+        if matches!(self.kind, ModalKind::Confirm { .. } | ModalKind::Notice) {
+            let (rows, visible) = crossterm::terminal::size()
+                .map(|(width, height)| self.message_rows(Rect::new(0, 0, width, height)))
+                .unwrap_or_default();
+            let max_scroll = rows.saturating_sub(visible);
+            let page = visible.saturating_sub(1).max(1);
+            match key.code {
+                KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+                KeyCode::Down => self.scroll = (self.scroll + 1).min(max_scroll),
+                KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(page),
+                KeyCode::PageDown => self.scroll = (self.scroll + page).min(max_scroll),
+                _ => {}
+            }
+        }
+
+        match &mut self.kind {
+            ModalKind::Confirm { enter } => match key.code {
+                KeyCode::Enter if *enter => return ModalResult::Confirm,
+                KeyCode::Char('y') | KeyCode::Char('Y') => return ModalResult::Confirm,
+                KeyCode::Char('n') | KeyCode::Char('N') => return ModalResult::Cancel,
+                _ => {}
+            },
+            ModalKind::Prompt(input) => {
+                if key.code == KeyCode::Enter {
+                    return ModalResult::Submit(input.value().to_string());
+                }
+                input.handle_key(key);
+            }
+            ModalKind::Picker { items, selected } => match key.code {
+                KeyCode::Enter if !items.is_empty() => return ModalResult::Pick(*selected),
+                KeyCode::Up => *selected = selected.saturating_sub(1),
+                KeyCode::Down if *selected + 1 < items.len() => *selected += 1,
+                _ => {}
+            },
+            ModalKind::Notice => {
+                if key.code == KeyCode::Enter {
+                    return ModalResult::Cancel;
+                }
+            }
+        }
+        ModalResult::Pending
+    }
+
+    /// Handle pasted text, which only a prompt accepts
+    pub fn handle_paste(&mut self, text: &str) {
+        if let ModalKind::Prompt(input) = &mut self.kind {
+            input.insert_str(text.lines().next().unwrap_or_default());
+        }
+    }
+
+    /// Rows under the message: the body, the error, and the key hints
+    fn rows_below_message(&self) -> u16 {
+        let error_height = if self.error.is_some() { 1 } else { 0 };
+        self.body_height() + error_height + 1
+    }
+
+    /// Rows of the input or list under the message
+    fn body_height(&self) -> u16 {
+        match &self.kind {
+            ModalKind::Confirm { .. } | ModalKind::Notice => 0,
+            ModalKind::Prompt(_) => 3,
+            ModalKind::Picker { items, .. } => items.len() as u16,
+        }
+    }
+
+    /// Rows the message takes when wrapped to fit on `screen`, and how many of them fit
+    fn message_rows(&self, screen: Rect) -> (u16, u16) {
+        let width = MAX_WIDTH.min(screen.width);
+        let rows = match &self.kind {
+            _ if self.message.is_empty() => 0,
+            ModalKind::Notice | ModalKind::Confirm { .. } => {
+                wrapped_height(&self.message, width.saturating_sub(2))
+            }
+            _ => 1,
+        };
+        // Leave room for the borders and everything below the message
+        let room = screen.height.saturating_sub(2 + self.rows_below_message());
+        (rows, rows.min(room))
+    }
+
+    /// Render the modal centered over the current view
+    pub fn render(&self, f: &mut Frame) {
+        let screen = f.size();
+        let width = MAX_WIDTH.min(screen.width);
+
+        let body_height = self.body_height();
+        let (message_rows, message_height) = self.message_rows(screen);
+        let scroll = self.scroll.min(message_rows - message_height);
+        let error_height = if self.error.is_some() { 1 } else { 0 };
+        // Borders, message, body, error, and key hints
+        let height = 2 + message_height + self.rows_below_message();
+
+        let area = Rect {
+            x: screen.x + (screen.width - width) / 2,
+            y: screen.y + screen.height.saturating_sub(height) / 2,
+            width,
+            height: height.min(screen.height),
+        };
+        f.render_widget(Clear, area);
+
+        let block = Block::default()
+            .title(self.title.as_str())
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Blue));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(message_height), // Message
+                Constraint::Length(body_height),    // Input or list
+                Constraint::Length(error_height),   // Error
+                Constraint::Length(1),              // Key hints
+            ])
+            .split(inner);
+
+        let message = Paragraph::new(self.message.as_str())
+            .wrap(Wrap { trim: true })
+            .scroll((scroll, 0));
+        f.render_widget(message, chunks[0]);
+
+        match &self.kind {
+            ModalKind::Confirm { .. } | ModalKind::Notice => {}
+            ModalKind::Prompt(input) => {
+                input.render(f, chunks[1], Block::default().borders(Borders::ALL), true);
+            }
+            ModalKind::Picker { items, selected } => {
+                let items: Vec<ListItem> = items
+                    .iter()
+                    .map(|item| ListItem::new(item.as_str()))
+                    .collect();
+                let list = List::new(items)
+                    .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+                    .highlight_symbol("> ");
+                let mut state = ListState::default();
+                state.select(Some(*selected));
+                f.render_stateful_widget(list, chunks[1], &mut state);
+            }
+        }
+
+        if let Some(error) = &self.error {
+            let error = Paragraph::new(error.as_str()).style(Style::default().fg(Color::Red));
+            f.render_widget(error, chunks[2]);
+        }
+
+        let key_style = |color| Style::default().fg(color).add_modifier(Modifier::BOLD);
+        let mut hints = match &self.kind {
+            ModalKind::Confirm { .. } => vec![
+                Span::styled("y", key_style(Color::Green)),
+                Span::raw(": Yes | "),
+                Span::styled("n/Esc", key_style(Color::Red)),
+                Span::raw(": No"),
+            ],
+            ModalKind::Prompt(_) => vec![
+                Span::styled("Enter", key_style(Color::Green)),
+                Span::raw(": OK | "),
+                Span::styled("Esc", key_style(Color::Red)),
+                Span::raw(": Cancel"),
+            ],
+            ModalKind::Notice => vec![
+                Span::styled("Enter/Esc", key_style(Color::Green)),
+                Span::raw(": Close"),
+            ],
+            ModalKind::Picker { .. } => vec![
+                Span::styled("↑/↓", key_style(Color::Blue)),
+                Span::raw(": Move | "),
+                Span::styled("Enter", key_style(Color::Green)),
+                Span::raw(": Select | "),
+                Span::styled("Esc", key_style(Color::Red)),
+                Span::raw(": Cancel"),
+            ],
+        };
+        if 0 < message_height && message_height < message_rows {
+            // Show which rows of the message are on screen
+            hints.splice(
+                0..0,
+                [
+                    Span::styled("↑/↓", key_style(Color::Blue)),
+                    Span::raw(format!(
+                        ": {}-{} of {} | ",
+                        scroll + 1,
+                        scroll + message_height,
+                        message_rows
+                    )),
+                ],
+            );
+        }
+        f.render_widget(
+            Paragraph::new(Line::from(hints)).alignment(Alignment::Center),
+            chunks[3],
+        );
+    }
+}
+
+/// Rows `text` takes when wrapped to `width` columns, roughly as `Paragraph` wraps it
+fn wrapped_height(text: &str, width: u16) -> u16 {
+    let width = width.max(1) as usize;
+    text.lines()
+        .map(|line| display_width(line).max(1).div_ceil(width) as u16)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_confirm() {
+        let mut modal = Modal::confirm((), "Remove", "Sure?");
+        assert!(matches!(
+            modal.handle_key(key(KeyCode::Char('x'))),
+            ModalResult::Pending
+        ));
+        assert!(matches!(
+            modal.handle_key(key(KeyCode::Char('y'))),
+            ModalResult::Confirm
+        ));
+        assert!(matches!(
+            modal.handle_key(key(KeyCode::Char('n'))),
+            ModalResult::Cancel
+        ));
+    }
+
+    #[test]
+    fn test_confirm_explicitly() {
+        let mut modal = Modal::confirm_explicitly((), "Send", "Sure?");
+        assert!(matches!(
+            modal.handle_key(key(KeyCode::Enter)),
+            ModalResult::Pending
+        ));
+        assert!(matches!(
+            modal.handle_key(key(KeyCode::Char('y'))),
+            ModalResult::Confirm
+        ));
+    }
+
+    #[test]
+    fn test_message_rows() {
+        let modal = Modal::notice((), "Summary", "line\n".repeat(30));
+        // Borders and key hints take three rows
+        assert_eq!(modal.message_rows(Rect::new(0, 0, 80, 40)), (30, 30));
+        assert_eq!(modal.message_rows(Rect::new(0, 0, 80, 20)), (30, 17));
+        let modal = Modal::prompt((), "Name", "Who?", TextInput::new());
+        assert_eq!(modal.message_rows(Rect::new(0, 0, 80, 20)), (1, 1));
+    }
+
+    #[test]
+    fn test_prompt() {
+        let mut modal = Modal::prompt((), "Name", "", TextInput::new());
+        modal.handle_key(key(KeyCode::Char('y')));
+        modal.handle_paste("o\nignored");
+        assert!(matches!(
+            modal.handle_key(key(KeyCode::Enter)),
+            ModalResult::Submit(text) if text == "yo"
+        ));
+        assert!(matches!(
+            modal.handle_key(key(KeyCode::Esc)),
+            ModalResult::Cancel
+        ));
+    }
+
+    #[test]
+    fn test_picker() {
+        let items = vec!["a".to_string(), "b".to_string()];
+        let mut modal = Modal::picker((), "Pick", "", items);
+        modal.handle_key(key(KeyCode::Down));
+        modal.handle_key(key(KeyCode::Down));
+        assert!(matches!(
+            modal.handle_key(key(KeyCode::Enter)),
+            ModalResult::Pick(1)
+        ));
+        modal.handle_key(key(KeyCode::Up));
+        modal.handle_key(key(KeyCode::Up));
+        assert!(matches!(
+            modal.handle_key(key(KeyCode::Enter)),
+            ModalResult::Pick(0)
+        ));
+    }
+}
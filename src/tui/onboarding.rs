@@ -0,0 +1,180 @@
+use crate::error::Result;
+use crate::i18n::t;
+use crate::permissions::Permission;
+use crate::tui::common::{run_terminal, TuiResult};
+use crossterm::event::{Event, KeyCode, KeyModifiers};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Paragraph, Wrap},
+};
+
+/// The onboarding view walking the user through granting permissions
+pub struct OnboardingView {
+    full_disk_access: bool,
+    automation: bool,
+}
+
+impl OnboardingView {
+    /// Create a new onboarding view with the current permission status
+    pub fn new() -> Self {
+        let mut view = Self {
+            full_disk_access: false,
+            automation: false,
+        };
+        view.test_access();
+        view
+    }
+
+    /// Re-check both permissions
+    fn test_access(&mut self) {
+        self.full_disk_access = Permission::FullDiskAccess.is_granted();
+        self.automation = Permission::Automation.is_granted();
+    }
+
+    /// Whether every permission is granted
+    fn is_complete(&self) -> bool {
+        self.full_disk_access && self.automation
+    }
+
+    /// Run the onboarding view. Returns whether all permissions were granted.
+    pub fn run(&mut self) -> Result<bool> {
+        if self.is_complete() {
+            return Ok(true);
+        }
+        run_terminal(|terminal| self.run_ui(terminal))
+    }
+
+    /// Handle the UI loop
+    fn run_ui(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    ) -> TuiResult<bool> {
+        loop {
+            // Draw UI
+            terminal.draw(|f| self.render(f))?;
+
+            // Handle events
+            if let Some(Event::Key(key)) = crate::tui::common::poll_event(100)? {
+                match key.code {
+                    KeyCode::Esc => {
+                        return Ok(self.is_complete());
+                    }
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(self.is_complete());
+                    }
+                    KeyCode::Char('1') => {
+                        Permission::FullDiskAccess.open_settings()?;
+                    }
+                    KeyCode::Char('2') => {
+                        Permission::Automation.open_settings()?;
+                    }
+                    KeyCode::Char('t') => {
+                        self.test_access();
+                    }
+                    KeyCode::Enter if self.is_complete() => {
+                        return Ok(true);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Render a single onboarding step
+    fn step(number: usize, title: String, granted: bool, lines: [String; 3]) -> Vec<Line<'static>> {
+        let (status, status_style) = if granted {
+            (t!("onboarding.granted"), Style::default().fg(Color::Green))
+        } else {
+            (t!("onboarding.missing"), Style::default().fg(Color::Red))
+        };
+
+        let mut text = vec![Line::from(vec![
+            Span::styled(
+                format!("{}. {} ", number, title),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(status, status_style.add_modifier(Modifier::BOLD)),
+        ])];
+        text.extend(lines.iter().map(|line| Line::from(format!("   {}", line))));
+        text.push(Line::from(""));
+        text
+    }
+
+    /// Render the UI
+    fn render(&self, f: &mut Frame) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(2)
+            .constraints([
+                Constraint::Length(3), // Title
+                Constraint::Length(1), // Spacer
+                Constraint::Min(0),    // Steps
+                Constraint::Length(3), // Instructions
+            ])
+            .split(f.size());
+
+        // Title
+        let title = Paragraph::new(t!("onboarding.welcome"))
+            .style(Style::default().fg(Color::White))
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL));
+        f.render_widget(title, chunks[0]);
+
+        // Steps
+        let mut text = vec![Line::from(t!("onboarding.intro")), Line::from("")];
+        text.extend(Self::step(
+            1,
+            t!("onboarding.full_disk_access"),
+            self.full_disk_access,
+            [
+                t!("onboarding.full_disk_access_why"),
+                t!("onboarding.full_disk_access_how"),
+                t!("onboarding.full_disk_access_then"),
+            ],
+        ));
+        text.extend(Self::step(
+            2,
+            t!("onboarding.automation"),
+            self.automation,
+            [
+                t!("onboarding.automation_why"),
+                t!("onboarding.automation_how"),
+                t!("onboarding.automation_then"),
+            ],
+        ));
+
+        let steps = Paragraph::new(text).wrap(Wrap { trim: false }).block(
+            Block::default()
+                .title(t!("onboarding.setup"))
+                .borders(Borders::ALL),
+        );
+        f.render_widget(steps, chunks[2]);
+
+        // Instructions
+        let key_style = |color| Style::default().fg(color).add_modifier(Modifier::BOLD);
+        let mut spans = vec![
+            Span::styled("1/2", key_style(Color::Blue)),
+            Span::raw(t!("onboarding.open_settings")),
+            Span::styled("t", key_style(Color::Blue)),
+            Span::raw(t!("onboarding.test_access")),
+        ];
+        if self.is_complete() {
+            spans.push(Span::styled("Enter", key_style(Color::Green)));
+            spans.push(Span::raw(t!("onboarding.continue")));
+        }
+        spans.push(Span::styled("Esc", key_style(Color::Red)));
+        spans.push(Span::raw(t!("onboarding.skip")));
+
+        let instructions = Paragraph::new(Line::from(spans))
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL));
+        f.render_widget(instructions, chunks[3]);
+    }
+}
+
+/// Convenience function to run the onboarding TUI.
+/// Returns whether all permissions were granted.
+pub fn run_onboarding_tui() -> Result<bool> {
+    let mut onboarding = OnboardingView::new();
+    onboarding.run()
+}
@@ -0,0 +1,87 @@
+/// Messages loaded at a time, both when a chat opens and when scrolling back.
+pub const PAGE_SIZE: usize = 50;
+
+/// Keeps the messages a chat holds in memory to at most `max` during long sessions.
+///
+/// New messages evict the oldest pages, which are loaded again a page at a time when scrolling
+/// back to them. Scrolling back far enough evicts the newest messages instead.
+pub struct MessageWindow {
+    max: usize,
+    /// Whether chat.db may have messages older than the first one held
+    has_older: bool,
+}
+
+impl MessageWindow {
+    /// Create a window holding at most `max` messages, and never less than two pages.
+    pub fn new(max: usize) -> Self {
+        Self {
+            max: max.max(PAGE_SIZE * 2),
+            has_older: false,
+        }
+    }
+
+    /// Note that messages were loaded afresh; `full_page` is whether the load filled its limit.
+    pub fn reset(&mut self, full_page: bool) {
+        self.has_older = full_page;
+    }
+
+    /// Whether scrolling back may load older messages.
+    pub fn has_older(&self) -> bool {
+        self.has_older
+    }
+
+    /// Note that a page of `count` older messages was loaded.
+    pub fn older_loaded(&mut self, count: usize) {
+        self.has_older = count >= PAGE_SIZE;
+    }
+
+    /// Drop whole pages of the oldest messages until at most `max` are held, returning how
+    /// many were dropped.
+    pub fn evict_oldest<T>(&mut self, messages: &mut Vec<T>) -> usize {
+        let excess = messages.len().saturating_sub(self.max);
+        if excess == 0 {
+            return 0;
+        }
+        let evicted = excess.div_ceil(PAGE_SIZE) * PAGE_SIZE;
+        let evicted = evicted.min(messages.len());
+        messages.drain(..evicted);
+        self.has_older = true;
+        evicted
+    }
+
+    /// Drop the newest messages until at most `max` are held, returning how many were dropped.
+    pub fn evict_newest<T>(&mut self, messages: &mut Vec<T>) -> usize {
+        let evicted = messages.len().saturating_sub(self.max);
+        messages.truncate(self.max);
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_window() {
+        let mut window = MessageWindow::new(10);
+        window.reset(true);
+        assert!(window.has_older());
+
+        let mut messages: Vec<usize> = (0..PAGE_SIZE * 2).collect();
+        assert_eq!(window.evict_oldest(&mut messages), 0);
+
+        // One message over evicts the whole oldest page
+        messages.push(PAGE_SIZE * 2);
+        window.older_loaded(3);
+        assert!(!window.has_older());
+        assert_eq!(window.evict_oldest(&mut messages), PAGE_SIZE);
+        assert_eq!(messages.first(), Some(&PAGE_SIZE));
+        assert!(window.has_older());
+
+        // Scrolling back past the limit drops the newest
+        let mut older: Vec<usize> = (0..PAGE_SIZE).collect();
+        older.append(&mut messages);
+        assert_eq!(window.evict_newest(&mut older), 1);
+        assert_eq!(older.last(), Some(&(PAGE_SIZE * 2 - 1)));
+    }
+}
@@ -0,0 +1,50 @@
+use image::RgbImage;
+use ratatui::prelude::*;
+
+/// Character whose foreground fills the top half of a cell and background the bottom half.
+const UPPER_HALF_BLOCK: &str = "▀";
+
+/// Draw an image as lines of half blocks, two pixel rows per line.
+pub fn image_lines(image: &RgbImage) -> Vec<Line<'static>> {
+    let color = |x, y| {
+        let [r, g, b] = image.get_pixel(x, y).0;
+        Color::Rgb(r, g, b)
+    };
+
+    (0..image.height())
+        .step_by(2)
+        .map(|y| {
+            let spans: Vec<Span> = (0..image.width())
+                .map(|x| {
+                    let mut style = Style::default().fg(color(x, y));
+                    // An odd last row leaves the bottom half of its cells empty
+                    if y + 1 < image.height() {
+                        style = style.bg(color(x, y + 1));
+                    }
+                    Span::styled(UPPER_HALF_BLOCK, style)
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn test_image_lines() {
+        let mut image = RgbImage::new(2, 3);
+        image.put_pixel(0, 0, Rgb([255, 0, 0]));
+        image.put_pixel(0, 1, Rgb([0, 255, 0]));
+
+        let lines = image_lines(&image);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].spans.len(), 2);
+        assert_eq!(lines[0].spans[0].style.fg, Some(Color::Rgb(255, 0, 0)));
+        assert_eq!(lines[0].spans[0].style.bg, Some(Color::Rgb(0, 255, 0)));
+        assert_eq!(lines[1].spans[0].style.bg, None);
+    }
+}
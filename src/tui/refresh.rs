@@ -0,0 +1,119 @@
+use crate::config::RefreshConfig;
+use crossterm::event::Event;
+use std::time::{Duration, Instant};
+
+/// Decides how often a view redraws and checks for new messages.
+///
+/// In power-saving mode both slow down while the terminal is in the background or nothing has
+/// happened for a while, and speed back up on the next key or new message.
+pub struct RefreshRate {
+    tick_rate: Duration,
+    poll_interval: Duration,
+    power_saving: bool,
+    idle_after: Duration,
+    idle_interval: Duration,
+    /// Whether the terminal window has focus, as far as the terminal reports it
+    focused: bool,
+    last_activity: Instant,
+}
+
+impl RefreshRate {
+    /// Create a refresh rate from the `[refresh]` settings
+    pub fn new(config: &RefreshConfig) -> Self {
+        Self {
+            tick_rate: Duration::from_millis(config.tick_rate_ms.max(1)),
+            poll_interval: Duration::from_millis(config.poll_interval_ms),
+            power_saving: config.power_saving,
+            idle_after: Duration::from_secs(config.idle_after_secs),
+            idle_interval: Duration::from_millis(config.idle_interval_ms.max(1)),
+            focused: true,
+            last_activity: Instant::now(),
+        }
+    }
+
+    /// Note a terminal event: focus changes, keys, and mouse use
+    pub fn observe(&mut self, event: &Event) {
+        match event {
+            Event::FocusLost => self.focused = false,
+            Event::FocusGained => {
+                self.focused = true;
+                self.record_activity();
+            }
+            Event::Key(_) | Event::Paste(_) | Event::Mouse(_) => {
+                // Some terminals don't report focus, so any input means it's back
+                self.focused = true;
+                self.record_activity();
+            }
+            Event::Resize(..) => {}
+        }
+    }
+
+    /// Note something worth watching closely, such as a new message
+    pub fn record_activity(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// Whether the terminal window has focus
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    /// Whether to slow down
+    pub fn is_idle(&self) -> bool {
+        self.power_saving && (!self.focused || self.last_activity.elapsed() >= self.idle_after)
+    }
+
+    /// How long to wait for keys before redrawing
+    pub fn tick_rate(&self) -> Duration {
+        if self.is_idle() {
+            self.idle_interval.max(self.tick_rate)
+        } else {
+            self.tick_rate
+        }
+    }
+
+    /// How often to check chat.db for writes
+    pub fn poll_interval(&self) -> Duration {
+        if self.is_idle() {
+            self.idle_interval.max(self.poll_interval)
+        } else {
+            self.poll_interval
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyEvent};
+
+    #[test]
+    fn test_refresh_rate() {
+        let config = RefreshConfig {
+            power_saving: true,
+            ..RefreshConfig::default()
+        };
+        let mut refresh = RefreshRate::new(&config);
+        assert_eq!(refresh.tick_rate(), Duration::from_millis(100));
+        assert_eq!(refresh.poll_interval(), Duration::from_millis(500));
+
+        refresh.observe(&Event::FocusLost);
+        assert!(refresh.is_idle());
+        assert_eq!(refresh.tick_rate(), Duration::from_secs(5));
+        assert_eq!(refresh.poll_interval(), Duration::from_secs(5));
+
+        refresh.observe(&Event::Key(KeyEvent::from(KeyCode::Char('a'))));
+        assert!(!refresh.is_idle());
+
+        let config = RefreshConfig {
+            idle_after_secs: 0,
+            ..config
+        };
+        assert!(RefreshRate::new(&config).is_idle());
+        let config = RefreshConfig {
+            power_saving: false,
+            ..config
+        };
+        assert!(!RefreshRate::new(&config).is_idle());
+    }
+}
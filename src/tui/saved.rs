@@ -0,0 +1,351 @@
+use crate::config::Config;
+use crate::error::Result;
+use crate::formatter::format_display_number;
+use crate::i18n::t;
+use crate::state::{StarredMessage, StateStore};
+use crate::tui::chat::ChatView;
+use crate::tui::common::{focus_style, run_terminal, FocusRing, Focusable, TuiResult};
+use crossterm::event::{Event, KeyCode, KeyModifiers};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+/// Width of the list of tags (columns)
+const TAG_LIST_WIDTH: u16 = 24;
+
+/// Which saved messages the view lists
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Saved {
+    /// Starred messages
+    Starred,
+    /// Messages with a tag, picked from a list of every tag in use
+    Tagged,
+}
+
+impl Saved {
+    /// The key that removes the selected message from the list, and what it says it does
+    fn remove_key(self) -> (char, String) {
+        match self {
+            Saved::Starred => ('s', t!("saved.unstar")),
+            Saved::Tagged => ('d', t!("saved.untag")),
+        }
+    }
+}
+
+/// Panes of the view that can have focus
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SavedPane {
+    Tags,
+    Messages,
+}
+
+impl Focusable for SavedPane {
+    const ORDER: &'static [Self] = &[SavedPane::Tags, SavedPane::Messages];
+}
+
+/// The view listing starred or tagged messages across all conversations
+pub struct SavedView {
+    config: Config,
+    state: StateStore,
+    saved: Saved,
+    /// Every tag in use and how many messages have it, when listing tagged messages
+    tags: Vec<(String, usize)>,
+    selected_tag: usize,
+    /// The messages listed, newest first
+    messages: Vec<StarredMessage>,
+    selected_message: usize,
+    focus: FocusRing<SavedPane>,
+}
+
+impl SavedView {
+    /// Create a view of `saved` messages
+    fn new(config: Config, saved: Saved) -> Result<Self> {
+        let mut focus = FocusRing::new();
+        if saved == Saved::Starred {
+            focus.focus(SavedPane::Messages);
+        }
+        let mut view = Self {
+            config,
+            state: StateStore::open()?,
+            saved,
+            tags: Vec::new(),
+            selected_tag: 0,
+            messages: Vec::new(),
+            selected_message: 0,
+            focus,
+        };
+        view.reload()?;
+        Ok(view)
+    }
+
+    /// Create a view of starred messages
+    pub fn starred(config: Config) -> Result<Self> {
+        Self::new(config, Saved::Starred)
+    }
+
+    /// Create a view of tagged messages, showing the messages with `tag` if one is given
+    pub fn tagged(config: Config, tag: Option<String>) -> Result<Self> {
+        let mut view = Self::new(config, Saved::Tagged)?;
+        if let Some(index) = tag.and_then(|tag| view.tags.iter().position(|(t, _)| *t == tag)) {
+            view.select_tag(index)?;
+            view.focus.focus(SavedPane::Messages);
+        }
+        Ok(view)
+    }
+
+    /// Reload the tags and the listed messages from the state store
+    fn reload(&mut self) -> Result<()> {
+        if self.saved == Saved::Tagged {
+            let selected = self.tags.get(self.selected_tag).map(|(tag, _)| tag.clone());
+            self.tags = self.state.tags()?;
+            // Stay on the same tag, unless its last message was untagged
+            self.selected_tag = selected
+                .and_then(|tag| self.tags.iter().position(|(t, _)| *t == tag))
+                .unwrap_or(self.selected_tag.min(self.tags.len().saturating_sub(1)));
+        }
+        self.messages = self.load_messages()?;
+        self.selected_message = self
+            .selected_message
+            .min(self.messages.len().saturating_sub(1));
+        Ok(())
+    }
+
+    /// Show the messages with the tag at `index`
+    fn select_tag(&mut self, index: usize) -> Result<()> {
+        self.selected_tag = index;
+        self.selected_message = 0;
+        self.messages = self.load_messages()?;
+        Ok(())
+    }
+
+    /// Get the messages to list: the starred ones, or those with the selected tag
+    fn load_messages(&self) -> Result<Vec<StarredMessage>> {
+        match self.saved {
+            Saved::Starred => self.state.starred(),
+            Saved::Tagged => match self.tags.get(self.selected_tag) {
+                Some((tag, _)) => self.state.tagged(tag),
+                None => Ok(Vec::new()),
+            },
+        }
+    }
+
+    /// Take the selected message out of the list: unstar it, or remove the selected tag
+    fn remove_selected(&mut self) -> Result<()> {
+        let Some(message) = self.messages.get(self.selected_message) else {
+            return Ok(());
+        };
+        match self.saved {
+            Saved::Starred => self.state.unstar(&message.guid)?,
+            Saved::Tagged => {
+                if let Some((tag, _)) = self.tags.get(self.selected_tag) {
+                    self.state.untag(&message.guid, tag)?;
+                }
+            }
+        }
+        self.reload()
+    }
+
+    /// Get the name to show for a conversation
+    fn conversation_name(&self, identifier: &str) -> String {
+        self.config
+            .display_name_for(identifier)
+            .unwrap_or_else(|| format_display_number(identifier))
+    }
+
+    /// Run the saved messages view
+    pub fn run(&mut self) -> Result<()> {
+        run_terminal(|terminal| self.run_ui(terminal))
+    }
+
+    /// Handle the UI loop
+    fn run_ui(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    ) -> TuiResult<()> {
+        let (remove_key, _) = self.saved.remove_key();
+        loop {
+            // Draw UI
+            terminal.draw(|f| self.render(f))?;
+
+            // Handle events
+            let Some(Event::Key(key)) = crate::tui::common::poll_event(100)? else {
+                continue;
+            };
+            let has_tags = self.saved == Saved::Tagged;
+            let on_tags = self.focus.is_focused(SavedPane::Tags);
+            match key.code {
+                KeyCode::Esc => {
+                    return Ok(());
+                }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(());
+                }
+                KeyCode::Tab | KeyCode::Left | KeyCode::Right if has_tags => {
+                    self.focus.next();
+                }
+                KeyCode::Up if on_tags && self.selected_tag > 0 => {
+                    self.select_tag(self.selected_tag - 1)?;
+                }
+                KeyCode::Down if on_tags && self.selected_tag + 1 < self.tags.len() => {
+                    self.select_tag(self.selected_tag + 1)?;
+                }
+                KeyCode::Enter if on_tags && !self.messages.is_empty() => {
+                    self.focus.focus(SavedPane::Messages);
+                }
+                KeyCode::Up if !on_tags && self.selected_message > 0 => {
+                    self.selected_message -= 1;
+                }
+                KeyCode::Down if !on_tags && self.selected_message + 1 < self.messages.len() => {
+                    self.selected_message += 1;
+                }
+                KeyCode::Char(c) if !on_tags && c == remove_key => {
+                    self.remove_selected()?;
+                }
+                KeyCode::Enter if !on_tags => {
+                    if let Some(message) = self.messages.get(self.selected_message) {
+                        let name = self.conversation_name(&message.conversation);
+                        let mut chat = ChatView::new(message.conversation.clone(), name)?;
+                        chat.jump_to(message.rowid);
+                        chat.run_ui(terminal)?;
+                        self.reload()?;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Render the UI
+    fn render(&self, f: &mut Frame) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3), // Title
+                Constraint::Min(0),    // Tags and messages
+                Constraint::Length(3), // Instructions
+            ])
+            .split(f.size());
+
+        // Title
+        let title = match self.saved {
+            Saved::Starred => t!("saved.starred_title", count = self.messages.len()),
+            Saved::Tagged => t!("saved.tags_title", count = self.tags.len()),
+        };
+        let title = Paragraph::new(title)
+            .block(Block::default().borders(Borders::ALL))
+            .alignment(Alignment::Center);
+        f.render_widget(title, chunks[0]);
+
+        let messages_area = match self.saved {
+            Saved::Starred => chunks[1],
+            Saved::Tagged => {
+                let panes = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Length(TAG_LIST_WIDTH), Constraint::Min(0)])
+                    .split(chunks[1]);
+                self.render_tags(f, panes[0]);
+                panes[1]
+            }
+        };
+        self.render_messages(f, messages_area);
+
+        // Instructions
+        let key_style = Style::default()
+            .fg(Color::Blue)
+            .add_modifier(Modifier::BOLD);
+        let (remove_key, remove_action) = self.saved.remove_key();
+        let mut spans = Vec::new();
+        if self.saved == Saved::Tagged {
+            spans.push(Span::styled("←/→", key_style));
+            spans.push(Span::raw(t!("saved.switch_pane")));
+        }
+        spans.extend([
+            Span::styled("Enter", key_style),
+            Span::raw(t!("saved.jump_to_context")),
+            Span::styled(remove_key.to_string(), key_style),
+            Span::raw(t!("saved.remove", action = remove_action)),
+            Span::styled("Esc", key_style),
+            Span::raw(t!("saved.quit")),
+        ]);
+        let instructions = Paragraph::new(Line::from(spans))
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL));
+        f.render_widget(instructions, chunks[2]);
+    }
+
+    /// Render the list of tags in use
+    fn render_tags(&self, f: &mut Frame, area: Rect) {
+        let tags: Vec<ListItem> = self
+            .tags
+            .iter()
+            .map(|(tag, count)| ListItem::new(format!("#{} ({})", tag, count)))
+            .collect();
+        let tags = List::new(tags)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(focus_style(self.focus.is_focused(SavedPane::Tags))),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        let mut state = ListState::default();
+        if !self.tags.is_empty() {
+            state.select(Some(self.selected_tag));
+        }
+        f.render_stateful_widget(tags, area, &mut state);
+    }
+
+    /// Render the listed messages, with their date and who sent them
+    fn render_messages(&self, f: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .messages
+            .iter()
+            .map(|message| {
+                let sender = if message.is_from_me {
+                    t!("chat.me")
+                } else {
+                    self.conversation_name(&message.conversation)
+                };
+
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("{}  ", message.date.format("%Y-%m-%d %H:%M")),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::styled(
+                        format!("{}: ", sender),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(message.text.as_deref().unwrap_or("").replace('\n', " ")),
+                ]))
+            })
+            .collect();
+
+        // With no tags to switch to, the messages always have focus
+        let focused = self.focus.is_focused(SavedPane::Messages);
+        let mut block = Block::default().borders(Borders::ALL);
+        if self.saved == Saved::Tagged {
+            block = block.border_style(focus_style(focused));
+        }
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+        let mut state = ListState::default();
+        if focused && !self.messages.is_empty() {
+            state.select(Some(self.selected_message));
+        }
+        f.render_stateful_widget(list, area, &mut state);
+    }
+}
+
+/// Convenience function to run the starred messages TUI
+pub fn run_starred_tui(config: Config) -> Result<()> {
+    SavedView::starred(config)?.run()
+}
+
+/// Convenience function to run the tags TUI
+pub fn run_tags_tui(config: Config, tag: Option<String>) -> Result<()> {
+    SavedView::tagged(config, tag)?.run()
+}
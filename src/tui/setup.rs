@@ -1,24 +1,30 @@
 use crate::config::Config;
 use crate::error::Result;
 use crate::formatter::format_phone_number;
-use crate::tui::common::{run_terminal, TuiResult};
+use crate::tui::common::{focus_style, run_terminal, FocusRing, Focusable, TuiResult};
+use crate::tui::input::TextInput;
 use crossterm::event::{Event, KeyCode, KeyModifiers};
 use ratatui::{
     prelude::*,
     widgets::{Block, Borders, Paragraph},
 };
 
-/// Input field enum for the setup view
+/// Input fields of the setup view
+#[derive(Clone, Copy, PartialEq)]
 enum InputField {
     Contact,
     DisplayName,
 }
 
+impl Focusable for InputField {
+    const ORDER: &'static [Self] = &[InputField::Contact, InputField::DisplayName];
+}
+
 /// The setup view for configuring default contact
 pub struct SetupView {
-    contact_input: String,
-    display_name_input: String,
-    active_field: InputField,
+    contact_input: TextInput,
+    display_name_input: TextInput,
+    focus: FocusRing<InputField>,
     config: Config,
 }
 
@@ -26,9 +32,9 @@ impl SetupView {
     /// Create a new setup view
     pub fn new() -> Self {
         Self {
-            contact_input: String::new(),
-            display_name_input: String::new(),
-            active_field: InputField::Contact,
+            contact_input: TextInput::new().placeholder("+15551234567 or name@example.com"),
+            display_name_input: TextInput::new(),
+            focus: FocusRing::new(),
             config: Config::default(),
         }
     }
@@ -52,60 +58,41 @@ impl SetupView {
             // Draw UI
             terminal.draw(|f| self.render(f))?;
 
-            // Hide the terminal cursor since we have our own cursor indicator
-            terminal.hide_cursor()?;
-
             // Handle events
-            if let Some(event) = crate::tui::common::poll_event(100)? {
-                if let Event::Key(key) = event {
-                    match key.code {
-                        KeyCode::Esc => {
-                            return Ok(self.get_config());
-                        }
-                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            return Ok(self.get_config());
-                        }
-                        KeyCode::Tab => {
-                            // Switch between input fields
-                            self.active_field = match self.active_field {
-                                InputField::Contact => InputField::DisplayName,
-                                InputField::DisplayName => InputField::Contact,
-                            };
-                        }
-                        KeyCode::Char(c) => {
-                            // Add character to the active input field
-                            match self.active_field {
-                                InputField::Contact => self.contact_input.push(c),
-                                InputField::DisplayName => self.display_name_input.push(c),
-                            }
-                        }
-                        KeyCode::Backspace => {
-                            // Remove character from the active input field
-                            match self.active_field {
-                                InputField::Contact => {
-                                    self.contact_input.pop();
-                                }
-                                InputField::DisplayName => {
-                                    self.display_name_input.pop();
-                                }
-                            }
-                        }
-                        KeyCode::Enter => {
-                            // Save if contact is not empty
-                            if !self.contact_input.is_empty() {
-                                let formatted_contact = format_phone_number(&self.contact_input);
-                                self.config.set_default_contact(formatted_contact);
-
-                                if !self.display_name_input.is_empty() {
-                                    self.config
-                                        .set_default_display_name(self.display_name_input.clone());
-                                }
-
-                                // Return from the setup TUI
-                                return Ok(self.get_config());
-                            }
+            if let Some(Event::Key(key)) = crate::tui::common::poll_event(100)? {
+                match key.code {
+                    KeyCode::Esc => {
+                        return Ok(self.get_config());
+                    }
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(self.get_config());
+                    }
+                    KeyCode::Tab => {
+                        self.focus.next();
+                    }
+                    KeyCode::BackTab => {
+                        self.focus.prev();
+                    }
+                    // Save if contact is not empty
+                    KeyCode::Enter if !self.contact_input.is_empty() => {
+                        let formatted_contact = format_phone_number(self.contact_input.value());
+                        self.config.set_default_contact(formatted_contact);
+
+                        if !self.display_name_input.is_empty() {
+                            self.config.set_default_display_name(
+                                self.display_name_input.value().to_string(),
+                            );
                         }
-                        _ => {}
+
+                        // Return from the setup TUI
+                        return Ok(self.get_config());
+                    }
+                    _ => {
+                        // Edit the active input field
+                        match self.focus.current() {
+                            InputField::Contact => self.contact_input.handle_key(key),
+                            InputField::DisplayName => self.display_name_input.handle_key(key),
+                        };
                     }
                 }
             }
@@ -137,54 +124,32 @@ impl SetupView {
         f.render_widget(title, chunks[0]);
 
         // Contact input
-        let contact_block_style = if matches!(self.active_field, InputField::Contact) {
-            Style::default().fg(Color::Blue)
-        } else {
-            Style::default().fg(Color::Gray)
-        };
-
-        // Add a blinking cursor indicator for the active field
-        let contact_text = if matches!(self.active_field, InputField::Contact) {
-            format!("{}▎", self.contact_input)
-        } else {
-            self.contact_input.clone()
-        };
-
-        let contact_input = Paragraph::new(contact_text)
-            .style(Style::default().fg(Color::White))
-            .block(
-                Block::default()
-                    .title("Enter default contact number/email (required)")
-                    .title_style(contact_block_style)
-                    .borders(Borders::ALL)
-                    .border_style(contact_block_style),
-            );
-        f.render_widget(contact_input, chunks[2]);
+        let contact_focused = self.focus.is_focused(InputField::Contact);
+        let contact_block_style = focus_style(contact_focused);
+        self.contact_input.render(
+            f,
+            chunks[2],
+            Block::default()
+                .title("Enter default contact number/email (required)")
+                .title_style(contact_block_style)
+                .borders(Borders::ALL)
+                .border_style(contact_block_style),
+            contact_focused,
+        );
 
         // Display name input
-        let name_block_style = if matches!(self.active_field, InputField::DisplayName) {
-            Style::default().fg(Color::Blue)
-        } else {
-            Style::default().fg(Color::Gray)
-        };
-
-        // Add a blinking cursor indicator for the active field
-        let display_name_text = if matches!(self.active_field, InputField::DisplayName) {
-            format!("{}▎", self.display_name_input)
-        } else {
-            self.display_name_input.clone()
-        };
-
-        let display_name_input = Paragraph::new(display_name_text)
-            .style(Style::default().fg(Color::White))
-            .block(
-                Block::default()
-                    .title("Enter default contact display name (optional)")
-                    .title_style(name_block_style)
-                    .borders(Borders::ALL)
-                    .border_style(name_block_style),
-            );
-        f.render_widget(display_name_input, chunks[4]);
+        let name_focused = self.focus.is_focused(InputField::DisplayName);
+        let name_block_style = focus_style(name_focused);
+        self.display_name_input.render(
+            f,
+            chunks[4],
+            Block::default()
+                .title("Enter default contact display name (optional)")
+                .title_style(name_block_style)
+                .borders(Borders::ALL)
+                .border_style(name_block_style),
+            name_focused,
+        );
 
         // Instructions styled with iMessage blue for emphasis
         let instructions = Paragraph::new(Text::from(vec![Line::from(vec![
@@ -0,0 +1,407 @@
+use crate::config::Config;
+use crate::db::MessageDB;
+use crate::error::Result;
+use crate::formatter::format_display_number;
+use crate::i18n::t;
+use crate::tui::input::TextInput;
+use chrono::{DateTime, Local};
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+};
+use std::collections::HashMap;
+
+/// Number of recent handles offered alongside configured contacts
+const RECENT_HANDLES: usize = 200;
+
+/// Score for the most recent conversation; older ones decay towards zero
+const MAX_RECENCY_SCORE: i64 = 40;
+
+/// A conversation the switcher can jump to
+struct Candidate {
+    identifier: String,
+    name: String,
+    /// Configured contact name, matched against the query along with the display name
+    contact_name: Option<String>,
+    last_message: Option<DateTime<Local>>,
+}
+
+/// What the chat view should do after the switcher handles a key
+pub enum SwitcherAction {
+    /// Keep the switcher open
+    None,
+    /// Close the switcher without switching
+    Close,
+    /// Open the conversation with an identifier and display name
+    Open(String, String),
+    /// Go ahead with the people chosen, by identifier
+    Choose(Vec<String>),
+}
+
+/// Search-as-you-type popup for switching conversations
+pub struct Switcher {
+    query: TextInput,
+    candidates: Vec<Candidate>,
+    /// Indices into `candidates` matching the query, best first
+    matches: Vec<usize>,
+    selected: usize,
+    /// Indices into `candidates` chosen so far when picking several people, in the order chosen
+    chosen: Option<Vec<usize>>,
+}
+
+impl Switcher {
+    /// Build the switcher from configured contacts and recent handles in chat.db
+    pub fn new(config: &Config, db: &MessageDB) -> Result<Self> {
+        let mut candidates: Vec<Candidate> = Vec::new();
+        let mut by_identifier: HashMap<String, usize> = HashMap::new();
+
+        for (identifier, last_message) in db.get_recent_handles(RECENT_HANDLES)? {
+            let (name, contact_name) = match config.find_contact_by_identifier(&identifier) {
+                Some((contact, entry)) => (
+                    entry
+                        .display_name
+                        .clone()
+                        .unwrap_or_else(|| contact.clone()),
+                    Some(contact.clone()),
+                ),
+                None => (
+                    config
+                        .display_name_for(&identifier)
+                        .unwrap_or_else(|| format_display_number(&identifier)),
+                    None,
+                ),
+            };
+            by_identifier.insert(identifier.clone(), candidates.len());
+            candidates.push(Candidate {
+                identifier,
+                name,
+                contact_name,
+                last_message: Some(last_message),
+            });
+        }
+
+        // Configured contacts without recent messages can still be opened
+        for (contact, entry) in config.list_contacts() {
+            if by_identifier.contains_key(&entry.identifier) {
+                continue;
+            }
+            by_identifier.insert(entry.identifier.clone(), candidates.len());
+            candidates.push(Candidate {
+                identifier: entry.identifier.clone(),
+                name: entry
+                    .display_name
+                    .clone()
+                    .unwrap_or_else(|| contact.clone()),
+                contact_name: Some(contact.clone()),
+                last_message: None,
+            });
+        }
+
+        let mut switcher = Self {
+            query: TextInput::new().placeholder(t!("switcher.placeholder")),
+            candidates,
+            matches: Vec::new(),
+            selected: 0,
+            chosen: None,
+        };
+        switcher.update_matches();
+        Ok(switcher)
+    }
+
+    /// Build a switcher that picks several people, such as the members of a new group
+    pub fn choose_several(config: &Config, db: &MessageDB) -> Result<Self> {
+        Ok(Self {
+            chosen: Some(Vec::new()),
+            ..Self::new(config, db)?
+        })
+    }
+
+    /// Add the highlighted candidate to the people chosen, or take it back out
+    fn toggle_chosen(&mut self) {
+        let (Some(chosen), Some(&candidate)) = (&mut self.chosen, self.matches.get(self.selected))
+        else {
+            return;
+        };
+        match chosen.iter().position(|&i| i == candidate) {
+            Some(position) => {
+                chosen.remove(position);
+            }
+            None => chosen.push(candidate),
+        }
+    }
+
+    /// Rank candidates by a blend of match quality and how recently they messaged
+    fn update_matches(&mut self) {
+        let now = Local::now();
+        let mut scored: Vec<(i64, usize)> = self
+            .candidates
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, candidate)| {
+                let match_score = [
+                    Some(candidate.name.as_str()),
+                    candidate.contact_name.as_deref(),
+                    Some(candidate.identifier.as_str()),
+                ]
+                .into_iter()
+                .flatten()
+                .filter_map(|text| fuzzy_score(self.query.value(), text))
+                .max()?;
+                let recency = candidate
+                    .last_message
+                    .map(|date| recency_score(date, now))
+                    .unwrap_or(0);
+                Some((match_score + recency, idx))
+            })
+            .collect();
+
+        scored.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| self.candidates[a.1].name.cmp(&self.candidates[b.1].name))
+        });
+        self.matches = scored.into_iter().map(|(_, idx)| idx).collect();
+        self.selected = 0;
+    }
+
+    /// Handle a key press
+    pub fn handle_key(&mut self, key: KeyEvent) -> SwitcherAction {
+        match key.code {
+            KeyCode::Esc => return SwitcherAction::Close,
+            KeyCode::Tab if self.chosen.is_some() => self.toggle_chosen(),
+            // Enter goes ahead once there are people to make a group of, and picks until then
+            KeyCode::Enter if self.chosen.as_ref().is_some_and(|chosen| chosen.len() >= 2) => {
+                let chosen = self.chosen.as_deref().unwrap_or_default();
+                return SwitcherAction::Choose(
+                    chosen
+                        .iter()
+                        .map(|&i| self.candidates[i].identifier.clone())
+                        .collect(),
+                );
+            }
+            KeyCode::Enter if self.chosen.is_some() => self.toggle_chosen(),
+            KeyCode::Enter => {
+                if let Some(candidate) = self
+                    .matches
+                    .get(self.selected)
+                    .map(|&i| &self.candidates[i])
+                {
+                    return SwitcherAction::Open(
+                        candidate.identifier.clone(),
+                        candidate.name.clone(),
+                    );
+                }
+            }
+            KeyCode::Up if self.selected > 0 => {
+                self.selected -= 1;
+            }
+            KeyCode::Down if self.selected + 1 < self.matches.len() => {
+                self.selected += 1;
+            }
+            _ => {
+                if self.query.handle_key(key) {
+                    self.update_matches();
+                }
+            }
+        }
+        SwitcherAction::None
+    }
+
+    /// Render the switcher as a popup over the current view
+    pub fn render(&self, f: &mut Frame) {
+        let area = centered_rect(60, 60, f.size());
+        f.render_widget(Clear, area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3), // Query
+                Constraint::Min(0),    // Matches
+            ])
+            .split(area);
+
+        let title = match &self.chosen {
+            Some(chosen) => t!("switcher.group_title", count = chosen.len()),
+            None => t!("switcher.title"),
+        };
+        self.query.render(
+            f,
+            chunks[0],
+            Block::default().title(title).borders(Borders::ALL),
+            true,
+        );
+
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|&i| {
+                let candidate = &self.candidates[i];
+                let mut spans = Vec::new();
+                if let Some(chosen) = &self.chosen {
+                    let mark = if chosen.contains(&i) { "✓ " } else { "  " };
+                    spans.push(Span::styled(mark, Style::default().fg(Color::Green)));
+                }
+                spans.push(Span::raw(candidate.name.clone()));
+                if candidate.name != candidate.identifier {
+                    spans.push(Span::styled(
+                        format!("  {}", candidate.identifier),
+                        Style::default().fg(Color::Gray),
+                    ));
+                }
+                if let Some(date) = candidate.last_message {
+                    spans.push(Span::styled(
+                        format!("  {}", date.format("%b %d")),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL))
+            .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+            .highlight_symbol("> ");
+
+        let mut state = ListState::default();
+        state.select(if self.matches.is_empty() {
+            None
+        } else {
+            Some(self.selected)
+        });
+        f.render_stateful_widget(list, chunks[1], &mut state);
+    }
+}
+
+/// Score how well `query` matches `text` as a case-insensitive subsequence.
+///
+/// Consecutive matches and matches at the start of words score higher.
+/// Returns `None` if some query character doesn't appear in order.
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let Some(&first) = query.first() else {
+        return Some(0);
+    };
+
+    // Try each place the match could start and keep the best
+    text.iter()
+        .enumerate()
+        .filter(|&(_, &c)| c == first)
+        .filter_map(|(start, _)| score_from(&query, &text, start))
+        .max()
+}
+
+/// Score a greedy match of `query` in `text` with the first character at `start`.
+fn score_from(query: &[char], text: &[char], start: usize) -> Option<i64> {
+    let mut score = -(start as i64);
+    let mut pos = start;
+    let mut last_match: Option<usize> = None;
+
+    for &q in query {
+        let found = pos + text[pos..].iter().position(|&c| c == q)?;
+        score += 10;
+        if last_match.is_some_and(|last| last + 1 == found) {
+            score += 15;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 20;
+        }
+        if last_match.is_some() {
+            score -= (found - pos) as i64;
+        }
+        last_match = Some(found);
+        pos = found + 1;
+    }
+
+    Some(score)
+}
+
+/// Score how recently a conversation was active, from `MAX_RECENCY_SCORE` down to zero.
+fn recency_score(date: DateTime<Local>, now: DateTime<Local>) -> i64 {
+    let days = (now - date).num_days().max(0);
+    MAX_RECENCY_SCORE / (1 + days)
+}
+
+/// A rectangle centered in `area`, sized as percentages of it
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert_eq!(fuzzy_score("xyz", "mom"), None);
+        assert!(fuzzy_score("", "mom").is_some());
+
+        // Prefix and word-start matches beat scattered ones
+        let prefix = fuzzy_score("al", "Alice").unwrap();
+        let word = fuzzy_score("al", "Sam Allen").unwrap();
+        let scattered = fuzzy_score("al", "Samuel").unwrap();
+        assert!(prefix > scattered);
+        assert!(word > scattered);
+    }
+
+    #[test]
+    fn test_choose_several() {
+        let candidate = |identifier: &str| Candidate {
+            identifier: identifier.to_string(),
+            name: identifier.to_string(),
+            contact_name: None,
+            last_message: None,
+        };
+        let mut switcher = Switcher {
+            query: TextInput::new(),
+            candidates: vec![candidate("alice"), candidate("bob"), candidate("carol")],
+            matches: Vec::new(),
+            selected: 0,
+            chosen: Some(Vec::new()),
+        };
+        switcher.update_matches();
+        let key = |code| KeyEvent::from(code);
+
+        // Enter picks until there are two people, then goes ahead
+        assert!(matches!(
+            switcher.handle_key(key(KeyCode::Enter)),
+            SwitcherAction::None
+        ));
+        switcher.handle_key(key(KeyCode::Down));
+        switcher.handle_key(key(KeyCode::Tab));
+        switcher.handle_key(key(KeyCode::Down));
+        switcher.handle_key(key(KeyCode::Tab));
+        switcher.handle_key(key(KeyCode::Up));
+        switcher.handle_key(key(KeyCode::Tab));
+        match switcher.handle_key(key(KeyCode::Enter)) {
+            SwitcherAction::Choose(chosen) => assert_eq!(chosen, ["alice", "carol"]),
+            _ => panic!("expected the chosen people"),
+        }
+    }
+
+    #[test]
+    fn test_recency_score() {
+        let now = Local::now();
+        assert_eq!(recency_score(now, now), MAX_RECENCY_SCORE);
+        assert!(recency_score(now - Duration::days(1), now) < MAX_RECENCY_SCORE);
+        assert_eq!(recency_score(now - Duration::days(365), now), 0);
+    }
+}
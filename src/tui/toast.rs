@@ -0,0 +1,85 @@
+use crate::error::Error;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+};
+use std::time::{Duration, Instant};
+
+/// How long a toast stays on screen.
+const TOAST_DURATION: Duration = Duration::from_secs(8);
+
+/// Widest a toast gets, in columns.
+const MAX_WIDTH: u16 = 60;
+
+/// A short-lived notice in the corner of a view, such as why a message failed to send.
+#[derive(Debug, Clone)]
+pub struct Toast {
+    message: String,
+    /// What the user can do about it.
+    suggestion: Option<String>,
+    shown_at: Instant,
+}
+
+impl Toast {
+    /// Create a toast with a message and what the user can do about it.
+    pub fn new(message: String, suggestion: Option<String>) -> Self {
+        Self {
+            message,
+            suggestion,
+            shown_at: Instant::now(),
+        }
+    }
+
+    /// Create a toast for an error, with a suggested fix when there is one.
+    pub fn error(error: &Error) -> Self {
+        let suggestion = match error {
+            Error::Send(error) => error.suggestion(),
+            _ => None,
+        };
+        Self::new(error.to_string(), suggestion)
+    }
+
+    /// Whether the toast has been shown long enough to go away.
+    pub fn is_expired(&self) -> bool {
+        self.shown_at.elapsed() >= TOAST_DURATION
+    }
+
+    /// Render the toast in the bottom-right corner of `area`.
+    pub fn render(&self, f: &mut Frame, area: Rect) {
+        let width = MAX_WIDTH.min(area.width);
+        let inner_width = width.saturating_sub(2).max(1) as usize;
+        let mut lines = vec![Line::from(Span::styled(
+            self.message.as_str(),
+            Style::default().fg(Color::Red),
+        ))];
+        if let Some(suggestion) = &self.suggestion {
+            lines.push(Line::from(Span::styled(
+                suggestion.as_str(),
+                Style::default().fg(Color::Gray),
+            )));
+        }
+
+        // Borders plus each line wrapped to the inner width
+        let text_height: usize = lines
+            .iter()
+            .map(|line| line.width().div_ceil(inner_width).max(1))
+            .sum();
+        let height = (text_height as u16 + 2).min(area.height);
+        let rect = Rect {
+            x: area.x + area.width - width,
+            y: area.y + area.height - height,
+            width,
+            height,
+        };
+
+        f.render_widget(Clear, rect);
+        f.render_widget(
+            Paragraph::new(lines).wrap(Wrap { trim: true }).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Red)),
+            ),
+            rect,
+        );
+    }
+}
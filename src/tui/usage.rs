@@ -0,0 +1,213 @@
+use crate::attachments::{format_size, AttachmentUsage};
+use crate::config::Config;
+use crate::error::Result;
+use crate::tui::common::{run_terminal, TuiResult};
+use crossterm::event::{Event, KeyCode, KeyModifiers};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState},
+};
+use std::cmp::Reverse;
+
+/// Columns the attachment usage table can be sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UsageSort {
+    Size,
+    Count,
+    Name,
+}
+
+impl UsageSort {
+    /// The column sorted by after this one
+    fn next(self) -> Self {
+        match self {
+            UsageSort::Size => UsageSort::Count,
+            UsageSort::Count => UsageSort::Name,
+            UsageSort::Name => UsageSort::Size,
+        }
+    }
+}
+
+/// One conversation in the table, with its name resolved
+struct UsageRow {
+    name: String,
+    usage: AttachmentUsage,
+}
+
+/// The view listing how much disk space each conversation's attachments take
+pub struct AttachmentUsageView {
+    rows: Vec<UsageRow>,
+    sort: UsageSort,
+    selected_index: usize,
+}
+
+impl AttachmentUsageView {
+    /// Create a new attachment usage view
+    pub fn new(config: &Config, usage: Vec<AttachmentUsage>) -> Self {
+        let rows = usage
+            .into_iter()
+            .map(|usage| UsageRow {
+                name: usage.name(config),
+                usage,
+            })
+            .collect();
+
+        let mut view = Self {
+            rows,
+            sort: UsageSort::Size,
+            selected_index: 0,
+        };
+        view.sort_rows();
+        view
+    }
+
+    /// Sort the rows by the current column: sizes and counts largest first, names A to Z
+    fn sort_rows(&mut self) {
+        match self.sort {
+            UsageSort::Size => self.rows.sort_by_key(|row| Reverse(row.usage.bytes)),
+            UsageSort::Count => self.rows.sort_by_key(|row| Reverse(row.usage.count)),
+            UsageSort::Name => self.rows.sort_by_key(|row| row.name.to_lowercase()),
+        }
+        self.selected_index = 0;
+    }
+
+    /// Run the attachment usage view
+    pub fn run(&mut self) -> Result<()> {
+        run_terminal(|terminal| self.run_ui(terminal))
+    }
+
+    /// Handle the UI loop
+    fn run_ui(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    ) -> TuiResult<()> {
+        loop {
+            // Draw UI
+            terminal.draw(|f| self.render(f))?;
+
+            // Handle events
+            if let Some(Event::Key(key)) = crate::tui::common::poll_event(100)? {
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        return Ok(());
+                    }
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(());
+                    }
+                    KeyCode::Up if self.selected_index > 0 => {
+                        self.selected_index -= 1;
+                    }
+                    KeyCode::Down if self.selected_index < self.rows.len().saturating_sub(1) => {
+                        self.selected_index += 1;
+                    }
+                    KeyCode::Char('s') => {
+                        self.sort = self.sort.next();
+                        self.sort_rows();
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Render the UI
+    fn render(&self, f: &mut Frame) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3), // Title
+                Constraint::Min(0),    // Conversations
+                Constraint::Length(3), // Instructions
+            ])
+            .split(f.size());
+
+        // Title
+        let total: u64 = self.rows.iter().map(|row| row.usage.bytes).sum();
+        let title = Paragraph::new(format!(
+            "Attachments: {} in {} conversations",
+            format_size(total),
+            self.rows.len()
+        ))
+        .block(Block::default().borders(Borders::ALL))
+        .alignment(Alignment::Center);
+        f.render_widget(title, chunks[0]);
+
+        // Conversations, with the sorted column's heading highlighted
+        let heading = |label: &'static str, sort: UsageSort| {
+            let style = Style::default().add_modifier(Modifier::BOLD);
+            if sort == self.sort {
+                let arrow = if sort == UsageSort::Name {
+                    "▲"
+                } else {
+                    "▼"
+                };
+                Cell::from(format!("{} {}", label, arrow)).style(style.fg(Color::Blue))
+            } else {
+                Cell::from(label).style(style)
+            }
+        };
+        let header = Row::new(vec![
+            heading("Conversation", UsageSort::Name),
+            heading("Attachments", UsageSort::Count),
+            Cell::from("Missing").style(Style::default().add_modifier(Modifier::BOLD)),
+            heading("Size", UsageSort::Size),
+        ]);
+
+        let rows: Vec<Row> =
+            self.rows
+                .iter()
+                .map(|row| {
+                    let missing = match row.usage.missing {
+                        0 => Cell::from(""),
+                        missing => Cell::from(missing.to_string())
+                            .style(Style::default().fg(Color::DarkGray)),
+                    };
+                    Row::new(vec![
+                        Cell::from(row.name.clone()),
+                        Cell::from(row.usage.count.to_string()),
+                        missing,
+                        Cell::from(format_size(row.usage.bytes)),
+                    ])
+                })
+                .collect();
+
+        let widths = [
+            Constraint::Min(20),
+            Constraint::Length(13),
+            Constraint::Length(9),
+            Constraint::Length(10),
+        ];
+        let table = Table::new(rows)
+            .header(header.bottom_margin(1))
+            .widths(&widths)
+            .block(Block::default().borders(Borders::ALL))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("> ");
+
+        let mut state = TableState::default();
+        if !self.rows.is_empty() {
+            state.select(Some(self.selected_index));
+        }
+        f.render_stateful_widget(table, chunks[1], &mut state);
+
+        // Instructions
+        let key_style = Style::default()
+            .fg(Color::Blue)
+            .add_modifier(Modifier::BOLD);
+        let instructions = Paragraph::new(Line::from(vec![
+            Span::styled("s", key_style),
+            Span::raw(": Sort by size, count, or name | "),
+            Span::styled("Esc", key_style),
+            Span::raw(": Quit"),
+        ]))
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL));
+        f.render_widget(instructions, chunks[2]);
+    }
+}
+
+/// Convenience function to run the attachment usage TUI
+pub fn run_attachment_usage_tui(config: &Config, usage: Vec<AttachmentUsage>) -> Result<()> {
+    let mut view = AttachmentUsageView::new(config, usage);
+    view.run()
+}
@@ -0,0 +1,256 @@
+use crate::address_book::{initials, Person};
+use crate::config::ContactEntry;
+use crate::formatter::{looks_like_identifier, normalize_identifier};
+
+/// Read the people in a vCard file (versions 3.0 and 4.0) who have a name and a phone number
+/// or email.
+pub fn parse_vcards(text: &str) -> Vec<Person> {
+    let mut people = Vec::new();
+    let mut card: Option<Card> = None;
+    for line in unfold(text) {
+        let Some((property, value)) = line.split_once(':') else {
+            continue;
+        };
+        // Drop parameters like `;TYPE=CELL` and a group prefix like `item1.`
+        let name = property.split(';').next().unwrap_or_default();
+        let name = name.rsplit('.').next().unwrap_or_default().to_uppercase();
+
+        match name.as_str() {
+            "BEGIN" if value.eq_ignore_ascii_case("VCARD") => card = Some(Card::default()),
+            "END" if value.eq_ignore_ascii_case("VCARD") => {
+                people.extend(card.take().and_then(Card::into_person));
+            }
+            _ => {
+                if let Some(card) = &mut card {
+                    card.add(&name, value);
+                }
+            }
+        }
+    }
+    people
+}
+
+/// Write a contact as a vCard 3.0 card, named by its display name if it has one.
+///
+/// The contact's own name goes in `X-IM-NAME`, which other address books ignore, so importing
+/// the card saves the contact under the same name.
+pub fn format_vcard(entry: &ContactEntry) -> String {
+    let name = escape(entry.display_name.as_deref().unwrap_or(&entry.name));
+    let mut card = format!(
+        "BEGIN:VCARD\r\nVERSION:3.0\r\nFN:{}\r\nN:;{};;;\r\nX-IM-NAME:{}\r\n",
+        name,
+        name,
+        escape(&entry.name)
+    );
+    for identifier in entry.identifiers() {
+        let property = if identifier.contains('@') {
+            "EMAIL"
+        } else {
+            "TEL"
+        };
+        card.push_str(&format!("{}:{}\r\n", property, escape(identifier)));
+    }
+    card.push_str("END:VCARD\r\n");
+    card
+}
+
+/// The properties of a card that become a contact.
+#[derive(Default)]
+struct Card {
+    full_name: Option<String>,
+    first_name: String,
+    last_name: String,
+    identifiers: Vec<String>,
+    contact_name: Option<String>,
+}
+
+impl Card {
+    /// Keep the value of the property `name` if it's one a contact needs.
+    fn add(&mut self, name: &str, value: &str) {
+        match name {
+            "FN" => self.full_name = Some(unescape(value)),
+            "X-IM-NAME" => self.contact_name = Some(unescape(value)),
+            // Family; Given; Additional; Prefixes; Suffixes
+            "N" => {
+                let mut parts = split_components(value).into_iter();
+                self.last_name = parts.next().unwrap_or_default();
+                self.first_name = parts.next().unwrap_or_default();
+            }
+            "TEL" | "EMAIL" => {
+                // vCard 4.0 may write phone numbers as `tel:` URIs
+                let value = unescape(value);
+                let value = value
+                    .strip_prefix("tel:")
+                    .or_else(|| value.strip_prefix("TEL:"))
+                    .unwrap_or(&value);
+                if looks_like_identifier(value) {
+                    let identifier = normalize_identifier(value);
+                    if !self.identifiers.contains(&identifier) {
+                        self.identifiers.push(identifier);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The person on the card, if it has a name and a phone number or email.
+    fn into_person(self) -> Option<Person> {
+        let contact_name = self.contact_name.filter(|name| !name.is_empty());
+        let name = self
+            .full_name
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| {
+                format!("{} {}", self.first_name, self.last_name)
+                    .trim()
+                    .to_string()
+            });
+        let name = match (name.is_empty(), &contact_name) {
+            (true, Some(contact_name)) => contact_name.clone(),
+            _ => name,
+        };
+        if name.is_empty() || self.identifiers.is_empty() {
+            return None;
+        }
+        Some(Person {
+            initials: initials(&self.first_name, &self.last_name, &name),
+            name: name.trim().to_string(),
+            identifiers: self.identifiers,
+            contact_name,
+        })
+    }
+}
+
+/// Split a file into logical lines, joining the continuation lines of folded ones.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Split a structured value like N's at unescaped semicolons.
+fn split_components(value: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                parts.last_mut().unwrap().push('\\');
+                parts.last_mut().unwrap().extend(chars.next());
+            }
+            ';' => parts.push(String::new()),
+            c => parts.last_mut().unwrap().push(c),
+        }
+    }
+    parts.iter().map(|part| unescape(part)).collect()
+}
+
+/// Undo vCard text escaping: `\n`, `\,`, `\;`, and `\\`.
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(other) => text.push(other),
+            None => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+/// Escape text for a vCard value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_parse_vcards() {
+        let text = "BEGIN:VCARD\r\n\
+                    VERSION:3.0\r\n\
+                    N:Appleseed;Jane;;;\r\n\
+                    FN:Jane Appleseed\r\n\
+                    item1.TEL;TYPE=CELL:(555) 123-4567\r\n\
+                    EMAIL;TYPE=INTERNET:Jane@Example.com\r\n\
+                    TEL:555-123-4567\r\n\
+                    END:VCARD\r\n\
+                    BEGIN:VCARD\n\
+                    VERSION:4.0\n\
+                    N:Smith\\, Jr.;Bob;;;\n\
+                    TEL;VALUE=uri;TYPE=\"voice,cell\":tel:+1-555-000-\n \
+                    1111\n\
+                    END:VCARD\n\
+                    BEGIN:VCARD\n\
+                    FN:No Number\n\
+                    END:VCARD\n";
+        let people = parse_vcards(text);
+        assert_eq!(
+            people,
+            vec![
+                Person {
+                    name: "Jane Appleseed".to_string(),
+                    initials: "JA".to_string(),
+                    identifiers: vec!["+15551234567".to_string(), "jane@example.com".to_string()],
+                    contact_name: None,
+                },
+                Person {
+                    name: "Bob Smith, Jr.".to_string(),
+                    initials: "BS".to_string(),
+                    identifiers: vec!["+15550001111".to_string()],
+                    contact_name: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_vcard() {
+        let mut config = Config::default();
+        config.add_contact(
+            "mom".to_string(),
+            "+15551234567".to_string(),
+            Some("Mom, Sr.".to_string()),
+        );
+        config.link_identifier("mom", "mom@example.com").unwrap();
+
+        let card = format_vcard(config.get_contact("mom").unwrap());
+        assert_eq!(
+            card,
+            "BEGIN:VCARD\r\nVERSION:3.0\r\nFN:Mom\\, Sr.\r\nN:;Mom\\, Sr.;;;\r\nX-IM-NAME:mom\r\n\
+             TEL:+15551234567\r\nEMAIL:mom@example.com\r\nEND:VCARD\r\n"
+        );
+
+        // Exported cards read back as the same person, saved under the same name
+        let people = parse_vcards(&card);
+        assert_eq!(people[0].name, "Mom, Sr.");
+        assert_eq!(people[0].contact_name.as_deref(), Some("mom"));
+        assert_eq!(
+            people[0].identifiers,
+            vec!["+15551234567", "mom@example.com"]
+        );
+
+        let mut imported = Config::default();
+        imported.import_person(&people[0]);
+        let mom = imported.get_contact("mom").unwrap();
+        assert_eq!(mom.display_name.as_deref(), Some("Mom, Sr."));
+        assert_eq!(mom.identifier, "+15551234567");
+    }
+}
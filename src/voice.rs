@@ -0,0 +1,171 @@
+use crate::config::VoiceConfig;
+use crate::error::{Error, Result};
+use crate::i18n::t;
+use crate::paths;
+use chrono::Local;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Composer command that records a voice memo.
+const VOICE_COMMAND: &str = "/voice";
+
+/// Environment variable holding the file `voice.command` records to.
+const FILE_VARIABLE: &str = "IM_VOICE_FILE";
+
+/// How long to wait for the recording command to finish writing after it's told to stop.
+const STOP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How often to check whether the recording command has stopped.
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Whether composer text is the `/voice` command.
+pub fn is_voice_command(text: &str) -> bool {
+    text.trim() == VOICE_COMMAND
+}
+
+/// A voice memo being recorded from the default microphone with `voice.command`.
+pub struct Recorder {
+    child: Child,
+    /// The WAV file being recorded
+    path: PathBuf,
+    started: Instant,
+    /// How long the memo is, once recording stopped
+    length: Option<Duration>,
+    /// Longest recording before it stops on its own
+    max_length: Duration,
+}
+
+impl Recorder {
+    /// Start recording.
+    pub fn start(config: &VoiceConfig) -> Result<Self> {
+        let dir = paths::voice().ok_or_else(|| Error::Generic(t!("paths.no_cache_dir")))?;
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.wav", Local::now().format("%Y%m%d-%H%M%S")));
+
+        // `exec` so the stop signal reaches the recorder rather than the shell
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(format!("exec {}", config.command))
+            .env(FILE_VARIABLE, &path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        Ok(Self {
+            child,
+            path,
+            started: Instant::now(),
+            length: None,
+            max_length: Duration::from_secs(config.max_secs),
+        })
+    }
+
+    /// Whether the microphone is still being recorded.
+    pub fn is_recording(&self) -> bool {
+        self.length.is_none()
+    }
+
+    /// How long the memo is so far.
+    pub fn length(&self) -> Duration {
+        self.length.unwrap_or_else(|| self.started.elapsed())
+    }
+
+    /// Stop at the length limit, and report a recording command that quit on its own.
+    pub fn check(&mut self) -> Result<()> {
+        if !self.is_recording() {
+            return Ok(());
+        }
+        if self.started.elapsed() >= self.max_length {
+            return self.stop();
+        }
+        match self.child.try_wait()? {
+            None => Ok(()),
+            Some(status) => {
+                self.length = Some(self.started.elapsed());
+                Err(Error::Generic(t!(
+                    "voice.recorder_stopped",
+                    status = status
+                )))
+            }
+        }
+    }
+
+    /// Stop recording, letting the command finish writing the file.
+    pub fn stop(&mut self) -> Result<()> {
+        if !self.is_recording() {
+            return Ok(());
+        }
+        self.length = Some(self.started.elapsed());
+
+        // Recorders like sox finish the file on SIGINT, as when Ctrl+C is pressed
+        let _ = Command::new("kill")
+            .args(["-INT", &self.child.id().to_string()])
+            .status();
+        let stopping = Instant::now();
+        while self.child.try_wait()?.is_none() {
+            if stopping.elapsed() >= STOP_TIMEOUT {
+                let _ = self.child.kill();
+                let _ = self.child.wait();
+                break;
+            }
+            thread::sleep(WAIT_INTERVAL);
+        }
+
+        if fs::metadata(&self.path).map_or(true, |metadata| metadata.len() == 0) {
+            return Err(Error::Generic(t!("voice.nothing_recorded")));
+        }
+        Ok(())
+    }
+
+    /// The finished memo, compressed to AAC with macOS's `afconvert` when it's available.
+    pub fn finish(mut self) -> Result<PathBuf> {
+        self.stop()?;
+        let compressed = self.path.with_extension("m4a");
+        let converted = Command::new("afconvert")
+            .args(["-f", "m4af", "-d", "aac"])
+            .arg(&self.path)
+            .arg(&compressed)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if converted {
+            let _ = fs::remove_file(&self.path);
+            Ok(compressed)
+        } else {
+            Ok(self.path.clone())
+        }
+    }
+
+    /// Stop recording and throw the memo away.
+    pub fn discard(mut self) {
+        let _ = self.stop();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // Don't leave the microphone on when the view closes mid-recording
+        if self.is_recording() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_voice_command() {
+        assert!(is_voice_command("/voice"));
+        assert!(is_voice_command(" /voice \n"));
+        assert!(!is_voice_command("/voice memo"));
+        assert!(!is_voice_command("/voices"));
+    }
+}
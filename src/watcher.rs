@@ -0,0 +1,103 @@
+use crate::config::Config;
+use crate::db::MessageDB;
+use crate::error::Result;
+use crate::paths;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Suffixes of the SQLite files Messages.app writes to.
+///
+/// New messages land in the write-ahead log first and are checkpointed into
+/// chat.db later, so both are watched.
+const WATCHED_SUFFIXES: &[&str] = &["", "-wal"];
+
+/// Modification time and size of a watched file.
+pub type FileStamp = Option<(SystemTime, u64)>;
+
+/// Detects writes to chat.db by watching the database and WAL file metadata.
+///
+/// Checking file metadata is far cheaper than querying, so views can check
+/// often and only query chat.db when Messages.app actually wrote something.
+pub struct DbWatcher {
+    paths: Vec<PathBuf>,
+    /// Stamps from the last check, or `None` before the first one
+    stamps: Option<Vec<FileStamp>>,
+}
+
+impl DbWatcher {
+    /// Create a watcher for the Messages database.
+    ///
+    /// The first call to `has_changed` always reports a change so callers load once.
+    pub fn new() -> Result<Self> {
+        let db_path = MessageDB::path()?;
+        let paths: Vec<PathBuf> = WATCHED_SUFFIXES
+            .iter()
+            .map(|suffix| {
+                let mut path = db_path.clone().into_os_string();
+                path.push(suffix);
+                PathBuf::from(path)
+            })
+            .collect();
+
+        Ok(Self {
+            paths,
+            stamps: None,
+        })
+    }
+
+    /// Check whether any watched file changed since the last call.
+    pub fn has_changed(&mut self) -> bool {
+        let stamps = self.stamps();
+        let changed = self.stamps.as_ref() != Some(&stamps);
+        self.stamps = Some(stamps);
+        changed
+    }
+
+    /// Stamp each watched file as it is now, so the stamps can be compared with later ones.
+    pub fn stamps(&self) -> Vec<FileStamp> {
+        self.paths.iter().map(|path| file_stamp(path)).collect()
+    }
+}
+
+/// Detects edits to the configuration file, such as from an editor in another terminal, so
+/// views can apply them without a restart.
+pub struct ConfigWatcher {
+    path: Option<PathBuf>,
+    stamp: FileStamp,
+}
+
+impl Default for ConfigWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigWatcher {
+    /// Create a watcher for the configuration file as it is now.
+    pub fn new() -> Self {
+        let path = paths::config_file();
+        let stamp = path.as_deref().and_then(file_stamp);
+        Self { path, stamp }
+    }
+
+    /// Load the configuration again if the file was written since the last call and says
+    /// something other than `current`, such as when a view saved it itself.
+    pub fn reload(&mut self, current: &Config) -> Option<Result<Config>> {
+        let stamp = self.path.as_deref().and_then(file_stamp);
+        if stamp == self.stamp {
+            return None;
+        }
+        self.stamp = stamp;
+        match Config::load() {
+            Ok(config) if config.same_as(current) => None,
+            loaded => Some(loaded),
+        }
+    }
+}
+
+/// Read the modification time and size of a file, if it exists.
+fn file_stamp(path: &Path) -> FileStamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
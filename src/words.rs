@@ -0,0 +1,135 @@
+use crate::db::Message;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Words listed by `im stats --words`.
+pub const TOP_WORDS: usize = 20;
+
+/// Emoji listed by `im stats --words`.
+pub const TOP_EMOJI: usize = 10;
+
+/// Words too common to say anything about a conversation.
+const STOP_WORDS: &[&str] = &[
+    "a", "about", "after", "again", "all", "also", "am", "an", "and", "any", "are", "as", "at",
+    "be", "because", "been", "before", "being", "but", "by", "can", "could", "did", "do", "does",
+    "doing", "don't", "for", "from", "get", "got", "had", "has", "have", "having", "he", "her",
+    "here", "him", "his", "how", "i", "i'll", "i'm", "i've", "if", "in", "into", "is", "it",
+    "it's", "its", "just", "me", "more", "my", "no", "not", "now", "of", "oh", "ok", "okay", "on",
+    "one", "or", "our", "out", "so", "some", "than", "that", "that's", "the", "their", "them",
+    "then", "there", "these", "they", "this", "those", "to", "too", "up", "us", "was", "we",
+    "were", "what", "when", "where", "which", "who", "why", "will", "with", "would", "yeah", "yes",
+    "you", "you're", "your",
+];
+
+/// How often each word and emoji comes up in a conversation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WordCounts {
+    words: HashMap<String, usize>,
+    /// Whole emoji, so skin tones and sequences like families count as one
+    emoji: HashMap<String, usize>,
+}
+
+impl WordCounts {
+    /// Count the words and emoji in the text of `messages`, leaving out stop words, links,
+    /// numbers, and single letters.
+    pub fn count(messages: &[Message]) -> Self {
+        let mut counts = Self::default();
+        for text in messages
+            .iter()
+            .filter_map(|message| message.text.as_deref())
+        {
+            counts.add(text);
+        }
+        counts
+    }
+
+    /// Count the words and emoji in one message.
+    fn add(&mut self, text: &str) {
+        for token in text.split_whitespace() {
+            if token.contains("://") || token.starts_with("www.") {
+                continue;
+            }
+
+            for emoji in token.graphemes(true).filter(|g| g.chars().any(is_emoji)) {
+                *self.emoji.entry(emoji.to_string()).or_default() += 1;
+            }
+
+            // Curly apostrophes from iOS count the same as straight ones
+            let token = token.replace('’', "'").to_lowercase();
+            for word in token.split(|c: char| !c.is_alphanumeric() && c != '\'') {
+                let word = word.trim_matches('\'');
+                if word.graphemes(true).count() < 2
+                    || word.chars().all(|c| c.is_numeric())
+                    || STOP_WORDS.contains(&word)
+                {
+                    continue;
+                }
+                *self.words.entry(word.to_string()).or_default() += 1;
+            }
+        }
+    }
+
+    /// The `limit` most frequent words, most frequent first.
+    pub fn top_words(&self, limit: usize) -> Vec<(&str, usize)> {
+        top(
+            self.words
+                .iter()
+                .map(|(word, &count)| (word.as_str(), count)),
+            limit,
+        )
+    }
+
+    /// The `limit` most frequent emoji, most frequent first.
+    pub fn top_emoji(&self, limit: usize) -> Vec<(&str, usize)> {
+        top(
+            self.emoji
+                .iter()
+                .map(|(emoji, &count)| (emoji.as_str(), count)),
+            limit,
+        )
+    }
+}
+
+/// The `limit` entries with the highest counts, ties broken by the entries themselves.
+fn top<T: Ord>(entries: impl Iterator<Item = (T, usize)>, limit: usize) -> Vec<(T, usize)> {
+    let mut entries: Vec<_> = entries.collect();
+    entries.sort_by(|a, b| (Reverse(a.1), &a.0).cmp(&(Reverse(b.1), &b.0)));
+    entries.truncate(limit);
+    entries
+}
+
+/// Whether `c` is an emoji, leaving out the modifiers and joiners that combine them.
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1F3FA | 0x1F400..=0x1FAFF | 0x2600..=0x27BF | 0x2B50
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_counts() {
+        let mut counts = WordCounts::default();
+        counts.add("Dinner tonight? 🍕🍕");
+        counts.add("YES dinner at 7, I’m starving 😂");
+        counts.add("see https://example.com/dinner and it's 2 blocks away 👍🏽");
+        counts.add("👨‍👩‍👧 👍🏽");
+
+        assert_eq!(
+            counts.top_words(3),
+            vec![("dinner", 2), ("away", 1), ("blocks", 1)]
+        );
+        assert!(counts
+            .top_words(20)
+            .iter()
+            .all(|(word, _)| { !["i'm", "it's", "yes", "7", "2", "example"].contains(word) }));
+        assert_eq!(
+            counts.top_emoji(5),
+            vec![("🍕", 2), ("👍🏽", 2), ("👨‍👩‍👧", 1), ("😂", 1)]
+        );
+    }
+}